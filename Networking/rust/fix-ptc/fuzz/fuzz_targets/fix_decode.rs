@@ -0,0 +1,16 @@
+//! Feeds arbitrary bytes to the FIX stream decoder, as `drive_session` does with whatever
+//! arrives on a socket. Run with `cargo +nightly fuzz run fix_decode`.
+
+#![no_main]
+
+use fix_ptc::fix::FixMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut buffer = data;
+    while let Ok(Some((message, consumed))) = FixMessage::decode(buffer) {
+        assert!(consumed > 0 && consumed <= buffer.len());
+        assert_eq!(FixMessage::parse(&buffer[..consumed]).as_ref(), Ok(&message));
+        buffer = &buffer[consumed..];
+    }
+});
@@ -0,0 +1,220 @@
+//! # Audit Module
+//!
+//! Append-only record of every application message a session sends and receives, for
+//! compliance investigations and replay.
+//!
+//! Unlike the [wire log](crate::wirelog), the audit file leaves out administrative messages,
+//! is never rotated and keeps each message byte for byte, so it can be read back with
+//! [`read_audit`]. Each line holds the UTC time the message was sent or received, the
+//! direction, its MsgSeqNum and the raw message (SOH delimited):
+//!
+//! ```text
+//! 20240101-12:00:00.000 IN  2 8=FIX.4.4<SOH>9=...<SOH>35=D<SOH>...<SOH>10=123<SOH>
+//! ```
+//!
+//! [`AuditFilter`] selects records by ClOrdID, Symbol or time range; the `audit` binary
+//! prints the records of a file that match one.
+//!
+//! A message is recorded before it is processed or sent, and a session whose audit record
+//! cannot be written ends its connection rather than go on unrecorded.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use crate::fix::{tags, FixMessage};
+use crate::session::{format_utc_timestamp, msg_types, parse_utc_timestamp};
+use crate::wirelog::Direction;
+
+/// The audit file of a session, opened for appending.
+#[derive(Debug)]
+pub struct AuditLog {
+    file: File,
+}
+
+impl AuditLog {
+    /// Opens (appending to) the audit file at `path`, creating its directory if needed.
+    ///
+    /// # Errors
+    /// Returns an error if the directory or file cannot be created.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        if let Some(dir) = path.as_ref().parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Appends `message` if it is an application message, stamped with `time`.
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be written.
+    pub fn record(&mut self, direction: Direction, message: &[u8], time: SystemTime) -> io::Result<()> {
+        let Ok(parsed) = FixMessage::parse(message) else {
+            return Ok(());
+        };
+        if parsed.get_msg_type().is_none_or(msg_types::is_admin) {
+            return Ok(());
+        }
+        let label = match direction {
+            Direction::Inbound => "IN ",
+            Direction::Outbound => "OUT",
+        };
+        let seq_num = parsed.get_uint(tags::MSG_SEQ_NUM).unwrap_or_default();
+        let mut line = format!("{} {} {} ", format_utc_timestamp(time), label, seq_num).into_bytes();
+        line.extend_from_slice(message);
+        line.push(b'\n');
+        // One write per line, so a crash never leaves half a record behind another.
+        self.file.write_all(&line)
+    }
+}
+
+/// One message read back from an audit file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    pub time: SystemTime,
+    pub direction: Direction,
+    pub seq_num: u64,
+    pub message: FixMessage,
+    /// The message as it was sent or received.
+    pub raw: Vec<u8>,
+}
+
+/// Selects audit records; every criterion that is set must match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuditFilter {
+    /// Matches ClOrdID (11) or OrigClOrdID (41), so that cancels and replaces are included.
+    pub cl_ord_id: Option<String>,
+    /// Matches Symbol (55).
+    pub symbol: Option<String>,
+    /// Earliest time, inclusive.
+    pub from: Option<SystemTime>,
+    /// Latest time, exclusive.
+    pub to: Option<SystemTime>,
+}
+
+impl AuditFilter {
+    /// Creates a filter that matches every record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches messages about the order `cl_ord_id`.
+    pub fn cl_ord_id(mut self, cl_ord_id: &str) -> Self {
+        self.cl_ord_id = Some(cl_ord_id.to_string());
+        self
+    }
+
+    /// Only matches messages for `symbol`.
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    /// Only matches messages recorded in `[from, to)`.
+    pub fn between(mut self, from: Option<SystemTime>, to: Option<SystemTime>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// Returns true if `record` meets every criterion.
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let message = &record.message;
+        self.cl_ord_id.as_deref().is_none_or(|cl_ord_id| {
+            message.get_field(tags::CL_ORD_ID) == Some(cl_ord_id) || message.get_field(tags::ORIG_CL_ORD_ID) == Some(cl_ord_id)
+        }) && self.symbol.as_deref().is_none_or(|symbol| message.get_field(tags::SYMBOL) == Some(symbol))
+            && self.from.is_none_or(|from| record.time >= from)
+            && self.to.is_none_or(|to| record.time < to)
+    }
+}
+
+/// Reads the records of the audit file at `path` that match `filter`, in file order.
+///
+/// # Errors
+/// Returns an error if the file cannot be read or a line is not a valid record.
+pub fn read_audit(path: impl AsRef<Path>, filter: &AuditFilter) -> io::Result<Vec<AuditRecord>> {
+    let contents = fs::read(path)?;
+    let mut records = Vec::new();
+    for (index, line) in contents.split(|byte| *byte == b'\n').enumerate().filter(|(_, line)| !line.is_empty()) {
+        let record = parse_record(line).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid audit record on line {}", index + 1)))?;
+        if filter.matches(&record) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Parses one line written by [`AuditLog::record`].
+fn parse_record(line: &[u8]) -> Option<AuditRecord> {
+    let header_end = line.windows(2).position(|window| window == b"8=")?;
+    let header = std::str::from_utf8(&line[..header_end]).ok()?;
+    let mut parts = header.split_whitespace();
+    let time = parse_utc_timestamp(parts.next()?)?;
+    let direction = match parts.next()? {
+        "IN" => Direction::Inbound,
+        "OUT" => Direction::Outbound,
+        _ => return None,
+    };
+    let seq_num = parts.next()?.parse().ok()?;
+    let raw = line[header_end..].to_vec();
+    let message = FixMessage::parse(&raw).ok()?;
+    Some(AuditRecord { time, direction, seq_num, message, raw })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::fix::FixMessageBuilder;
+
+    fn message(msg_type: &str, seq_num: u64, fields: &[(u32, &str)]) -> Vec<u8> {
+        let mut builder = FixMessageBuilder::new("FIX.4.4", msg_type).field(tags::MSG_SEQ_NUM, seq_num);
+        for (tag, value) in fields {
+            builder = builder.field(*tag, *value);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_record_and_filter(){
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("audit.log");
+        let at = |seconds: u64| UNIX_EPOCH + Duration::from_secs(1_700_000_000 + seconds);
+
+        let mut audit = AuditLog::open(&path).unwrap();
+        audit.record(Direction::Inbound, &message("A", 1, &[]), at(0)).unwrap();
+        audit.record(Direction::Inbound, &message("D", 2, &[(tags::CL_ORD_ID, "1"), (tags::SYMBOL, "ACME")]), at(1)).unwrap();
+        audit.record(Direction::Outbound, &message("8", 2, &[(tags::CL_ORD_ID, "1"), (tags::TEXT, "a b")]), at(1)).unwrap();
+        audit.record(Direction::Inbound, &message("D", 3, &[(tags::CL_ORD_ID, "2"), (tags::SYMBOL, "OTHER")]), at(5)).unwrap();
+        audit.record(Direction::Inbound, &message("F", 4, &[(tags::CL_ORD_ID, "3"), (tags::ORIG_CL_ORD_ID, "1"), (tags::SYMBOL, "ACME")]), at(9)).unwrap();
+        audit.record(Direction::Outbound, &message("0", 3, &[]), at(9)).unwrap();
+        drop(audit);
+
+        // Appending after a reopen keeps what was there.
+        let mut audit = AuditLog::open(&path).unwrap();
+        audit.record(Direction::Outbound, &message("8", 4, &[(tags::CL_ORD_ID, "3")]), at(10)).unwrap();
+
+        let all = read_audit(&path, &AuditFilter::new()).unwrap();
+        assert_eq!(all.iter().map(|record| record.seq_num).collect::<Vec<_>>(), vec![2, 2, 3, 4, 4]);
+        assert_eq!((all[1].direction, all[1].time), (Direction::Outbound, at(1)));
+        assert_eq!(all[1].message.get_field(tags::TEXT), Some("a b"));
+
+        let order = read_audit(&path, &AuditFilter::new().cl_ord_id("1")).unwrap();
+        assert_eq!(order.iter().map(|record| record.message.get_msg_type().unwrap()).collect::<Vec<_>>(), vec!["D", "8", "F"]);
+        assert_eq!(read_audit(&path, &AuditFilter::new().symbol("ACME")).unwrap().len(), 2);
+        let window = read_audit(&path, &AuditFilter::new().symbol("ACME").between(Some(at(2)), Some(at(10)))).unwrap();
+        assert_eq!(window.iter().map(|record| record.seq_num).collect::<Vec<_>>(), vec![4]);
+
+        fs::write(&path, b"garbage\n").unwrap();
+        assert!(read_audit(&path, &AuditFilter::new()).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_record_reports_write_errors(){
+        let mut audit = AuditLog::open("/dev/full").unwrap();
+        assert!(audit.record(Direction::Outbound, &message("0", 1, &[]), SystemTime::now()).is_ok());
+        assert!(audit.record(Direction::Outbound, &message("8", 2, &[(tags::CL_ORD_ID, "1")]), SystemTime::now()).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use fix_ptc::audit::{read_audit, AuditFilter};
+use fix_ptc::session::{format_utc_timestamp, parse_utc_timestamp};
+use fix_ptc::wirelog::{render, Direction};
+
+const USAGE: &str = "Usage: audit <file> [--cl-ord-id ID] [--symbol SYMBOL] [--from YYYYMMDD-HH:MM:SS] [--to YYYYMMDD-HH:MM:SS]";
+
+/// Reads the filter options that follow the file name.
+fn parse_filter(mut args: impl Iterator<Item = String>) -> Result<AuditFilter, String> {
+    let mut filter = AuditFilter::new();
+    while let Some(option) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{} needs a value", option))?;
+        let time = || parse_utc_timestamp(&value).ok_or_else(|| format!("Invalid time {}", value));
+        match option.as_str() {
+            "--cl-ord-id" => filter = filter.cl_ord_id(&value),
+            "--symbol" => filter = filter.symbol(&value),
+            "--from" => filter.from = Some(time()?),
+            "--to" => filter.to = Some(time()?),
+            _ => return Err(format!("Unknown option {}", option)),
+        }
+    }
+    Ok(filter)
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let filter = parse_filter(args).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        std::process::exit(2);
+    });
+    let records = read_audit(&path, &filter).unwrap_or_else(|err| {
+        eprintln!("Cannot read {}: {}", path, err);
+        std::process::exit(1);
+    });
+    for record in &records {
+        let direction = match record.direction {
+            Direction::Inbound => "IN ",
+            Direction::Outbound => "OUT",
+        };
+        println!("{} {} {:>6} {}", format_utc_timestamp(record.time), direction, record.seq_num, render(&record.raw));
+    }
+}
@@ -0,0 +1,1364 @@
+//! # Bridge Module
+//!
+//! Connects FIX application messages to the matching engine in the `orderbook` crate.
+//!
+//! ## NewOrderSingle (`35=D`)
+//! | FIX field          | Engine                                                          |
+//! |--------------------|-----------------------------------------------------------------|
+//! | ClOrdID (11)       | Client reference, mapped to an engine [`OrderId`] by the bridge |
+//! | Side (54)          | `1` → [`Side::Buy`], `2` → [`Side::Sell`], `5` → short sell      |
+//! | OrdType (40)       | `1` → [`OrderType::Market`], `2` → limit                         |
+//! | TimeInForce (59)   | `0`/absent → GoodForDay, `1` → GoodTillCancel, `3` → FillAndKill, `4` → FillOrKill |
+//! | Price (44)         | Limit price; must be a whole number of ticks                    |
+//! | OrderQty (38)      | Quantity                                                        |
+//! | OrderCapacity (528)| `A` → agency, `P` → principal; optional                          |
+//!
+//! Capacity and the short-sell indicator become the order's [`OrderFlags`] and are echoed on
+//! every ExecutionReport. A short sale the book's short sale rule refuses is rejected up front.
+//!
+//! ## ExecutionReport (`35=8`)
+//! The engine does not publish events, so the bridge derives them from each submission:
+//! - `New` as soon as the order is accepted (or `Rejected` if it is invalid),
+//! - one `Trade` per fill, sent to the owner of each side, with LastQty, LastPx (the price the
+//!   trade printed at, i.e. the resting order's), CumQty, LeavesQty and AvgPx,
+//! - `Canceled` when the engine discarded the unfilled remainder (F&K, FOK, or a market order
+//!   that found no liquidity).
+//!
+//! ## OrderCancelRequest (`35=F`) and OrderCancelReplaceRequest (`35=G`)
+//! OrigClOrdID (41) is resolved against the sender's live orders. A cancel is answered with a
+//! `Canceled` ExecutionReport and a replace with `Replaced` (followed by any fills the new
+//! price causes). The new OrderQty is the total quantity including what has already been
+//! filled. Requests that cannot be applied are answered with OrderCancelReject (`35=9`).
+//!
+//! ## Position limits
+//! Each owner is an account. Orders and replacements that would let the account's worst-case
+//! position in the Symbol (55) break its [`PositionLimit`] are rejected as a risk limit; see
+//! [`risk`](crate::risk). [`OrderBridge::get_utilization`] reports where an account stands.
+//!
+//! ## Margin
+//! With [`OrderBridge::with_margin`], orders must also be covered by the account's collateral
+//! as described in [`margin`](crate::margin): those exceeding the buying power are rejected as
+//! a risk limit, and after every message accounts that fell below their maintenance margin are
+//! sent a [`MarginCall`].
+//!
+//! ## Clearing
+//! With [`OrderBridge::with_clearing`], both counterparties of every trade are confirmed to
+//! clearing, and the day's trades are netted per account by
+//! [`OrderBridge::close_clearing_day`]; see [`clearing`](crate::clearing).
+//!
+//! ## Rejects
+//! Every failure is a [`BridgeError`], which decides how it is answered:
+//! - an order the bridge cannot accept (invalid field, duplicate ClOrdID, risk limit) gets a
+//!   `Rejected` ExecutionReport,
+//! - a cancel or replace that cannot be applied gets an OrderCancelReject with the
+//!   CxlRejReason (102) of the error,
+//! - an unknown Symbol (55) or an unsupported MsgType gets a BusinessMessageReject (`35=j`)
+//!   with the BusinessRejectReason (380) of the error.
+//!
+//! ## Drop copy
+//! A drop-copy session receives a copy of every ExecutionReport of the accounts it follows,
+//! built by [`Report::to_drop_copy`] with Account (1) set to the owner of the order. It may
+//! not send application messages; the server answers them with a BusinessMessageReject
+//! ([`BridgeError::NotAuthorized`]) without passing them on.
+
+use std::{collections::{BTreeMap, HashMap}, fmt};
+use orderbook::{Order, OrderFlags, OrderId, OrderModify, OrderPointer, OrderType, Orderbook, Price, Quantity, Side, Trade, TradeInfo, Trades};
+use crate::clearing::{Clearing, Confirmation, FeeSchedule, Liquidity, NettingReport};
+use crate::fields::{self, ExecType, FieldEnum, OrdStatus, OrdType, OrderCapacity, TimeInForce};
+use crate::fix::{tags, FixError, FixMessage, FixMessageBuilder, Tag};
+use crate::margin::{MarginAccount, MarginCall, MarginRates, MarginStatus};
+use crate::risk::{Position, PositionLimit, Utilization};
+
+/// MsgType of NewOrderSingle.
+pub const NEW_ORDER_SINGLE: &str = "D";
+/// MsgType of OrderCancelRequest.
+pub const ORDER_CANCEL_REQUEST: &str = "F";
+/// MsgType of OrderCancelReplaceRequest.
+pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+/// MsgType of OrderCancelReject.
+pub const ORDER_CANCEL_REJECT: &str = "9";
+/// MsgType of BusinessMessageReject.
+pub const BUSINESS_MESSAGE_REJECT: &str = "j";
+
+/// Why an inbound order message could not be applied to the book.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BridgeError {
+    /// A required field is missing or has an invalid value.
+    InvalidField { tag: Tag, reason: String },
+    /// The ClOrdID is already used by a live order.
+    DuplicateClOrdId(String),
+    /// The OrigClOrdID does not name a live order of the sender.
+    UnknownOrder(String),
+    /// The Symbol is not traded by this bridge.
+    UnknownSymbol(String),
+    /// The order breaches a risk limit.
+    RiskReject(String),
+    /// The MsgType is not handled by the bridge.
+    UnsupportedMessageType(String),
+    /// The session may not send this message, e.g. an order on a drop-copy session.
+    NotAuthorized(String),
+}
+
+impl BridgeError {
+    /// Returns the CxlRejReason (102) sent when the error answers a cancel or replace request.
+    pub fn get_cxl_rej_reason(&self) -> u32 {
+        match self {
+            BridgeError::UnknownOrder(_) => cxl_rej_reason::UNKNOWN_ORDER,
+            BridgeError::DuplicateClOrdId(_) => cxl_rej_reason::DUPLICATE_CL_ORD_ID,
+            _ => cxl_rej_reason::OTHER,
+        }
+    }
+
+    /// Returns the BusinessRejectReason (380) if the error is answered with a
+    /// BusinessMessageReject rather than an order-level reject.
+    pub fn get_business_reject_reason(&self) -> Option<u32> {
+        match self {
+            BridgeError::UnknownSymbol(_) => Some(business_reject_reason::UNKNOWN_SECURITY),
+            BridgeError::UnsupportedMessageType(_) => Some(business_reject_reason::UNSUPPORTED_MESSAGE_TYPE),
+            BridgeError::NotAuthorized(_) => Some(business_reject_reason::NOT_AUTHORIZED),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeError::InvalidField { tag, reason } => write!(f, "Invalid field {}: {}", tag, reason),
+            BridgeError::DuplicateClOrdId(cl_ord_id) => write!(f, "Duplicate ClOrdID {}", cl_ord_id),
+            BridgeError::UnknownOrder(cl_ord_id) => write!(f, "Unknown order {}", cl_ord_id),
+            BridgeError::UnknownSymbol(symbol) => write!(f, "Unknown symbol {}", symbol),
+            BridgeError::RiskReject(reason) => write!(f, "Risk reject: {}", reason),
+            BridgeError::UnsupportedMessageType(msg_type) => write!(f, "Unsupported MsgType {}", msg_type),
+            BridgeError::NotAuthorized(reason) => write!(f, "Not authorized: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+/// A NewOrderSingle translated into engine terms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewOrder {
+    pub cl_ord_id: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    /// `None` for market orders.
+    pub price: Option<Price>,
+    pub quantity: Quantity,
+    pub flags: OrderFlags,
+}
+
+impl NewOrder {
+    /// Reads a NewOrderSingle.
+    ///
+    /// # Errors
+    /// Returns [`BridgeError::InvalidField`] for the first missing or invalid field.
+    pub fn from_message(message: &FixMessage) -> Result<Self, BridgeError> {
+        let cl_ord_id = message.get_field(tags::CL_ORD_ID).ok_or_else(|| missing(tags::CL_ORD_ID))?.to_string();
+
+        let side = read_enum::<fields::Side>(message, "Side")?;
+        let capacity = if message.has_field(tags::ORDER_CAPACITY) {
+            Some(read_enum::<OrderCapacity>(message, "OrderCapacity")?.into())
+        } else {
+            None
+        };
+        let flags = OrderFlags { capacity, short_sell: side == fields::Side::SellShort };
+        let side = side.into();
+
+        let quantity = match message.get_uint(tags::ORDER_QTY) {
+            Ok(quantity) if quantity > 0 => Quantity::try_from(quantity).map_err(|_| invalid(tags::ORDER_QTY, "too large".to_string()))?,
+            Ok(_) => return Err(invalid(tags::ORDER_QTY, "must be positive".to_string())),
+            Err(_) => return Err(missing_or_invalid(message, tags::ORDER_QTY)),
+        };
+
+        let (order_type, price) = match read_enum::<OrdType>(message, "OrdType")? {
+            OrdType::Market => (OrderType::Market, None),
+            OrdType::Limit => {
+                let time_in_force = if message.has_field(tags::TIME_IN_FORCE) {
+                    read_enum::<TimeInForce>(message, "TimeInForce")?
+                } else {
+                    TimeInForce::Day
+                };
+                let order_type = match time_in_force {
+                    TimeInForce::Day => OrderType::GoodForDay,
+                    TimeInForce::GoodTillCancel => OrderType::GoodTillCancel,
+                    TimeInForce::ImmediateOrCancel => OrderType::FillAndKill,
+                    TimeInForce::FillOrKill => OrderType::FillOrKill,
+                };
+                (order_type, Some(parse_price(message)?))
+            }
+        };
+
+        Ok(Self { cl_ord_id, side, order_type, price, quantity, flags })
+    }
+
+    /// Builds the NewOrderSingle that [`NewOrder::from_message`] reads back as `self`.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        let (ord_type, time_in_force) = match self.order_type {
+            OrderType::Market => (OrdType::Market, None),
+            OrderType::GoodForDay => (OrdType::Limit, Some(TimeInForce::Day)),
+            OrderType::GoodTillCancel => (OrdType::Limit, Some(TimeInForce::GoodTillCancel)),
+            OrderType::FillAndKill => (OrdType::Limit, Some(TimeInForce::ImmediateOrCancel)),
+            OrderType::FillOrKill => (OrdType::Limit, Some(TimeInForce::FillOrKill)),
+        };
+        FixMessageBuilder::new(begin_string, NEW_ORDER_SINGLE)
+            .field(tags::CL_ORD_ID, &self.cl_ord_id)
+            .field(tags::SIDE, wire_side(self.side, self.flags))
+            .field(tags::ORDER_QTY, self.quantity)
+            .field(tags::ORD_TYPE, ord_type)
+            .optional_field(tags::PRICE, self.price)
+            .optional_field(tags::TIME_IN_FORCE, time_in_force)
+            .optional_field(tags::ORDER_CAPACITY, self.flags.capacity.map(OrderCapacity::from))
+    }
+}
+
+/// CxlRejReason (tag 102) values sent by the bridge.
+pub mod cxl_rej_reason {
+    pub const TOO_LATE_TO_CANCEL: u32 = 0;
+    pub const UNKNOWN_ORDER: u32 = 1;
+    pub const DUPLICATE_CL_ORD_ID: u32 = 6;
+    pub const OTHER: u32 = 99;
+}
+
+/// BusinessRejectReason (tag 380) values sent by the bridge.
+pub mod business_reject_reason {
+    pub const OTHER: u32 = 0;
+    pub const UNKNOWN_SECURITY: u32 = 2;
+    pub const UNSUPPORTED_MESSAGE_TYPE: u32 = 3;
+    pub const NOT_AUTHORIZED: u32 = 6;
+}
+
+/// MsgType of ExecutionReport.
+pub const EXECUTION_REPORT: &str = "8";
+
+/// An ExecutionReport (`35=8`) addressed to the session that owns the order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionReport {
+    /// Key of the session the report must be sent to.
+    pub owner: String,
+    /// Engine id, or `None` when the order never reached the book.
+    pub order_id: Option<OrderId>,
+    pub cl_ord_id: String,
+    /// ClOrdID of the order being cancelled or replaced.
+    pub orig_cl_ord_id: Option<String>,
+    pub exec_id: u64,
+    pub exec_type: ExecType,
+    pub ord_status: OrdStatus,
+    pub side: Side,
+    /// Capacity and short-sell indicator of the order, sent as OrderCapacity (528) and Side `5`.
+    pub flags: OrderFlags,
+    pub order_qty: Quantity,
+    pub price: Option<Price>,
+    /// Quantity and price of this fill (trade reports only).
+    pub last_fill: Option<(Quantity, Price)>,
+    pub cum_qty: Quantity,
+    pub leaves_qty: Quantity,
+    pub avg_px: f64,
+    pub text: Option<String>,
+}
+
+impl ExecutionReport {
+    /// Builds the FIX message; the session adds the standard header.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        FixMessageBuilder::new(begin_string, EXECUTION_REPORT)
+            .field(tags::ORDER_ID, self.order_id.map_or("NONE".to_string(), |id| id.to_string()))
+            .field(tags::CL_ORD_ID, &self.cl_ord_id)
+            .optional_field(tags::ORIG_CL_ORD_ID, self.orig_cl_ord_id.as_ref())
+            .field(tags::EXEC_ID, self.exec_id)
+            .field(tags::EXEC_TYPE, self.exec_type)
+            .field(tags::ORD_STATUS, self.ord_status)
+            .field(tags::SIDE, wire_side(self.side, self.flags))
+            .optional_field(tags::ORDER_CAPACITY, self.flags.capacity.map(OrderCapacity::from))
+            .field(tags::ORDER_QTY, self.order_qty)
+            .optional_field(tags::PRICE, self.price)
+            .optional_field(tags::LAST_QTY, self.last_fill.map(|(quantity, _)| quantity))
+            .optional_field(tags::LAST_PX, self.last_fill.map(|(_, price)| price))
+            .field(tags::CUM_QTY, self.cum_qty)
+            .field(tags::LEAVES_QTY, self.leaves_qty)
+            .field(tags::AVG_PX, self.avg_px)
+            .optional_field(tags::TEXT, self.text.as_ref())
+    }
+}
+
+/// An OrderCancelReject (`35=9`) addressed to the session that sent the request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderCancelReject {
+    pub owner: String,
+    /// Engine id of the order, or `None` if it could not be resolved.
+    pub order_id: Option<OrderId>,
+    pub cl_ord_id: String,
+    pub orig_cl_ord_id: String,
+    /// OrdStatus of the order the request referred to.
+    pub ord_status: OrdStatus,
+    /// `1` for a cancel request, `2` for a cancel/replace request.
+    pub response_to: char,
+    pub reason: u32,
+    pub text: String,
+}
+
+impl OrderCancelReject {
+    /// Builds the FIX message; the session adds the standard header.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        FixMessageBuilder::new(begin_string, ORDER_CANCEL_REJECT)
+            .field(tags::ORDER_ID, self.order_id.map_or("NONE".to_string(), |id| id.to_string()))
+            .field(tags::CL_ORD_ID, &self.cl_ord_id)
+            .field(tags::ORIG_CL_ORD_ID, &self.orig_cl_ord_id)
+            .field(tags::ORD_STATUS, self.ord_status)
+            .field(tags::CXL_REJ_RESPONSE_TO, self.response_to)
+            .field(tags::CXL_REJ_REASON, self.reason)
+            .field(tags::TEXT, &self.text)
+    }
+}
+
+/// A BusinessMessageReject (`35=j`) addressed to the session that sent the rejected message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BusinessMessageReject {
+    pub owner: String,
+    /// MsgSeqNum of the rejected message.
+    pub ref_seq_num: Option<u64>,
+    pub ref_msg_type: String,
+    /// ClOrdID of the rejected message, if it had one.
+    pub business_reject_ref_id: Option<String>,
+    pub reason: u32,
+    pub text: String,
+}
+
+impl BusinessMessageReject {
+    /// Answers `message` from `owner`, which failed with `err`.
+    pub fn new(owner: &str, message: &FixMessage, err: &BridgeError) -> Self {
+        Self {
+            owner: owner.to_string(),
+            ref_seq_num: message.get_uint(tags::MSG_SEQ_NUM).ok(),
+            ref_msg_type: message.get_msg_type().unwrap_or_default().to_string(),
+            business_reject_ref_id: message.get_field(tags::CL_ORD_ID).map(str::to_string),
+            reason: err.get_business_reject_reason().unwrap_or(business_reject_reason::OTHER),
+            text: err.to_string(),
+        }
+    }
+
+    /// Builds the FIX message; the session adds the standard header.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        FixMessageBuilder::new(begin_string, BUSINESS_MESSAGE_REJECT)
+            .optional_field(tags::REF_SEQ_NUM, self.ref_seq_num)
+            .field(tags::REF_MSG_TYPE, &self.ref_msg_type)
+            .optional_field(tags::BUSINESS_REJECT_REF_ID, self.business_reject_ref_id.as_ref())
+            .field(tags::BUSINESS_REJECT_REASON, self.reason)
+            .field(tags::TEXT, &self.text)
+    }
+}
+
+/// A message the bridge wants delivered to the owning session.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Report {
+    Execution(ExecutionReport),
+    CancelReject(OrderCancelReject),
+    BusinessReject(BusinessMessageReject),
+    MarginCall(MarginCall),
+}
+
+impl Report {
+    /// Returns the key of the session the message must be sent to.
+    pub fn get_owner(&self) -> &str {
+        match self {
+            Report::Execution(report) => &report.owner,
+            Report::CancelReject(reject) => &reject.owner,
+            Report::BusinessReject(reject) => &reject.owner,
+            Report::MarginCall(call) => &call.owner,
+        }
+    }
+
+    /// Builds the FIX message; the session adds the standard header.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        match self {
+            Report::Execution(report) => report.to_builder(begin_string),
+            Report::CancelReject(reject) => reject.to_builder(begin_string),
+            Report::BusinessReject(reject) => reject.to_builder(begin_string),
+            Report::MarginCall(call) => call.to_builder(begin_string),
+        }
+    }
+
+    /// Builds the copy sent to drop-copy sessions, with Account (1) set to the owner, if the
+    /// report is an ExecutionReport.
+    pub fn to_drop_copy(&self, begin_string: &str) -> Option<FixMessageBuilder> {
+        match self {
+            Report::Execution(report) => Some(report.to_builder(begin_string).field(tags::ACCOUNT, &report.owner)),
+            Report::CancelReject(_) | Report::BusinessReject(_) | Report::MarginCall(_) => None,
+        }
+    }
+}
+
+/// The result of submitting an order to the book.
+#[derive(Debug)]
+pub struct Submission {
+    /// Engine id assigned to the order.
+    pub order_id: OrderId,
+    /// The order as held by the engine; its fill state reflects matching so far.
+    pub order: OrderPointer,
+    /// Trades produced by the submission.
+    pub trades: Trades,
+    /// ExecutionReports for the submitter and for every resting order that traded.
+    pub reports: Vec<ExecutionReport>,
+}
+
+/// An order the bridge submitted, remembered so fills and later messages can be reported
+/// against it.
+#[derive(Debug)]
+struct LiveOrder {
+    owner: String,
+    /// Symbol (55) of the order, empty if it had none.
+    symbol: String,
+    cl_ord_id: String,
+    side: Side,
+    flags: OrderFlags,
+    price: Option<Price>,
+    order_qty: Quantity,
+    cum_qty: Quantity,
+    /// Sum of price × quantity over all fills, for AvgPx.
+    notional: i64,
+}
+
+impl LiveOrder {
+    fn get_leaves_qty(&self) -> Quantity {
+        self.order_qty - self.cum_qty
+    }
+
+    fn get_avg_px(&self) -> f64 {
+        if self.cum_qty == 0 {
+            return 0.0;
+        }
+        self.notional as f64 / self.cum_qty as f64
+    }
+}
+
+/// Submits FIX orders to an [`Orderbook`], keeps the ClOrdID ↔ [`OrderId`] mapping and turns
+/// the outcome into ExecutionReports.
+///
+/// Orders are owned by a session key (e.g. the counterparty's CompID) so that fills of resting
+/// orders are reported to whoever entered them, not to the aggressor.
+///
+/// A bridge trades a single book, so a server with several instruments keeps one bridge per
+/// symbol, each built [`with_symbol`](Self::with_symbol).
+pub struct OrderBridge {
+    orderbook: Orderbook,
+    /// Symbol (55) orders must carry; any symbol is accepted if `None`.
+    symbol: Option<String>,
+    /// Largest OrderQty accepted; unlimited if `None`.
+    max_order_qty: Option<Quantity>,
+    next_order_id: OrderId,
+    next_exec_id: u64,
+    orders: HashMap<OrderId, LiveOrder>,
+    cl_ord_ids: HashMap<(String, String), OrderId>,
+    /// Limits by account and symbol; accounts without one are not limited.
+    position_limits: HashMap<(String, String), PositionLimit>,
+    positions: HashMap<(String, String), Position>,
+    /// Margin model; orders are not margined if `None`.
+    margin: Option<MarginRates>,
+    margin_accounts: HashMap<String, MarginAccount>,
+    /// Trades are not confirmed if `None`.
+    clearing: Option<Clearing>,
+}
+
+impl OrderBridge {
+    /// Creates a bridge in front of an empty book.
+    pub fn new() -> Self {
+        Self::with_orderbook(Orderbook::new(BTreeMap::new(), BTreeMap::new()))
+    }
+
+    /// Creates a bridge in front of an existing book.
+    pub fn with_orderbook(orderbook: Orderbook) -> Self {
+        Self {
+            orderbook,
+            symbol: None,
+            max_order_qty: None,
+            next_order_id: 1,
+            next_exec_id: 1,
+            orders: HashMap::new(),
+            cl_ord_ids: HashMap::new(),
+            position_limits: HashMap::new(),
+            positions: HashMap::new(),
+            margin: None,
+            margin_accounts: HashMap::new(),
+            clearing: None,
+        }
+    }
+
+    /// Only accepts orders for `symbol`; others are answered with a BusinessMessageReject.
+    pub fn with_symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    /// Rejects orders and replacements for more than `max_order_qty`.
+    pub fn with_max_order_qty(mut self, max_order_qty: Quantity) -> Self {
+        self.max_order_qty = Some(max_order_qty);
+        self
+    }
+
+    /// Holds `account` to `limit` in `symbol`; an empty symbol covers orders without one.
+    pub fn with_position_limit(mut self, account: &str, symbol: &str, limit: PositionLimit) -> Self {
+        self.position_limits.insert((account.to_string(), symbol.to_string()), limit);
+        self
+    }
+
+    /// Margins every order at `rates`; accounts without collateral cannot trade.
+    pub fn with_margin(mut self, rates: MarginRates) -> Self {
+        self.margin = Some(rates);
+        self
+    }
+
+    /// Adds `amount` to the collateral of `account`; a negative amount withdraws.
+    pub fn deposit_collateral(&mut self, account: &str, amount: i64) {
+        self.margin_accounts.entry(account.to_string()).or_default().collateral += amount;
+    }
+
+    /// Returns the equity and margin requirements of `account`, if orders are margined and
+    /// the book has a price to mark positions at.
+    pub fn get_margin_status(&self, account: &str) -> Option<MarginStatus> {
+        let mark = self.orderbook.get_reference_prices().get_current()?;
+        self.margin_status(account, None, mark)
+    }
+
+    /// Confirms every trade to clearing, charging `fees`.
+    pub fn with_clearing(mut self, fees: FeeSchedule) -> Self {
+        self.clearing = Some(Clearing::new(fees));
+        self
+    }
+
+    /// Returns the confirmations of the trades since the last call; none without clearing.
+    pub fn take_confirmations(&mut self) -> Vec<Confirmation> {
+        self.clearing.as_mut().map(Clearing::take_confirmations).unwrap_or_default()
+    }
+
+    /// Nets the trades of the day per account and symbol and starts a new day.
+    pub fn close_clearing_day(&mut self) -> Option<NettingReport> {
+        self.clearing.as_mut().map(Clearing::close_day)
+    }
+
+    /// Returns the position of `account` in `symbol` and the limit it is held to.
+    pub fn get_utilization(&self, account: &str, symbol: &str) -> Utilization {
+        let key = (account.to_string(), symbol.to_string());
+        Utilization {
+            position: self.positions.get(&key).copied().unwrap_or_default(),
+            limit: self.position_limits.get(&key).copied(),
+        }
+    }
+
+    /// Returns the book the bridge submits to.
+    pub fn get_orderbook(&self) -> &Orderbook {
+        &self.orderbook
+    }
+
+    /// Returns the engine id of `owner`'s live order with `cl_ord_id`, if any.
+    pub fn get_order_id(&self, owner: &str, cl_ord_id: &str) -> Option<OrderId> {
+        self.cl_ord_ids.get(&(owner.to_string(), cl_ord_id.to_string())).copied()
+    }
+
+    /// Handles an application message from `owner` and returns the resulting reports.
+    ///
+    /// Requests that fail are answered as described in the [module docs](self) rather than
+    /// dropped.
+    pub fn on_message(&mut self, owner: &str, message: &FixMessage) -> Vec<Report> {
+        let mut reports = match message.get_msg_type() {
+            Some(NEW_ORDER_SINGLE) => match self.on_new_order_single(owner, message) {
+                Ok(submission) => submission.reports.into_iter().map(Report::Execution).collect(),
+                Err(err) if err.get_business_reject_reason().is_some() => vec![Report::BusinessReject(BusinessMessageReject::new(owner, message, &err))],
+                Err(err) => vec![Report::Execution(self.reject_report(owner, message, &err))],
+            },
+            Some(ORDER_CANCEL_REQUEST) => self.on_order_cancel_request(owner, message),
+            Some(ORDER_CANCEL_REPLACE_REQUEST) => self.on_order_cancel_replace_request(owner, message),
+            msg_type => {
+                let err = BridgeError::UnsupportedMessageType(msg_type.unwrap_or_default().to_string());
+                vec![Report::BusinessReject(BusinessMessageReject::new(owner, message, &err))]
+            }
+        };
+        reports.extend(self.margin_calls().into_iter().map(Report::MarginCall));
+        reports
+    }
+
+    /// Cancels `owner`'s order identified by OrigClOrdID.
+    pub fn on_order_cancel_request(&mut self, owner: &str, message: &FixMessage) -> Vec<Report> {
+        let (order_id, cl_ord_id) = match self.resolve(owner, message, '1') {
+            Ok(resolved) => resolved,
+            Err(reject) => return vec![Report::CancelReject(reject)],
+        };
+        self.orderbook.cancel_order(order_id);
+        let mut report = self.report(order_id, ExecType::Canceled, OrdStatus::Canceled, None);
+        report.orig_cl_ord_id = Some(std::mem::replace(&mut report.cl_ord_id, cl_ord_id));
+        self.forget(order_id);
+        vec![Report::Execution(report)]
+    }
+
+    /// Replaces the price and quantity of `owner`'s order identified by OrigClOrdID.
+    ///
+    /// Side and order type cannot be changed, and the new OrderQty must exceed the quantity
+    /// already filled.
+    pub fn on_order_cancel_replace_request(&mut self, owner: &str, message: &FixMessage) -> Vec<Report> {
+        let (order_id, cl_ord_id) = match self.resolve(owner, message, '2') {
+            Ok(resolved) => resolved,
+            Err(reject) => return vec![Report::CancelReject(reject)],
+        };
+        let live = &self.orders[&order_id];
+        let replacement = match self.read_order(message) {
+            Ok(replacement) if replacement.side != live.side => Err(invalid(tags::SIDE, "cannot be changed".to_string())),
+            Ok(replacement) if replacement.price.is_none() => Err(invalid(tags::ORD_TYPE, "only limit orders can be replaced".to_string())),
+            Ok(replacement) if replacement.quantity <= live.cum_qty => {
+                Err(invalid(tags::ORDER_QTY, format!("{} does not exceed the filled quantity {}", replacement.quantity, live.cum_qty)))
+            }
+            // The engine keeps the order's flags, so a short sale stays one whatever Side says.
+            Ok(replacement) if live.flags.short_sell => {
+                self.orderbook.check_short_sale(replacement.price.unwrap()).map(|()| replacement).map_err(BridgeError::RiskReject)
+            }
+            result => result,
+        };
+        let replacement = replacement.and_then(|replacement| {
+            let grown = (replacement.quantity - live.cum_qty).saturating_sub(live.get_leaves_qty());
+            self.check_risk(owner, &live.symbol, live.side, grown, replacement.price).map(|()| replacement)
+        });
+        let replacement = match replacement {
+            Ok(replacement) => replacement,
+            Err(err) => return vec![Report::CancelReject(self.cancel_reject(owner, message, Some(order_id), '2', &err))],
+        };
+
+        let live = self.orders.get_mut(&order_id).unwrap();
+        let orig_cl_ord_id = std::mem::replace(&mut live.cl_ord_id, cl_ord_id.clone());
+        let position = self.positions.entry((owner.to_string(), live.symbol.clone())).or_default();
+        position.on_release(live.side, live.get_leaves_qty());
+        live.price = replacement.price;
+        live.order_qty = replacement.quantity;
+        let leaves_qty = live.get_leaves_qty();
+        *position = position.with_open(live.side, leaves_qty);
+        let status = if live.cum_qty == 0 { OrdStatus::New } else { OrdStatus::PartiallyFilled };
+        self.cl_ord_ids.remove(&(owner.to_string(), orig_cl_ord_id.clone()));
+        self.cl_ord_ids.insert((owner.to_string(), cl_ord_id), order_id);
+
+        let mut report = self.report(order_id, ExecType::Replaced, status, None);
+        report.orig_cl_ord_id = Some(orig_cl_ord_id);
+        let mut reports = vec![Report::Execution(report)];
+
+        let modify = OrderModify::new(order_id, replacement.side, replacement.price.unwrap(), leaves_qty);
+        for trade in self.orderbook.modify_order(modify) {
+            reports.extend(self.on_trade(order_id, &trade).into_iter().map(Report::Execution));
+        }
+        reports
+    }
+
+    /// Finds the live order a cancel or replace request refers to.
+    ///
+    /// Returns the engine id and the request's new ClOrdID, or the OrderCancelReject to send.
+    fn resolve(&self, owner: &str, message: &FixMessage, response_to: char) -> Result<(OrderId, String), OrderCancelReject> {
+        let reject = |order_id, err: BridgeError| Err(self.cancel_reject(owner, message, order_id, response_to, &err));
+        let Some(cl_ord_id) = message.get_field(tags::CL_ORD_ID) else {
+            return reject(None, missing(tags::CL_ORD_ID));
+        };
+        let Some(orig_cl_ord_id) = message.get_field(tags::ORIG_CL_ORD_ID) else {
+            return reject(None, missing(tags::ORIG_CL_ORD_ID));
+        };
+        let Some(order_id) = self.get_order_id(owner, orig_cl_ord_id) else {
+            return reject(None, BridgeError::UnknownOrder(orig_cl_ord_id.to_string()));
+        };
+        if cl_ord_id != orig_cl_ord_id && self.get_order_id(owner, cl_ord_id).is_some() {
+            return reject(Some(order_id), BridgeError::DuplicateClOrdId(cl_ord_id.to_string()));
+        }
+        Ok((order_id, cl_ord_id.to_string()))
+    }
+
+    fn cancel_reject(&self, owner: &str, message: &FixMessage, order_id: Option<OrderId>, response_to: char, err: &BridgeError) -> OrderCancelReject {
+        let ord_status = match order_id.and_then(|order_id| self.orders.get(&order_id)) {
+            Some(live) if live.cum_qty > 0 => OrdStatus::PartiallyFilled,
+            Some(_) => OrdStatus::New,
+            None => OrdStatus::Rejected,
+        };
+        OrderCancelReject {
+            owner: owner.to_string(),
+            order_id,
+            cl_ord_id: message.get_field(tags::CL_ORD_ID).unwrap_or("NONE").to_string(),
+            orig_cl_ord_id: message.get_field(tags::ORIG_CL_ORD_ID).unwrap_or("NONE").to_string(),
+            ord_status,
+            response_to,
+            reason: err.get_cxl_rej_reason(),
+            text: err.to_string(),
+        }
+    }
+
+    /// Reads an order or replacement and checks it against the symbol and risk limits.
+    fn read_order(&self, message: &FixMessage) -> Result<NewOrder, BridgeError> {
+        if let Some(symbol) = &self.symbol {
+            match message.get_field(tags::SYMBOL) {
+                Some(value) if value == symbol => {}
+                Some(value) => return Err(BridgeError::UnknownSymbol(value.to_string())),
+                None => return Err(missing(tags::SYMBOL)),
+            }
+        }
+        let order = NewOrder::from_message(message)?;
+        if let Some(max_order_qty) = self.max_order_qty.filter(|max_order_qty| order.quantity > *max_order_qty) {
+            return Err(BridgeError::RiskReject(format!("OrderQty {} exceeds the limit of {}", order.quantity, max_order_qty)));
+        }
+        if let (true, Some(price)) = (order.flags.short_sell, order.price) {
+            self.orderbook.check_short_sale(price).map_err(BridgeError::RiskReject)?;
+        }
+        Ok(order)
+    }
+
+    /// Checks that `quantity` more open on `side` at `price` keeps `account` within its
+    /// position limit in `symbol` and its buying power.
+    fn check_risk(&self, account: &str, symbol: &str, side: Side, quantity: Quantity, price: Option<Price>) -> Result<(), BridgeError> {
+        let key = (account.to_string(), symbol.to_string());
+        let position = self.positions.get(&key).copied().unwrap_or_default().with_open(side, quantity);
+        if let Some(limit) = self.position_limits.get(&key) {
+            limit.check(&position).map_err(|reason| BridgeError::RiskReject(format!("{} in {}", reason, display_symbol(symbol))))?;
+        }
+        if self.margin.is_none() {
+            return Ok(());
+        }
+        let mark = self.orderbook.get_reference_prices().get_current().or(price)
+            .ok_or_else(|| BridgeError::RiskReject("no price to margin the order at".to_string()))?;
+        let status = self.margin_status(account, Some((symbol, position)), mark).unwrap_or_default();
+        if status.get_buying_power() < 0 {
+            return Err(BridgeError::RiskReject(format!("initial margin {} exceeds the equity of {}", status.initial, status.equity)));
+        }
+        Ok(())
+    }
+
+    /// Computes the margin state of `account` at `mark`, with its position in a symbol
+    /// replaced if `candidate` is given.
+    fn margin_status(&self, account: &str, candidate: Option<(&str, Position)>, mark: Price) -> Option<MarginStatus> {
+        let rates = self.margin?;
+        let mut positions: HashMap<&str, Position> = self.positions.iter()
+            .filter(|((owner, _), _)| owner == account)
+            .map(|((_, symbol), position)| (symbol.as_str(), *position))
+            .collect();
+        if let Some((symbol, position)) = candidate {
+            positions.insert(symbol, position);
+        }
+        let margin_account = self.margin_accounts.get(account).copied().unwrap_or_default();
+        Some(rates.get_status(&margin_account, positions.values(), mark))
+    }
+
+    /// Calls every account whose equity fell below its maintenance margin, once until it
+    /// recovers.
+    fn margin_calls(&mut self) -> Vec<MarginCall> {
+        let Some(mark) = self.orderbook.get_reference_prices().get_current().filter(|_| self.margin.is_some()) else {
+            return Vec::new();
+        };
+        let mut accounts: Vec<String> = self.positions.keys().map(|(account, _)| account.clone()).collect();
+        accounts.sort_unstable();
+        accounts.dedup();
+
+        let mut calls = Vec::new();
+        for account in accounts {
+            let status = self.margin_status(&account, None, mark).unwrap_or_default();
+            let margin_account = self.margin_accounts.entry(account.clone()).or_default();
+            if status.is_margin_call() && !margin_account.called {
+                calls.push(MarginCall { owner: account, status, mark });
+            }
+            margin_account.called = status.is_margin_call();
+        }
+        calls
+    }
+
+    /// Translates a NewOrderSingle from `owner` and submits it to the book.
+    ///
+    /// # Errors
+    /// Returns a [`BridgeError`] if the message is invalid, its ClOrdID is in use or it fails
+    /// the symbol or risk checks; nothing is submitted in that case.
+    pub fn on_new_order_single(&mut self, owner: &str, message: &FixMessage) -> Result<Submission, BridgeError> {
+        let new_order = self.read_order(message)?;
+        let key = (owner.to_string(), new_order.cl_ord_id.clone());
+        if self.cl_ord_ids.contains_key(&key) {
+            return Err(BridgeError::DuplicateClOrdId(new_order.cl_ord_id));
+        }
+        let symbol = message.get_field(tags::SYMBOL).unwrap_or_default().to_string();
+        self.check_risk(owner, &symbol, new_order.side, new_order.quantity, new_order.price)?;
+        let position = self.positions.entry((owner.to_string(), symbol.clone())).or_default();
+        *position = position.with_open(new_order.side, new_order.quantity);
+
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+        self.cl_ord_ids.insert(key, order_id);
+        self.orders.insert(order_id, LiveOrder {
+            owner: owner.to_string(),
+            symbol,
+            cl_ord_id: new_order.cl_ord_id.clone(),
+            side: new_order.side,
+            flags: new_order.flags,
+            price: new_order.price,
+            order_qty: new_order.quantity,
+            cum_qty: 0,
+            notional: 0,
+        });
+        let mut reports = vec![self.report(order_id, ExecType::New, OrdStatus::New, None)];
+
+        let order = match new_order.price {
+            Some(price) => Order::new(new_order.order_type, order_id, new_order.side, price, new_order.quantity),
+            None => Order::new_market(order_id, new_order.side, new_order.quantity),
+        };
+        order.lock().unwrap().set_flags(new_order.flags);
+        let trades = self.orderbook.add_order(order.clone());
+        for trade in &trades {
+            reports.extend(self.on_trade(order_id, trade));
+        }
+
+        if self.orders.contains_key(&order_id) && !self.orderbook.contains_order(order_id) {
+            reports.push(self.report(order_id, ExecType::Canceled, OrdStatus::Canceled, None));
+            self.forget(order_id);
+        }
+        Ok(Submission { order_id, order, trades, reports })
+    }
+
+    /// Builds the rejected ExecutionReport for a message the bridge could not accept.
+    pub fn reject_report(&mut self, owner: &str, message: &FixMessage, err: &BridgeError) -> ExecutionReport {
+        let exec_id = self.take_exec_id();
+        let side = fields::Side::read(message).unwrap_or(fields::Side::Buy);
+        let capacity = OrderCapacity::read(message).ok().map(orderbook::Capacity::from);
+        ExecutionReport {
+            owner: owner.to_string(),
+            order_id: None,
+            cl_ord_id: message.get_field(tags::CL_ORD_ID).unwrap_or("NONE").to_string(),
+            orig_cl_ord_id: None,
+            exec_id,
+            exec_type: ExecType::Rejected,
+            ord_status: OrdStatus::Rejected,
+            side: side.into(),
+            flags: OrderFlags { capacity, short_sell: side == fields::Side::SellShort },
+            order_qty: message.get_uint(tags::ORDER_QTY).ok().and_then(|qty| Quantity::try_from(qty).ok()).unwrap_or(0),
+            price: None,
+            last_fill: None,
+            cum_qty: 0,
+            leaves_qty: 0,
+            avg_px: 0.0,
+            text: Some(err.to_string()),
+        }
+    }
+
+    /// Applies both sides of a trade caused by the order `aggressor` and confirms them to
+    /// clearing.
+    fn on_trade(&mut self, aggressor: OrderId, trade: &Trade) -> Vec<ExecutionReport> {
+        let trade_id = self.clearing.as_mut().map(Clearing::take_trade_id);
+        let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+        // Both sides clear at the resting order's price, as the trade printed.
+        let price = if bid.order_id == aggressor { ask.price } else { bid.price };
+        let mut reports = Vec::new();
+        for fill in [bid, ask] {
+            if let (Some(trade_id), Some(clearing), Some(live)) = (trade_id, self.clearing.as_mut(), self.orders.get(&fill.order_id)) {
+                clearing.confirm(Confirmation {
+                    trade_id,
+                    account: live.owner.clone(),
+                    symbol: live.symbol.clone(),
+                    side: live.side,
+                    price,
+                    quantity: fill.quantity,
+                    liquidity: if fill.order_id == aggressor { Liquidity::Taker } else { Liquidity::Maker },
+                    fee: 0,
+                });
+            }
+            reports.extend(self.on_fill(fill, price));
+        }
+        reports
+    }
+
+    /// Applies one side of a trade at the execution `price` to its live order and reports it.
+    fn on_fill(&mut self, fill: TradeInfo, price: Price) -> Option<ExecutionReport> {
+        let live = self.orders.get_mut(&fill.order_id)?;
+        live.cum_qty += fill.quantity;
+        self.positions.entry((live.owner.clone(), live.symbol.clone())).or_default().on_fill(live.side, fill.quantity);
+        let notional = i64::from(price) * i64::from(fill.quantity);
+        let cash = &mut self.margin_accounts.entry(live.owner.clone()).or_default().cash;
+        match live.side {
+            Side::Buy => *cash -= notional,
+            Side::Sell => *cash += notional,
+        }
+        live.notional += notional;
+        let status = if live.get_leaves_qty() == 0 { OrdStatus::Filled } else { OrdStatus::PartiallyFilled };
+        let report = self.report(fill.order_id, ExecType::Trade, status, Some((fill.quantity, price)));
+        if status == OrdStatus::Filled {
+            self.forget(fill.order_id);
+        }
+        Some(report)
+    }
+
+    /// Builds a report from the current state of a live order.
+    ///
+    /// A canceled report has no leaves quantity.
+    fn report(&mut self, order_id: OrderId, exec_type: ExecType, ord_status: OrdStatus, last_fill: Option<(Quantity, Price)>) -> ExecutionReport {
+        let exec_id = self.take_exec_id();
+        let live = &self.orders[&order_id];
+        ExecutionReport {
+            owner: live.owner.clone(),
+            order_id: Some(order_id),
+            cl_ord_id: live.cl_ord_id.clone(),
+            orig_cl_ord_id: None,
+            exec_id,
+            exec_type,
+            ord_status,
+            side: live.side,
+            flags: live.flags,
+            order_qty: live.order_qty,
+            price: live.price,
+            last_fill,
+            cum_qty: live.cum_qty,
+            leaves_qty: if ord_status == OrdStatus::Canceled { 0 } else { live.get_leaves_qty() },
+            avg_px: live.get_avg_px(),
+            text: None,
+        }
+    }
+
+    fn take_exec_id(&mut self) -> u64 {
+        self.next_exec_id += 1;
+        self.next_exec_id - 1
+    }
+
+    /// Drops a live order that left the book, releasing whatever it had open.
+    fn forget(&mut self, order_id: OrderId) {
+        if let Some(live) = self.orders.remove(&order_id) {
+            if let Some(position) = self.positions.get_mut(&(live.owner.clone(), live.symbol.clone())) {
+                position.on_release(live.side, live.get_leaves_qty());
+            }
+            self.cl_ord_ids.remove(&(live.owner, live.cl_ord_id));
+        }
+    }
+}
+
+impl Default for OrderBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn display_symbol(symbol: &str) -> &str {
+    if symbol.is_empty() { "orders without a Symbol" } else { symbol }
+}
+
+/// Returns the Side (54) of an order, `5` for a short sale.
+fn wire_side(side: Side, flags: OrderFlags) -> fields::Side {
+    if flags.short_sell {
+        fields::Side::SellShort
+    } else {
+        fields::Side::from(side)
+    }
+}
+
+/// Reads an enumerated field, naming it in the error if the value is not supported.
+fn read_enum<T: FieldEnum>(message: &FixMessage, name: &str) -> Result<T, BridgeError> {
+    T::read(message).map_err(|err| match err {
+        FixError::InvalidValue { value, .. } => invalid(T::TAG, format!("unsupported {} {}", name, value)),
+        _ => missing(T::TAG),
+    })
+}
+
+fn parse_price(message: &FixMessage) -> Result<Price, BridgeError> {
+    let price = message.get_float(tags::PRICE).map_err(|_| missing_or_invalid(message, tags::PRICE))?;
+    if price.fract() != 0.0 || price < Price::MIN as f64 || price > Price::MAX as f64 {
+        return Err(invalid(tags::PRICE, format!("{} is not a whole number of ticks", price)));
+    }
+    Ok(price as Price)
+}
+
+fn missing(tag: Tag) -> BridgeError {
+    invalid(tag, "required field missing".to_string())
+}
+
+fn missing_or_invalid(message: &FixMessage, tag: Tag) -> BridgeError {
+    match message.get_field(tag) {
+        Some(value) => invalid(tag, format!("cannot parse {}", value)),
+        None => missing(tag),
+    }
+}
+
+fn invalid(tag: Tag, reason: String) -> BridgeError {
+    BridgeError::InvalidField { tag, reason }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use orderbook::ReferencePrices;
+    use crate::fix::FixMessageBuilder;
+
+    fn order(cl_ord_id: &str, side: char, ord_type: char, price: Option<&str>, quantity: u32, tif: Option<char>) -> FixMessage {
+        FixMessageBuilder::new("FIX.4.4", NEW_ORDER_SINGLE)
+            .field(tags::CL_ORD_ID, cl_ord_id)
+            .field(tags::SIDE, side)
+            .field(tags::ORD_TYPE, ord_type)
+            .optional_field(tags::PRICE, price)
+            .field(tags::ORDER_QTY, quantity)
+            .optional_field(tags::TIME_IN_FORCE, tif)
+            .build_message()
+    }
+
+    #[test]
+    fn test_new_order_mapping(){
+        let limit = NewOrder::from_message(&order("A", '1', '2', Some("101"), 10, Some('1'))).unwrap();
+        assert_eq!(limit, NewOrder {
+            cl_ord_id: "A".to_string(),
+            side: Side::Buy,
+            order_type: OrderType::GoodTillCancel,
+            price: Some(101),
+            quantity: 10,
+            flags: OrderFlags::default(),
+        });
+
+        let day = NewOrder::from_message(&order("B", '2', '2', Some("99.0"), 5, None)).unwrap();
+        assert_eq!((day.side, day.order_type, day.price), (Side::Sell, OrderType::GoodForDay, Some(99)));
+        assert_eq!(NewOrder::from_message(&order("C", '1', '2', Some("1"), 1, Some('3'))).unwrap().order_type, OrderType::FillAndKill);
+        assert_eq!(NewOrder::from_message(&order("D", '1', '2', Some("1"), 1, Some('4'))).unwrap().order_type, OrderType::FillOrKill);
+
+        let market = NewOrder::from_message(&order("E", '2', '1', None, 3, None)).unwrap();
+        assert_eq!((market.order_type, market.price), (OrderType::Market, None));
+
+        for new_order in [limit, day, market] {
+            assert_eq!(NewOrder::from_message(&new_order.to_builder("FIX.4.4").build_message()), Ok(new_order));
+        }
+    }
+
+    #[test]
+    fn test_new_order_rejects_invalid_fields(){
+        let field = |message| match NewOrder::from_message(&message) {
+            Err(BridgeError::InvalidField { tag, .. }) => tag,
+            other => panic!("expected an invalid field, got {:?}", other),
+        };
+        assert_eq!(field(order("A", '7', '2', Some("1"), 1, None)), tags::SIDE);
+        assert_eq!(field(order("A", '1', '2', None, 1, None)), tags::PRICE);
+        assert_eq!(field(order("A", '1', '2', Some("1.5"), 1, None)), tags::PRICE);
+        assert_eq!(field(order("A", '1', '2', Some("1"), 0, None)), tags::ORDER_QTY);
+        assert_eq!(field(order("A", '1', 'P', Some("1"), 1, None)), tags::ORD_TYPE);
+        assert_eq!(field(order("A", '1', '2', Some("1"), 1, Some('6'))), tags::TIME_IN_FORCE);
+    }
+
+    #[test]
+    fn test_bridge_submits_to_book(){
+        let mut bridge = OrderBridge::new();
+
+        let resting = bridge.on_new_order_single("MAKER", &order("ASK-1", '2', '2', Some("100"), 10, Some('1'))).unwrap();
+        assert!(resting.trades.is_empty());
+        assert_eq!(bridge.get_order_id("MAKER", "ASK-1"), Some(resting.order_id));
+        assert_eq!(
+            bridge.on_new_order_single("MAKER", &order("ASK-1", '2', '2', Some("100"), 10, Some('1'))).unwrap_err(),
+            BridgeError::DuplicateClOrdId("ASK-1".to_string())
+        );
+
+        let aggressor = bridge.on_new_order_single("TAKER", &order("BID-1", '1', '2', Some("100"), 4, Some('3'))).unwrap();
+        assert_eq!(aggressor.trades.len(), 1);
+        assert_eq!(aggressor.trades[0].get_bid_trade().quantity, 4);
+        assert!(aggressor.order.lock().unwrap().is_filled());
+        assert_eq!(bridge.get_order_id("TAKER", "BID-1"), None);
+
+        let sweep = bridge.on_new_order_single("TAKER", &order("BID-2", '1', '1', None, 6, None)).unwrap();
+        assert_eq!(sweep.trades.len(), 1);
+        assert_eq!(bridge.get_order_id("MAKER", "ASK-1"), None);
+        assert_eq!(bridge.get_orderbook().size(), 0);
+    }
+
+    fn executions(reports: Vec<Report>) -> Vec<ExecutionReport> {
+        reports
+            .into_iter()
+            .map(|report| match report {
+                Report::Execution(report) => report,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    fn summary(reports: &[ExecutionReport]) -> Vec<(&str, &str, ExecType, OrdStatus, Quantity, Quantity)> {
+        reports
+            .iter()
+            .map(|r| (r.owner.as_str(), r.cl_ord_id.as_str(), r.exec_type, r.ord_status, r.cum_qty, r.leaves_qty))
+            .collect()
+    }
+
+    #[test]
+    fn test_execution_reports_for_fills(){
+        let mut bridge = OrderBridge::new();
+        let reports = executions(bridge.on_message("MAKER", &order("ASK-1", '2', '2', Some("100"), 5, Some('1'))));
+        assert_eq!(summary(&reports), vec![("MAKER", "ASK-1", ExecType::New, OrdStatus::New, 0, 5)]);
+        bridge.on_message("MAKER", &order("ASK-2", '2', '2', Some("102"), 5, Some('1')));
+
+        let reports = executions(bridge.on_message("TAKER", &order("BID-1", '1', '2', Some("102"), 8, Some('1'))));
+        assert_eq!(summary(&reports), vec![
+            ("TAKER", "BID-1", ExecType::New, OrdStatus::New, 0, 8),
+            ("TAKER", "BID-1", ExecType::Trade, OrdStatus::PartiallyFilled, 5, 3),
+            ("MAKER", "ASK-1", ExecType::Trade, OrdStatus::Filled, 5, 0),
+            ("TAKER", "BID-1", ExecType::Trade, OrdStatus::Filled, 8, 0),
+            ("MAKER", "ASK-2", ExecType::Trade, OrdStatus::PartiallyFilled, 3, 2),
+        ]);
+        // Both sides are told the price the trade printed at, not their own limits.
+        assert_eq!(reports[1].last_fill, Some((5, 100)));
+        assert_eq!(reports[2].last_fill, Some((5, 100)));
+        assert_eq!(reports[3].last_fill, Some((3, 102)));
+        assert_eq!(reports[3].avg_px, 100.75);
+
+        let message = reports[4].to_builder("FIX.4.4").build_message();
+        assert_eq!(message.get_msg_type(), Some(EXECUTION_REPORT));
+        assert_eq!(ExecType::read(&message), Ok(ExecType::Trade));
+        assert_eq!((message.get_uint(tags::LAST_QTY), message.get_int(tags::LAST_PX)), (Ok(3), Ok(102)));
+        assert_eq!((message.get_uint(tags::CUM_QTY), message.get_uint(tags::LEAVES_QTY)), (Ok(3), Ok(2)));
+        assert_eq!(message.get_float(tags::AVG_PX), Ok(102.0));
+    }
+
+    #[test]
+    fn test_execution_reports_for_cancel_and_reject(){
+        let mut bridge = OrderBridge::new();
+        bridge.on_message("MAKER", &order("ASK-1", '2', '2', Some("100"), 2, Some('1')));
+
+        let reports = executions(bridge.on_message("TAKER", &order("IOC", '1', '2', Some("100"), 5, Some('3'))));
+        assert_eq!(summary(&reports), vec![
+            ("TAKER", "IOC", ExecType::New, OrdStatus::New, 0, 5),
+            ("TAKER", "IOC", ExecType::Trade, OrdStatus::PartiallyFilled, 2, 3),
+            ("MAKER", "ASK-1", ExecType::Trade, OrdStatus::Filled, 2, 0),
+            ("TAKER", "IOC", ExecType::Canceled, OrdStatus::Canceled, 2, 0),
+        ]);
+
+        let reports = executions(bridge.on_message("TAKER", &order("BAD", '1', '2', Some("1.5"), 5, None)));
+        assert_eq!(summary(&reports), vec![("TAKER", "BAD", ExecType::Rejected, OrdStatus::Rejected, 0, 0)]);
+        assert_eq!(reports[0].order_id, None);
+        assert!(reports[0].text.as_deref().unwrap().contains("44"));
+    }
+
+    fn request(msg_type: &str, cl_ord_id: &str, orig_cl_ord_id: &str) -> FixMessageBuilder {
+        FixMessageBuilder::new("FIX.4.4", msg_type)
+            .field(tags::CL_ORD_ID, cl_ord_id)
+            .field(tags::ORIG_CL_ORD_ID, orig_cl_ord_id)
+    }
+
+    #[test]
+    fn test_cancel_request(){
+        let mut bridge = OrderBridge::new();
+        bridge.on_message("MAKER", &order("ASK-1", '2', '2', Some("100"), 5, Some('1')));
+
+        let reports = executions(bridge.on_message("MAKER", &request(ORDER_CANCEL_REQUEST, "CXL-1", "ASK-1").build_message()));
+        assert_eq!(summary(&reports), vec![("MAKER", "CXL-1", ExecType::Canceled, OrdStatus::Canceled, 0, 0)]);
+        assert_eq!(reports[0].orig_cl_ord_id.as_deref(), Some("ASK-1"));
+        assert_eq!(bridge.get_orderbook().size(), 0);
+
+        let rejects = bridge.on_message("MAKER", &request(ORDER_CANCEL_REQUEST, "CXL-2", "ASK-1").build_message());
+        let Report::CancelReject(reject) = &rejects[0] else { panic!("expected a cancel reject") };
+        assert_eq!((reject.reason, reject.response_to, reject.ord_status), (cxl_rej_reason::UNKNOWN_ORDER, '1', OrdStatus::Rejected));
+        let message = rejects[0].to_builder("FIX.4.4").build_message();
+        assert_eq!(message.get_msg_type(), Some(ORDER_CANCEL_REJECT));
+        assert_eq!(message.get_field(tags::ORIG_CL_ORD_ID), Some("ASK-1"));
+        assert_eq!(message.get_uint(tags::CXL_REJ_REASON), Ok(1));
+    }
+
+    #[test]
+    fn test_cancel_replace_request(){
+        let mut bridge = OrderBridge::new();
+        bridge.on_message("MAKER", &order("BID-1", '1', '2', Some("99"), 10, Some('1')));
+        bridge.on_message("TAKER", &order("ASK-1", '2', '2', Some("99"), 4, Some('1')));
+        bridge.on_message("TAKER", &order("ASK-2", '2', '2', Some("101"), 3, Some('1')));
+
+        let replace = |cl_ord_id: &str, orig: &str, price: &str, quantity: u32| {
+            request(ORDER_CANCEL_REPLACE_REQUEST, cl_ord_id, orig)
+                .field(tags::SIDE, '1')
+                .field(tags::ORD_TYPE, '2')
+                .field(tags::PRICE, price)
+                .field(tags::ORDER_QTY, quantity)
+                .build_message()
+        };
+        // Another session cannot touch the order.
+        assert!(matches!(bridge.on_message("TAKER", &replace("R-1", "BID-1", "101", 12))[..], [Report::CancelReject(_)]));
+
+        // Reprice through the ask: 4 already filled, 8 more wanted, 3 available at 101.
+        let reports = executions(bridge.on_message("MAKER", &replace("R-1", "BID-1", "101", 12)));
+        assert_eq!(summary(&reports), vec![
+            ("MAKER", "R-1", ExecType::Replaced, OrdStatus::PartiallyFilled, 4, 8),
+            ("MAKER", "R-1", ExecType::Trade, OrdStatus::PartiallyFilled, 7, 5),
+            ("TAKER", "ASK-2", ExecType::Trade, OrdStatus::Filled, 3, 0),
+        ]);
+        assert_eq!(reports[0].orig_cl_ord_id.as_deref(), Some("BID-1"));
+        assert_eq!(bridge.get_order_id("MAKER", "BID-1"), None);
+        assert!(bridge.get_order_id("MAKER", "R-1").is_some());
+        let infos = bridge.get_orderbook().get_order_infos();
+        assert_eq!(infos.get_bids().iter().map(|level| (level.price, level.quantity)).collect::<Vec<_>>(), vec![(101, 5)]);
+
+        // The new quantity must exceed what has been filled.
+        let rejects = bridge.on_message("MAKER", &replace("R-2", "R-1", "101", 7));
+        let Report::CancelReject(reject) = &rejects[0] else { panic!("expected a cancel reject") };
+        assert_eq!((reject.response_to, reject.ord_status), ('2', OrdStatus::PartiallyFilled));
+    }
+
+    #[test]
+    fn test_business_rejects(){
+        let mut bridge = OrderBridge::new().with_symbol("ACME").with_max_order_qty(100);
+        let order = |symbol: &str, quantity: u32| {
+            FixMessageBuilder::new("FIX.4.4", NEW_ORDER_SINGLE)
+                .field(tags::MSG_SEQ_NUM, 7)
+                .field(tags::CL_ORD_ID, "1")
+                .field(tags::SYMBOL, symbol)
+                .field(tags::SIDE, '1')
+                .field(tags::ORD_TYPE, '2')
+                .field(tags::PRICE, "100")
+                .field(tags::ORDER_QTY, quantity)
+                .build_message()
+        };
+
+        let reports = bridge.on_message("TAKER", &order("OTHER", 10));
+        let Report::BusinessReject(reject) = &reports[0] else { panic!("expected a business reject") };
+        assert_eq!((reject.ref_seq_num, reject.ref_msg_type.as_str(), reject.reason), (Some(7), NEW_ORDER_SINGLE, business_reject_reason::UNKNOWN_SECURITY));
+        let message = reports[0].to_builder("FIX.4.4").build_message();
+        assert_eq!(message.get_msg_type(), Some(BUSINESS_MESSAGE_REJECT));
+        assert_eq!(message.get_field(tags::BUSINESS_REJECT_REF_ID), Some("1"));
+        assert_eq!(message.get_field(tags::TEXT), Some("Unknown symbol OTHER"));
+
+        let reports = executions(bridge.on_message("TAKER", &order("ACME", 500)));
+        assert_eq!(summary(&reports), vec![("TAKER", "1", ExecType::Rejected, OrdStatus::Rejected, 0, 0)]);
+        assert_eq!(reports[0].text.as_deref(), Some("Risk reject: OrderQty 500 exceeds the limit of 100"));
+        assert_eq!(executions(bridge.on_message("TAKER", &order("ACME", 100)))[0].exec_type, ExecType::New);
+
+        let reports = bridge.on_message("TAKER", &FixMessageBuilder::new("FIX.4.4", "E").build_message());
+        let Report::BusinessReject(reject) = &reports[0] else { panic!("expected a business reject") };
+        assert_eq!((reject.ref_seq_num, reject.reason), (None, business_reject_reason::UNSUPPORTED_MESSAGE_TYPE));
+        assert_eq!(reject.text, "Unsupported MsgType E");
+    }
+
+    #[test]
+    fn test_position_limits(){
+        let limit = PositionLimit { max_net: Some(10), max_gross: None };
+        let mut bridge = OrderBridge::new().with_position_limit("MAKER", "", limit);
+        bridge.on_message("MAKER", &order("BID-1", '1', '2', Some("99"), 8, Some('1')));
+
+        let reports = executions(bridge.on_message("MAKER", &order("BID-2", '1', '2', Some("98"), 3, Some('1'))));
+        assert_eq!(reports[0].exec_type, ExecType::Rejected);
+        assert_eq!(reports[0].text.as_deref(), Some("Risk reject: worst-case net position 11 exceeds the limit of 10 in orders without a Symbol"));
+
+        // Fills keep the quantity in the position; a cancel gives it back.
+        bridge.on_message("TAKER", &order("ASK-1", '2', '2', Some("99"), 5, Some('1')));
+        bridge.on_message("MAKER", &order("BID-2", '1', '2', Some("98"), 2, Some('1')));
+        assert_eq!(bridge.get_utilization("MAKER", "").position, Position { net: 5, open_buys: 5, open_sells: 0 });
+        bridge.on_message("MAKER", &request(ORDER_CANCEL_REQUEST, "CXL-1", "BID-2").build_message());
+        let utilization = bridge.get_utilization("MAKER", "");
+        assert_eq!((utilization.position.get_worst_net(), utilization.get_net_ratio()), (8, Some(0.8)));
+
+        // A replacement may only grow the order within the limit.
+        let replace = |cl_ord_id: &str, quantity: u32| {
+            request(ORDER_CANCEL_REPLACE_REQUEST, cl_ord_id, "BID-1")
+                .field(tags::SIDE, '1')
+                .field(tags::ORD_TYPE, '2')
+                .field(tags::PRICE, "99")
+                .field(tags::ORDER_QTY, quantity)
+                .build_message()
+        };
+        assert!(matches!(bridge.on_message("MAKER", &replace("R-1", 11))[..], [Report::CancelReject(_)]));
+        assert_eq!(executions(bridge.on_message("MAKER", &replace("R-1", 10)))[0].exec_type, ExecType::Replaced);
+        assert_eq!(bridge.get_utilization("MAKER", "").position.get_worst_net(), 10);
+        assert_eq!(bridge.get_utilization("TAKER", ""), Utilization { position: Position { net: -5, ..Default::default() }, limit: None });
+    }
+
+    #[test]
+    fn test_margin(){
+        let mut bridge = OrderBridge::new().with_margin(MarginRates { initial: 0.5, maintenance: 0.25 });
+        bridge.deposit_collateral("MAKER", 500);
+        bridge.deposit_collateral("TAKER", 1_000);
+        bridge.on_message("TAKER", &order("ASK-1", '2', '2', Some("100"), 11, Some('1')));
+
+        // 10 at 100 uses all of the buying power.
+        let reports = executions(bridge.on_message("MAKER", &order("BID-1", '1', '2', Some("100"), 10, Some('1'))));
+        assert_eq!(reports[1].exec_type, ExecType::Trade);
+        let reports = executions(bridge.on_message("MAKER", &order("BID-2", '1', '2', Some("100"), 1, Some('1'))));
+        assert_eq!(reports[0].text.as_deref(), Some("Risk reject: initial margin 550 exceeds the equity of 500"));
+        assert_eq!(bridge.get_margin_status("MAKER"), Some(MarginStatus { equity: 500, initial: 500, maintenance: 250 }));
+
+        // Marked down to 60 the equity no longer covers the maintenance margin; the call is sent once.
+        bridge.orderbook.set_reference_prices(ReferencePrices { last: Some(60), ..Default::default() });
+        let reports = bridge.on_message("TAKER", &request(ORDER_CANCEL_REQUEST, "CXL-1", "ASK-1").build_message());
+        let [Report::Execution(_), Report::MarginCall(call)] = &reports[..] else { panic!("unexpected {:?}", reports) };
+        assert_eq!((call.owner.as_str(), call.status.equity, call.status.maintenance), ("MAKER", 100, 150));
+        let message = call.to_builder("FIX.4.4").build_message();
+        assert_eq!(message.get_msg_type(), Some(crate::margin::NEWS));
+        assert_eq!(bridge.on_message("MAKER", &order("BID-3", '1', '2', Some("50"), 1, Some('1'))).len(), 1);
+    }
+
+    #[test]
+    fn test_margin_cash_at_execution_price(){
+        let mut bridge = OrderBridge::new().with_margin(MarginRates { initial: 0.5, maintenance: 0.25 });
+        bridge.deposit_collateral("MAKER", 1_000);
+        bridge.deposit_collateral("TAKER", 1_000);
+        bridge.on_message("MAKER", &order("ASK-1", '2', '2', Some("100"), 5, Some('1')));
+        bridge.on_message("TAKER", &order("BID-1", '1', '2', Some("102"), 5, Some('1')));
+
+        // The taker paid 500 for what it marks at 500, not the 510 its limit would have cost.
+        bridge.orderbook.set_reference_prices(ReferencePrices { last: Some(100), ..Default::default() });
+        assert_eq!(bridge.get_margin_status("TAKER").map(|status| status.equity), Some(1_000));
+        assert_eq!(bridge.get_margin_status("MAKER").map(|status| status.equity), Some(1_000));
+    }
+
+    #[test]
+    fn test_clearing(){
+        let mut bridge = OrderBridge::new().with_clearing(FeeSchedule { maker: 0.0, taker: 0.01 });
+        bridge.on_message("MAKER", &order("ASK-1", '2', '2', Some("100"), 5, Some('1')));
+        bridge.on_message("TAKER", &order("BID-1", '1', '2', Some("101"), 3, Some('1')));
+        bridge.on_message("MAKER", &order("BID-2", '1', '2', Some("99"), 4, Some('1')));
+        bridge.on_message("TAKER", &order("ASK-2", '2', '2', Some("99"), 4, Some('1')));
+
+        let confirmations = bridge.take_confirmations();
+        let rows: Vec<String> = confirmations.iter().map(Confirmation::to_csv_row).collect();
+        assert_eq!(rows, [
+            "1,TAKER,,BUY,100,3,TAKER,3",
+            "1,MAKER,,SELL,100,3,MAKER,0",
+            "2,MAKER,,BUY,99,4,MAKER,0",
+            "2,TAKER,,SELL,99,4,TAKER,4",
+        ]);
+
+        let report = bridge.close_clearing_day().unwrap();
+        assert_eq!(report.trade_count, 2);
+        let taker = report.nettings[&("TAKER".to_string(), String::new())];
+        assert_eq!((taker.get_net_quantity(), taker.cash, taker.fees), (-1, 89, 7));
+        assert!(OrderBridge::new().close_clearing_day().is_none());
+    }
+
+    #[test]
+    fn test_short_sales_and_capacity(){
+        let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        orderbook.set_short_sale_rule(Some(std::sync::Arc::new(orderbook::regulation::UptickRule)));
+        let mut bridge = OrderBridge::with_orderbook(orderbook);
+        let short = |cl_ord_id: &str, price: &str| {
+            FixMessageBuilder::new("FIX.4.4", NEW_ORDER_SINGLE)
+                .field(tags::CL_ORD_ID, cl_ord_id)
+                .field(tags::SIDE, '5')
+                .field(tags::ORD_TYPE, '2')
+                .field(tags::PRICE, price)
+                .field(tags::ORDER_QTY, 1)
+                .field(tags::TIME_IN_FORCE, '1')
+                .field(tags::ORDER_CAPACITY, 'A')
+                .build_message()
+        };
+        let new_order = NewOrder::from_message(&short("S", "1")).unwrap();
+        assert_eq!((new_order.side, new_order.flags), (Side::Sell, OrderFlags { capacity: Some(orderbook::Capacity::Agency), short_sell: true }));
+        assert_eq!(NewOrder::from_message(&new_order.to_builder("FIX.4.4").build_message()), Ok(new_order));
+
+        bridge.on_message("MAKER", &order("BID-1", '1', '2', Some("100"), 5, Some('1')));
+        bridge.on_message("MAKER", &order("BID-2", '1', '2', Some("99"), 5, Some('1')));
+        bridge.on_message("TAKER", &order("ASK-1", '2', '2', Some("99"), 6, Some('1')));
+
+        // Last sale 99 on a down-tick: a short at 99 is refused before it reaches the book.
+        let reports = executions(bridge.on_message("TAKER", &short("SHORT-1", "99")));
+        assert_eq!(summary(&reports), vec![("TAKER", "SHORT-1", ExecType::Rejected, OrdStatus::Rejected, 0, 0)]);
+        assert!(reports[0].flags.short_sell);
+        assert!(reports[0].text.as_deref().unwrap().starts_with("Risk reject: Short sale at 99"));
+
+        // Above it the short rests, and its flags come back on every report.
+        let reports = executions(bridge.on_message("TAKER", &short("SHORT-2", "101")));
+        let message = reports[0].to_builder("FIX.4.4").build_message();
+        assert_eq!((message.get_field(tags::SIDE), message.get_field(tags::ORDER_CAPACITY)), (Some("5"), Some("A")));
+        let reports = executions(bridge.on_message("MAKER", &order("BID-3", '1', '2', Some("101"), 1, Some('3'))));
+        assert_eq!(reports[2].cl_ord_id, "SHORT-2");
+        assert_eq!(reports[2].flags.capacity, Some(orderbook::Capacity::Agency));
+    }
+
+    #[test]
+    fn test_drop_copy(){
+        let mut bridge = OrderBridge::new();
+        let reports = bridge.on_message("MAKER", &order("1", '2', '2', Some("100"), 10, None));
+        let copy = reports[0].to_drop_copy("FIX.4.4").unwrap().build_message();
+        assert_eq!(copy.get_msg_type(), Some(EXECUTION_REPORT));
+        assert_eq!(copy.get_field(tags::ACCOUNT), Some("MAKER"));
+        assert_eq!(copy.get_field(tags::CL_ORD_ID), Some("1"));
+
+        let reports = bridge.on_message("MAKER", &FixMessageBuilder::new("FIX.4.4", "E").build_message());
+        assert_eq!(reports[0].to_drop_copy("FIX.4.4"), None);
+
+        let order = order("2", '1', '2', Some("100"), 10, None);
+        let reject = BusinessMessageReject::new("COPY", &order, &BridgeError::NotAuthorized("drop-copy session".to_string()));
+        assert_eq!((reject.reason, reject.text.as_str()), (business_reject_reason::NOT_AUTHORIZED, "Not authorized: drop-copy session"));
+        assert_eq!(reject.business_reject_ref_id.as_deref(), Some("2"));
+    }
+}
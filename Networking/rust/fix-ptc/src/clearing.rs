@@ -0,0 +1,275 @@
+//! # Clearing Module
+//!
+//! The back-office leg of the [`OrderBridge`](crate::bridge::OrderBridge): trade
+//! confirmations for clearing and the end-of-day netting of what each account owes.
+//!
+//! ## Confirmations
+//! With [`OrderBridge::with_clearing`](crate::bridge::OrderBridge::with_clearing), every trade
+//! produces two [`Confirmation`]s, one per counterparty, sharing a trade id that counts from 1.
+//! Each carries the account (the owner of the order), the symbol, the side, price and quantity,
+//! whether the account provided or took liquidity, and the fee charged by the
+//! [`FeeSchedule`]. Confirmations queue up in the bridge until
+//! [`take_confirmations`](crate::bridge::OrderBridge::take_confirmations) collects them,
+//! e.g. to append them to a [`ClearingFile`]:
+//!
+//! ```text
+//! trade_id,account,symbol,side,price,quantity,liquidity,fee
+//! 1,MAKER,,SELL,100,5,MAKER,1
+//! ```
+//!
+//! ## Netting
+//! [`close_clearing_day`](crate::bridge::OrderBridge::close_clearing_day) nets the day's
+//! confirmations into a [`NettingReport`]: per account and symbol, the quantity bought and
+//! sold and the cash to settle, sales minus purchases minus fees.
+
+use std::{collections::BTreeMap, fmt};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use orderbook::{Price, Quantity, Side};
+
+/// Header row of a [`ClearingFile`].
+pub const CONFIRMATION_HEADER: &str = "trade_id,account,symbol,side,price,quantity,liquidity,fee";
+
+/// Fees as fractions of a trade's notional, rounded up to a whole unit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeSchedule {
+    /// Charged to the resting order's account; negative for a rebate.
+    pub maker: f64,
+    /// Charged to the aggressor's account.
+    pub taker: f64,
+}
+
+impl FeeSchedule {
+    /// Returns the fee for `liquidity` on a trade of `notional`.
+    pub fn get_fee(&self, liquidity: Liquidity, notional: i64) -> i64 {
+        let rate = match liquidity {
+            Liquidity::Maker => self.maker,
+            Liquidity::Taker => self.taker,
+        };
+        (rate * notional as f64).ceil() as i64
+    }
+}
+
+/// Whether an account's order rested in the book or caused the trade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+/// One counterparty's side of a trade, as sent to clearing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Confirmation {
+    pub trade_id: u64,
+    pub account: String,
+    /// Symbol (55) of the order; empty if it had none.
+    pub symbol: String,
+    pub side: Side,
+    pub price: Price,
+    pub quantity: Quantity,
+    pub liquidity: Liquidity,
+    pub fee: i64,
+}
+
+impl Confirmation {
+    /// Returns price × quantity.
+    pub fn get_notional(&self) -> i64 {
+        i64::from(self.price) * i64::from(self.quantity)
+    }
+
+    /// Returns the confirmation as a row under [`CONFIRMATION_HEADER`].
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{}",
+            self.trade_id,
+            self.account,
+            self.symbol,
+            if self.side == Side::Buy { "BUY" } else { "SELL" },
+            self.price,
+            self.quantity,
+            if self.liquidity == Liquidity::Maker { "MAKER" } else { "TAKER" },
+            self.fee
+        )
+    }
+}
+
+/// Numbers trades, prices their fees and queues their confirmations.
+#[derive(Clone, Debug)]
+pub struct Clearing {
+    fees: FeeSchedule,
+    next_trade_id: u64,
+    queue: Vec<Confirmation>,
+    /// Confirmations of the current day, kept for netting.
+    day: Vec<Confirmation>,
+}
+
+impl Clearing {
+    pub fn new(fees: FeeSchedule) -> Self {
+        Self { fees, next_trade_id: 1, queue: Vec::new(), day: Vec::new() }
+    }
+
+    /// Returns the id of the next trade.
+    pub fn take_trade_id(&mut self) -> u64 {
+        self.next_trade_id += 1;
+        self.next_trade_id - 1
+    }
+
+    /// Prices the fee of `confirmation` and queues it.
+    pub fn confirm(&mut self, mut confirmation: Confirmation) {
+        confirmation.fee = self.fees.get_fee(confirmation.liquidity, confirmation.get_notional());
+        self.day.push(confirmation.clone());
+        self.queue.push(confirmation);
+    }
+
+    /// Returns the confirmations queued since the last call.
+    pub fn take_confirmations(&mut self) -> Vec<Confirmation> {
+        std::mem::take(&mut self.queue)
+    }
+
+    /// Nets the day's confirmations and starts a new day.
+    pub fn close_day(&mut self) -> NettingReport {
+        NettingReport::from_confirmations(&std::mem::take(&mut self.day))
+    }
+}
+
+/// A file of confirmations, opened for appending.
+#[derive(Debug)]
+pub struct ClearingFile {
+    file: File,
+}
+
+impl ClearingFile {
+    /// Opens (appending to) the file at `path`, creating its directory if needed and writing
+    /// the header if it is new.
+    ///
+    /// # Errors
+    /// Returns an error if the directory or file cannot be created or written.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        if let Some(dir) = path.as_ref().parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", CONFIRMATION_HEADER)?;
+        }
+        Ok(Self { file })
+    }
+
+    /// Appends one row per confirmation.
+    ///
+    /// # Errors
+    /// Returns any I/O error from writing.
+    pub fn write(&mut self, confirmations: &[Confirmation]) -> io::Result<()> {
+        let rows: String = confirmations.iter().map(|confirmation| confirmation.to_csv_row() + "\n").collect();
+        self.file.write_all(rows.as_bytes())
+    }
+}
+
+/// What one account traded in one symbol over a day.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Netting {
+    pub bought: u64,
+    pub sold: u64,
+    /// Received for sales minus paid for purchases and fees.
+    pub cash: i64,
+    pub fees: i64,
+}
+
+impl Netting {
+    /// Returns bought minus sold.
+    pub fn get_net_quantity(&self) -> i64 {
+        self.bought as i64 - self.sold as i64
+    }
+}
+
+/// The day's confirmations netted per account and symbol.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NettingReport {
+    pub trade_count: u64,
+    /// Keyed by account, then symbol.
+    pub nettings: BTreeMap<(String, String), Netting>,
+}
+
+impl NettingReport {
+    pub fn from_confirmations(confirmations: &[Confirmation]) -> Self {
+        let mut report = Self::default();
+        for confirmation in confirmations {
+            let netting = report.nettings.entry((confirmation.account.clone(), confirmation.symbol.clone())).or_default();
+            match confirmation.side {
+                Side::Buy => {
+                    netting.bought += u64::from(confirmation.quantity);
+                    netting.cash -= confirmation.get_notional();
+                }
+                Side::Sell => {
+                    netting.sold += u64::from(confirmation.quantity);
+                    netting.cash += confirmation.get_notional();
+                }
+            }
+            netting.cash -= confirmation.fee;
+            netting.fees += confirmation.fee;
+        }
+        // Every trade is confirmed to both counterparties.
+        report.trade_count = confirmations.len() as u64 / 2;
+        report
+    }
+}
+
+impl fmt::Display for NettingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<12} {:<8} {:>10} {:>10} {:>10} {:>14} {:>10}", "ACCOUNT", "SYMBOL", "BOUGHT", "SOLD", "NET", "CASH", "FEES")?;
+        for ((account, symbol), netting) in &self.nettings {
+            writeln!(
+                f,
+                "{:<12} {:<8} {:>10} {:>10} {:>10} {:>14} {:>10}",
+                account, symbol, netting.bought, netting.sold, netting.get_net_quantity(), netting.cash, netting.fees
+            )?;
+        }
+        write!(f, "{} trades", self.trade_count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_netting_and_file(){
+        let mut clearing = Clearing::new(FeeSchedule { maker: -0.001, taker: 0.002 });
+        for (price, quantity) in [(100, 10), (101, 5)] {
+            let trade_id = clearing.take_trade_id();
+            let confirmation = |account: &str, side, liquidity| Confirmation {
+                trade_id,
+                account: account.to_string(),
+                symbol: "XYZ".to_string(),
+                side,
+                price,
+                quantity,
+                liquidity,
+                fee: 0,
+            };
+            clearing.confirm(confirmation("MAKER", Side::Sell, Liquidity::Maker));
+            clearing.confirm(confirmation("TAKER", Side::Buy, Liquidity::Taker));
+        }
+        let confirmations = clearing.take_confirmations();
+        assert_eq!(confirmations.iter().map(|confirmation| confirmation.fee).collect::<Vec<_>>(), [-1, 2, 0, 2]);
+        assert!(clearing.take_confirmations().is_empty());
+
+        let report = clearing.close_day();
+        assert_eq!(report.trade_count, 2);
+        let taker = report.nettings[&("TAKER".to_string(), "XYZ".to_string())];
+        assert_eq!(taker, Netting { bought: 15, sold: 0, cash: -1_509, fees: 4 });
+        assert_eq!(report.nettings[&("MAKER".to_string(), "XYZ".to_string())].cash, 1_506);
+        assert_eq!(clearing.close_day(), NettingReport::default());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clearing.csv");
+        ClearingFile::open(&path).unwrap().write(&confirmations[..2]).unwrap();
+        ClearingFile::open(&path).unwrap().write(&confirmations[2..]).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], CONFIRMATION_HEADER);
+        assert_eq!(lines[1], "1,MAKER,XYZ,SELL,100,10,MAKER,-1");
+    }
+}
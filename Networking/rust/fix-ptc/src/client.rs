@@ -1,29 +1,49 @@
-use tokio::{
-    net::TcpStream, 
-    io::{AsyncReadExt, AsyncWriteExt}, 
-    time::sleep, time::Duration
-};
+use fix_ptc::config::Config;
+use fix_ptc::fix::{fix_client, ConnectionState, FixMessage, MessageHandler};
 
-#[tokio::main]
-async fn main() {
-    let mut stream = TcpStream::connect("127.0.0.1:7000").await.unwrap();
-    let duration: u32 = 7;
-    let send_at = duration - 1; //need to send before not at timeout
-    stream.write_all(&duration.to_be_bytes()).await;
-    
-    loop {
-        stream.write_all(b"HB").await.unwrap();
+/// Prints whatever the server sends.
+struct Printer;
+
+impl MessageHandler for Printer {
+    fn on_logon(&mut self) {
+        println!("Logged on, server is alive");
+    }
+
+    fn on_message(&mut self, message: FixMessage) {
+        println!("Received MsgType {}", message.get_msg_type().unwrap_or("?"));
+    }
+
+    fn on_disconnect(&mut self, reason: &str) {
+        println!("Session ended: {}", reason);
+    }
 
-        let mut response = [0u8; 2];
-        match stream.read_exact(&mut response).await {
-            Ok(_) if &response == b"OK" => println!("Server is alive"),
-            _ => {
-                println!("No response. Server might be down.");
-                break;
-            }
+    fn on_connection_state(&mut self, state: &ConnectionState) {
+        match state {
+            ConnectionState::Reconnecting { attempt, delay } => println!("Reconnecting in {:?} (attempt {})", delay, attempt),
+            ConnectionState::ReconnectFailed { reason, .. } => println!("Reconnection failed: {}", reason),
+            ConnectionState::GaveUp => println!("Gave up reconnecting"),
+            ConnectionState::LoggedOn | ConnectionState::Disconnected(_) => {}
         }
+    }
+}
 
-        sleep(Duration::from_secs(send_at.into())).await; //11
+#[tokio::main]
+async fn main() {
+    // Usage: client [config.toml]
+    let config = match std::env::args().nth(1) {
+        Some(path) => Config::load(path),
+        None => include_str!("../client.toml").parse(),
     }
+    .unwrap_or_else(|err| panic!("{}", err));
+    let settings = config.get_sessions().first().expect("No session configured");
 
+    let mut client = fix_client::from_settings(settings);
+    if let Err(err) = client.connect(Printer).await {
+        println!("No response. Server might be down: {}", err);
+        return;
+    }
+    let _ = tokio::signal::ctrl_c().await;
+    if let Err(err) = client.disconnect().await {
+        println!("Connection lost: {}", err);
+    }
 }
@@ -0,0 +1,536 @@
+//! # Config Module
+//!
+//! TOML session configuration shared by [`fix_client`](crate::fix::fix_client) and
+//! [`fix_server`](crate::fix::fix_server).
+//!
+//! A file declares any number of `[[session]]` tables. A session is identified by its
+//! `sender_comp_id`/`target_comp_id` pair, which must be unique within the file.
+//!
+//! ```toml
+//! [[session]]
+//! sender_comp_id = "SERVER"
+//! target_comp_id = "CLIENT"
+//! host = "127.0.0.1"
+//! port = 7000
+//! heartbeat_interval = 30    # seconds, HeartBtInt (108)
+//! heartbeat_tolerance = 20   # percent of the interval to wait before a TestRequest
+//! # begin_string = "FIXT.1.1" # FIX 5.0 over FIXT, together with
+//! # default_appl_ver_id = "9" # DefaultApplVerID (1137), 9 = FIX 5.0 SP2
+//! reset_on_logon = false     # start from MsgSeqNum 1, discard the store, send ResetSeqNumFlag
+//! reset_time = "22:00:00"    # UTC; reset sequence numbers daily
+//! start_time = "07:00:00"    # UTC; the window may span midnight
+//! end_time = "21:00:00"
+//! store_path = "store/SERVER-CLIENT.log"
+//! validate = true            # reject messages that break the FIX 4.4 dictionary
+//! wire_log_dir = "log"        # raw messages in log/SERVER-CLIENT.log
+//! wire_log_max_bytes = 10485760
+//! wire_log_files = 5          # rotated files kept
+//! audit_path = "audit/SERVER-CLIENT.log" # application messages, never rotated
+//! reconnect_delay = 1         # initiator: seconds before the first reconnection attempt
+//! reconnect_max_delay = 60    # cap of the doubling delay
+//! reconnect_attempts = 10     # give up after this many attempts
+//! max_messages = 100          # accepted per rate_window; more are rejected
+//! rate_window = 1000          # milliseconds
+//! max_rate_violations = 3     # windows over the limit in a row before a Logout
+//! drop_copy = false           # acceptor: only receive copies of ExecutionReports
+//! drop_copy_accounts = []     # accounts copied to a drop-copy session; all if empty
+//! tls_cert = "tls/server.pem" # PEM certificate chain presented to the counterparty
+//! tls_key = "tls/server.key"  # PEM private key of tls_cert
+//! tls_ca = "tls/ca.pem"       # PEM CAs the counterparty's certificate must be issued by
+//! tls_require_client_cert = false # acceptor: refuse clients without a certificate
+//! ```
+//!
+//! Only `sender_comp_id`, `target_comp_id` and `port` are required. `begin_string` defaults to
+//! `FIX.4.4`, `host` to `127.0.0.1`, `heartbeat_interval` to 30 seconds and
+//! `heartbeat_tolerance` to 20%. Without `start_time`/`end_time` the session is always open;
+//! without `store_path` sent messages and sequence numbers are kept in memory only, so a
+//! restarted session starts again from MsgSeqNum 1. Without `wire_log_dir` no wire
+//! log is written; the size limit defaults to 10 MiB and five rotated files are kept. Without
+//! `audit_path` no audit file is written. An initiator reconnects only if `reconnect_delay` is
+//! set, retrying without limit unless `reconnect_attempts` is set. `drop_copy_accounts`
+//! requires `drop_copy`. Without `max_messages` inbound traffic is not rate limited.
+//! Without `tls_ca` an initiator connects in plaintext, and without `tls_cert` an acceptor
+//! does; the [TLS module](crate::tls) describes what is checked when they are set.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::Deserialize;
+use crate::audit::AuditLog;
+use crate::dictionary::Dictionary;
+use crate::fix::{ReconnectPolicy, SessionKind};
+use crate::session::{RateLimit, Session, SessionConfig, SessionRole, FIXT_1_1};
+use crate::store::{FileStore, MemoryStore, MessageStore};
+use crate::wirelog::WireLog;
+
+/// Errors produced while loading a configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// The file is not valid TOML or does not match the expected layout.
+    Parse(String),
+    /// The file parsed but a value is unusable.
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "Cannot read {}: {}", path.display(), source),
+            ConfigError::Parse(reason) => write!(f, "Invalid configuration: {}", reason),
+            ConfigError::Invalid(reason) => write!(f, "Invalid configuration: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A UTC time of day, written `HH:MM:SS` in configuration files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay {
+    seconds: u32,
+}
+
+impl TimeOfDay {
+    /// Creates a time of day, or `None` if a component is out of range.
+    pub fn new(hours: u32, minutes: u32, seconds: u32) -> Option<Self> {
+        (hours < 24 && minutes < 60 && seconds < 60).then_some(Self { seconds: hours * 3600 + minutes * 60 + seconds })
+    }
+
+    /// Returns the UTC time of day of `time`.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() % 86_400;
+        Self { seconds: seconds as u32 }
+    }
+
+    /// Returns the number of seconds since midnight.
+    pub fn get_seconds(&self) -> u32 {
+        self.seconds
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let parts: Vec<u32> = value.split(':').map(str::parse).collect::<Result<_, _>>().map_err(|_| format!("Invalid time of day {:?}", value))?;
+        match parts[..] {
+            [hours, minutes, seconds] => TimeOfDay::new(hours, minutes, seconds),
+            [hours, minutes] => TimeOfDay::new(hours, minutes, 0),
+            _ => None,
+        }
+        .ok_or_else(|| format!("Invalid time of day {:?}, expected HH:MM:SS", value))
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.seconds / 3600, self.seconds / 60 % 60, self.seconds % 60)
+    }
+}
+
+fn default_begin_string() -> String {
+    "FIX.4.4".to_string()
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_heartbeat_interval() -> u64 {
+    30
+}
+
+fn default_heartbeat_tolerance() -> u32 {
+    20
+}
+
+fn default_wire_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_wire_log_files() -> usize {
+    5
+}
+
+fn default_reconnect_max_delay() -> u64 {
+    60
+}
+
+fn default_rate_window() -> u64 {
+    1000
+}
+
+fn default_max_rate_violations() -> u32 {
+    3
+}
+
+/// One `[[session]]` table.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionSettings {
+    #[serde(default = "default_begin_string")]
+    pub begin_string: String,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+    /// Address an initiator connects to, or an acceptor listens on.
+    #[serde(default = "default_host")]
+    pub host: String,
+    pub port: u16,
+    /// HeartBtInt (108) in seconds.
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+    /// Silence tolerated beyond `heartbeat_interval` before a TestRequest, in percent.
+    #[serde(default = "default_heartbeat_tolerance")]
+    pub heartbeat_tolerance: u32,
+    /// DefaultApplVerID (1137) sent at Logon; required when `begin_string` is `FIXT.1.1`.
+    pub default_appl_ver_id: Option<String>,
+    /// Discard stored messages and start from MsgSeqNum 1 on every logon; an initiator also
+    /// sends ResetSeqNumFlag (141).
+    #[serde(default)]
+    pub reset_on_logon: bool,
+    /// Reset sequence numbers once a day at this time.
+    pub reset_time: Option<TimeOfDay>,
+    pub start_time: Option<TimeOfDay>,
+    pub end_time: Option<TimeOfDay>,
+    /// File keeping sent messages for resends and, next to it, the sequence numbers; in memory
+    /// if absent.
+    pub store_path: Option<PathBuf>,
+    /// Validate inbound messages against [`Dictionary::fix44`].
+    #[serde(default)]
+    pub validate: bool,
+    /// Directory of the [`WireLog`]; no wire log if absent.
+    pub wire_log_dir: Option<PathBuf>,
+    /// Size at which the wire log is rotated.
+    #[serde(default = "default_wire_log_max_bytes")]
+    pub wire_log_max_bytes: u64,
+    /// Rotated wire log files kept besides the current one.
+    #[serde(default = "default_wire_log_files")]
+    pub wire_log_files: usize,
+    /// Append-only [`AuditLog`] of application messages; none if absent.
+    pub audit_path: Option<PathBuf>,
+    /// Seconds before an initiator's first reconnection attempt; no reconnection if absent.
+    pub reconnect_delay: Option<u64>,
+    /// Longest delay between reconnection attempts, in seconds.
+    #[serde(default = "default_reconnect_max_delay")]
+    pub reconnect_max_delay: u64,
+    /// Reconnection attempts before giving up; unlimited if absent.
+    pub reconnect_attempts: Option<u32>,
+    /// Messages accepted from the counterparty per `rate_window`; unlimited if absent.
+    pub max_messages: Option<u32>,
+    /// Length of a rate-limit window, in milliseconds.
+    #[serde(default = "default_rate_window")]
+    pub rate_window: u64,
+    /// Consecutive windows over the limit before the counterparty is logged out.
+    #[serde(default = "default_max_rate_violations")]
+    pub max_rate_violations: u32,
+    /// Make an accepted session a [`SessionKind::DropCopy`].
+    #[serde(default)]
+    pub drop_copy: bool,
+    /// Accounts whose ExecutionReports a drop-copy session receives; all if empty.
+    #[serde(default)]
+    pub drop_copy_accounts: Vec<String>,
+    /// PEM certificate chain presented to the counterparty; an acceptor uses TLS if set.
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of `tls_cert`.
+    pub tls_key: Option<PathBuf>,
+    /// PEM CA certificates the counterparty's certificate must be issued by; an initiator uses
+    /// TLS if set.
+    pub tls_ca: Option<PathBuf>,
+    /// Refuse a client that presents no certificate.
+    #[serde(default)]
+    pub tls_require_client_cert: bool,
+}
+
+impl SessionSettings {
+    /// Returns the `host:port` address of the session.
+    pub fn get_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Returns the parameters the [`Session`](crate::session::Session) state machine needs.
+    pub fn to_session_config(&self) -> SessionConfig {
+        SessionConfig {
+            begin_string: self.begin_string.clone(),
+            sender_comp_id: self.sender_comp_id.clone(),
+            target_comp_id: self.target_comp_id.clone(),
+            heartbeat_interval: Duration::from_secs(self.heartbeat_interval),
+            heartbeat_tolerance: self.heartbeat_tolerance,
+            default_appl_ver_id: self.default_appl_ver_id.clone(),
+            reset_on_logon: self.reset_on_logon,
+            reset_time: self.reset_time,
+            rate_limit: self.max_messages.map(|max_messages| RateLimit {
+                max_messages,
+                window: Duration::from_millis(self.rate_window),
+                max_violations: self.max_rate_violations,
+            }),
+        }
+    }
+
+    /// Returns whether the session trades or receives drop copies.
+    pub fn get_kind(&self) -> SessionKind {
+        if self.drop_copy {
+            SessionKind::DropCopy { accounts: self.drop_copy_accounts.clone() }
+        } else {
+            SessionKind::Trading
+        }
+    }
+
+    /// Returns how an initiator reconnects, if `reconnect_delay` is set.
+    pub fn get_reconnect_policy(&self) -> Option<ReconnectPolicy> {
+        let initial_delay = Duration::from_secs(self.reconnect_delay?);
+        Some(ReconnectPolicy {
+            initial_delay,
+            max_delay: Duration::from_secs(self.reconnect_max_delay).max(initial_delay),
+            max_attempts: self.reconnect_attempts,
+            ..Default::default()
+        })
+    }
+
+    /// Opens the message store of the session, emptying it if `reset_on_logon` is set.
+    ///
+    /// # Errors
+    /// Returns an error if the store file cannot be opened.
+    pub fn open_store(&self) -> io::Result<Box<dyn MessageStore>> {
+        let Some(path) = &self.store_path else {
+            return Ok(Box::new(MemoryStore::new()));
+        };
+        let mut store = FileStore::open(path)?;
+        if self.reset_on_logon {
+            store.reset()?;
+        }
+        Ok(Box::new(store))
+    }
+
+    /// Opens the wire log of the session, if `wire_log_dir` is set.
+    ///
+    /// # Errors
+    /// Returns an error if the log directory or file cannot be created.
+    pub fn open_wire_log(&self) -> io::Result<Option<WireLog>> {
+        let Some(dir) = &self.wire_log_dir else {
+            return Ok(None);
+        };
+        let session_id = format!("{}-{}", self.sender_comp_id, self.target_comp_id);
+        WireLog::open(dir, &session_id, self.wire_log_max_bytes, self.wire_log_files).map(Some)
+    }
+
+    /// Creates the session state machine with its store and, if enabled, its dictionary, wire
+    /// log and audit log.
+    ///
+    /// # Errors
+    /// Returns an error if the store, wire log or audit file cannot be opened.
+    pub fn open_session(&self, role: SessionRole, now: Instant) -> io::Result<Session> {
+        let mut session = Session::new(self.to_session_config(), role, now).with_store(self.open_store()?);
+        if self.validate {
+            session = session.with_dictionary(Arc::new(Dictionary::fix44()));
+        }
+        if let Some(wire_log) = self.open_wire_log()? {
+            session = session.with_wire_log(wire_log);
+        }
+        if let Some(path) = &self.audit_path {
+            session = session.with_audit_log(AuditLog::open(path)?);
+        }
+        Ok(session)
+    }
+
+    /// Returns true if `now` falls inside the configured session window.
+    ///
+    /// A window whose end is before its start spans midnight.
+    pub fn is_in_session(&self, now: SystemTime) -> bool {
+        let (Some(start), Some(end)) = (self.start_time, self.end_time) else {
+            return true;
+        };
+        let now = TimeOfDay::from_system_time(now);
+        if start <= end {
+            start <= now && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let name = format!("{}->{}", self.sender_comp_id, self.target_comp_id);
+        if self.sender_comp_id.is_empty() || self.target_comp_id.is_empty() {
+            return Err(format!("Session {}: CompIDs must not be empty", name));
+        }
+        if self.heartbeat_interval == 0 {
+            return Err(format!("Session {}: heartbeat_interval must be positive", name));
+        }
+        if (self.begin_string == FIXT_1_1) != self.default_appl_ver_id.is_some() {
+            return Err(format!("Session {}: default_appl_ver_id must be set exactly when begin_string is {}", name, FIXT_1_1));
+        }
+        if self.start_time.is_some() != self.end_time.is_some() {
+            return Err(format!("Session {}: start_time and end_time must be set together", name));
+        }
+        if self.max_messages == Some(0) || self.rate_window == 0 || self.max_rate_violations == 0 {
+            return Err(format!("Session {}: max_messages, rate_window and max_rate_violations must be positive", name));
+        }
+        if !self.drop_copy && !self.drop_copy_accounts.is_empty() {
+            return Err(format!("Session {}: drop_copy_accounts requires drop_copy", name));
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err(format!("Session {}: tls_cert and tls_key must be set together", name));
+        }
+        if self.tls_require_client_cert && self.tls_ca.is_none() {
+            return Err(format!("Session {}: tls_require_client_cert requires tls_ca", name));
+        }
+        Ok(())
+    }
+}
+
+/// The contents of a configuration file.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, rename = "session")]
+    pub sessions: Vec<SessionSettings>,
+}
+
+impl Config {
+    /// Reads and validates the configuration file at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, parsed or validated.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })?;
+        contents.parse()
+    }
+
+    /// Returns the sessions declared in the file.
+    pub fn get_sessions(&self) -> &[SessionSettings] {
+        &self.sessions
+    }
+
+    /// Returns the session whose SenderCompID and TargetCompID match.
+    pub fn get_session(&self, sender_comp_id: &str, target_comp_id: &str) -> Option<&SessionSettings> {
+        self.sessions.iter().find(|session| session.sender_comp_id == sender_comp_id && session.target_comp_id == target_comp_id)
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(contents).map_err(|err| ConfigError::Parse(err.message().to_string()))?;
+        for (index, session) in config.sessions.iter().enumerate() {
+            session.check().map_err(ConfigError::Invalid)?;
+            if config.sessions[..index].iter().any(|other| other.sender_comp_id == session.sender_comp_id && other.target_comp_id == session.target_comp_id) {
+                return Err(ConfigError::Invalid(format!("Session {}->{} is declared twice", session.sender_comp_id, session.target_comp_id)));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [[session]]
+        sender_comp_id = "SERVER"
+        target_comp_id = "CLIENT"
+        port = 7000
+
+        [[session]]
+        begin_string = "FIX.4.2"
+        sender_comp_id = "SERVER"
+        target_comp_id = "BROKER"
+        host = "0.0.0.0"
+        port = 7001
+        heartbeat_interval = 10
+        heartbeat_tolerance = 50
+        reset_on_logon = true
+        reset_time = "21:30:00"
+        start_time = "22:00:00"
+        end_time = "06:00"
+        store_path = "store/broker.log"
+        validate = true
+        wire_log_dir = "log"
+        wire_log_files = 2
+        reconnect_delay = 2
+        reconnect_attempts = 5
+        drop_copy = true
+        drop_copy_accounts = ["ALPHA"]
+        max_messages = 50
+        rate_window = 500
+        tls_cert = "tls/server.pem"
+        tls_key = "tls/server.key"
+        tls_ca = "tls/ca.pem"
+        tls_require_client_cert = true
+    "#;
+
+    #[test]
+    fn test_load_sessions(){
+        let config: Config = CONFIG.parse().unwrap();
+        assert_eq!(config.get_sessions().len(), 2);
+
+        let client = config.get_session("SERVER", "CLIENT").unwrap();
+        assert_eq!(client.get_address(), "127.0.0.1:7000");
+        assert_eq!(client.to_session_config(), SessionConfig { sender_comp_id: "SERVER".to_string(), target_comp_id: "CLIENT".to_string(), ..Default::default() });
+        assert!(!client.reset_on_logon);
+        assert_eq!(client.store_path, None);
+        assert!(!client.validate);
+        assert!(client.open_wire_log().unwrap().is_none());
+        assert_eq!(client.get_reconnect_policy(), None);
+        assert_eq!(client.get_kind(), SessionKind::Trading);
+        assert_eq!((&client.tls_cert, &client.tls_ca, client.tls_require_client_cert), (&None, &None, false));
+
+        let broker = config.get_session("SERVER", "BROKER").unwrap();
+        assert_eq!(broker.get_address(), "0.0.0.0:7001");
+        assert_eq!(broker.to_session_config().begin_string, "FIX.4.2");
+        assert_eq!(broker.to_session_config().heartbeat_interval, Duration::from_secs(10));
+        assert_eq!(broker.to_session_config().heartbeat_tolerance, 50);
+        assert!(broker.to_session_config().reset_on_logon);
+        assert_eq!(broker.to_session_config().reset_time, TimeOfDay::new(21, 30, 0));
+        assert_eq!(broker.to_session_config().rate_limit, Some(RateLimit { max_messages: 50, window: Duration::from_millis(500), max_violations: 3 }));
+        assert_eq!(broker.end_time, TimeOfDay::new(6, 0, 0));
+        assert_eq!(broker.store_path, Some(PathBuf::from("store/broker.log")));
+        assert!(broker.validate);
+        assert_eq!((broker.wire_log_max_bytes, broker.wire_log_files), (10 * 1024 * 1024, 2));
+        assert_eq!(broker.get_kind(), SessionKind::DropCopy { accounts: vec!["ALPHA".to_string()] });
+        assert_eq!(broker.tls_key, Some(PathBuf::from("tls/server.key")));
+        assert_eq!(broker.tls_ca, Some(PathBuf::from("tls/ca.pem")));
+        assert!(broker.tls_require_client_cert);
+        let policy = broker.get_reconnect_policy().unwrap();
+        assert_eq!((policy.initial_delay, policy.max_delay, policy.max_attempts), (Duration::from_secs(2), Duration::from_secs(60), Some(5)));
+        assert!(config.get_session("CLIENT", "SERVER").is_none());
+    }
+
+    #[test]
+    fn test_session_window(){
+        let config: Config = CONFIG.parse().unwrap();
+        let at = |hours: u64| UNIX_EPOCH + Duration::from_secs(hours * 3600);
+
+        assert!(config.get_session("SERVER", "CLIENT").unwrap().is_in_session(at(12)));
+        let broker = config.get_session("SERVER", "BROKER").unwrap();
+        assert!(broker.is_in_session(at(23)));
+        assert!(broker.is_in_session(at(24 + 5)));
+        assert!(!broker.is_in_session(at(6)));
+        assert!(!broker.is_in_session(at(12)));
+    }
+
+    #[test]
+    fn test_invalid_config(){
+        let invalid = |contents: &str| contents.parse::<Config>().unwrap_err().to_string();
+
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\n").contains("port"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\nstart_time = \"25:00:00\"\nend_time = \"01:00:00\"\n").contains("25:00:00"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\nstart_time = \"01:00:00\"\n").contains("together"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\nheartbeat_interval = 0\n").contains("positive"));
+        assert!(invalid("[[session]]\nbegin_string = \"FIXT.1.1\"\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\n").contains("default_appl_ver_id"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\ndefault_appl_ver_id = \"9\"\n").contains("default_appl_ver_id"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\nmax_messages = 0\n").contains("positive"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\ndrop_copy_accounts = [\"C\"]\n").contains("drop_copy"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\ntls_cert = \"a.pem\"\n").contains("tls_key"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\ntls_require_client_cert = true\n").contains("tls_ca"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\n[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 2\n").contains("twice"));
+        assert!(invalid("[[session]]\nsender_comp_id = \"A\"\ntarget_comp_id = \"B\"\nport = 1\nhart_beat = 3\n").contains("hart_beat"));
+    }
+}
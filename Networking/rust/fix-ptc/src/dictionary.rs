@@ -0,0 +1,487 @@
+//! # Dictionary Module
+//!
+//! Field and message definitions used to validate inbound messages before they reach the
+//! application.
+//!
+//! [`Dictionary::fix44`] describes the FIX 4.4 fields this engine reads or writes and the
+//! messages it handles. Validation checks, in order:
+//! - the standard header fields every message must carry,
+//! - the required fields of the message type (types the dictionary does not know are left to
+//!   the application),
+//! - that each known field parses as its data type and, for enumerated fields, holds one of
+//!   the enumerated values,
+//! - that each repeating group of the message type starts every entry with its delimiter and
+//!   holds as many entries as its NumInGroup field declares.
+//!
+//! Unknown tags are allowed, so counterparties may send user-defined fields. A
+//! [`Session`](crate::session::Session) given a dictionary answers violations with a
+//! session-level Reject (`35=3`) carrying RefTagID (371) and SessionRejectReason (373).
+
+use std::{collections::HashMap, fmt};
+use crate::fix::{tags, FixError, FixMessage, GroupDef, Tag};
+use crate::session::reject_reason;
+
+/// Data types of FIX field values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    /// Signed integer.
+    Int,
+    /// Unsigned integer: SeqNum, Length and NumInGroup.
+    UInt,
+    /// Decimal: Price, Qty and Amt.
+    Float,
+    /// A single character.
+    Char,
+    /// `Y` or `N`.
+    Boolean,
+    /// Free text.
+    String,
+    /// `YYYYMMDD-HH:MM:SS` with optional fractional seconds, in UTC.
+    UtcTimestamp,
+}
+
+/// The definition of one field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldDef {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    /// The allowed values of an enumerated field, or empty.
+    pub values: &'static [&'static str],
+}
+
+/// Why a message failed validation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A required field is absent.
+    RequiredTagMissing(Tag),
+    /// A field is not one of its enumerated values.
+    ValueIncorrect { tag: Tag, value: String },
+    /// A field does not parse as its data type.
+    IncorrectDataFormat { tag: Tag, value: String },
+    /// A repeating group entry does not start with the delimiter; the tag is the delimiter.
+    GroupFieldsOutOfOrder(Tag),
+    /// A repeating group holds a different number of entries than its NumInGroup field.
+    IncorrectNumInGroupCount(Tag),
+}
+
+impl ValidationError {
+    /// Returns the offending tag, sent as RefTagID (371).
+    pub fn get_tag(&self) -> Tag {
+        match self {
+            ValidationError::RequiredTagMissing(tag)
+            | ValidationError::ValueIncorrect { tag, .. }
+            | ValidationError::IncorrectDataFormat { tag, .. }
+            | ValidationError::GroupFieldsOutOfOrder(tag)
+            | ValidationError::IncorrectNumInGroupCount(tag) => *tag,
+        }
+    }
+
+    /// Returns the SessionRejectReason (373) for the error.
+    pub fn get_reason(&self) -> u32 {
+        match self {
+            ValidationError::RequiredTagMissing(_) => reject_reason::REQUIRED_TAG_MISSING,
+            ValidationError::ValueIncorrect { .. } => reject_reason::VALUE_INCORRECT,
+            ValidationError::IncorrectDataFormat { .. } => reject_reason::INCORRECT_DATA_FORMAT,
+            ValidationError::GroupFieldsOutOfOrder(_) => reject_reason::REPEATING_GROUP_FIELDS_OUT_OF_ORDER,
+            ValidationError::IncorrectNumInGroupCount(_) => reject_reason::INCORRECT_NUM_IN_GROUP_COUNT,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::RequiredTagMissing(tag) => write!(f, "Required tag {} missing", tag),
+            ValidationError::ValueIncorrect { tag, value } => write!(f, "Value {:?} is incorrect for tag {}", value, tag),
+            ValidationError::IncorrectDataFormat { tag, value } => write!(f, "Incorrect data format {:?} for tag {}", value, tag),
+            ValidationError::GroupFieldsOutOfOrder(tag) => write!(f, "Repeating group entry does not start with tag {}", tag),
+            ValidationError::IncorrectNumInGroupCount(tag) => write!(f, "Incorrect NumInGroup count for tag {}", tag),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Standard header fields required on every message.
+const HEADER_REQUIRED: [Tag; 7] = [
+    tags::BEGIN_STRING,
+    tags::BODY_LENGTH,
+    tags::MSG_TYPE,
+    tags::SENDER_COMP_ID,
+    tags::TARGET_COMP_ID,
+    tags::MSG_SEQ_NUM,
+    tags::SENDING_TIME,
+];
+
+/// Repeating groups of the FIX 4.4 messages this engine handles.
+pub mod groups {
+    use crate::fix::{tags, GroupDef};
+
+    /// NoMDEntryTypes (267) of a MarketDataRequest.
+    pub const MD_ENTRY_TYPES: GroupDef = GroupDef { count_tag: tags::NO_MD_ENTRY_TYPES, fields: &[tags::MD_ENTRY_TYPE], groups: &[] };
+    /// NoRelatedSym (146) of a MarketDataRequest.
+    pub const RELATED_SYM: GroupDef = GroupDef { count_tag: tags::NO_RELATED_SYM, fields: &[tags::SYMBOL], groups: &[] };
+    /// NoMDEntries (268) of a MarketDataSnapshotFullRefresh.
+    pub const MD_FULL_REFRESH_ENTRIES: GroupDef = GroupDef {
+        count_tag: tags::NO_MD_ENTRIES,
+        fields: &[tags::MD_ENTRY_TYPE, tags::MD_ENTRY_PX, tags::MD_ENTRY_SIZE],
+        groups: &[],
+    };
+    /// NoMDEntries (268) of a MarketDataIncrementalRefresh, delimited by MDUpdateAction.
+    pub const MD_INCREMENTAL_ENTRIES: GroupDef = GroupDef {
+        count_tag: tags::NO_MD_ENTRIES,
+        fields: &[tags::MD_UPDATE_ACTION, tags::MD_ENTRY_TYPE, tags::MD_ENTRY_PX, tags::MD_ENTRY_SIZE, tags::SYMBOL],
+        groups: &[],
+    };
+    /// LinesOfText (33) of a News message.
+    pub const LINES_OF_TEXT: GroupDef = GroupDef { count_tag: tags::LINES_OF_TEXT, fields: &[tags::TEXT], groups: &[] };
+    /// NoPartySubIDs (802), nested in [`PARTY_IDS`].
+    pub const PARTY_SUB_IDS: GroupDef = GroupDef { count_tag: tags::NO_PARTY_SUB_IDS, fields: &[tags::PARTY_SUB_ID, tags::PARTY_SUB_ID_TYPE], groups: &[] };
+    /// NoPartyIDs (453) of orders and ExecutionReports.
+    pub const PARTY_IDS: GroupDef = GroupDef {
+        count_tag: tags::NO_PARTY_IDS,
+        fields: &[tags::PARTY_ID, tags::PARTY_ID_SOURCE, tags::PARTY_ROLE],
+        groups: &[PARTY_SUB_IDS],
+    };
+}
+
+/// Field and message definitions to validate against.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    fields: HashMap<Tag, FieldDef>,
+    messages: HashMap<String, Vec<Tag>>,
+    groups: HashMap<String, Vec<GroupDef>>,
+}
+
+impl Dictionary {
+    /// Creates an empty dictionary, which accepts any message with a standard header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the FIX 4.4 subset used by this engine.
+    ///
+    /// Required fields are those the engine needs to act on a message.
+    pub fn fix44() -> Self {
+        use FieldType::*;
+        const ANY: &[&str] = &[];
+        const APPL_VER_IDS: &[&str] = &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+        let fields: [(Tag, &'static str, FieldType, &'static [&'static str]); 73] = [
+            (tags::ACCOUNT, "Account", String, ANY),
+            (tags::AVG_PX, "AvgPx", Float, ANY),
+            (tags::BEGIN_SEQ_NO, "BeginSeqNo", UInt, ANY),
+            (tags::BEGIN_STRING, "BeginString", String, ANY),
+            (tags::BODY_LENGTH, "BodyLength", UInt, ANY),
+            (tags::CHECKSUM, "CheckSum", String, ANY),
+            (tags::CL_ORD_ID, "ClOrdID", String, ANY),
+            (tags::CUM_QTY, "CumQty", Float, ANY),
+            (tags::END_SEQ_NO, "EndSeqNo", UInt, ANY),
+            (tags::EXEC_ID, "ExecID", String, ANY),
+            (tags::LAST_PX, "LastPx", Float, ANY),
+            (tags::LAST_QTY, "LastQty", Float, ANY),
+            (tags::LINES_OF_TEXT, "LinesOfText", UInt, ANY),
+            (tags::MSG_SEQ_NUM, "MsgSeqNum", UInt, ANY),
+            (tags::MSG_TYPE, "MsgType", String, ANY),
+            (tags::NEW_SEQ_NO, "NewSeqNo", UInt, ANY),
+            (tags::ORDER_ID, "OrderID", String, ANY),
+            (tags::ORDER_QTY, "OrderQty", Float, ANY),
+            (tags::ORD_STATUS, "OrdStatus", Char, &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E"]),
+            (tags::ORD_TYPE, "OrdType", Char, &["1", "2", "3", "4", "5", "6", "7", "8", "9", "D", "E", "G", "I", "J", "K", "L", "M", "P"]),
+            (tags::ORIG_CL_ORD_ID, "OrigClOrdID", String, ANY),
+            (tags::POSS_DUP_FLAG, "PossDupFlag", Boolean, ANY),
+            (tags::PRICE, "Price", Float, ANY),
+            (tags::REF_SEQ_NUM, "RefSeqNum", UInt, ANY),
+            (tags::SENDER_COMP_ID, "SenderCompID", String, ANY),
+            (tags::SENDER_SUB_ID, "SenderSubID", String, ANY),
+            (tags::SENDING_TIME, "SendingTime", UtcTimestamp, ANY),
+            (tags::SIDE, "Side", Char, &["1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F", "G"]),
+            (tags::SYMBOL, "Symbol", String, ANY),
+            (tags::TARGET_COMP_ID, "TargetCompID", String, ANY),
+            (tags::TARGET_SUB_ID, "TargetSubID", String, ANY),
+            (tags::TEXT, "Text", String, ANY),
+            (tags::TIME_IN_FORCE, "TimeInForce", Char, &["0", "1", "2", "3", "4", "5", "6", "7"]),
+            (tags::POSS_RESEND, "PossResend", Boolean, ANY),
+            (tags::ENCRYPT_METHOD, "EncryptMethod", Int, &["0", "1", "2", "3", "4", "5", "6"]),
+            (tags::CXL_REJ_REASON, "CxlRejReason", Int, ANY),
+            (tags::ORD_REJ_REASON, "OrdRejReason", Int, ANY),
+            (tags::HEART_BT_INT, "HeartBtInt", UInt, ANY),
+            (tags::TEST_REQ_ID, "TestReqID", String, ANY),
+            (tags::ORIG_SENDING_TIME, "OrigSendingTime", UtcTimestamp, ANY),
+            (tags::GAP_FILL_FLAG, "GapFillFlag", Boolean, ANY),
+            (tags::RESET_SEQ_NUM_FLAG, "ResetSeqNumFlag", Boolean, ANY),
+            (tags::NO_RELATED_SYM, "NoRelatedSym", UInt, ANY),
+            (tags::HEADLINE, "Headline", String, ANY),
+            (tags::EXEC_TYPE, "ExecType", Char, &["0", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F", "G", "H", "I"]),
+            (tags::LEAVES_QTY, "LeavesQty", Float, ANY),
+            (tags::MD_REQ_ID, "MDReqID", String, ANY),
+            (tags::SUBSCRIPTION_REQUEST_TYPE, "SubscriptionRequestType", Char, &["0", "1", "2"]),
+            (tags::MARKET_DEPTH, "MarketDepth", UInt, ANY),
+            (tags::MD_UPDATE_TYPE, "MDUpdateType", Int, &["0", "1"]),
+            (tags::NO_MD_ENTRY_TYPES, "NoMDEntryTypes", UInt, ANY),
+            (tags::NO_MD_ENTRIES, "NoMDEntries", UInt, ANY),
+            (tags::MD_ENTRY_TYPE, "MDEntryType", Char, ANY),
+            (tags::MD_ENTRY_PX, "MDEntryPx", Float, ANY),
+            (tags::MD_ENTRY_SIZE, "MDEntrySize", Float, ANY),
+            (tags::MD_UPDATE_ACTION, "MDUpdateAction", Char, &["0", "1", "2", "3", "4", "5"]),
+            (tags::MD_REQ_REJ_REASON, "MDReqRejReason", Char, ANY),
+            (tags::REF_TAG_ID, "RefTagID", UInt, ANY),
+            (tags::REF_MSG_TYPE, "RefMsgType", String, ANY),
+            (tags::SESSION_REJECT_REASON, "SessionRejectReason", Int, ANY),
+            (tags::BUSINESS_REJECT_REF_ID, "BusinessRejectRefID", String, ANY),
+            (tags::BUSINESS_REJECT_REASON, "BusinessRejectReason", Int, ANY),
+            (tags::CXL_REJ_RESPONSE_TO, "CxlRejResponseTo", Char, &["1", "2"]),
+            (tags::PARTY_ID_SOURCE, "PartyIDSource", Char, ANY),
+            (tags::PARTY_ID, "PartyID", String, ANY),
+            (tags::PARTY_ROLE, "PartyRole", Int, ANY),
+            (tags::NO_PARTY_IDS, "NoPartyIDs", UInt, ANY),
+            (tags::PARTY_SUB_ID, "PartySubID", String, ANY),
+            (tags::ORDER_CAPACITY, "OrderCapacity", Char, &["A", "G", "I", "P", "R", "W"]),
+            (tags::NO_PARTY_SUB_IDS, "NoPartySubIDs", UInt, ANY),
+            (tags::PARTY_SUB_ID_TYPE, "PartySubIDType", Int, ANY),
+            (tags::APPL_VER_ID, "ApplVerID", String, APPL_VER_IDS),
+            (tags::DEFAULT_APPL_VER_ID, "DefaultApplVerID", String, APPL_VER_IDS),
+        ];
+        let messages: [(&str, &[Tag]); 17] = [
+            ("0", &[]),
+            ("1", &[tags::TEST_REQ_ID]),
+            ("2", &[tags::BEGIN_SEQ_NO, tags::END_SEQ_NO]),
+            ("3", &[tags::REF_SEQ_NUM]),
+            ("4", &[tags::NEW_SEQ_NO]),
+            ("5", &[]),
+            ("A", &[tags::ENCRYPT_METHOD, tags::HEART_BT_INT]),
+            ("D", &[tags::CL_ORD_ID, tags::SIDE, tags::ORDER_QTY, tags::ORD_TYPE]),
+            ("F", &[tags::ORIG_CL_ORD_ID, tags::CL_ORD_ID]),
+            ("G", &[tags::ORIG_CL_ORD_ID, tags::CL_ORD_ID, tags::SIDE, tags::ORDER_QTY, tags::ORD_TYPE]),
+            ("8", &[tags::ORDER_ID, tags::EXEC_ID, tags::EXEC_TYPE, tags::ORD_STATUS, tags::SIDE, tags::LEAVES_QTY, tags::CUM_QTY]),
+            ("9", &[tags::ORDER_ID, tags::CL_ORD_ID, tags::ORIG_CL_ORD_ID, tags::ORD_STATUS, tags::CXL_REJ_RESPONSE_TO]),
+            ("j", &[tags::REF_MSG_TYPE, tags::BUSINESS_REJECT_REASON]),
+            ("B", &[tags::HEADLINE]),
+            ("V", &[tags::MD_REQ_ID, tags::SUBSCRIPTION_REQUEST_TYPE, tags::MARKET_DEPTH]),
+            ("W", &[tags::NO_MD_ENTRIES]),
+            ("X", &[tags::NO_MD_ENTRIES]),
+        ];
+
+        let message_groups: [(&str, &[GroupDef]); 7] = [
+            ("D", &[groups::PARTY_IDS]),
+            ("G", &[groups::PARTY_IDS]),
+            ("8", &[groups::PARTY_IDS]),
+            ("B", &[groups::LINES_OF_TEXT]),
+            ("V", &[groups::MD_ENTRY_TYPES, groups::RELATED_SYM]),
+            ("W", &[groups::MD_FULL_REFRESH_ENTRIES]),
+            ("X", &[groups::MD_INCREMENTAL_ENTRIES]),
+        ];
+
+        let mut dictionary = Self::new();
+        for (tag, name, field_type, values) in fields {
+            dictionary.add_field(tag, FieldDef { name, field_type, values });
+        }
+        for (msg_type, required) in messages {
+            dictionary.add_message(msg_type, required);
+        }
+        for (msg_type, defs) in message_groups {
+            for def in defs {
+                dictionary.add_group(msg_type, *def);
+            }
+        }
+        dictionary
+    }
+
+    /// Defines (or redefines) a field.
+    pub fn add_field(&mut self, tag: Tag, def: FieldDef) {
+        self.fields.insert(tag, def);
+    }
+
+    /// Defines (or redefines) a message type and its required body fields.
+    pub fn add_message(&mut self, msg_type: &str, required: &[Tag]) {
+        self.messages.insert(msg_type.to_string(), required.to_vec());
+    }
+
+    /// Returns the definition of `tag`, if known.
+    pub fn get_field(&self, tag: Tag) -> Option<&FieldDef> {
+        self.fields.get(&tag)
+    }
+
+    /// Returns the required body fields of `msg_type`, if the message type is known.
+    pub fn get_required(&self, msg_type: &str) -> Option<&[Tag]> {
+        self.messages.get(msg_type).map(Vec::as_slice)
+    }
+
+    /// Adds (or replaces) a repeating group of `msg_type`.
+    pub fn add_group(&mut self, msg_type: &str, def: GroupDef) {
+        let defs = self.groups.entry(msg_type.to_string()).or_default();
+        defs.retain(|existing| existing.count_tag != def.count_tag);
+        defs.push(def);
+    }
+
+    /// Returns the repeating group of `msg_type` counted by `count_tag`, if known.
+    pub fn get_group(&self, msg_type: &str, count_tag: Tag) -> Option<&GroupDef> {
+        self.groups.get(msg_type)?.iter().find(|def| def.count_tag == count_tag)
+    }
+
+    /// Validates `message`, returning the first violation found.
+    ///
+    /// # Errors
+    /// Returns the [`ValidationError`] to report in a session-level Reject.
+    pub fn validate(&self, message: &FixMessage) -> Result<(), ValidationError> {
+        let required = self.get_required(message.get_msg_type().unwrap_or_default()).unwrap_or_default();
+        if let Some(tag) = HEADER_REQUIRED.iter().chain(required).find(|tag| !message.has_field(**tag)) {
+            return Err(ValidationError::RequiredTagMissing(*tag));
+        }
+        for (tag, value) in message.get_fields() {
+            let Some(def) = self.fields.get(tag) else {
+                continue;
+            };
+            if !parses_as(def.field_type, value) {
+                return Err(ValidationError::IncorrectDataFormat { tag: *tag, value: value.clone() });
+            }
+            if !def.values.is_empty() && !def.values.contains(&value.as_str()) {
+                return Err(ValidationError::ValueIncorrect { tag: *tag, value: value.clone() });
+            }
+        }
+        let defs = self.groups.get(message.get_msg_type().unwrap_or_default()).map(Vec::as_slice).unwrap_or_default();
+        for def in defs {
+            check_group(message, def)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the entries of group `def` in `message` and, recursively, their nested groups.
+fn check_group(message: &FixMessage, def: &GroupDef) -> Result<(), ValidationError> {
+    let entries = message.get_group(def).map_err(|err| match err {
+        FixError::GroupDelimiterExpected { delimiter, .. } => ValidationError::GroupFieldsOutOfOrder(delimiter),
+        _ => ValidationError::IncorrectNumInGroupCount(def.count_tag),
+    })?;
+    for entry in &entries {
+        for nested in def.groups {
+            check_group(entry, nested)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if `value` is well formed for `field_type`.
+fn parses_as(field_type: FieldType, value: &str) -> bool {
+    match field_type {
+        FieldType::Int => value.parse::<i64>().is_ok(),
+        FieldType::UInt => value.parse::<u64>().is_ok(),
+        FieldType::Float => value.bytes().all(|byte| byte.is_ascii_digit() || byte == b'.' || byte == b'-') && value.parse::<f64>().is_ok(),
+        FieldType::Char => value.chars().count() == 1,
+        FieldType::Boolean => value == "Y" || value == "N",
+        FieldType::String => true,
+        FieldType::UtcTimestamp => is_utc_timestamp(value),
+    }
+}
+
+/// Checks the `YYYYMMDD-HH:MM:SS[.s…]` format, allowing a leap second.
+fn is_utc_timestamp(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.len() < 17 || bytes[8] != b'-' || bytes[11] != b':' || bytes[14] != b':' {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        let digits = &value[range];
+        digits.bytes().all(|byte| byte.is_ascii_digit()).then(|| digits.parse().ok()).flatten()
+    };
+    let fields = (number(0..4), number(4..6), number(6..8), number(9..11), number(12..14), number(15..17));
+    let (Some(_), Some(month), Some(day), Some(hours), Some(minutes), Some(seconds)) = fields else {
+        return false;
+    };
+    let fraction = &value[17..];
+    let fraction_ok = fraction.is_empty()
+        || (fraction.len() >= 2 && fraction.len() <= 10 && fraction.starts_with('.') && fraction[1..].bytes().all(|byte| byte.is_ascii_digit()));
+    (1..=12).contains(&month) && (1..=31).contains(&day) && hours < 24 && minutes < 60 && seconds <= 60 && fraction_ok
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fix::{FixMessageBuilder, GroupEntry};
+
+    fn order() -> FixMessageBuilder {
+        FixMessageBuilder::new("FIX.4.4", "D")
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, 2)
+            .field(tags::SENDING_TIME, "20240101-12:00:00.000")
+            .field(tags::CL_ORD_ID, "A")
+            .field(tags::SIDE, 1)
+            .field(tags::ORDER_QTY, 10)
+    }
+
+    #[test]
+    fn test_validate_required_fields(){
+        let dictionary = Dictionary::fix44();
+        assert_eq!(dictionary.validate(&order().field(tags::ORD_TYPE, 2).build_message()), Ok(()));
+
+        let err = dictionary.validate(&order().build_message()).unwrap_err();
+        assert_eq!(err, ValidationError::RequiredTagMissing(tags::ORD_TYPE));
+        assert_eq!((err.get_tag(), err.get_reason()), (tags::ORD_TYPE, reject_reason::REQUIRED_TAG_MISSING));
+
+        let mut builder = order().field(tags::ORD_TYPE, 2);
+        builder.remove_field(tags::SENDING_TIME);
+        assert_eq!(dictionary.validate(&builder.build_message()), Err(ValidationError::RequiredTagMissing(tags::SENDING_TIME)));
+
+        // Unknown message types and tags are left to the application.
+        let custom = FixMessageBuilder::new("FIX.4.4", "U1")
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, 2)
+            .field(tags::SENDING_TIME, "20240101-12:00:00")
+            .field(5000, "x");
+        assert_eq!(dictionary.validate(&custom.build_message()), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_values_and_formats(){
+        let dictionary = Dictionary::fix44();
+        let error = |builder: FixMessageBuilder| dictionary.validate(&builder.build_message()).unwrap_err();
+
+        assert_eq!(error(order().field(tags::ORD_TYPE, 'Z')), ValidationError::ValueIncorrect { tag: tags::ORD_TYPE, value: "Z".to_string() });
+        assert_eq!(error(order().field(tags::ORD_TYPE, "22")), ValidationError::IncorrectDataFormat { tag: tags::ORD_TYPE, value: "22".to_string() });
+        assert_eq!(error(order().field(tags::ORD_TYPE, 2).field(tags::PRICE, "1O1")).get_reason(), reject_reason::INCORRECT_DATA_FORMAT);
+        assert_eq!(error(order().field(tags::ORD_TYPE, 2).field(tags::PRICE, "inf")).get_tag(), tags::PRICE);
+        assert_eq!(error(order().field(tags::ORD_TYPE, 2).field(tags::POSS_DUP_FLAG, "yes")).get_tag(), tags::POSS_DUP_FLAG);
+
+        let mut builder = order().field(tags::ORD_TYPE, 2);
+        builder.set_field(tags::SENDING_TIME, "20241301-12:00:00");
+        assert_eq!(error(builder).get_tag(), tags::SENDING_TIME);
+    }
+
+    #[test]
+    fn test_validate_repeating_groups(){
+        let dictionary = Dictionary::fix44();
+        let party = |id: &str, role: u32| GroupEntry::new().field(tags::PARTY_ID, id).field(tags::PARTY_ID_SOURCE, 'D').field(tags::PARTY_ROLE, role);
+        let parties = [
+            party("FIRM", 1).group(&groups::PARTY_SUB_IDS, [GroupEntry::new().field(tags::PARTY_SUB_ID, "DESK-7").field(tags::PARTY_SUB_ID_TYPE, 4)]),
+            party("TRADER", 12),
+        ];
+        let message = order().field(tags::ORD_TYPE, 2).group(&groups::PARTY_IDS, parties).build_message();
+        assert_eq!(dictionary.validate(&message), Ok(()));
+        assert_eq!(dictionary.get_group("D", tags::NO_PARTY_IDS), Some(&groups::PARTY_IDS));
+
+        let entries = message.get_group(&groups::PARTY_IDS).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.get_field(tags::PARTY_ID).unwrap()).collect::<Vec<_>>(), vec!["FIRM", "TRADER"]);
+        let sub_ids = entries[0].get_group(&groups::PARTY_SUB_IDS).unwrap();
+        assert_eq!(sub_ids[0].get_field(tags::PARTY_SUB_ID), Some("DESK-7"));
+        assert!(entries[1].get_group(&groups::PARTY_SUB_IDS).unwrap().is_empty());
+
+        // NumInGroup must match the entries, and each entry must open with the delimiter.
+        let short = order().field(tags::ORD_TYPE, 2).field(tags::NO_PARTY_IDS, 2).field(tags::PARTY_ID, "FIRM").field(tags::PARTY_ROLE, 1);
+        let err = dictionary.validate(&short.build_message()).unwrap_err();
+        assert_eq!((err.get_tag(), err.get_reason()), (tags::NO_PARTY_IDS, reject_reason::INCORRECT_NUM_IN_GROUP_COUNT));
+        let unordered = order().field(tags::ORD_TYPE, 2).field(tags::NO_PARTY_IDS, 1).field(tags::PARTY_ROLE, 1).field(tags::PARTY_ID, "FIRM");
+        assert_eq!(dictionary.validate(&unordered.build_message()), Err(ValidationError::GroupFieldsOutOfOrder(tags::PARTY_ID)));
+    }
+
+    #[test]
+    fn test_utc_timestamp_format(){
+        assert!(is_utc_timestamp("20240229-23:59:60"));
+        assert!(is_utc_timestamp("20240229-12:34:56.789"));
+        assert!(is_utc_timestamp("20240229-12:34:56.123456789"));
+        assert!(!is_utc_timestamp("20240229-12:34:56."));
+        assert!(!is_utc_timestamp("20240229 12:34:56"));
+        assert!(!is_utc_timestamp("20240229-24:00:00"));
+        assert!(!is_utc_timestamp("2024022-12:34:56"));
+    }
+}
@@ -0,0 +1,212 @@
+//! # Fields Module
+//!
+//! Typed values for the enumerated fields the engine reads and writes.
+//!
+//! Each enum converts to and from its wire character with [`FieldEnum::to_char`] and
+//! [`FieldEnum::from_char`], displays as that character so it can be passed straight to
+//! [`FixMessageBuilder::field`](crate::fix::FixMessageBuilder::field), and is read from a
+//! message with [`FieldEnum::read`]. Only the values this engine can act on are listed; any
+//! other value is reported as [`FixError::InvalidValue`].
+
+use std::fmt;
+use crate::fix::{tags, FixError, FixMessage, Tag};
+
+/// An enumerated field with single-character values.
+pub trait FieldEnum: Sized + Copy {
+    /// The tag the values belong to.
+    const TAG: Tag;
+
+    /// Returns the wire character of the value.
+    fn to_char(self) -> char;
+
+    /// Returns the value for a wire character, or `None` if it is not listed.
+    fn from_char(code: char) -> Option<Self>;
+
+    /// Reads the field from `message`.
+    ///
+    /// # Errors
+    /// Returns [`FixError::FieldNotFound`] if the field is absent and
+    /// [`FixError::InvalidValue`] if it is not one of the listed values.
+    fn read(message: &FixMessage) -> Result<Self, FixError> {
+        let value = message.get_field(Self::TAG).ok_or(FixError::FieldNotFound(Self::TAG))?;
+        let mut chars = value.chars();
+        match (chars.next().and_then(Self::from_char), chars.next()) {
+            (Some(parsed), None) => Ok(parsed),
+            _ => Err(FixError::InvalidValue { tag: Self::TAG, value: value.to_string() }),
+        }
+    }
+}
+
+macro_rules! fix_enum {
+    ($(#[$meta:meta])* $name:ident, $tag:expr, { $($(#[$variant_meta:meta])* $variant:ident = $code:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant),+
+        }
+
+        impl FieldEnum for $name {
+            const TAG: Tag = $tag;
+
+            fn to_char(self) -> char {
+                match self {
+                    $($name::$variant => $code),+
+                }
+            }
+
+            fn from_char(code: char) -> Option<Self> {
+                match code {
+                    $($code => Some($name::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+
+        impl TryFrom<char> for $name {
+            type Error = char;
+
+            fn try_from(code: char) -> Result<Self, Self::Error> {
+                Self::from_char(code).ok_or(code)
+            }
+        }
+
+        impl From<$name> for char {
+            fn from(value: $name) -> char {
+                value.to_char()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.to_char())
+            }
+        }
+    };
+}
+
+fix_enum!(
+    /// Side (tag 54).
+    Side, tags::SIDE, {
+        Buy = '1',
+        Sell = '2',
+        /// A sell the seller does not own; the engine treats it as a sell with
+        /// [`OrderFlags::short_sell`](orderbook::OrderFlags::short_sell) set.
+        SellShort = '5',
+    }
+);
+
+fix_enum!(
+    /// OrdType (tag 40).
+    OrdType, tags::ORD_TYPE, {
+        Market = '1',
+        Limit = '2',
+    }
+);
+
+fix_enum!(
+    /// TimeInForce (tag 59). Absent means [`TimeInForce::Day`].
+    TimeInForce, tags::TIME_IN_FORCE, {
+        Day = '0',
+        GoodTillCancel = '1',
+        ImmediateOrCancel = '3',
+        FillOrKill = '4',
+    }
+);
+
+fix_enum!(
+    /// ExecType (tag 150).
+    ExecType, tags::EXEC_TYPE, {
+        New = '0',
+        Canceled = '4',
+        Replaced = '5',
+        Rejected = '8',
+        Trade = 'F',
+    }
+);
+
+fix_enum!(
+    /// OrdStatus (tag 39).
+    OrdStatus, tags::ORD_STATUS, {
+        New = '0',
+        PartiallyFilled = '1',
+        Filled = '2',
+        Canceled = '4',
+        Rejected = '8',
+    }
+);
+
+fix_enum!(
+    /// OrderCapacity (tag 528). Absent means the capacity was not stated.
+    OrderCapacity, tags::ORDER_CAPACITY, {
+        Agency = 'A',
+        Principal = 'P',
+    }
+);
+
+impl From<orderbook::Side> for Side {
+    fn from(side: orderbook::Side) -> Self {
+        match side {
+            orderbook::Side::Buy => Side::Buy,
+            orderbook::Side::Sell => Side::Sell,
+        }
+    }
+}
+
+impl From<Side> for orderbook::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => orderbook::Side::Buy,
+            Side::Sell | Side::SellShort => orderbook::Side::Sell,
+        }
+    }
+}
+
+impl From<orderbook::Capacity> for OrderCapacity {
+    fn from(capacity: orderbook::Capacity) -> Self {
+        match capacity {
+            orderbook::Capacity::Agency => OrderCapacity::Agency,
+            orderbook::Capacity::Principal => OrderCapacity::Principal,
+        }
+    }
+}
+
+impl From<OrderCapacity> for orderbook::Capacity {
+    fn from(capacity: OrderCapacity) -> Self {
+        match capacity {
+            OrderCapacity::Agency => orderbook::Capacity::Agency,
+            OrderCapacity::Principal => orderbook::Capacity::Principal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fix::FixMessageBuilder;
+
+    #[test]
+    fn test_round_trip_through_wire_characters(){
+        for status in [OrdStatus::New, OrdStatus::PartiallyFilled, OrdStatus::Filled, OrdStatus::Canceled, OrdStatus::Rejected] {
+            assert_eq!(OrdStatus::try_from(char::from(status)), Ok(status));
+        }
+        assert_eq!(ExecType::Trade.to_char(), 'F');
+        assert_eq!(TimeInForce::try_from('2'), Err('2'));
+        assert_eq!(Side::from(orderbook::Side::Sell).to_string(), "2");
+        assert_eq!(orderbook::Side::from(Side::SellShort), orderbook::Side::Sell);
+        assert_eq!(OrderCapacity::from(orderbook::Capacity::Principal).to_char(), 'P');
+    }
+
+    #[test]
+    fn test_read_from_message(){
+        let message = FixMessageBuilder::new("FIX.4.4", "D")
+            .field(tags::SIDE, Side::Sell)
+            .field(tags::ORD_TYPE, "P")
+            .field(tags::TIME_IN_FORCE, "44")
+            .build_message();
+
+        assert_eq!(Side::read(&message), Ok(Side::Sell));
+        assert_eq!(OrdType::read(&message), Err(FixError::InvalidValue { tag: tags::ORD_TYPE, value: "P".to_string() }));
+        assert_eq!(TimeInForce::read(&message), Err(FixError::InvalidValue { tag: tags::TIME_IN_FORCE, value: "44".to_string() }));
+        assert_eq!(ExecType::read(&message), Err(FixError::FieldNotFound(tags::EXEC_TYPE)));
+    }
+}
@@ -1,36 +1,1210 @@
-use tokio::{
-    net::TcpStream, 
-    io::{AsyncReadExt, AsyncWriteExt}, 
-    time::sleep, time::Duration
-};
+//! # FIX Module
+//!
+//! Parsing of FIX tag=value messages and the client/server endpoints built on top of them.
+//!
+//! A message on the wire looks like (SOH shown as `|`):
+//!
+//! ```text
+//! 8=FIX.4.4|9=65|35=A|49=CLIENT|56=SERVER|34=1|52=20240101-12:00:00.000|98=0|108=30|10=062|
+//! ```
+//!
+//! - `8` (BeginString) must be the first field, `9` (BodyLength) the second and `35` (MsgType)
+//!   the third.
+//! - BodyLength counts the bytes after the BodyLength field up to and including the SOH that
+//!   precedes `10=`.
+//! - `10` (CheckSum) is the last field: the sum of every preceding byte modulo 256, written as
+//!   exactly three digits.
+//!
+//! ## Repeating groups
+//! A repeating group is a NumInGroup field (e.g. NoMDEntries, 268) followed by that many
+//! entries. Each entry starts with the group's delimiter field and the entries follow each
+//! other without any separator, so a [`GroupDef`] (usually taken from the
+//! [`Dictionary`](crate::dictionary::Dictionary)) is needed to tell where an entry and the group
+//! end. [`FixMessage::get_group`] splits a group into entries and
+//! [`FixMessageBuilder::group`] writes one, keeping entries and their fields in order.
 
+#![allow(non_camel_case_types)]
+
+use std::{fmt, str};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::{io::{AsyncRead, AsyncReadExt}, net::{TcpListener, TcpStream}, sync::{mpsc, watch}, task::{JoinHandle, JoinSet}, time};
+use crate::bridge::{BridgeError, BusinessMessageReject, NewOrder};
+use crate::config::{Config, ConfigError, SessionSettings};
+use crate::latency::{LatencyMetrics, LatencyStats};
+use crate::session::{drive_session, run_session_with, Session, SessionCommand, SessionConfig, SessionEvent, SessionRole};
+use crate::tls::{SessionStream, TlsClient, TlsServer};
+
+/// Field delimiter (ASCII "start of header").
+pub const SOH: u8 = 0x01;
+
+/// Largest BodyLength accepted. Longer messages are rejected instead of buffered.
+pub const MAX_BODY_LENGTH: usize = 1 << 20;
+
+/// Longest BeginString or BodyLength field accepted, SOH excluded.
+const MAX_HEADER_FIELD_LEN: usize = 32;
+
+/// Numeric FIX tag.
+pub type Tag = u32;
+
+/// Tag numbers used by the parser and session layer.
+pub mod tags {
+    use super::Tag;
+
+    pub const ACCOUNT: Tag = 1;
+    pub const AVG_PX: Tag = 6;
+    pub const BEGIN_SEQ_NO: Tag = 7;
+    pub const BEGIN_STRING: Tag = 8;
+    pub const BODY_LENGTH: Tag = 9;
+    pub const CHECKSUM: Tag = 10;
+    pub const CL_ORD_ID: Tag = 11;
+    pub const CUM_QTY: Tag = 14;
+    pub const END_SEQ_NO: Tag = 16;
+    pub const EXEC_ID: Tag = 17;
+    pub const LAST_PX: Tag = 31;
+    pub const LAST_QTY: Tag = 32;
+    pub const LINES_OF_TEXT: Tag = 33;
+    pub const MSG_SEQ_NUM: Tag = 34;
+    pub const MSG_TYPE: Tag = 35;
+    pub const NEW_SEQ_NO: Tag = 36;
+    pub const ORDER_ID: Tag = 37;
+    pub const ORDER_QTY: Tag = 38;
+    pub const ORD_STATUS: Tag = 39;
+    pub const ORD_TYPE: Tag = 40;
+    pub const ORIG_CL_ORD_ID: Tag = 41;
+    pub const POSS_DUP_FLAG: Tag = 43;
+    pub const PRICE: Tag = 44;
+    pub const REF_SEQ_NUM: Tag = 45;
+    pub const SENDER_COMP_ID: Tag = 49;
+    pub const SENDER_SUB_ID: Tag = 50;
+    pub const SENDING_TIME: Tag = 52;
+    pub const SIDE: Tag = 54;
+    pub const SYMBOL: Tag = 55;
+    pub const TARGET_COMP_ID: Tag = 56;
+    pub const TARGET_SUB_ID: Tag = 57;
+    pub const TEXT: Tag = 58;
+    pub const TIME_IN_FORCE: Tag = 59;
+    pub const POSS_RESEND: Tag = 97;
+    pub const ENCRYPT_METHOD: Tag = 98;
+    pub const CXL_REJ_REASON: Tag = 102;
+    pub const ORD_REJ_REASON: Tag = 103;
+    pub const HEART_BT_INT: Tag = 108;
+    pub const TEST_REQ_ID: Tag = 112;
+    pub const ORIG_SENDING_TIME: Tag = 122;
+    pub const GAP_FILL_FLAG: Tag = 123;
+    pub const RESET_SEQ_NUM_FLAG: Tag = 141;
+    pub const NO_RELATED_SYM: Tag = 146;
+    pub const HEADLINE: Tag = 148;
+    pub const EXEC_TYPE: Tag = 150;
+    pub const LEAVES_QTY: Tag = 151;
+    pub const MD_REQ_ID: Tag = 262;
+    pub const SUBSCRIPTION_REQUEST_TYPE: Tag = 263;
+    pub const MARKET_DEPTH: Tag = 264;
+    pub const MD_UPDATE_TYPE: Tag = 265;
+    pub const NO_MD_ENTRY_TYPES: Tag = 267;
+    pub const NO_MD_ENTRIES: Tag = 268;
+    pub const MD_ENTRY_TYPE: Tag = 269;
+    pub const MD_ENTRY_PX: Tag = 270;
+    pub const MD_ENTRY_SIZE: Tag = 271;
+    pub const MD_UPDATE_ACTION: Tag = 279;
+    pub const MD_REQ_REJ_REASON: Tag = 281;
+    pub const REF_TAG_ID: Tag = 371;
+    pub const REF_MSG_TYPE: Tag = 372;
+    pub const SESSION_REJECT_REASON: Tag = 373;
+    pub const BUSINESS_REJECT_REF_ID: Tag = 379;
+    pub const BUSINESS_REJECT_REASON: Tag = 380;
+    pub const CXL_REJ_RESPONSE_TO: Tag = 434;
+    pub const PARTY_ID_SOURCE: Tag = 447;
+    pub const PARTY_ID: Tag = 448;
+    pub const PARTY_ROLE: Tag = 452;
+    pub const NO_PARTY_IDS: Tag = 453;
+    pub const PARTY_SUB_ID: Tag = 523;
+    pub const ORDER_CAPACITY: Tag = 528;
+    pub const NO_PARTY_SUB_IDS: Tag = 802;
+    pub const PARTY_SUB_ID_TYPE: Tag = 803;
+    pub const APPL_VER_ID: Tag = 1128;
+    pub const DEFAULT_APPL_VER_ID: Tag = 1137;
+}
+
+/// Standard header fields in the order [`FixMessageBuilder`] writes them after MsgType.
+const HEADER_TAGS: [Tag; 10] = [
+    tags::SENDER_COMP_ID,
+    tags::TARGET_COMP_ID,
+    tags::SENDER_SUB_ID,
+    tags::TARGET_SUB_ID,
+    tags::MSG_SEQ_NUM,
+    tags::POSS_DUP_FLAG,
+    tags::POSS_RESEND,
+    tags::SENDING_TIME,
+    tags::ORIG_SENDING_TIME,
+    tags::APPL_VER_ID,
+];
+
+/// Errors produced while decoding or reading a FIX message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FixError {
+    /// The message does not start with a `8=FIX...` BeginString field.
+    InvalidBeginString,
+    /// The second field is not a numeric BodyLength (`9=`).
+    InvalidBodyLength,
+    /// The third field is not MsgType (`35=`).
+    MissingMsgType,
+    /// The BodyLength exceeds [`MAX_BODY_LENGTH`].
+    BodyLengthTooLarge { declared: usize },
+    /// The BodyLength does not point at a `10=NNN<SOH>` trailer.
+    BodyLengthMismatch { declared: usize },
+    /// The trailer is not a three digit CheckSum.
+    InvalidChecksum,
+    /// The declared CheckSum differs from the computed one.
+    ChecksumMismatch { declared: u8, computed: u8 },
+    /// A field is not of the form `tag=value`, or the tag is not a positive integer.
+    MalformedField(String),
+    /// The message is not valid UTF-8.
+    InvalidUtf8,
+    /// A requested field is absent.
+    FieldNotFound(Tag),
+    /// A field value could not be converted to the requested type.
+    InvalidValue { tag: Tag, value: String },
+    /// A repeating group holds a different number of entries than its NumInGroup field.
+    NumInGroupMismatch { tag: Tag, declared: usize, found: usize },
+    /// A repeating group entry does not start with the delimiter field.
+    GroupDelimiterExpected { delimiter: Tag, found: Tag },
+    /// A value given to a [`FixMessageBuilder`] contains the SOH delimiter.
+    ValueContainsSoh(Tag),
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixError::InvalidBeginString => write!(f, "message does not start with a valid BeginString (8)"),
+            FixError::InvalidBodyLength => write!(f, "second field is not a valid BodyLength (9)"),
+            FixError::MissingMsgType => write!(f, "third field is not MsgType (35)"),
+            FixError::BodyLengthTooLarge { declared } => write!(f, "BodyLength {} exceeds the limit of {}", declared, MAX_BODY_LENGTH),
+            FixError::BodyLengthMismatch { declared } => write!(f, "BodyLength {} does not end at the CheckSum field", declared),
+            FixError::InvalidChecksum => write!(f, "trailer is not a three digit CheckSum (10)"),
+            FixError::ChecksumMismatch { declared, computed } => {
+                write!(f, "CheckSum mismatch: declared {:03}, computed {:03}", declared, computed)
+            }
+            FixError::MalformedField(field) => write!(f, "malformed field: {}", field),
+            FixError::InvalidUtf8 => write!(f, "message is not valid UTF-8"),
+            FixError::FieldNotFound(tag) => write!(f, "field {} not found", tag),
+            FixError::InvalidValue { tag, value } => write!(f, "invalid value for field {}: {}", tag, value),
+            FixError::NumInGroupMismatch { tag, declared, found } => {
+                write!(f, "NumInGroup {} declares {} entries, found {}", tag, declared, found)
+            }
+            FixError::GroupDelimiterExpected { delimiter, found } => {
+                write!(f, "group entry starts with field {}, expected delimiter {}", found, delimiter)
+            }
+            FixError::ValueContainsSoh(tag) => write!(f, "value of field {} contains SOH", tag),
+        }
+    }
+}
+
+impl std::error::Error for FixError {}
+
+/// A decoded FIX message.
+///
+/// Fields are kept in wire order (including BeginString, BodyLength and CheckSum) so that
+/// repeated tags are preserved; lookups return the first occurrence.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct FixMessage {
+    fields: Vec<(Tag, String)>,
+}
+
+impl FixMessage {
+    /// Parses exactly one complete message.
+    ///
+    /// # Errors
+    /// Returns a [`FixError`] if the framing, BodyLength or CheckSum is invalid, or if any
+    /// field is malformed. Trailing bytes after the CheckSum field are rejected.
+    pub fn parse(bytes: &[u8]) -> Result<Self, FixError> {
+        match Self::decode(bytes)? {
+            Some((message, consumed)) if consumed == bytes.len() => Ok(message),
+            Some(_) => Err(FixError::MalformedField("trailing bytes after CheckSum".to_string())),
+            None => Err(FixError::BodyLengthMismatch { declared: declared_body_length(bytes).unwrap_or(0) }),
+        }
+    }
+
+    /// Decodes the first message at the start of `buffer`, as read from a stream.
+    ///
+    /// # Returns
+    /// - `Ok(Some((message, consumed)))` when a full message is available; `consumed` bytes
+    ///   should be dropped from the buffer.
+    /// - `Ok(None)` when more bytes are needed.
+    ///
+    /// # Errors
+    /// Returns a [`FixError`] as soon as the available bytes can no longer form a valid message,
+    /// so a stream never buffers more than one message of at most [`MAX_BODY_LENGTH`] bytes of
+    /// body. Malformed input of any kind yields an error, never a panic.
+    pub fn decode(buffer: &[u8]) -> Result<Option<(Self, usize)>, FixError> {
+        let Some(length_start) = find_soh(buffer, 0).map(|i| i + 1) else {
+            if buffer.len() > MAX_HEADER_FIELD_LEN {
+                return Err(FixError::InvalidBeginString);
+            }
+            return check_prefix(buffer, b"8=FIX").map(|_| None);
+        };
+        let begin_string = &buffer[..length_start - 1];
+        if !begin_string.starts_with(b"8=FIX") || begin_string.len() > MAX_HEADER_FIELD_LEN {
+            return Err(FixError::InvalidBeginString);
+        }
+
+        let Some(body_start) = find_soh(buffer, length_start).map(|i| i + 1) else {
+            if buffer.len() - length_start > MAX_HEADER_FIELD_LEN {
+                return Err(FixError::InvalidBodyLength);
+            }
+            return check_prefix(&buffer[length_start..], b"9=").map(|_| None);
+        };
+        let body_length: usize = buffer[length_start..body_start - 1]
+            .strip_prefix(b"9=")
+            .and_then(|digits| str::from_utf8(digits).ok())
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse().ok())
+            .ok_or(FixError::InvalidBodyLength)?;
+        if body_length > MAX_BODY_LENGTH {
+            return Err(FixError::BodyLengthTooLarge { declared: body_length });
+        }
+
+        let trailer_start = body_start + body_length;
+        let total_length = trailer_start + 7; // "10=NNN<SOH>"
+        if buffer.len() < total_length {
+            let available = &buffer[body_start..];
+            return check_prefix(available, b"35=").map(|_| None);
+        }
+        if !buffer[body_start..].starts_with(b"35=") {
+            return Err(FixError::MissingMsgType);
+        }
+        if body_length == 0 || buffer[trailer_start - 1] != SOH || !buffer[trailer_start..].starts_with(b"10=") {
+            return Err(FixError::BodyLengthMismatch { declared: body_length });
+        }
+
+        let trailer = &buffer[trailer_start + 3..total_length];
+        if trailer[3] != SOH || !trailer[..3].iter().all(u8::is_ascii_digit) {
+            return Err(FixError::InvalidChecksum);
+        }
+        let declared: u32 = str::from_utf8(&trailer[..3]).unwrap().parse().unwrap();
+        let computed = checksum(&buffer[..trailer_start]);
+        if declared != computed as u32 {
+            return Err(FixError::ChecksumMismatch { declared: declared.min(255) as u8, computed });
+        }
+
+        let fields = buffer[..total_length - 1]
+            .split(|b| *b == SOH)
+            .map(parse_field)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some((Self { fields }, total_length)))
+    }
+
+    /// Returns all fields in wire order.
+    pub fn get_fields(&self) -> &[(Tag, String)] {
+        &self.fields
+    }
+
+    /// Returns the first value of `tag`, if present.
+    pub fn get_field(&self, tag: Tag) -> Option<&str> {
+        self.fields.iter().find(|(t, _)| *t == tag).map(|(_, value)| value.as_str())
+    }
+
+    /// Returns every value of `tag` in wire order.
+    pub fn get_all(&self, tag: Tag) -> impl Iterator<Item = &str> {
+        self.fields.iter().filter(move |(t, _)| *t == tag).map(|(_, value)| value.as_str())
+    }
+
+    /// Returns `true` if `tag` is present.
+    pub fn has_field(&self, tag: Tag) -> bool {
+        self.get_field(tag).is_some()
+    }
+
+    /// Returns the first value of `tag` or [`FixError::FieldNotFound`].
+    pub fn get_required(&self, tag: Tag) -> Result<&str, FixError> {
+        self.get_field(tag).ok_or(FixError::FieldNotFound(tag))
+    }
+
+    /// Returns BeginString (tag 8), e.g. `FIX.4.4`.
+    pub fn get_begin_string(&self) -> Option<&str> {
+        self.get_field(tags::BEGIN_STRING)
+    }
+
+    /// Returns MsgType (tag 35), e.g. `A` for Logon.
+    pub fn get_msg_type(&self) -> Option<&str> {
+        self.get_field(tags::MSG_TYPE)
+    }
+
+    /// Reads `tag` as a signed integer.
+    pub fn get_int(&self, tag: Tag) -> Result<i64, FixError> {
+        self.get_parsed(tag)
+    }
+
+    /// Reads `tag` as an unsigned integer (lengths, sequence numbers, quantities).
+    pub fn get_uint(&self, tag: Tag) -> Result<u64, FixError> {
+        self.get_parsed(tag)
+    }
+
+    /// Reads `tag` as a decimal number (prices, amounts).
+    pub fn get_float(&self, tag: Tag) -> Result<f64, FixError> {
+        self.get_parsed(tag)
+    }
+
+    /// Reads `tag` as a single character (enumerations like Side or OrdType).
+    pub fn get_char(&self, tag: Tag) -> Result<char, FixError> {
+        let value = self.get_required(tag)?;
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(FixError::InvalidValue { tag, value: value.to_string() }),
+        }
+    }
+
+    /// Reads `tag` as a FIX boolean (`Y` or `N`).
+    pub fn get_bool(&self, tag: Tag) -> Result<bool, FixError> {
+        match self.get_char(tag)? {
+            'Y' => Ok(true),
+            'N' => Ok(false),
+            other => Err(FixError::InvalidValue { tag, value: other.to_string() }),
+        }
+    }
+
+    /// Splits the repeating group `def` into its entries, in wire order.
+    ///
+    /// Each entry is returned as a message holding only its own fields (including any nested
+    /// groups, which can be read with `get_group` in turn). A message without the NumInGroup
+    /// field has no entries.
+    ///
+    /// # Errors
+    /// Returns [`FixError::GroupDelimiterExpected`] if the first field after NumInGroup is not
+    /// the delimiter, and [`FixError::NumInGroupMismatch`] if the number of entries differs
+    /// from NumInGroup.
+    pub fn get_group(&self, def: &GroupDef) -> Result<Vec<FixMessage>, FixError> {
+        let Some(start) = self.fields.iter().position(|(tag, _)| *tag == def.count_tag) else {
+            return Ok(Vec::new());
+        };
+        let declared: usize = self.get_parsed(def.count_tag)?;
+        let mut entries: Vec<FixMessage> = Vec::new();
+        for (tag, value) in &self.fields[start + 1..] {
+            if *tag == def.get_delimiter() {
+                entries.push(FixMessage::default());
+            } else if !def.contains(*tag) {
+                break;
+            }
+            match entries.last_mut() {
+                Some(entry) => entry.fields.push((*tag, value.clone())),
+                None => return Err(FixError::GroupDelimiterExpected { delimiter: def.get_delimiter(), found: *tag }),
+            }
+        }
+        if entries.len() != declared {
+            return Err(FixError::NumInGroupMismatch { tag: def.count_tag, declared, found: entries.len() });
+        }
+        Ok(entries)
+    }
+
+    fn get_parsed<T: str::FromStr>(&self, tag: Tag) -> Result<T, FixError> {
+        let value = self.get_required(tag)?;
+        value.parse().map_err(|_| FixError::InvalidValue { tag, value: value.to_string() })
+    }
+}
+
+/// Layout of a repeating group.
+///
+/// `fields` lists the tags an entry may contain; the first one is the delimiter that opens
+/// every entry. `groups` lists the groups that may be nested in an entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroupDef {
+    /// The NumInGroup tag that counts the entries.
+    pub count_tag: Tag,
+    pub fields: &'static [Tag],
+    pub groups: &'static [GroupDef],
+}
+
+impl GroupDef {
+    /// Returns the tag that opens every entry.
+    pub fn get_delimiter(&self) -> Tag {
+        self.fields[0]
+    }
+
+    /// Returns `true` if `tag` belongs to an entry, directly or through a nested group.
+    pub fn contains(&self, tag: Tag) -> bool {
+        self.fields.contains(&tag) || self.groups.iter().any(|group| group.count_tag == tag || group.contains(tag))
+    }
+}
+
+/// One entry of a repeating group being built; fields keep insertion order.
+///
+/// ```
+/// use fix_ptc::dictionary::groups;
+/// use fix_ptc::fix::{tags, FixMessageBuilder, GroupEntry};
+///
+/// let message = FixMessageBuilder::new("FIX.4.4", "V")
+///     .group(&groups::MD_ENTRY_TYPES, ['0', '1'].map(|entry_type| GroupEntry::new().field(tags::MD_ENTRY_TYPE, entry_type)))
+///     .build_message();
+///
+/// let entries = message.get_group(&groups::MD_ENTRY_TYPES).unwrap();
+/// assert_eq!(entries[1].get_char(tags::MD_ENTRY_TYPE), Ok('1'));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupEntry {
+    fields: Vec<(Tag, String)>,
+}
+
+impl GroupEntry {
+    /// Starts an empty entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a field. The first field must be the group's delimiter.
+    ///
+    /// A value containing SOH makes [`FixMessageBuilder::build`] fail.
+    pub fn field(mut self, tag: Tag, value: impl ToString) -> Self {
+        self.fields.push((tag, value.to_string()));
+        self
+    }
+
+    /// Appends a field if `value` is `Some`.
+    pub fn optional_field(self, tag: Tag, value: Option<impl ToString>) -> Self {
+        match value {
+            Some(value) => self.field(tag, value),
+            None => self,
+        }
+    }
+
+    /// Appends a nested repeating group.
+    pub fn group(mut self, def: &GroupDef, entries: impl IntoIterator<Item = GroupEntry>) -> Self {
+        self.fields.extend(group_fields(def, entries));
+        self
+    }
+}
+
+/// Returns NumInGroup followed by the fields of every entry.
+fn group_fields(def: &GroupDef, entries: impl IntoIterator<Item = GroupEntry>) -> Vec<(Tag, String)> {
+    let entries: Vec<GroupEntry> = entries.into_iter().collect();
+    let mut fields = vec![(def.count_tag, entries.len().to_string())];
+    for entry in entries {
+        debug_assert_eq!(entry.fields.first().map(|(tag, _)| *tag), Some(def.get_delimiter()), "group {} entry must start with its delimiter", def.count_tag);
+        fields.extend(entry.fields);
+    }
+    fields
+}
+
+/// Assembles an outbound FIX message.
+///
+/// BeginString, BodyLength, MsgType and CheckSum are written automatically. Standard header
+/// fields (SenderCompID, MsgSeqNum, SendingTime, ...) are moved to the header in their
+/// conventional order no matter when they are added; all other fields keep insertion order.
+///
+/// ```
+/// use fix_ptc::fix::{tags, FixMessage, FixMessageBuilder};
+///
+/// let bytes = FixMessageBuilder::new("FIX.4.4", "0")
+///     .field(112, "PING")
+///     .field(tags::MSG_SEQ_NUM, 2)
+///     .field(tags::SENDER_COMP_ID, "CLIENT")
+///     .build()
+///     .unwrap();
+///
+/// let message = FixMessage::parse(&bytes).unwrap();
+/// assert_eq!(message.get_uint(tags::MSG_SEQ_NUM), Ok(2));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixMessageBuilder {
+    begin_string: String,
+    msg_type: String,
+    header: Vec<(Tag, String)>,
+    body: Vec<(Tag, String)>,
+}
+
+impl FixMessageBuilder {
+    /// Starts a message of type `msg_type` (tag 35) for protocol `begin_string` (tag 8).
+    pub fn new(begin_string: &str, msg_type: &str) -> Self {
+        Self {
+            begin_string: begin_string.to_string(),
+            msg_type: msg_type.to_string(),
+            header: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Starts a builder holding every field of `message` except the framing fields, so a
+    /// received or stored message can be amended and re-sent.
+    pub fn from_message(message: &FixMessage) -> Self {
+        let mut builder = Self::new(message.get_begin_string().unwrap_or_default(), message.get_msg_type().unwrap_or_default());
+        for (tag, value) in message.get_fields() {
+            if !matches!(*tag, tags::BEGIN_STRING | tags::BODY_LENGTH | tags::MSG_TYPE | tags::CHECKSUM) {
+                builder.push(*tag, value.clone());
+            }
+        }
+        builder
+    }
+
+    /// Appends a field. Header tags replace any earlier value; body tags may repeat.
+    ///
+    /// A value containing SOH makes [`build`](Self::build) fail.
+    pub fn field(mut self, tag: Tag, value: impl ToString) -> Self {
+        self.push(tag, value.to_string());
+        self
+    }
+
+    /// Appends a field if `value` is `Some`.
+    pub fn optional_field(self, tag: Tag, value: Option<impl ToString>) -> Self {
+        match value {
+            Some(value) => self.field(tag, value),
+            None => self,
+        }
+    }
+
+    /// Appends the repeating group `def`: NumInGroup followed by each entry in order.
+    ///
+    /// Group fields stay in the body even if they share a tag with the header.
+    pub fn group(mut self, def: &GroupDef, entries: impl IntoIterator<Item = GroupEntry>) -> Self {
+        self.body.extend(group_fields(def, entries));
+        self
+    }
+
+    /// Sets a field in place, replacing every earlier occurrence of `tag`.
+    pub fn set_field(&mut self, tag: Tag, value: impl ToString) {
+        self.remove_field(tag);
+        self.push(tag, value.to_string());
+    }
+
+    /// Removes every occurrence of `tag`.
+    pub fn remove_field(&mut self, tag: Tag) {
+        self.header.retain(|(t, _)| *t != tag);
+        self.body.retain(|(t, _)| *t != tag);
+    }
+
+    /// Returns the first value of `tag` added so far.
+    pub fn get_field(&self, tag: Tag) -> Option<&str> {
+        self.header.iter().chain(&self.body).find(|(t, _)| *t == tag).map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the MsgType (tag 35).
+    pub fn get_msg_type(&self) -> &str {
+        &self.msg_type
+    }
+
+    /// Checks that the message can be serialized.
+    ///
+    /// # Errors
+    /// Returns [`FixError::ValueContainsSoh`] if a value contains the SOH delimiter, which would
+    /// split it into fields of the peer's choosing.
+    pub fn check(&self) -> Result<(), FixError> {
+        let framing = [(tags::BEGIN_STRING, &self.begin_string), (tags::MSG_TYPE, &self.msg_type)];
+        let fields = self.header.iter().chain(&self.body).map(|(tag, value)| (*tag, value));
+        match framing.into_iter().chain(fields).find(|(_, value)| value.as_bytes().contains(&SOH)) {
+            Some((tag, _)) => Err(FixError::ValueContainsSoh(tag)),
+            None => Ok(()),
+        }
+    }
+
+    /// Serializes the message, computing BodyLength and CheckSum.
+    ///
+    /// # Errors
+    /// Returns an error if [`check`](Self::check) fails.
+    pub fn build(&self) -> Result<Vec<u8>, FixError> {
+        self.check()?;
+        let mut body = Vec::new();
+        write_field(&mut body, tags::MSG_TYPE, &self.msg_type);
+        for (tag, value) in self.header.iter().chain(&self.body) {
+            write_field(&mut body, *tag, value);
+        }
+
+        let mut bytes = Vec::with_capacity(body.len() + 32);
+        write_field(&mut bytes, tags::BEGIN_STRING, &self.begin_string);
+        write_field(&mut bytes, tags::BODY_LENGTH, &body.len().to_string());
+        bytes.extend_from_slice(&body);
+        let sum = checksum(&bytes);
+        write_field(&mut bytes, tags::CHECKSUM, &format!("{:03}", sum));
+        Ok(bytes)
+    }
+
+    /// Serializes the message and returns it in decoded form.
+    ///
+    /// # Panics
+    /// Panics if [`build`](Self::build) fails or the message does not parse back.
+    pub fn build_message(&self) -> FixMessage {
+        FixMessage::parse(&self.build().expect("builder has an invalid value")).expect("builder produced an invalid message")
+    }
+
+    fn push(&mut self, tag: Tag, value: String) {
+        match HEADER_TAGS.iter().position(|t| *t == tag) {
+            Some(rank) => {
+                self.header.retain(|(t, _)| *t != tag);
+                let index = self.header.partition_point(|(t, _)| HEADER_TAGS.iter().position(|h| h == t) < Some(rank));
+                self.header.insert(index, (tag, value));
+            }
+            None => self.body.push((tag, value)),
+        }
+    }
+}
+
+fn write_field(bytes: &mut Vec<u8>, tag: Tag, value: &str) {
+    bytes.extend_from_slice(tag.to_string().as_bytes());
+    bytes.push(b'=');
+    bytes.extend_from_slice(value.as_bytes());
+    bytes.push(SOH);
+}
+
+/// Computes the FIX CheckSum of `bytes`: the byte sum modulo 256.
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn find_soh(buffer: &[u8], from: usize) -> Option<usize> {
+    buffer[from..].iter().position(|b| *b == SOH).map(|i| i + from)
+}
+
+/// Succeeds if `partial` could still grow into something starting with `prefix`.
+fn check_prefix(partial: &[u8], prefix: &[u8]) -> Result<(), FixError> {
+    let n = partial.len().min(prefix.len());
+    if partial[..n] == prefix[..n] {
+        return Ok(());
+    }
+    Err(match prefix {
+        b"8=FIX" => FixError::InvalidBeginString,
+        b"9=" => FixError::InvalidBodyLength,
+        _ => FixError::MissingMsgType,
+    })
+}
+
+fn declared_body_length(bytes: &[u8]) -> Option<usize> {
+    let start = find_soh(bytes, 0)? + 1;
+    let end = find_soh(bytes, start)?;
+    str::from_utf8(bytes[start..end].strip_prefix(b"9=")?).ok()?.parse().ok()
+}
+
+fn parse_field(field: &[u8]) -> Result<(Tag, String), FixError> {
+    let field = str::from_utf8(field).map_err(|_| FixError::InvalidUtf8)?;
+    let (tag, value) = field.split_once('=').ok_or_else(|| FixError::MalformedField(field.to_string()))?;
+    let tag: Tag = tag
+        .parse()
+        .ok()
+        .filter(|tag| *tag > 0 && !tag_has_leading_zero(field))
+        .ok_or_else(|| FixError::MalformedField(field.to_string()))?;
+    if value.is_empty() {
+        return Err(FixError::MalformedField(field.to_string()));
+    }
+    Ok((tag, value.to_string()))
+}
+
+fn tag_has_leading_zero(field: &str) -> bool {
+    field.starts_with('0')
+}
+
+/// Callbacks through which [`fix_client`] delivers inbound traffic.
+///
+/// Callbacks run on the client's dispatch task, one at a time and in arrival order.
+pub trait MessageHandler: Send + 'static {
+    /// Called once the Logon handshake has completed.
+    fn on_logon(&mut self) {}
+
+    /// Called for every inbound application message.
+    fn on_message(&mut self, message: FixMessage);
+
+    /// Called once when the session ends, with the reason.
+    fn on_disconnect(&mut self, _reason: &str) {}
+
+    /// Called whenever the connection changes state, including while reconnecting.
+    fn on_connection_state(&mut self, _state: &ConnectionState) {}
+}
+
+/// Connectivity of a [`fix_client`], reported through
+/// [`MessageHandler::on_connection_state`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// The session is logged on.
+    LoggedOn,
+    /// The session ended, with the reason.
+    Disconnected(String),
+    /// Reconnection attempt `attempt` (from 1) will start after `delay`.
+    Reconnecting { attempt: u32, delay: Duration },
+    /// A reconnection attempt failed to connect or log on.
+    ReconnectFailed { attempt: u32, reason: String },
+    /// The reconnection attempts are exhausted; the client stays disconnected.
+    GaveUp,
+}
+
+/// When a [`fix_client`] reconnects after losing its session.
+///
+/// Attempt `n` waits `initial_delay * multiplier^(n-1)`, capped at `max_delay`, then varied
+/// by up to `jitter` (a fraction) either way so that many clients do not retry in step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: u32,
+    pub jitter: f64,
+    /// Attempts before giving up; unlimited if `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2,
+            jitter: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before attempt `attempt` (from 1), with `random` in `[0, 1)` choosing
+    /// the jitter.
+    pub fn get_delay(&self, attempt: u32, random: f64) -> Duration {
+        let growth = self.multiplier.checked_pow(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        let base = self.initial_delay.checked_mul(growth).unwrap_or(self.max_delay).min(self.max_delay);
+        base.mul_f64((1.0 + self.jitter * (2.0 * random - 1.0)).max(0.0))
+    }
+}
+
+/// Returns a random number in `[0, 1)` for jitter.
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let bits = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// An initiating FIX session over TCP.
 pub struct fix_client {
     target : String,
-    is_connected : bool
+    config : SessionConfig,
+    settings : Option<SessionSettings>,
+    reconnect : Option<ReconnectPolicy>,
+    latency : LatencyMetrics,
+    is_connected : Arc<AtomicBool>,
+    is_stopping : Arc<AtomicBool>,
+    commands : Option<mpsc::UnboundedSender<SessionCommand>>,
+    supervisor : Option<JoinHandle<Result<(), String>>>,
+}
+
+/// Callbacks through which [`fix_server`] delivers traffic from its sessions.
+///
+/// Every session runs on its own task, so callbacks for different sessions may run
+/// concurrently; callbacks for one session run one at a time and in arrival order.
+pub trait SessionHandler: Send + Sync + 'static {
+    /// Called once the counterparty has logged on.
+    fn on_logon(&self, _session: &SessionHandle) {}
+
+    /// Called for every inbound application message.
+    fn on_message(&self, session: &SessionHandle, message: FixMessage);
+
+    /// Called once when a logged-on session ends, with the reason.
+    fn on_disconnect(&self, _session: &SessionHandle, _reason: &str) {}
+
+    /// Called when a connection from `peer` ends with an error, e.g. a failed TLS handshake,
+    /// a Logon for no configured session or a broken stream.
+    fn on_connection_error(&self, _peer: SocketAddr, _error: &str) {}
+}
+
+/// What an accepted session is allowed to do.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SessionKind {
+    /// Enters orders and receives the reports of its own orders.
+    #[default]
+    Trading,
+    /// Receives copies of the ExecutionReports of `accounts` (all accounts if empty) and may
+    /// not send application messages.
+    DropCopy { accounts: Vec<String> },
+}
+
+impl SessionKind {
+    /// Returns true for a drop-copy session.
+    pub fn is_drop_copy(&self) -> bool {
+        matches!(self, SessionKind::DropCopy { .. })
+    }
+
+    /// Returns true if reports of `account` are copied to the session.
+    pub fn is_copied(&self, account: &str) -> bool {
+        match self {
+            SessionKind::Trading => false,
+            SessionKind::DropCopy { accounts } => accounts.is_empty() || accounts.iter().any(|copied| copied == account),
+        }
+    }
+}
+
+/// A session accepted by a [`fix_server`], through which the application sends messages.
+#[derive(Clone, Debug)]
+pub struct SessionHandle {
+    config: SessionConfig,
+    kind: SessionKind,
+    commands: mpsc::UnboundedSender<SessionCommand>,
+    latency: LatencyMetrics,
+}
+
+impl SessionHandle {
+    /// Returns the parameters of the session.
+    pub fn get_config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Returns the counterparty's CompID.
+    pub fn get_target_comp_id(&self) -> &str {
+        &self.config.target_comp_id
+    }
+
+    /// Returns whether the session trades or receives drop copies.
+    pub fn get_kind(&self) -> &SessionKind {
+        &self.kind
+    }
+
+    /// Returns the latency and clock-skew estimates of the session.
+    pub fn get_latency(&self) -> LatencyStats {
+        self.latency.get_stats()
+    }
+
+    /// Sends an application message; the session fills in the standard header.
+    ///
+    /// # Errors
+    /// Returns an error if a value contains SOH or the session has ended.
+    pub fn send(&self, builder: FixMessageBuilder) -> Result<(), String> {
+        builder.check().map_err(|err| format!("Cannot send {}: {}", builder.get_msg_type(), err))?;
+        self.commands.send(SessionCommand::Send(builder)).map_err(|_| format!("Session with {} has ended", self.config.target_comp_id))
+    }
+
+    /// Starts a graceful logout.
+    pub fn logout(&self, text: &str) {
+        let _ = self.commands.send(SessionCommand::Logout(text.to_string()));
+    }
+}
+
+/// The sessions a [`fix_server`] has accepted, keyed by (SenderCompID, TargetCompID).
+///
+/// A session is listed from the moment its connection is matched to a configured session
+/// until it ends. Clones share the same table, so the application can keep one to route
+/// messages to sessions other than the one it is handling.
+#[derive(Clone, Debug, Default)]
+pub struct SessionTable {
+    sessions: Arc<Mutex<HashMap<(String, String), SessionHandle>>>,
 }
 
+impl SessionTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the session with these CompIDs, if it is connected.
+    pub fn get(&self, sender_comp_id: &str, target_comp_id: &str) -> Option<SessionHandle> {
+        self.sessions.lock().unwrap().get(&(sender_comp_id.to_string(), target_comp_id.to_string())).cloned()
+    }
+
+    /// Returns the connected session whose counterparty is `target_comp_id`.
+    pub fn get_by_target(&self, target_comp_id: &str) -> Option<SessionHandle> {
+        self.sessions.lock().unwrap().values().find(|handle| handle.get_target_comp_id() == target_comp_id).cloned()
+    }
+
+    /// Returns the connected drop-copy sessions that receive the reports of `account`.
+    pub fn get_drop_copies(&self, account: &str) -> Vec<SessionHandle> {
+        self.sessions.lock().unwrap().values().filter(|handle| handle.kind.is_copied(account)).cloned().collect()
+    }
+
+    /// Returns every connected session.
+    pub fn get_handles(&self) -> Vec<SessionHandle> {
+        self.sessions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Returns the number of connected sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no session is connected.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds `handle`, returning `false` if its session is already listed.
+    fn insert(&self, handle: &SessionHandle) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let key = (handle.config.sender_comp_id.clone(), handle.config.target_comp_id.clone());
+        if sessions.contains_key(&key) {
+            return false;
+        }
+        sessions.insert(key, handle.clone());
+        true
+    }
+
+    fn remove(&self, handle: &SessionHandle) {
+        self.sessions.lock().unwrap().remove(&(handle.config.sender_comp_id.clone(), handle.config.target_comp_id.clone()));
+    }
+}
+
+/// How long an accepted connection may take to send its Logon.
+const LOGON_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An acceptor for the sessions declared in a configuration.
+///
+/// A connection is matched to a session by the CompIDs of its Logon. Connections that name no
+/// configured session, fall outside the session's schedule or duplicate a session that is
+/// already logged on are closed without a reply. Any number of sessions may be connected at
+/// once; they are listed in the server's [`SessionTable`].
+///
+/// Application messages from a [`SessionKind::DropCopy`] session are answered with a
+/// BusinessMessageReject and never reach the [`SessionHandler`]; the application finds the
+/// sessions to copy reports to with [`SessionTable::get_drop_copies`].
+///
+/// If the sessions set `tls_cert`, every connection starts with a TLS handshake, and the
+/// client certificate is checked against the session its Logon names; see the
+/// [TLS module](crate::tls).
+///
+/// Clones share the same sessions, so one clone can call [`fix_server::disconnect`] while
+/// another is serving.
+#[derive(Clone)]
 pub struct fix_server {
     addr: String,
+    sessions: Arc<Vec<SessionSettings>>,
+    tls: Option<Arc<TlsServer>>,
+    table: SessionTable,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+/// The outcome of one connection of a [`fix_client`]: the session and command queue to reuse
+/// on the next connection, and how the connection ended.
+type Connection = (Session, mpsc::UnboundedReceiver<SessionCommand>, Result<(), String>);
+
+/// Connects to `target`, over TLS if `tls` is set.
+async fn open_stream(target: &str, tls: Option<&TlsClient>) -> std::io::Result<Box<dyn SessionStream>> {
+    let stream = TcpStream::connect(target).await?;
+    Ok(match tls {
+        Some(tls) => Box::new(tls.connect(stream).await?),
+        None => Box::new(stream),
+    })
 }
 
-pub struct fix_message
+/// Drives `session` over `stream` on its own task, handing the session back when it ends.
+fn spawn_connection(
+    stream: Box<dyn SessionStream>,
+    mut session: Session,
+    mut commands: mpsc::UnboundedReceiver<SessionCommand>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+) -> JoinHandle<Connection> {
+    tokio::spawn(async move {
+        let result = drive_session(stream, Vec::new(), &mut session, &mut commands, &events).await;
+        (session, commands, result)
+    })
+}
 
 impl fix_client{
 
     pub fn new(addr: &str) -> Self {
         Self{
-            target : addr,
-            is_connected: false,
+            target : addr.to_string(),
+            config : SessionConfig::default(),
+            settings : None,
+            reconnect : None,
+            latency : LatencyMetrics::new(),
+            is_connected : Arc::new(AtomicBool::new(false)),
+            is_stopping : Arc::new(AtomicBool::new(false)),
+            commands : None,
+            supervisor : None,
+        }
+    }
+
+    /// Creates a client for a session declared in a configuration file.
+    pub fn from_settings(settings: &SessionSettings) -> Self {
+        Self{
+            config : settings.to_session_config(),
+            settings : Some(settings.clone()),
+            reconnect : settings.get_reconnect_policy(),
+            ..Self::new(&settings.get_address())
         }
     }
 
-    pub fn connect() -> Result<(), Box<dyn std::error::Error>> {
-        // stream = TcpStream::connect(&addr)?;
-        todo!();
+    /// Reconnects according to `policy` whenever the session is lost after the first logon.
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
     }
 
-    pub fn disconnect() -> Result<(), Box<dyn std::error::Error>> {
-        todo!();
+    /// Returns the session parameters used at logon.
+    pub fn get_config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Returns the reconnection policy, if the client reconnects.
+    pub fn get_reconnect_policy(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect.as_ref()
+    }
+
+    /// Returns the latency and clock-skew estimates of the session, kept across reconnections.
+    pub fn get_latency(&self) -> LatencyStats {
+        self.latency.get_stats()
+    }
+
+    /// Returns true while the session is logged on, between a successful
+    /// [`fix_client::connect`] and [`fix_client::disconnect`].
+    pub fn is_connected(&self) -> bool {
+        self.is_connected.load(Ordering::SeqCst) && self.supervisor.as_ref().is_some_and(|supervisor| !supervisor.is_finished())
+    }
+
+    /// Connects, logs on and starts delivering inbound messages to `handler`.
+    ///
+    /// Connects over TLS if the session's settings set `tls_ca`.
+    ///
+    /// Returns once the counterparty has answered the Logon. If a [`ReconnectPolicy`] is set,
+    /// a session lost afterwards is re-established in the background, resuming its sequence
+    /// numbers, and every change is reported through [`MessageHandler::on_connection_state`].
+    ///
+    /// # Errors
+    /// Returns an error if already connected, if the TLS settings cannot be loaded, if the
+    /// connection or TLS handshake fails, or if the logon is refused or times out.
+    pub async fn connect<H: MessageHandler>(&mut self, mut handler: H) -> Result<(), Box<dyn std::error::Error>> {
+        if self.supervisor.as_ref().is_some_and(|supervisor| !supervisor.is_finished()) {
+            return Err("Already connected".into());
+        }
+        let tls = match &self.settings {
+            Some(settings) => TlsClient::from_settings(settings)?,
+            None => None,
+        };
+        let stream = open_stream(&self.target, tls.as_ref()).await?;
+        let session = match &self.settings {
+            Some(settings) => settings.open_session(SessionRole::Initiator, Instant::now())?,
+            None => Session::new(self.config.clone(), SessionRole::Initiator, Instant::now()),
+        }
+        .with_latency_metrics(self.latency.clone());
+
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let mut driver = spawn_connection(stream, session, commands_rx, events_tx.clone());
+
+        let refused = tokio::select! {
+            biased;
+            event = events.recv() => match event {
+                Some(SessionEvent::LoggedOn) => None,
+                Some(SessionEvent::Disconnected(reason)) => Some(reason),
+                Some(SessionEvent::Message(_)) => Some("Application message received before Logon".to_string()),
+                None => Some("Connection closed during logon".to_string()),
+            },
+            // A failed read ends the connection without an event.
+            connection = &mut driver => {
+                let (_, _, result) = connection?;
+                return Err(format!("Logon to {} failed: {}", self.target, result.err().unwrap_or_else(|| "Connection closed during logon".to_string())).into());
+            }
+        };
+        if let Some(reason) = refused {
+            drop(commands);
+            let (_, _, result) = driver.await?;
+            return Err(format!("Logon to {} failed: {}", self.target, result.err().unwrap_or(reason)).into());
+        }
+
+        handler.on_logon();
+        handler.on_connection_state(&ConnectionState::LoggedOn);
+        self.is_connected.store(true, Ordering::SeqCst);
+        self.is_stopping.store(false, Ordering::SeqCst);
+        let target = self.target.clone();
+        let policy = self.reconnect.clone();
+        let connected = self.is_connected.clone();
+        let stopping = self.is_stopping.clone();
+        self.supervisor = Some(tokio::spawn(async move {
+            let mut attempt = 0;
+            loop {
+                let mut logged_on = attempt == 0;
+                let mut ended = None;
+                let (mut session, mut commands_rx, result) = loop {
+                    tokio::select! {
+                        biased;
+                        Some(event) = events.recv() => match event {
+                            SessionEvent::Message(message) => handler.on_message(message),
+                            SessionEvent::LoggedOn => {
+                                logged_on = true;
+                                attempt = 0;
+                                connected.store(true, Ordering::SeqCst);
+                                handler.on_logon();
+                                handler.on_connection_state(&ConnectionState::LoggedOn);
+                            }
+                            SessionEvent::Disconnected(reason) => {
+                                connected.store(false, Ordering::SeqCst);
+                                handler.on_disconnect(&reason);
+                                if logged_on {
+                                    handler.on_connection_state(&ConnectionState::Disconnected(reason.clone()));
+                                }
+                                ended = Some(reason);
+                            }
+                        },
+                        connection = &mut driver => break connection.map_err(|err| err.to_string())?,
+                    }
+                };
+                connected.store(false, Ordering::SeqCst);
+                let policy = match &policy {
+                    Some(policy) if !stopping.load(Ordering::SeqCst) => policy,
+                    _ => return result,
+                };
+                if !logged_on {
+                    let reason = result.err().or(ended).unwrap_or_else(|| "Connection closed during logon".to_string());
+                    handler.on_connection_state(&ConnectionState::ReconnectFailed { attempt, reason });
+                }
+
+                // Retry until a connection is made, then log on over it.
+                let stream = loop {
+                    attempt += 1;
+                    if policy.max_attempts.is_some_and(|max_attempts| attempt > max_attempts) {
+                        handler.on_connection_state(&ConnectionState::GaveUp);
+                        return Err(format!("Gave up reconnecting to {} after {} attempts", target, attempt - 1));
+                    }
+                    let delay = policy.get_delay(attempt, random_fraction());
+                    handler.on_connection_state(&ConnectionState::Reconnecting { attempt, delay });
+                    let sleep = time::sleep(delay);
+                    tokio::pin!(sleep);
+                    loop {
+                        tokio::select! {
+                            _ = &mut sleep => break,
+                            command = commands_rx.recv() => match command {
+                                Some(SessionCommand::Logout(_)) | None => return Ok(()),
+                                // Nothing can be sent without a session.
+                                Some(_) => {}
+                            },
+                        }
+                    }
+                    match open_stream(&target, tls.as_ref()).await {
+                        Ok(stream) => break stream,
+                        Err(err) => handler.on_connection_state(&ConnectionState::ReconnectFailed { attempt, reason: err.to_string() }),
+                    }
+                };
+                session.reconnect(Instant::now());
+                driver = spawn_connection(stream, session, commands_rx, events_tx.clone());
+            }
+        }));
+        self.commands = Some(commands);
+        Ok(())
+    }
+
+    /// Sends an application message; the session fills in the standard header.
+    ///
+    /// # Errors
+    /// Returns an error if a value contains SOH or the client is not connected.
+    pub fn send_raw(&self, builder: FixMessageBuilder) -> Result<(), Box<dyn std::error::Error>> {
+        builder.check()?;
+        let commands = self.commands.as_ref().filter(|_| self.is_connected()).ok_or("Not connected")?;
+        commands.send(SessionCommand::Send(builder)).map_err(|_| "Session has ended")?;
+        Ok(())
+    }
+
+    /// Sends `order` as a NewOrderSingle.
+    ///
+    /// # Errors
+    /// Returns an error if the client is not connected.
+    pub fn send_order(&self, order: &NewOrder) -> Result<(), Box<dyn std::error::Error>> {
+        self.send_raw(order.to_builder(&self.config.begin_string))
+    }
+
+    /// Logs out, stops reconnecting, and waits for the session to end and for the handler to
+    /// see every message.
+    ///
+    /// # Errors
+    /// Returns an error if the connection failed while logging out.
+    pub async fn disconnect(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.is_stopping.store(true, Ordering::SeqCst);
+        self.is_connected.store(false, Ordering::SeqCst);
+        if let Some(commands) = self.commands.take() {
+            let _ = commands.send(SessionCommand::Logout("Client disconnecting".to_string()));
+        }
+        match self.supervisor.take() {
+            Some(supervisor) => supervisor.await?.map_err(Into::into),
+            None => Ok(()),
+        }
     }
 }
 
@@ -38,15 +1212,769 @@ impl fix_server{
 
     pub fn new(addr: &str) -> Self {
         Self{
-            addr : String,
+            addr : addr.to_string(),
+            sessions : Arc::new(Vec::new()),
+            tls : None,
+            table : SessionTable::new(),
+            shutdown : Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Creates a server accepting every session declared in `config`, listening on the address
+    /// of the first one.
+    ///
+    /// # Errors
+    /// Returns an error if `config` declares no session or its TLS settings cannot be loaded.
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let first = config.get_sessions().first().ok_or_else(|| ConfigError::Invalid("No session declared".to_string()))?;
+        Ok(Self{
+            sessions : Arc::new(config.get_sessions().to_vec()),
+            tls : TlsServer::from_sessions(config.get_sessions())?.map(Arc::new),
+            ..Self::new(&first.get_address())
+        })
+    }
+
+    /// Returns the address [`fix_server::listen`] binds to.
+    pub fn get_address(&self) -> &str {
+        &self.addr
+    }
+
+    /// Returns true if the session with these CompIDs is currently connected.
+    pub fn is_active(&self, sender_comp_id: &str, target_comp_id: &str) -> bool {
+        self.table.get(sender_comp_id, target_comp_id).is_some()
+    }
+
+    /// Returns the table of connected sessions.
+    pub fn get_session_table(&self) -> SessionTable {
+        self.table.clone()
+    }
+
+    /// Binds the configured address and serves connections until [`fix_server::disconnect`]
+    /// is called or accepting fails.
+    ///
+    /// # Errors
+    /// Returns an error if the address cannot be bound or accepting a connection fails.
+    pub async fn listen<H: SessionHandler>(&self, handler: H) -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        self.serve(listener, handler).await
+    }
+
+    /// Serves connections from an already bound `listener`, each on its own task. A connection
+    /// that fails is reported to [`SessionHandler::on_connection_error`] and does not stop the
+    /// server.
+    ///
+    /// After [`fix_server::disconnect`], returns once every session has ended.
+    ///
+    /// # Errors
+    /// Returns an error if accepting a connection fails.
+    pub async fn serve<H: SessionHandler>(&self, listener: TcpListener, handler: H) -> Result<(), Box<dyn std::error::Error>> {
+        let handler = Arc::new(handler);
+        let mut shutdown = self.shutdown.subscribe();
+        let mut connections = JoinSet::new();
+        loop {
+            let (socket, peer) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            };
+            let sessions = Arc::clone(&self.sessions);
+            let tls = self.tls.clone();
+            let table = self.table.clone();
+            let shutdown = self.shutdown.subscribe();
+            let handler = Arc::clone(&handler);
+            connections.spawn(async move {
+                if let Err(err) = accept(socket, tls.as_deref(), &sessions, &table, shutdown, handler.as_ref()).await {
+                    handler.on_connection_error(peer, &err);
+                }
+            });
+            while connections.try_join_next().is_some() {}
+        }
+        while connections.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    /// Stops accepting connections and logs out every session.
+    pub fn disconnect(&self) {
+        self.shutdown.send_replace(true);
+        for handle in self.table.get_handles() {
+            handle.logout("Server shutting down");
+        }
+    }
+}
+
+/// Identifies the session of a new connection from its Logon and runs it to completion.
+async fn accept<H: SessionHandler>(
+    socket: TcpStream,
+    tls: Option<&TlsServer>,
+    sessions: &[SessionSettings],
+    table: &SessionTable,
+    mut shutdown: watch::Receiver<bool>,
+    handler: &H,
+) -> Result<(), String> {
+    let opening = async {
+        let (mut stream, certificates): (Box<dyn SessionStream>, _) = match tls {
+            Some(tls) => {
+                let stream = tls.accept(socket).await.map_err(|err| format!("TLS handshake failed: {}", err))?;
+                let certificates = stream.get_ref().1.peer_certificates().map(<[_]>::to_vec);
+                (Box::new(stream), certificates)
+            }
+            None => (Box::new(socket), None),
+        };
+        let (logon, received) = read_first_message(&mut stream).await?;
+        Ok::<_, String>((stream, certificates, logon, received))
+    };
+    let (stream, certificates, logon, received) = tokio::select! {
+        opened = time::timeout(LOGON_TIMEOUT, opening) => opened.map_err(|_| "No Logon received".to_string())??,
+        _ = shutdown.wait_for(|stopped| *stopped) => return Err("Server shutting down".to_string()),
+    };
+    let sender_comp_id = logon.get_field(tags::SENDER_COMP_ID).unwrap_or_default();
+    let target_comp_id = logon.get_field(tags::TARGET_COMP_ID).unwrap_or_default();
+    let settings = sessions
+        .iter()
+        .find(|settings| settings.sender_comp_id == target_comp_id && settings.target_comp_id == sender_comp_id)
+        .ok_or_else(|| format!("No session configured for {}->{}", sender_comp_id, target_comp_id))?;
+    if !settings.is_in_session(SystemTime::now()) {
+        return Err(format!("Session {}->{} is outside its schedule", sender_comp_id, target_comp_id));
+    }
+    if let Some(tls) = tls {
+        tls.check_client_certificate(settings, certificates.as_deref())?;
+    }
+
+    let (commands, commands_rx) = mpsc::unbounded_channel();
+    let handle = SessionHandle { config: settings.to_session_config(), kind: settings.get_kind(), commands, latency: LatencyMetrics::new() };
+    if !table.insert(&handle) {
+        return Err(format!("Session {}->{} is already logged on", sender_comp_id, target_comp_id));
+    }
+    // A shutdown that began before the session was listed did not log it out.
+    if *shutdown.borrow() {
+        handle.logout("Server shutting down");
+    }
+    let result = run_accepted(stream, received, settings, handle.clone(), commands_rx, handler).await;
+    table.remove(&handle);
+    result
+}
+
+/// Runs an identified session, dispatching its events to `handler`.
+async fn run_accepted<H: SessionHandler>(
+    stream: Box<dyn SessionStream>,
+    received: Vec<u8>,
+    settings: &SessionSettings,
+    handle: SessionHandle,
+    commands_rx: mpsc::UnboundedReceiver<SessionCommand>,
+    handler: &H,
+) -> Result<(), String> {
+    let session = settings
+        .open_session(SessionRole::Acceptor, Instant::now())
+        .map_err(|err| format!("Cannot open store: {}", err))?
+        .with_latency_metrics(handle.latency.clone());
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+
+    let driver = tokio::spawn(run_session_with(stream, received, session, commands_rx, events_tx));
+    let mut logged_on = false;
+    while let Some(event) = events.recv().await {
+        match event {
+            SessionEvent::LoggedOn => {
+                logged_on = true;
+                handler.on_logon(&handle);
+            }
+            // A drop-copy session only listens; whatever it sends is refused here.
+            SessionEvent::Message(message) if handle.kind.is_drop_copy() => {
+                let err = BridgeError::NotAuthorized("drop-copy sessions cannot send application messages".to_string());
+                let reject = BusinessMessageReject::new(handle.get_target_comp_id(), &message, &err);
+                let _ = handle.send(reject.to_builder(&handle.config.begin_string));
+            }
+            SessionEvent::Message(message) => handler.on_message(&handle, message),
+            SessionEvent::Disconnected(reason) if logged_on => handler.on_disconnect(&handle, &reason),
+            SessionEvent::Disconnected(_) => {}
+        }
+    }
+    driver.await.map_err(|err| err.to_string())?
+}
+
+/// Reads until the first complete message, returning it together with every byte read so far.
+async fn read_first_message(socket: &mut (impl AsyncRead + Unpin)) -> Result<(FixMessage, Vec<u8>), String> {
+    let mut received = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = socket.read(&mut chunk).await.map_err(|err| format!("Read failed: {}", err))?;
+        if read == 0 {
+            return Err("Connection closed before Logon".to_string());
+        }
+        received.extend_from_slice(&chunk[..read]);
+        if let Some((message, _)) = FixMessage::decode(&received).map_err(|err| err.to_string())? {
+            return Ok((message, received));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::session::run_session;
+
+    /// Builds a wire message from `|`-separated body fields, filling in BodyLength and CheckSum.
+    fn wire(begin_string: &str, body: &str) -> Vec<u8> {
+        let body = body.replace('|', "\u{1}");
+        let mut bytes = format!("8={}\u{1}9={}\u{1}{}", begin_string, body.len(), body).into_bytes();
+        let sum = checksum(&bytes);
+        bytes.extend(format!("10={:03}\u{1}", sum).into_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_parse_logon(){
+        let bytes = wire("FIX.4.4", "35=A|49=CLIENT|56=SERVER|34=1|52=20240101-12:00:00.000|98=0|108=30|");
+        let message = FixMessage::parse(&bytes).unwrap();
+
+        assert_eq!(message.get_begin_string(), Some("FIX.4.4"));
+        assert_eq!(message.get_msg_type(), Some("A"));
+        assert_eq!(message.get_field(tags::SENDER_COMP_ID), Some("CLIENT"));
+        assert_eq!(message.get_uint(tags::MSG_SEQ_NUM), Ok(1));
+        assert_eq!(message.get_int(108), Ok(30));
+        assert_eq!(message.get_fields().first(), Some(&(tags::BEGIN_STRING, "FIX.4.4".to_string())));
+        assert_eq!(message.get_fields().last().map(|(tag, _)| *tag), Some(tags::CHECKSUM));
+    }
+
+    #[test]
+    fn test_typed_accessors(){
+        let bytes = wire("FIX.4.4", "35=D|44=101.25|54=1|43=Y|58=hello|58=world|");
+        let message = FixMessage::parse(&bytes).unwrap();
+
+        assert_eq!(message.get_float(44), Ok(101.25));
+        assert_eq!(message.get_char(54), Ok('1'));
+        assert_eq!(message.get_bool(43), Ok(true));
+        assert_eq!(message.get_all(58).collect::<Vec<_>>(), vec!["hello", "world"]);
+        assert_eq!(message.get_int(58), Err(FixError::InvalidValue { tag: 58, value: "hello".to_string() }));
+        assert_eq!(message.get_char(44), Err(FixError::InvalidValue { tag: 44, value: "101.25".to_string() }));
+        assert_eq!(message.get_field(11), None);
+        assert_eq!(message.get_required(11), Err(FixError::FieldNotFound(11)));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_checksum(){
+        let mut bytes = wire("FIX.4.4", "35=0|");
+        let len = bytes.len();
+        bytes[len - 2] = if bytes[len - 2] == b'9' { b'0' } else { bytes[len - 2] + 1 };
+
+        assert!(matches!(FixMessage::parse(&bytes), Err(FixError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_framing(){
+        let good = wire("FIX.4.4", "35=0|");
+        let as_string = String::from_utf8(good.clone()).unwrap();
+
+        let wrong_length = as_string.replace("9=5", "9=4");
+        assert!(matches!(FixMessage::parse(wrong_length.as_bytes()), Err(FixError::BodyLengthMismatch { .. })));
+        assert_eq!(FixMessage::parse(&good[2..]), Err(FixError::InvalidBeginString));
+        assert_eq!(FixMessage::parse(&wire("FIX.4.4", "49=X|35=0|")), Err(FixError::MissingMsgType));
+        assert_eq!(FixMessage::parse(&wire("FIX.4.4", "35=0|abc|")), Err(FixError::MalformedField("abc".to_string())));
+        assert!(FixMessage::parse(&[good.clone(), b"8".to_vec()].concat()).is_err());
+    }
+
+    #[test]
+    fn test_decode_stream(){
+        let first = wire("FIX.4.4", "35=0|34=2|");
+        let second = wire("FIX.4.4", "35=1|34=3|112=TEST|");
+        let stream = [first.clone(), second.clone()].concat();
+
+        for end in 0..first.len() {
+            assert_eq!(FixMessage::decode(&stream[..end]), Ok(None), "prefix of {} bytes", end);
         }
+        let (message, consumed) = FixMessage::decode(&stream).unwrap().unwrap();
+        assert_eq!(consumed, first.len());
+        assert_eq!(message.get_msg_type(), Some("0"));
+
+        let (message, consumed) = FixMessage::decode(&stream[consumed..]).unwrap().unwrap();
+        assert_eq!(consumed, second.len());
+        assert_eq!(message.get_field(112), Some("TEST"));
+
+        assert_eq!(FixMessage::decode(b"GET / HTTP/1.1"), Err(FixError::InvalidBeginString));
     }
 
-    pub fn listen() -> Result<(), Box<dyn std::error::Error>> {
-        todo!();
+    #[test]
+    fn test_decode_rejects_oversized_headers(){
+        assert_eq!(FixMessage::decode(b"8=FIX.4.4\x019=1048577\x01"), Err(FixError::BodyLengthTooLarge { declared: MAX_BODY_LENGTH + 1 }));
+        assert_eq!(FixMessage::decode(b"8=FIX.4.4\x019=99999999999999999999999\x01"), Err(FixError::InvalidBodyLength));
+        assert_eq!(FixMessage::decode(&[b"8=FIX".as_slice(), &[b'4'; 64]].concat()), Err(FixError::InvalidBeginString));
+        assert_eq!(FixMessage::decode(&[b"8=FIX.4.4\x019=".as_slice(), &[b'0'; 64]].concat()), Err(FixError::InvalidBodyLength));
     }
 
-    pub fn disconnect() -> Result<(), Box<dyn std::error::Error>> {
-        todo!();
+    #[test]
+    fn test_decode_never_panics_on_mutated_input(){
+        let good = wire("FIX.4.4", "35=D|49=CLIENT|56=SERVER|34=2|11=ORD-1|453=1|448=X|");
+        for end in 0..=good.len() {
+            let _ = FixMessage::decode(&good[..end]);
+        }
+        for index in 0..good.len() {
+            for replacement in [0x00, SOH, b'=', b'0', b'9', 0x80, 0xFF] {
+                let mut mutated = good.clone();
+                mutated[index] = replacement;
+                let _ = FixMessage::decode(&mutated);
+                let _ = FixMessage::parse(&mutated);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_builder_orders_header_and_computes_trailer(){
+        let bytes = FixMessageBuilder::new("FIX.4.4", "D")
+            .field(11, "ORD-1")
+            .field(tags::SENDING_TIME, "20240101-12:00:00.000")
+            .field(54, '1')
+            .field(tags::MSG_SEQ_NUM, 7)
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(38, 100)
+            .build()
+            .unwrap();
+
+        let expected = wire("FIX.4.4", "35=D|49=CLIENT|56=SERVER|34=7|52=20240101-12:00:00.000|11=ORD-1|54=1|38=100|");
+        assert_eq!(bytes, expected);
+        assert_eq!(FixMessage::parse(&bytes).unwrap().get_char(54), Ok('1'));
+    }
+
+    #[test]
+    fn test_builder_rejects_soh_in_values(){
+        let builder = FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "1").field(tags::TEXT, "a\x0135=A");
+        assert_eq!(builder.build(), Err(FixError::ValueContainsSoh(tags::TEXT)));
+        let builder = FixMessageBuilder::new("FIX.4.4", "V")
+            .group(&crate::dictionary::groups::MD_ENTRY_TYPES, [GroupEntry::new().field(tags::MD_ENTRY_TYPE, "0\x01")]);
+        assert_eq!(builder.check(), Err(FixError::ValueContainsSoh(tags::MD_ENTRY_TYPE)));
+        assert_eq!(FixMessageBuilder::new("FIX.4.4\x01", "0").build(), Err(FixError::ValueContainsSoh(tags::BEGIN_STRING)));
+    }
+
+    #[test]
+    fn test_builder_round_trip_and_amend(){
+        let original = FixMessageBuilder::new("FIX.4.4", "8")
+            .field(tags::MSG_SEQ_NUM, 3)
+            .field(58, "a")
+            .field(58, "b")
+            .optional_field(44, None::<f64>)
+            .build_message();
+
+        let mut builder = FixMessageBuilder::from_message(&original);
+        assert_eq!(builder.build_message(), original);
+
+        builder.set_field(tags::MSG_SEQ_NUM, 4);
+        builder.set_field(tags::POSS_DUP_FLAG, 'Y');
+        let amended = builder.build_message();
+        assert_eq!(amended.get_uint(tags::MSG_SEQ_NUM), Ok(4));
+        assert_eq!(amended.get_bool(tags::POSS_DUP_FLAG), Ok(true));
+        assert_eq!(amended.get_all(58).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert!(!amended.has_field(44));
+    }
+
+    /// Forwards everything the client receives to a channel.
+    struct Forward(mpsc::UnboundedSender<String>);
+
+    impl MessageHandler for Forward {
+        fn on_logon(&mut self) {
+            let _ = self.0.send("logon".to_string());
+        }
+
+        fn on_message(&mut self, message: FixMessage) {
+            let _ = self.0.send(format!("message {}", message.get_msg_type().unwrap_or_default()));
+        }
+
+        fn on_disconnect(&mut self, _reason: &str) {
+            let _ = self.0.send("disconnect".to_string());
+        }
+    }
+
+    /// Accepts one connection on an ephemeral port with the given acceptor CompIDs.
+    async fn acceptor(sender_comp_id: &str, target_comp_id: &str) -> (String, mpsc::UnboundedSender<SessionCommand>, mpsc::UnboundedReceiver<SessionEvent>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let config = SessionConfig { sender_comp_id: sender_comp_id.to_string(), target_comp_id: target_comp_id.to_string(), ..Default::default() };
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let (events_tx, events) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let session = Session::new(config, SessionRole::Acceptor, Instant::now());
+            let _ = run_session(socket, session, commands_rx, events_tx).await;
+        });
+        (addr, commands, events)
+    }
+
+    #[tokio::test]
+    async fn test_client_logon_send_and_disconnect(){
+        let (addr, server_commands, mut server_events) = acceptor("SERVER", "CLIENT").await;
+        let (handler_tx, mut received) = mpsc::unbounded_channel();
+
+        let mut client = fix_client::new(&addr);
+        assert!(client.send_raw(FixMessageBuilder::new("FIX.4.4", "D")).is_err());
+        client.connect(Forward(handler_tx)).await.unwrap();
+        assert!(client.is_connected());
+        assert_eq!(received.recv().await.as_deref(), Some("logon"));
+        assert_eq!(server_events.recv().await, Some(SessionEvent::LoggedOn));
+
+        let order = NewOrder { cl_ord_id: "ORD-1".to_string(), side: orderbook::Side::Buy, order_type: orderbook::OrderType::GoodTillCancel, price: Some(100), quantity: 5, flags: orderbook::OrderFlags::default() };
+        client.send_order(&order).unwrap();
+        match server_events.recv().await {
+            Some(SessionEvent::Message(message)) => assert_eq!(NewOrder::from_message(&message), Ok(order)),
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        server_commands.send(SessionCommand::Send(FixMessageBuilder::new("FIX.4.4", "8").field(tags::CL_ORD_ID, "ORD-1"))).unwrap();
+        assert_eq!(received.recv().await.as_deref(), Some("message 8"));
+
+        client.disconnect().await.unwrap();
+        assert!(!client.is_connected());
+        assert_eq!(received.recv().await.as_deref(), Some("disconnect"));
+        assert!(matches!(server_events.recv().await, Some(SessionEvent::Disconnected(_))));
+    }
+
+    #[tokio::test]
+    async fn test_client_logon_refused(){
+        let (addr, _server_commands, _server_events) = acceptor("SERVER", "SOMEONE_ELSE").await;
+        let (handler_tx, _received) = mpsc::unbounded_channel();
+
+        let mut client = fix_client::new(&addr);
+        let err = client.connect(Forward(handler_tx)).await.unwrap_err();
+        assert!(err.to_string().contains("Logon"), "{}", err);
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn test_reconnect_delays(){
+        let policy = ReconnectPolicy { initial_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1), ..Default::default() };
+
+        assert_eq!(policy.get_delay(1, 0.5), Duration::from_millis(100));
+        assert_eq!(policy.get_delay(3, 0.5), Duration::from_millis(400));
+        assert_eq!(policy.get_delay(5, 0.5), Duration::from_secs(1));
+        assert_eq!(policy.get_delay(100, 0.5), Duration::from_secs(1));
+        assert_eq!(policy.get_delay(2, 0.0), Duration::from_millis(160));
+        assert_eq!(policy.get_delay(2, 1.0), Duration::from_millis(240));
+        let random = random_fraction();
+        assert!((0.0..1.0).contains(&random));
+    }
+
+    /// Reports every connection state change.
+    struct Watch(mpsc::UnboundedSender<ConnectionState>);
+
+    impl MessageHandler for Watch {
+        fn on_message(&mut self, _message: FixMessage) {}
+
+        fn on_connection_state(&mut self, state: &ConnectionState) {
+            let _ = self.0.send(state.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_reconnects_and_resumes_sequence_numbers(){
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (drop_tx, mut drop_rx) = mpsc::unbounded_channel::<()>();
+        let (events_tx, mut server_events) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let config = SessionConfig { sender_comp_id: "SERVER".to_string(), target_comp_id: "CLIENT".to_string(), ..Default::default() };
+            let mut session = Session::new(config, SessionRole::Acceptor, Instant::now());
+            let (_commands, mut commands_rx) = mpsc::unbounded_channel();
+            // The first connection is dropped without a Logout, the second served until it ends.
+            let (socket, _) = listener.accept().await.unwrap();
+            tokio::select! {
+                _ = drive_session(socket, Vec::new(), &mut session, &mut commands_rx, &events_tx) => {}
+                _ = drop_rx.recv() => {}
+            }
+            session.reconnect(Instant::now());
+            let (socket, _) = listener.accept().await.unwrap();
+            let _ = drive_session(socket, Vec::new(), &mut session, &mut commands_rx, &events_tx).await;
+        });
+
+        let policy = ReconnectPolicy { initial_delay: Duration::from_millis(10), max_attempts: Some(3), ..Default::default() };
+        let mut client = fix_client::new(&addr).with_reconnect(policy);
+        let (states_tx, mut states) = mpsc::unbounded_channel();
+        client.connect(Watch(states_tx)).await.unwrap();
+        assert_eq!(states.recv().await, Some(ConnectionState::LoggedOn));
+        assert_eq!(server_events.recv().await, Some(SessionEvent::LoggedOn));
+
+        drop_tx.send(()).unwrap();
+        assert_eq!(states.recv().await, Some(ConnectionState::Disconnected("Connection closed by peer".to_string())));
+        assert!(matches!(states.recv().await, Some(ConnectionState::Reconnecting { attempt: 1, .. })));
+        assert_eq!(states.recv().await, Some(ConnectionState::LoggedOn));
+        assert!(client.is_connected());
+        assert_eq!(server_events.recv().await, Some(SessionEvent::LoggedOn));
+
+        client.send_raw(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "ORD-1")).unwrap();
+        match server_events.recv().await {
+            Some(SessionEvent::Message(message)) => assert_eq!(message.get_uint(tags::MSG_SEQ_NUM), Ok(3)),
+            other => panic!("unexpected event {:?}", other),
+        }
+        client.disconnect().await.unwrap();
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_client_gives_up_reconnecting(){
+        let (addr, server_commands, mut server_events) = acceptor("SERVER", "CLIENT").await;
+        let policy = ReconnectPolicy { initial_delay: Duration::from_millis(1), max_attempts: Some(2), ..Default::default() };
+        let mut client = fix_client::new(&addr).with_reconnect(policy);
+        let (states_tx, mut states) = mpsc::unbounded_channel();
+        client.connect(Watch(states_tx)).await.unwrap();
+        assert_eq!(server_events.recv().await, Some(SessionEvent::LoggedOn));
+
+        // The acceptor serves a single connection, so every attempt is refused.
+        server_commands.send(SessionCommand::Logout("Maintenance".to_string())).unwrap();
+        let mut seen = Vec::new();
+        while let Some(state) = states.recv().await {
+            seen.push(state);
+        }
+        assert_eq!(seen[..2], [ConnectionState::LoggedOn, ConnectionState::Disconnected("Maintenance".to_string())]);
+        assert!(matches!(seen[2..], [
+            ConnectionState::Reconnecting { attempt: 1, .. },
+            ConnectionState::ReconnectFailed { attempt: 1, .. },
+            ConnectionState::Reconnecting { attempt: 2, .. },
+            ConnectionState::ReconnectFailed { attempt: 2, .. },
+            ConnectionState::GaveUp,
+        ]), "{:?}", seen);
+        assert!(!client.is_connected());
+        assert!(client.disconnect().await.unwrap_err().to_string().contains("Gave up"));
+    }
+
+    /// Answers every message with an ExecutionReport echoing its ClOrdID.
+    struct Echo(mpsc::UnboundedSender<String>);
+
+    impl SessionHandler for Echo {
+        fn on_logon(&self, session: &SessionHandle) {
+            let _ = self.0.send(format!("logon {}", session.get_target_comp_id()));
+        }
+
+        fn on_message(&self, session: &SessionHandle, message: FixMessage) {
+            let cl_ord_id = message.get_field(tags::CL_ORD_ID).unwrap_or_default();
+            session.send(FixMessageBuilder::new("FIX.4.4", "8").field(tags::CL_ORD_ID, cl_ord_id)).unwrap();
+        }
+
+        fn on_disconnect(&self, session: &SessionHandle, _reason: &str) {
+            let _ = self.0.send(format!("disconnect {}", session.get_target_comp_id()));
+        }
+
+        fn on_connection_error(&self, _peer: SocketAddr, error: &str) {
+            let _ = self.0.send(format!("error {}", error));
+        }
+    }
+
+    /// Records the MsgSeqNum and ClOrdID of every ExecutionReport.
+    struct Record(mpsc::UnboundedSender<(u64, String)>);
+
+    impl MessageHandler for Record {
+        fn on_message(&mut self, message: FixMessage) {
+            let _ = self.0.send((message.get_uint(tags::MSG_SEQ_NUM).unwrap(), message.get_field(tags::CL_ORD_ID).unwrap().to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_accepts_configured_sessions(){
+        let config: Config = "
+            [[session]]
+            sender_comp_id = \"SERVER\"
+            target_comp_id = \"ALPHA\"
+            port = 1
+            [[session]]
+            sender_comp_id = \"SERVER\"
+            target_comp_id = \"BETA\"
+            port = 1
+        ".parse().unwrap();
+        let server = Arc::new(fix_server::from_config(&config).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (handler_tx, mut server_events) = mpsc::unbounded_channel();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move {
+            let _ = serving.serve(listener, Echo(handler_tx)).await;
+        });
+
+        let connect = |sender_comp_id: &str| {
+            let mut client = fix_client::new(&addr);
+            client.config = SessionConfig { sender_comp_id: sender_comp_id.to_string(), ..Default::default() };
+            client
+        };
+        let (alpha_tx, mut alpha_received) = mpsc::unbounded_channel();
+        let (beta_tx, mut beta_received) = mpsc::unbounded_channel();
+        let mut alpha = connect("ALPHA");
+        let mut beta = connect("BETA");
+        alpha.connect(Record(alpha_tx)).await.unwrap();
+        beta.connect(Record(beta_tx)).await.unwrap();
+        assert_eq!(server_events.recv().await.as_deref(), Some("logon ALPHA"));
+        assert_eq!(server_events.recv().await.as_deref(), Some("logon BETA"));
+        assert!(server.is_active("SERVER", "ALPHA"));
+
+        alpha.send_raw(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "A-1")).unwrap();
+        alpha.send_raw(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "A-2")).unwrap();
+        beta.send_raw(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "B-1")).unwrap();
+        assert_eq!(alpha_received.recv().await, Some((2, "A-1".to_string())));
+        assert_eq!(alpha_received.recv().await, Some((3, "A-2".to_string())));
+        assert_eq!(beta_received.recv().await, Some((2, "B-1".to_string())));
+
+        let (unused_tx, _unused) = mpsc::unbounded_channel();
+        assert!(connect("GAMMA").connect(Forward(unused_tx.clone())).await.is_err());
+        assert!(connect("ALPHA").connect(Forward(unused_tx)).await.is_err());
+        assert_eq!(server_events.recv().await.as_deref(), Some("error No session configured for GAMMA->SERVER"));
+        assert_eq!(server_events.recv().await.as_deref(), Some("error Session ALPHA->SERVER is already logged on"));
+
+        alpha.disconnect().await.unwrap();
+        assert_eq!(server_events.recv().await.as_deref(), Some("disconnect ALPHA"));
+        assert!(!server.is_active("SERVER", "ALPHA"));
+        beta.disconnect().await.unwrap();
+    }
+
+    /// Writes a CA and certificates it issued to `dir`: `server` for 127.0.0.1 and one client
+    /// certificate naming each of `clients`, as `<name>.pem` and `<name>.key`.
+    fn write_certificates(dir: &std::path::Path, clients: &[&str]) {
+        use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+        let ca_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&ca_key).unwrap();
+        std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+        let issue = |name: &str, subject_alt_name: &str, usage: ExtendedKeyUsagePurpose| {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![subject_alt_name.to_string()]).unwrap();
+            params.extended_key_usages = vec![usage];
+            let certificate = params.signed_by(&key, &ca, &ca_key).unwrap();
+            std::fs::write(dir.join(format!("{}.pem", name)), certificate.pem()).unwrap();
+            std::fs::write(dir.join(format!("{}.key", name)), key.serialize_pem()).unwrap();
+        };
+        issue("server", "127.0.0.1", ExtendedKeyUsagePurpose::ServerAuth);
+        for client in clients {
+            issue(client, client, ExtendedKeyUsagePurpose::ClientAuth);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tls_session_checks_client_certificates(){
+        let dir = tempfile::tempdir().unwrap();
+        write_certificates(dir.path(), &["ALPHA", "MALLORY"]);
+        let path = |file: &str| dir.path().join(file).display().to_string();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config: Config = format!(
+            "[[session]]\nsender_comp_id = \"SERVER\"\ntarget_comp_id = \"ALPHA\"\nport = {}\ntls_cert = '{}'\ntls_key = '{}'\ntls_ca = '{}'\ntls_require_client_cert = true\n",
+            addr.port(), path("server.pem"), path("server.key"), path("ca.pem"),
+        ).parse().unwrap();
+        let server = fix_server::from_config(&config).unwrap();
+        let (handler_tx, mut server_events) = mpsc::unbounded_channel();
+        let serving = server.clone();
+        let served = tokio::spawn(async move { serving.serve(listener, Echo(handler_tx)).await.is_ok() });
+
+        let client = |certificate: Option<&str>| {
+            let mut session = format!("[[session]]\nsender_comp_id = \"ALPHA\"\ntarget_comp_id = \"SERVER\"\nport = {}\ntls_ca = '{}'\n", addr.port(), path("ca.pem"));
+            if let Some(name) = certificate {
+                session += &format!("tls_cert = '{}'\ntls_key = '{}'\n", path(&format!("{}.pem", name)), path(&format!("{}.key", name)));
+            }
+            fix_client::from_settings(&session.parse::<Config>().unwrap().get_sessions()[0])
+        };
+
+        // The handshake succeeds and the session runs over TLS.
+        let mut alpha = client(Some("ALPHA"));
+        let (alpha_tx, mut alpha_received) = mpsc::unbounded_channel();
+        alpha.connect(Record(alpha_tx)).await.unwrap();
+        assert_eq!(server_events.recv().await.as_deref(), Some("logon ALPHA"));
+        alpha.send_raw(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "A-1")).unwrap();
+        assert_eq!(alpha_received.recv().await, Some((2, "A-1".to_string())));
+        alpha.disconnect().await.unwrap();
+        assert_eq!(server_events.recv().await.as_deref(), Some("disconnect ALPHA"));
+
+        // No client certificate, a certificate naming another CompID, and plaintext are refused.
+        let mut plaintext = fix_client::new(&addr.to_string());
+        plaintext.config = SessionConfig { sender_comp_id: "ALPHA".to_string(), ..Default::default() };
+        for mut refused in [client(None), client(Some("MALLORY")), plaintext] {
+            let (unused_tx, _unused) = mpsc::unbounded_channel();
+            assert!(refused.connect(Forward(unused_tx)).await.is_err());
+            assert!(!server.is_active("SERVER", "ALPHA"));
+        }
+
+        server.disconnect();
+        assert!(served.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_drop_copy_session_cannot_send_orders(){
+        let config: Config = "
+            [[session]]
+            sender_comp_id = \"SERVER\"
+            target_comp_id = \"ALPHA\"
+            port = 1
+            [[session]]
+            sender_comp_id = \"SERVER\"
+            target_comp_id = \"COPY\"
+            port = 1
+            drop_copy = true
+            drop_copy_accounts = [\"ALPHA\"]
+        ".parse().unwrap();
+        let server = fix_server::from_config(&config).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (handler_tx, mut server_events) = mpsc::unbounded_channel();
+        let serving = server.clone();
+        let served = tokio::spawn(async move { serving.serve(listener, Echo(handler_tx)).await.is_ok() });
+
+        let mut client = fix_client::new(&addr);
+        client.config = SessionConfig { sender_comp_id: "COPY".to_string(), ..Default::default() };
+        let (client_tx, mut received) = mpsc::unbounded_channel();
+        client.connect(Forward(client_tx)).await.unwrap();
+        assert_eq!(received.recv().await.as_deref(), Some("logon"));
+        assert_eq!(server_events.recv().await, Some("logon COPY".to_string()));
+        let table = server.get_session_table();
+        assert_eq!(table.get_drop_copies("ALPHA").len(), 1);
+        assert!(table.get_drop_copies("BETA").is_empty());
+        assert!(!table.get_by_target("COPY").unwrap().get_kind().is_copied("BETA"));
+
+        // The order is refused before it reaches Echo, which would answer with an ExecutionReport.
+        client.send_raw(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "1")).unwrap();
+        let copy = table.get_drop_copies("ALPHA").remove(0);
+        assert_eq!(received.recv().await.as_deref(), Some("message j"));
+        copy.send(FixMessageBuilder::new("FIX.4.4", "8").field(tags::CL_ORD_ID, "2")).unwrap();
+        assert_eq!(received.recv().await.as_deref(), Some("message 8"));
+
+        server.disconnect();
+        assert!(served.await.unwrap());
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_shutdown_logs_out_every_session(){
+        let config: Config = "
+            [[session]]
+            sender_comp_id = \"SERVER\"
+            target_comp_id = \"ALPHA\"
+            port = 1
+            [[session]]
+            sender_comp_id = \"SERVER\"
+            target_comp_id = \"BETA\"
+            port = 1
+        ".parse().unwrap();
+        let server = fix_server::from_config(&config).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (handler_tx, mut server_events) = mpsc::unbounded_channel();
+        let serving = server.clone();
+        let served = tokio::spawn(async move { serving.serve(listener, Echo(handler_tx)).await.is_ok() });
+
+        let mut clients = Vec::new();
+        let mut received = Vec::new();
+        for sender_comp_id in ["ALPHA", "BETA"] {
+            let mut client = fix_client::new(&addr);
+            client.config = SessionConfig { sender_comp_id: sender_comp_id.to_string(), ..Default::default() };
+            let (client_tx, client_rx) = mpsc::unbounded_channel();
+            client.connect(Forward(client_tx)).await.unwrap();
+            assert_eq!(server_events.recv().await, Some(format!("logon {}", sender_comp_id)));
+            clients.push(client);
+            received.push(client_rx);
+        }
+        let table = server.get_session_table();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get_by_target("BETA").unwrap().get_target_comp_id(), "BETA");
+
+        server.disconnect();
+        assert!(served.await.unwrap());
+        assert!(table.is_empty());
+        for client_rx in &mut received {
+            assert_eq!(client_rx.recv().await.as_deref(), Some("logon"));
+            assert_eq!(client_rx.recv().await.as_deref(), Some("disconnect"));
+        }
+        for client in &mut clients {
+            client.disconnect().await.unwrap();
+        }
+        assert!(TcpStream::connect(&addr).await.is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! # Latency Module
+//!
+//! Per-session latency and clock-skew estimates, derived from the SendingTime (tag 52) of
+//! inbound messages.
+//!
+//! ## Estimates
+//! - The *transit* of a message is its local receive time minus its SendingTime. It is the
+//!   one-way latency plus the offset between the two clocks, so it can be negative when the
+//!   counterparty's clock runs ahead of ours.
+//! - The *round trip* is measured without either clock being trusted: from our Logon to the
+//!   counterparty's reply (initiator only), and from each TestRequest to the Heartbeat that
+//!   answers it.
+//! - The one-way *latency* is taken as half the round trip, and the *clock skew* as the smallest
+//!   transit minus that latency; a positive skew means the counterparty's clock is behind ours.
+//!
+//! The smallest transit is used for the skew because it is the sample least inflated by
+//! queuing. SendingTime carries milliseconds at best, so estimates are no finer than that.
+//!
+//! [`LatencyMetrics`] is shared between a running [`Session`](crate::session::Session) and the
+//! application, which reads a [`LatencyStats`] snapshot at any time.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Weight of a new transit sample in the smoothed transit, as in TCP's SRTT.
+const SMOOTHING: f64 = 0.125;
+
+/// A snapshot of the latency estimates of one session. Transits and skew are in microseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyStats {
+    samples: u64,
+    last_transit: Option<i64>,
+    min_transit: Option<i64>,
+    max_transit: Option<i64>,
+    smoothed_transit: Option<f64>,
+    round_trip: Option<Duration>,
+}
+
+impl LatencyStats {
+    /// Records a message sent at `sent` by the counterparty's clock and received at `received`.
+    pub fn record_transit(&mut self, sent: SystemTime, received: SystemTime) {
+        let transit = match received.duration_since(sent) {
+            Ok(elapsed) => elapsed.as_micros() as i64,
+            Err(err) => -(err.duration().as_micros() as i64),
+        };
+        self.samples += 1;
+        self.last_transit = Some(transit);
+        self.min_transit = Some(self.min_transit.map_or(transit, |min| min.min(transit)));
+        self.max_transit = Some(self.max_transit.map_or(transit, |max| max.max(transit)));
+        self.smoothed_transit = Some(match self.smoothed_transit {
+            Some(smoothed) => smoothed + SMOOTHING * (transit as f64 - smoothed),
+            None => transit as f64,
+        });
+    }
+
+    /// Records a measured round trip.
+    pub fn record_round_trip(&mut self, round_trip: Duration) {
+        self.round_trip = Some(round_trip);
+    }
+
+    /// Returns the number of transit samples.
+    pub fn get_samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Returns the transit of the latest message.
+    pub fn get_last_transit(&self) -> Option<i64> {
+        self.last_transit
+    }
+
+    /// Returns the smallest transit seen.
+    pub fn get_min_transit(&self) -> Option<i64> {
+        self.min_transit
+    }
+
+    /// Returns the largest transit seen.
+    pub fn get_max_transit(&self) -> Option<i64> {
+        self.max_transit
+    }
+
+    /// Returns the exponentially smoothed transit.
+    pub fn get_smoothed_transit(&self) -> Option<i64> {
+        self.smoothed_transit.map(|smoothed| smoothed.round() as i64)
+    }
+
+    /// Returns the latest round trip.
+    pub fn get_round_trip(&self) -> Option<Duration> {
+        self.round_trip
+    }
+
+    /// Returns the one-way latency estimate, half the latest round trip.
+    pub fn get_latency(&self) -> Option<Duration> {
+        self.round_trip.map(|round_trip| round_trip / 2)
+    }
+
+    /// Returns how far the counterparty's clock is behind ours, negative if it is ahead.
+    pub fn get_clock_skew(&self) -> Option<i64> {
+        Some(self.min_transit? - self.get_latency()?.as_micros() as i64)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = |micros: Option<i64>| micros.map_or("-".to_string(), |micros| format!("{:.3}ms", micros as f64 / 1000.0));
+        write!(
+            f,
+            "{} samples, transit last {} min {} avg {} max {}, latency {}, clock skew {}",
+            self.samples,
+            millis(self.last_transit),
+            millis(self.min_transit),
+            millis(self.get_smoothed_transit()),
+            millis(self.max_transit),
+            millis(self.get_latency().map(|latency| latency.as_micros() as i64)),
+            millis(self.get_clock_skew())
+        )
+    }
+}
+
+/// The latency estimates of a session, shared between the session and the application.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyMetrics {
+    stats: Arc<Mutex<LatencyStats>>,
+}
+
+impl LatencyMetrics {
+    /// Creates metrics without samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a snapshot of the current estimates.
+    pub fn get_stats(&self) -> LatencyStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Records a message sent at `sent` and received at `received`.
+    pub fn record_transit(&self, sent: SystemTime, received: SystemTime) {
+        self.stats.lock().unwrap().record_transit(sent, received);
+    }
+
+    /// Records a measured round trip.
+    pub fn record_round_trip(&self, round_trip: Duration) {
+        self.stats.lock().unwrap().record_round_trip(round_trip);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_latency_estimates(){
+        let at = |millis: u64| UNIX_EPOCH + Duration::from_millis(millis);
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.get_clock_skew(), None);
+
+        // The counterparty's clock is 40ms behind ours and messages take 5 to 13ms.
+        stats.record_transit(at(1_000), at(1_045));
+        stats.record_transit(at(2_000), at(2_053));
+        stats.record_transit(at(3_000), at(3_049));
+        assert_eq!(stats.get_samples(), 3);
+        assert_eq!((stats.get_last_transit(), stats.get_min_transit(), stats.get_max_transit()), (Some(49_000), Some(45_000), Some(53_000)));
+        assert_eq!(stats.get_smoothed_transit(), Some(46_375));
+        assert_eq!(stats.get_clock_skew(), None);
+
+        stats.record_round_trip(Duration::from_millis(10));
+        assert_eq!(stats.get_latency(), Some(Duration::from_millis(5)));
+        assert_eq!(stats.get_clock_skew(), Some(40_000));
+
+        // A clock ahead of ours gives negative transits.
+        stats.record_transit(at(5_000), at(4_990));
+        assert_eq!(stats.get_min_transit(), Some(-10_000));
+        assert_eq!(stats.get_clock_skew(), Some(-15_000));
+        assert!(stats.to_string().contains("clock skew -15.000ms"), "{}", stats);
+    }
+}
@@ -0,0 +1,19 @@
+//! # FIX PTC
+//!
+//! Prototype FIX engine shared by the `server` and `client` binaries.
+
+pub mod audit;
+pub mod bridge;
+pub mod clearing;
+pub mod config;
+pub mod dictionary;
+pub mod fields;
+pub mod fix;
+pub mod latency;
+pub mod margin;
+pub mod market_data;
+pub mod risk;
+pub mod session;
+pub mod store;
+pub mod tls;
+pub mod wirelog;
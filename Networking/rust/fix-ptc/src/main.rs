@@ -1,58 +1,110 @@
-use tokio::{
-    net::TcpListener, 
-    io::{AsyncReadExt, AsyncWriteExt}, 
-    time::{timeout, Duration}
-};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use fix_ptc::bridge::{BridgeError, BusinessMessageReject, OrderBridge};
+use fix_ptc::config::Config;
+use fix_ptc::dictionary::groups;
+use fix_ptc::fix::{fix_server, tags, FixMessage, FixMessageBuilder, SessionHandle, SessionHandler, SessionTable};
+use fix_ptc::market_data::{MarketDataPublisher, MARKET_DATA_REQUEST};
 
-use std::net::Shutdown;
+/// Routes every session's orders to the book of their Symbol (55) and publishes its market
+/// data. A book is opened the first time a symbol is seen.
+struct Exchange {
+    sessions: SessionTable,
+    books: Mutex<HashMap<String, (OrderBridge, MarketDataPublisher)>>,
+}
 
-#[tokio::main]
-async fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7000").await.unwrap();
-    
-    
-    let (mut socket, _) = listener.accept().await.unwrap();
-    let mut dur = [0u8; 4];
-    let timer;
-    let res1 = timeout(Duration::from_secs(5), socket.read_exact(&mut dur)).await;
-
-    match res1 {
-        Ok(Ok(_)) =>{
-            timer = u32::from_be_bytes(dur);
-            println!("Duration recieved! ({})", timer);
-        }
-        _ => {
-            println!("Invalid connection: no heartbeat set");
-            return;
+impl Exchange {
+    /// Sends the message built for its BeginString to the session whose counterparty is
+    /// `owner`, if it is connected.
+    fn send_to(&self, owner: &str, build: impl FnOnce(&str) -> FixMessageBuilder) {
+        if let Some(session) = self.sessions.get_by_target(owner) {
+            let _ = session.send(build(&session.get_config().begin_string));
         }
-            //  stream.shutdown(std::net::Shutdown::Write).expect("Shutdown failed");
     }
-     
-    
-    
+}
 
-    tokio::spawn(async move {
-        let mut count = 0;
-        while count < 3 {
-            let mut buf = [0u8; 2];
-            let res2 = timeout(Duration::from_secs(timer.into()), socket.read_exact(&mut buf)).await;
-
-            match res2 {
-                Ok(Ok(_)) if &buf == b"HB" => {
-                    println!("Heartbeat received.");
-                    socket.write_all(b"OK").await.unwrap();
-                    
-                },
-                _ => {
-                    println!("Connection lost or timeout.");
-                    return;
+/// Returns the symbol whose book `message` is for: its Symbol (55), or the first one a
+/// MarketDataRequest asks for.
+fn get_symbol(message: &FixMessage) -> Option<String> {
+    if message.get_msg_type() == Some(MARKET_DATA_REQUEST) {
+        let related_sym = message.get_group(&groups::RELATED_SYM).ok()?;
+        return related_sym.first()?.get_field(tags::SYMBOL).map(str::to_string);
+    }
+    message.get_field(tags::SYMBOL).map(str::to_string)
+}
+
+impl SessionHandler for Exchange {
+    fn on_logon(&self, session: &SessionHandle) {
+        println!("{} logged on ({} sessions).", session.get_target_comp_id(), self.sessions.len());
+    }
+
+    fn on_message(&self, session: &SessionHandle, message: FixMessage) {
+        println!("Received MsgType {} from {}", message.get_msg_type().unwrap_or("?"), session.get_target_comp_id());
+        let owner = session.get_target_comp_id();
+        let Some(symbol) = get_symbol(&message) else {
+            let err = BridgeError::InvalidField { tag: tags::SYMBOL, reason: "required field missing".to_string() };
+            let _ = session.send(BusinessMessageReject::new(owner, &message, &err).to_builder(&session.get_config().begin_string));
+            return;
+        };
+        let mut books = self.books.lock().unwrap();
+        let (bridge, publisher) = books
+            .entry(symbol.clone())
+            .or_insert_with(|| (OrderBridge::new().with_symbol(&symbol), MarketDataPublisher::new()));
+        if message.get_msg_type() == Some(MARKET_DATA_REQUEST) {
+            for update in publisher.on_request(owner, &message, &bridge.get_orderbook().get_order_infos()) {
+                let _ = session.send(update.to_builder(&session.get_config().begin_string));
+            }
+            return;
+        }
+        for report in bridge.on_message(owner, &message) {
+            self.send_to(report.get_owner(), |begin_string| report.to_builder(begin_string));
+            for copy in self.sessions.get_drop_copies(report.get_owner()) {
+                if let Some(builder) = report.to_drop_copy(&copy.get_config().begin_string) {
+                    let _ = copy.send(builder);
                 }
             }
-            count += 1;
         }
-        socket.shutdown().await.expect("Shutdown failed");
-        socket.flush().await.expect("Flush failed");
-        drop(socket);
-    }).await;
+        for update in publisher.on_book_update(&bridge.get_orderbook().get_order_infos()) {
+            self.send_to(update.get_owner(), |begin_string| update.to_builder(begin_string));
+        }
+    }
+
+    fn on_disconnect(&self, session: &SessionHandle, reason: &str) {
+        println!("Session with {} ended: {} ({})", session.get_target_comp_id(), reason, session.get_latency());
+        for (_, publisher) in self.books.lock().unwrap().values_mut() {
+            publisher.remove_owner(session.get_target_comp_id());
+        }
+    }
+
+    fn on_connection_error(&self, peer: SocketAddr, error: &str) {
+        println!("Connection from {} closed: {}", peer, error);
+    }
 }
 
+#[tokio::main]
+async fn main() {
+    // Usage: server [config.toml]
+    let config = match std::env::args().nth(1) {
+        Some(path) => Config::load(path),
+        None => include_str!("../server.toml").parse(),
+    }
+    .unwrap_or_else(|err| panic!("{}", err));
+    let server = fix_server::from_config(&config).unwrap_or_else(|err| panic!("{}", err));
+    let exchange = Exchange {
+        sessions: server.get_session_table(),
+        books: Mutex::new(HashMap::new()),
+    };
+
+    let stopping = server.clone();
+    tokio::spawn(async move {
+        let _ = tokio::signal::ctrl_c().await;
+        println!("Shutting down.");
+        stopping.disconnect();
+    });
+
+    println!("Listening on {}", server.get_address());
+    if let Err(err) = server.listen(exchange).await {
+        println!("Server failed: {}", err);
+    }
+}
@@ -0,0 +1,123 @@
+//! # Margin Module
+//!
+//! A simple margin model for the accounts of an [`OrderBridge`](crate::bridge::OrderBridge).
+//!
+//! ## Model
+//! Every account has a [`MarginAccount`]: the collateral it deposited and the cash its trades
+//! paid or received. Positions are marked at the book's current
+//! [reference price](orderbook::ReferencePrices::get_current):
+//! - *equity* is collateral plus cash plus the marked value of the net positions,
+//! - the *initial* margin is the initial rate times the marked worst-case net position, open
+//!   orders included (see [`risk`](crate::risk)),
+//! - the *maintenance* margin is the maintenance rate times the marked net position.
+//!
+//! An order whose initial margin would exceed the account's equity is rejected. An account
+//! whose equity falls below its maintenance margin gets a [`MarginCall`], sent as a News
+//! (`35=B`) message, once until it recovers.
+
+use orderbook::Price;
+use crate::dictionary::groups;
+use crate::fix::{tags, FixMessageBuilder, GroupEntry};
+use crate::risk::Position;
+
+/// MsgType of News.
+pub const NEWS: &str = "B";
+
+/// Margin requirements as fractions of the marked position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarginRates {
+    pub initial: f64,
+    pub maintenance: f64,
+}
+
+impl MarginRates {
+    /// Returns the margin state of `account` holding `positions`, marked at `mark`.
+    pub fn get_status<'a>(&self, account: &MarginAccount, positions: impl IntoIterator<Item = &'a Position>, mark: Price) -> MarginStatus {
+        let (mut value, mut worst_net, mut net) = (0, 0, 0);
+        for position in positions {
+            value += position.net * i64::from(mark);
+            worst_net += position.get_worst_net();
+            net += position.net.unsigned_abs();
+        }
+        let requirement = |rate: f64, quantity: u64| (rate * quantity as f64 * f64::from(mark)).ceil() as i64;
+        MarginStatus {
+            equity: account.collateral + account.cash + value,
+            initial: requirement(self.initial, worst_net),
+            maintenance: requirement(self.maintenance, net),
+        }
+    }
+}
+
+/// Collateral and trading cash of one account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MarginAccount {
+    pub collateral: i64,
+    /// Received for sales minus paid for purchases.
+    pub cash: i64,
+    /// Set while a margin call is outstanding.
+    pub called: bool,
+}
+
+/// An account's equity and margin requirements, in price × quantity units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MarginStatus {
+    pub equity: i64,
+    pub initial: i64,
+    pub maintenance: i64,
+}
+
+impl MarginStatus {
+    /// Returns the equity left over the initial margin; negative if it falls short.
+    pub fn get_buying_power(&self) -> i64 {
+        self.equity - self.initial
+    }
+
+    /// Returns `true` if the equity is below the maintenance margin.
+    pub fn is_margin_call(&self) -> bool {
+        self.equity < self.maintenance
+    }
+}
+
+/// A News (`35=B`) message telling `owner` its equity fell below the maintenance margin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarginCall {
+    pub owner: String,
+    pub status: MarginStatus,
+    /// Price the positions were marked at.
+    pub mark: Price,
+}
+
+impl MarginCall {
+    /// Builds the FIX message; the session adds the standard header.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        let text = format!(
+            "Equity {} is below the maintenance margin of {} at a mark of {}",
+            self.status.equity, self.status.maintenance, self.mark
+        );
+        FixMessageBuilder::new(begin_string, NEWS)
+            .field(tags::HEADLINE, "Margin call")
+            .group(&groups::LINES_OF_TEXT, [GroupEntry::new().field(tags::TEXT, text)])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_marks_positions(){
+        let rates = MarginRates { initial: 0.5, maintenance: 0.25 };
+        // Bought 10 at 100, 5 more bid for.
+        let account = MarginAccount { collateral: 1_000, cash: -1_000, called: false };
+        let position = Position { net: 10, open_buys: 5, open_sells: 0 };
+
+        let status = rates.get_status(&account, [&position], 100);
+        assert_eq!(status, MarginStatus { equity: 1_000, initial: 750, maintenance: 250 });
+        assert_eq!(status.get_buying_power(), 250);
+
+        let status = rates.get_status(&account, [&position], 20);
+        assert_eq!((status.equity, status.maintenance), (200, 50));
+        assert!(!status.is_margin_call());
+        assert!(rates.get_status(&MarginAccount { collateral: 0, ..account }, [&position], 90).is_margin_call());
+    }
+}
@@ -0,0 +1,395 @@
+//! # Market Data Module
+//!
+//! MarketDataRequest (`35=V`) subscriptions and the refreshes published for them.
+//!
+//! The book does not publish depth events, so the owner of the book calls
+//! [`MarketDataPublisher::on_book_update`] with the current depth after every change. Each
+//! subscription keeps the view it last published (its top `MarketDepth` levels) and is sent
+//! only what changed in that view.
+//!
+//! ## Request options
+//! - SubscriptionRequestType (263): `0` snapshot only, `1` snapshot plus updates, `2` unsubscribe.
+//! - MarketDepth (264): `0` for the full book, otherwise the number of levels per side.
+//! - MDUpdateType (265): `0` full refresh (`35=W` on every change) or `1` incremental refresh
+//!   (`35=X` with New/Change/Delete entries). Defaults to incremental.
+//! - MDEntryType (269) entries: `0` bids, `1` offers. Both sides are sent if none are listed.
+//!
+//! Invalid requests are answered with MarketDataRequestReject (`35=Y`).
+
+use std::collections::BTreeMap;
+use orderbook::{OrderbookLevelInfos, Price, Quantity, Side};
+use crate::dictionary::groups;
+use crate::fix::{tags, FixMessage, FixMessageBuilder, GroupEntry};
+
+/// MsgType of MarketDataRequest.
+pub const MARKET_DATA_REQUEST: &str = "V";
+/// MsgType of MarketDataSnapshotFullRefresh.
+pub const MARKET_DATA_SNAPSHOT: &str = "W";
+/// MsgType of MarketDataIncrementalRefresh.
+pub const MARKET_DATA_INCREMENTAL: &str = "X";
+/// MsgType of MarketDataRequestReject.
+pub const MARKET_DATA_REQUEST_REJECT: &str = "Y";
+
+/// MDUpdateAction (tag 279) values.
+pub mod update_action {
+    pub const NEW: char = '0';
+    pub const CHANGE: char = '1';
+    pub const DELETE: char = '2';
+}
+
+/// MDReqRejReason (tag 281) values sent by the publisher.
+pub mod rej_reason {
+    pub const DUPLICATE_MD_REQ_ID: char = '1';
+    pub const UNSUPPORTED_SUBSCRIPTION_REQUEST_TYPE: char = '4';
+    pub const UNSUPPORTED_MARKET_DEPTH: char = '5';
+    pub const UNSUPPORTED_MD_UPDATE_TYPE: char = '6';
+    pub const UNSUPPORTED_MD_ENTRY_TYPE: char = '8';
+}
+
+/// How a subscription is kept up to date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateType {
+    /// A complete snapshot whenever the subscribed view changes.
+    FullRefresh,
+    /// Only the levels that changed.
+    Incremental,
+}
+
+/// One price level as sent in a market data message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MdEntry {
+    pub side: Side,
+    pub price: Price,
+    /// Aggregate quantity; `0` on delete entries.
+    pub quantity: Quantity,
+}
+
+/// A message the publisher wants delivered to a subscriber.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarketDataMessage {
+    /// MarketDataSnapshotFullRefresh (`35=W`).
+    Snapshot { owner: String, md_req_id: String, symbol: Option<String>, entries: Vec<MdEntry> },
+    /// MarketDataIncrementalRefresh (`35=X`); each entry carries its MDUpdateAction.
+    Incremental { owner: String, md_req_id: String, symbol: Option<String>, changes: Vec<(char, MdEntry)> },
+    /// MarketDataRequestReject (`35=Y`).
+    Reject { owner: String, md_req_id: String, reason: Option<char>, text: String },
+}
+
+impl MarketDataMessage {
+    /// Returns the key of the session the message must be sent to.
+    pub fn get_owner(&self) -> &str {
+        match self {
+            MarketDataMessage::Snapshot { owner, .. }
+            | MarketDataMessage::Incremental { owner, .. }
+            | MarketDataMessage::Reject { owner, .. } => owner,
+        }
+    }
+
+    /// Builds the FIX message; the session adds the standard header.
+    pub fn to_builder(&self, begin_string: &str) -> FixMessageBuilder {
+        match self {
+            MarketDataMessage::Snapshot { md_req_id, symbol, entries, .. } => {
+                FixMessageBuilder::new(begin_string, MARKET_DATA_SNAPSHOT)
+                    .field(tags::MD_REQ_ID, md_req_id)
+                    .optional_field(tags::SYMBOL, symbol.as_ref())
+                    .group(&groups::MD_FULL_REFRESH_ENTRIES, entries.iter().map(|entry| write_entry(GroupEntry::new(), entry)))
+            }
+            MarketDataMessage::Incremental { md_req_id, symbol, changes, .. } => {
+                let entries = changes.iter().map(|(action, entry)| {
+                    write_entry(GroupEntry::new().field(tags::MD_UPDATE_ACTION, action), entry).optional_field(tags::SYMBOL, symbol.as_ref())
+                });
+                FixMessageBuilder::new(begin_string, MARKET_DATA_INCREMENTAL)
+                    .field(tags::MD_REQ_ID, md_req_id)
+                    .group(&groups::MD_INCREMENTAL_ENTRIES, entries)
+            }
+            MarketDataMessage::Reject { md_req_id, reason, text, .. } => {
+                FixMessageBuilder::new(begin_string, MARKET_DATA_REQUEST_REJECT)
+                    .field(tags::MD_REQ_ID, md_req_id)
+                    .optional_field(tags::MD_REQ_REJ_REASON, *reason)
+                    .field(tags::TEXT, text)
+            }
+        }
+    }
+}
+
+fn write_entry(builder: GroupEntry, entry: &MdEntry) -> GroupEntry {
+    let entry_type = match entry.side {
+        Side::Buy => '0',
+        Side::Sell => '1',
+    };
+    builder
+        .field(tags::MD_ENTRY_TYPE, entry_type)
+        .field(tags::MD_ENTRY_PX, entry.price)
+        .field(tags::MD_ENTRY_SIZE, entry.quantity)
+}
+
+/// Quantity per price level on one side of the book.
+type Levels = BTreeMap<Price, Quantity>;
+
+/// An active subscription and the view last published to it.
+#[derive(Debug)]
+struct Subscription {
+    owner: String,
+    md_req_id: String,
+    symbol: Option<String>,
+    /// Levels per side, or `None` for the full book.
+    depth: Option<usize>,
+    update_type: UpdateType,
+    bids: bool,
+    asks: bool,
+    last_bids: Levels,
+    last_asks: Levels,
+}
+
+impl Subscription {
+    /// Returns the `(bids, asks)` this subscription sees in `infos`.
+    fn view(&self, infos: &OrderbookLevelInfos) -> (Levels, Levels) {
+        let depth = self.depth.unwrap_or(usize::MAX);
+        let collect = |wanted: bool, levels: &[orderbook::LevelInfo], best_first: fn(&Price, &Price) -> std::cmp::Ordering| -> Levels {
+            if !wanted {
+                return Levels::new();
+            }
+            let mut levels: Vec<(Price, Quantity)> = levels.iter().map(|level| (level.price, level.quantity)).collect();
+            levels.sort_by(|a, b| best_first(&a.0, &b.0));
+            levels.into_iter().take(depth).collect()
+        };
+        (
+            collect(self.bids, infos.get_bids(), |a, b| b.cmp(a)),
+            collect(self.asks, infos.get_asks(), |a, b| a.cmp(b)),
+        )
+    }
+
+    fn snapshot(&self) -> MarketDataMessage {
+        let bids = self.last_bids.iter().rev().map(|(price, quantity)| MdEntry { side: Side::Buy, price: *price, quantity: *quantity });
+        let asks = self.last_asks.iter().map(|(price, quantity)| MdEntry { side: Side::Sell, price: *price, quantity: *quantity });
+        MarketDataMessage::Snapshot {
+            owner: self.owner.clone(),
+            md_req_id: self.md_req_id.clone(),
+            symbol: self.symbol.clone(),
+            entries: bids.chain(asks).collect(),
+        }
+    }
+}
+
+/// Manages MarketDataRequest subscriptions and produces their refreshes.
+#[derive(Debug, Default)]
+pub struct MarketDataPublisher {
+    subscriptions: Vec<Subscription>,
+}
+
+impl MarketDataPublisher {
+    /// Creates a publisher with no subscriptions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of active subscriptions.
+    pub fn get_subscription_count(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Handles a MarketDataRequest from `owner`, answering with a snapshot of `infos`.
+    pub fn on_request(&mut self, owner: &str, message: &FixMessage, infos: &OrderbookLevelInfos) -> Vec<MarketDataMessage> {
+        let md_req_id = message.get_field(tags::MD_REQ_ID).unwrap_or_default().to_string();
+        let reject = |reason: Option<char>, text: &str| {
+            vec![MarketDataMessage::Reject { owner: owner.to_string(), md_req_id: md_req_id.clone(), reason, text: text.to_string() }]
+        };
+        if md_req_id.is_empty() {
+            return reject(None, "MDReqID (262) missing");
+        }
+        let existing = self.subscriptions.iter().position(|s| s.owner == owner && s.md_req_id == md_req_id);
+
+        let subscribe = match message.get_field(tags::SUBSCRIPTION_REQUEST_TYPE) {
+            Some("0") => false,
+            Some("1") => true,
+            Some("2") => {
+                return match existing {
+                    Some(index) => {
+                        self.subscriptions.remove(index);
+                        Vec::new()
+                    }
+                    None => reject(None, "Unknown MDReqID"),
+                };
+            }
+            _ => return reject(Some(rej_reason::UNSUPPORTED_SUBSCRIPTION_REQUEST_TYPE), "Unsupported SubscriptionRequestType (263)"),
+        };
+        if subscribe && existing.is_some() {
+            return reject(Some(rej_reason::DUPLICATE_MD_REQ_ID), "Duplicate MDReqID");
+        }
+        let depth = match message.get_uint(tags::MARKET_DEPTH) {
+            Ok(0) => None,
+            Ok(depth) => Some(depth as usize),
+            Err(_) => return reject(Some(rej_reason::UNSUPPORTED_MARKET_DEPTH), "MarketDepth (264) missing or invalid"),
+        };
+        let update_type = match message.get_field(tags::MD_UPDATE_TYPE) {
+            Some("0") => UpdateType::FullRefresh,
+            None | Some("1") => UpdateType::Incremental,
+            Some(_) => return reject(Some(rej_reason::UNSUPPORTED_MD_UPDATE_TYPE), "Unsupported MDUpdateType (265)"),
+        };
+        let (entry_types, related_sym) = match (message.get_group(&groups::MD_ENTRY_TYPES), message.get_group(&groups::RELATED_SYM)) {
+            (Ok(entry_types), Ok(related_sym)) => (entry_types, related_sym),
+            (Err(err), _) | (_, Err(err)) => return reject(None, &err.to_string()),
+        };
+        let entry_types: Vec<&str> = entry_types.iter().filter_map(|entry| entry.get_field(tags::MD_ENTRY_TYPE)).collect();
+        if entry_types.iter().any(|entry_type| !matches!(*entry_type, "0" | "1")) {
+            return reject(Some(rej_reason::UNSUPPORTED_MD_ENTRY_TYPE), "Only bids (0) and offers (1) are published");
+        }
+
+        let mut subscription = Subscription {
+            owner: owner.to_string(),
+            md_req_id,
+            symbol: related_sym.first().and_then(|entry| entry.get_field(tags::SYMBOL)).map(str::to_string),
+            depth,
+            update_type,
+            bids: entry_types.is_empty() || entry_types.contains(&"0"),
+            asks: entry_types.is_empty() || entry_types.contains(&"1"),
+            last_bids: Levels::new(),
+            last_asks: Levels::new(),
+        };
+        (subscription.last_bids, subscription.last_asks) = subscription.view(infos);
+        let snapshot = subscription.snapshot();
+        if subscribe {
+            self.subscriptions.push(subscription);
+        }
+        vec![snapshot]
+    }
+
+    /// Removes every subscription of `owner`, e.g. when its session disconnects.
+    pub fn remove_owner(&mut self, owner: &str) {
+        self.subscriptions.retain(|subscription| subscription.owner != owner);
+    }
+
+    /// Publishes whatever changed in each subscription's view of the book.
+    pub fn on_book_update(&mut self, infos: &OrderbookLevelInfos) -> Vec<MarketDataMessage> {
+        let mut messages = Vec::new();
+        for subscription in &mut self.subscriptions {
+            let (bids, asks) = subscription.view(infos);
+            let mut changes = diff(Side::Buy, &subscription.last_bids, &bids);
+            changes.extend(diff(Side::Sell, &subscription.last_asks, &asks));
+            if changes.is_empty() {
+                continue;
+            }
+            (subscription.last_bids, subscription.last_asks) = (bids, asks);
+            messages.push(match subscription.update_type {
+                UpdateType::FullRefresh => subscription.snapshot(),
+                UpdateType::Incremental => MarketDataMessage::Incremental {
+                    owner: subscription.owner.clone(),
+                    md_req_id: subscription.md_req_id.clone(),
+                    symbol: subscription.symbol.clone(),
+                    changes,
+                },
+            });
+        }
+        messages
+    }
+}
+
+/// Returns the incremental entries that turn `last` into `current`.
+fn diff(side: Side, last: &Levels, current: &Levels) -> Vec<(char, MdEntry)> {
+    let deleted = last
+        .iter()
+        .filter(|(price, _)| !current.contains_key(price))
+        .map(|(price, _)| (update_action::DELETE, MdEntry { side, price: *price, quantity: 0 }));
+    let changed = current.iter().filter_map(|(price, quantity)| {
+        let action = match last.get(price) {
+            None => update_action::NEW,
+            Some(previous) if previous != quantity => update_action::CHANGE,
+            Some(_) => return None,
+        };
+        Some((action, MdEntry { side, price: *price, quantity: *quantity }))
+    });
+    deleted.chain(changed).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use orderbook::LevelInfo;
+
+    fn book(bids: &[(Price, Quantity)], asks: &[(Price, Quantity)]) -> OrderbookLevelInfos {
+        let levels = |levels: &[(Price, Quantity)]| levels.iter().map(|(price, quantity)| LevelInfo { price: *price, quantity: *quantity }).collect();
+        OrderbookLevelInfos::new(levels(bids), levels(asks))
+    }
+
+    fn request(md_req_id: &str, subscription_type: char, depth: u32, update_type: Option<char>, entry_types: &[char]) -> FixMessage {
+        FixMessageBuilder::new("FIX.4.4", MARKET_DATA_REQUEST)
+            .field(tags::MD_REQ_ID, md_req_id)
+            .field(tags::SUBSCRIPTION_REQUEST_TYPE, subscription_type)
+            .field(tags::MARKET_DEPTH, depth)
+            .optional_field(tags::MD_UPDATE_TYPE, update_type)
+            .group(&groups::MD_ENTRY_TYPES, entry_types.iter().map(|entry_type| GroupEntry::new().field(tags::MD_ENTRY_TYPE, entry_type)))
+            .group(&groups::RELATED_SYM, [GroupEntry::new().field(tags::SYMBOL, "TEST")])
+            .build_message()
+    }
+
+    #[test]
+    fn test_snapshot_respects_depth_and_entry_types(){
+        let mut publisher = MarketDataPublisher::new();
+        let infos = book(&[(98, 5), (99, 3), (97, 1)], &[(101, 2), (102, 4)]);
+
+        let messages = publisher.on_request("CLIENT", &request("MD-1", '0', 2, None, &['0']), &infos);
+        let MarketDataMessage::Snapshot { entries, symbol, .. } = &messages[0] else { panic!("expected a snapshot") };
+        assert_eq!(symbol.as_deref(), Some("TEST"));
+        assert_eq!(entries, &vec![MdEntry { side: Side::Buy, price: 99, quantity: 3 }, MdEntry { side: Side::Buy, price: 98, quantity: 5 }]);
+        assert_eq!(publisher.get_subscription_count(), 0);
+
+        let message = messages[0].to_builder("FIX.4.4").build_message();
+        assert_eq!(message.get_msg_type(), Some(MARKET_DATA_SNAPSHOT));
+        assert_eq!(message.get_uint(tags::NO_MD_ENTRIES), Ok(2));
+        assert_eq!(message.get_all(tags::MD_ENTRY_PX).collect::<Vec<_>>(), vec!["99", "98"]);
+    }
+
+    #[test]
+    fn test_incremental_updates(){
+        let mut publisher = MarketDataPublisher::new();
+        publisher.on_request("CLIENT", &request("MD-1", '1', 1, Some('1'), &[]), &book(&[(99, 3)], &[(101, 2)]));
+
+        assert!(publisher.on_book_update(&book(&[(99, 3), (98, 7)], &[(101, 2)])).is_empty());
+
+        let messages = publisher.on_book_update(&book(&[(100, 1), (99, 3)], &[(101, 5)]));
+        let MarketDataMessage::Incremental { changes, md_req_id, .. } = &messages[0] else { panic!("expected an incremental refresh") };
+        assert_eq!(md_req_id, "MD-1");
+        assert_eq!(changes, &vec![
+            (update_action::DELETE, MdEntry { side: Side::Buy, price: 99, quantity: 0 }),
+            (update_action::NEW, MdEntry { side: Side::Buy, price: 100, quantity: 1 }),
+            (update_action::CHANGE, MdEntry { side: Side::Sell, price: 101, quantity: 5 }),
+        ]);
+
+        let message = messages[0].to_builder("FIX.4.4").build_message();
+        assert_eq!(message.get_msg_type(), Some(MARKET_DATA_INCREMENTAL));
+        assert_eq!(message.get_all(tags::MD_UPDATE_ACTION).collect::<Vec<_>>(), vec!["2", "0", "1"]);
+        let entries = message.get_group(&groups::MD_INCREMENTAL_ENTRIES).unwrap();
+        assert_eq!(entries[2].get_fields(), &[
+            (tags::MD_UPDATE_ACTION, "1".to_string()),
+            (tags::MD_ENTRY_TYPE, "1".to_string()),
+            (tags::MD_ENTRY_PX, "101".to_string()),
+            (tags::MD_ENTRY_SIZE, "5".to_string()),
+            (tags::SYMBOL, "TEST".to_string()),
+        ]);
+
+        publisher.on_request("CLIENT", &request("MD-1", '2', 0, None, &[]), &book(&[], &[]));
+        assert_eq!(publisher.get_subscription_count(), 0);
+    }
+
+    #[test]
+    fn test_full_refresh_and_rejects(){
+        let mut publisher = MarketDataPublisher::new();
+        publisher.on_request("CLIENT", &request("MD-1", '1', 0, Some('0'), &[]), &book(&[], &[]));
+
+        let messages = publisher.on_book_update(&book(&[(99, 3)], &[]));
+        assert!(matches!(&messages[..], [MarketDataMessage::Snapshot { entries, .. }] if entries.len() == 1));
+
+        let reject = |messages: Vec<MarketDataMessage>| match &messages[..] {
+            [MarketDataMessage::Reject { reason, .. }] => *reason,
+            other => panic!("expected a reject, got {:?}", other),
+        };
+        let infos = book(&[], &[]);
+        assert_eq!(reject(publisher.on_request("CLIENT", &request("MD-1", '1', 0, None, &[]), &infos)), Some(rej_reason::DUPLICATE_MD_REQ_ID));
+        assert_eq!(reject(publisher.on_request("CLIENT", &request("MD-2", '7', 0, None, &[]), &infos)), Some(rej_reason::UNSUPPORTED_SUBSCRIPTION_REQUEST_TYPE));
+        assert_eq!(reject(publisher.on_request("CLIENT", &request("MD-2", '1', 0, Some('3'), &[]), &infos)), Some(rej_reason::UNSUPPORTED_MD_UPDATE_TYPE));
+        assert_eq!(reject(publisher.on_request("CLIENT", &request("MD-2", '1', 0, None, &['2']), &infos)), Some(rej_reason::UNSUPPORTED_MD_ENTRY_TYPE));
+
+        publisher.remove_owner("CLIENT");
+        assert_eq!(publisher.get_subscription_count(), 0);
+    }
+}
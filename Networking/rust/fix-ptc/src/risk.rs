@@ -0,0 +1,155 @@
+//! # Risk Module
+//!
+//! Pre-trade position limits per account and symbol, enforced by the
+//! [`OrderBridge`](crate::bridge::OrderBridge) before an order reaches the book.
+//!
+//! ## Positions
+//! A [`Position`] holds what an account has traded in a symbol and what it could still trade:
+//! - *net*: quantity bought minus quantity sold,
+//! - *open buys* / *open sells*: leaves quantity of the account's live orders.
+//!
+//! Limits are checked against the worst case, as if open orders filled:
+//! - the *worst net* position is the larger of `|net + open buys|` and `|net - open sells|`,
+//!   i.e. every order on one side filling;
+//! - the *gross* position is `|net| + open buys + open sells`, every order filling.
+//!
+//! An order, or a replacement that grows an order, is rejected if the position with it added
+//! breaks a [`PositionLimit`]. Fills move quantity from open to net; cancels release it.
+
+use std::fmt;
+use orderbook::{Quantity, Side};
+
+/// Largest worst-case positions allowed; `None` is unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PositionLimit {
+    pub max_net: Option<u64>,
+    pub max_gross: Option<u64>,
+}
+
+impl PositionLimit {
+    /// Checks `position`, which includes the order being checked, against the limit.
+    ///
+    /// # Errors
+    /// Describes the first limit breached.
+    pub fn check(&self, position: &Position) -> Result<(), String> {
+        if let Some(max_net) = self.max_net.filter(|max_net| position.get_worst_net() > *max_net) {
+            return Err(format!("worst-case net position {} exceeds the limit of {}", position.get_worst_net(), max_net));
+        }
+        if let Some(max_gross) = self.max_gross.filter(|max_gross| position.get_gross() > *max_gross) {
+            return Err(format!("gross position {} exceeds the limit of {}", position.get_gross(), max_gross));
+        }
+        Ok(())
+    }
+}
+
+/// Traded and open quantity of one account in one symbol.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    /// Bought minus sold.
+    pub net: i64,
+    pub open_buys: u64,
+    pub open_sells: u64,
+}
+
+impl Position {
+    /// Returns the larger absolute net position reached if every open order on one side filled.
+    pub fn get_worst_net(&self) -> u64 {
+        let long = self.net + self.open_buys as i64;
+        let short = self.net - self.open_sells as i64;
+        long.unsigned_abs().max(short.unsigned_abs())
+    }
+
+    /// Returns the net position plus all open quantity.
+    pub fn get_gross(&self) -> u64 {
+        self.net.unsigned_abs() + self.open_buys + self.open_sells
+    }
+
+    /// Returns the position with `quantity` more open on `side`.
+    pub fn with_open(mut self, side: Side, quantity: Quantity) -> Self {
+        *self.open(side) += u64::from(quantity);
+        self
+    }
+
+    /// Moves `quantity` on `side` from open to traded.
+    pub fn on_fill(&mut self, side: Side, quantity: Quantity) {
+        self.on_release(side, quantity);
+        match side {
+            Side::Buy => self.net += i64::from(quantity),
+            Side::Sell => self.net -= i64::from(quantity),
+        }
+    }
+
+    /// Releases `quantity` of open orders on `side`, e.g. after a cancel.
+    pub fn on_release(&mut self, side: Side, quantity: Quantity) {
+        let open = self.open(side);
+        *open = open.saturating_sub(u64::from(quantity));
+    }
+
+    fn open(&mut self, side: Side) -> &mut u64 {
+        match side {
+            Side::Buy => &mut self.open_buys,
+            Side::Sell => &mut self.open_sells,
+        }
+    }
+}
+
+/// A position and the limit it is held to, as reported by
+/// [`OrderBridge::get_utilization`](crate::bridge::OrderBridge::get_utilization).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Utilization {
+    pub position: Position,
+    /// `None` if the account is not limited in the symbol.
+    pub limit: Option<PositionLimit>,
+}
+
+impl Utilization {
+    /// Returns the worst-case net position as a fraction of its limit, if there is one.
+    pub fn get_net_ratio(&self) -> Option<f64> {
+        let max_net = self.limit?.max_net?;
+        Some(self.position.get_worst_net() as f64 / max_net as f64)
+    }
+
+    /// Returns the gross position as a fraction of its limit, if there is one.
+    pub fn get_gross_ratio(&self) -> Option<f64> {
+        let max_gross = self.limit?.max_gross?;
+        Some(self.position.get_gross() as f64 / max_gross as f64)
+    }
+}
+
+impl fmt::Display for Utilization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ratio = |ratio: Option<f64>| ratio.map_or("unlimited".to_string(), |ratio| format!("{:.0}%", ratio * 100.0));
+        write!(
+            f,
+            "net {} (worst {}, {}), gross {} ({})",
+            self.position.net,
+            self.position.get_worst_net(),
+            ratio(self.get_net_ratio()),
+            self.position.get_gross(),
+            ratio(self.get_gross_ratio())
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_worst_case_positions(){
+        let mut position = Position::default().with_open(Side::Buy, 10).with_open(Side::Sell, 4);
+        position.on_fill(Side::Buy, 6);
+        assert_eq!(position, Position { net: 6, open_buys: 4, open_sells: 4 });
+        assert_eq!((position.get_worst_net(), position.get_gross()), (10, 14));
+
+        let limit = PositionLimit { max_net: Some(10), max_gross: Some(20) };
+        assert_eq!(limit.check(&position), Ok(()));
+        assert!(limit.check(&position.with_open(Side::Buy, 1)).unwrap_err().starts_with("worst-case net position 11"));
+        // Selling down the long stays within the net limit but counts towards the gross one.
+        assert_eq!(limit.check(&position.with_open(Side::Sell, 6)), Ok(()));
+        assert!(limit.check(&position.with_open(Side::Sell, 7)).unwrap_err().starts_with("gross position 21"));
+
+        let utilization = Utilization { position, limit: Some(PositionLimit { max_net: Some(20), max_gross: None }) };
+        assert_eq!(utilization.to_string(), "net 6 (worst 10, 50%), gross 14 (unlimited)");
+    }
+}
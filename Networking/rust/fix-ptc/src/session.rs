@@ -0,0 +1,1909 @@
+//! # Session Module
+//!
+//! The FIX session layer: Logon, Heartbeat, TestRequest and Logout.
+//!
+//! [`Session`] is a pure state machine. It never touches a socket or a clock; the caller feeds
+//! it decoded messages and the current time and collects the bytes it wants written from
+//! [`Session::take_outbound`]. [`run_session`] is the tokio driver that does exactly that over
+//! any byte stream, so the client and server share one implementation.
+//!
+//! ## Lifecycle
+//! - The initiator sends Logon (`35=A`) with its HeartBtInt as soon as it connects.
+//! - The acceptor adopts the initiator's HeartBtInt and answers with its own Logon.
+//! - While active, each side sends a Heartbeat (`35=0`) when it has been quiet for one interval.
+//!   If nothing arrives for an interval plus a tolerance (20% by default, see
+//!   [`SessionConfig::heartbeat_tolerance`]) a TestRequest (`35=1`) is sent, and the connection
+//!   is dropped if that is not answered within another interval.
+//! - Either side may send Logout (`35=5`); the peer confirms with its own Logout and the
+//!   initiator of the logout disconnects.
+//!
+//! ## FIXT
+//! A session whose BeginString is `FIXT.1.1` carries FIX 5.0 application messages. Both Logons
+//! then include DefaultApplVerID (tag 1137), and a Logon whose value is missing or differs from
+//! the configured one ends the session. An application message may override the default with
+//! ApplVerID (tag 1128); [`Session::get_appl_ver_id`] resolves the version of a message.
+//!
+//! ## Sequence numbers
+//! Every message carries MsgSeqNum (tag 34), counted separately in each direction from 1.
+//! - A message above the expected number means messages were lost: the session sends a
+//!   ResendRequest (`35=2`) for the missing range and drops messages until the gap is filled.
+//! - A message below the expected number is a fatal error unless it is flagged as a possible
+//!   duplicate (PossDupFlag `43=Y`), in which case it is ignored.
+//! - When the peer asks for a resend, stored application messages are sent again with
+//!   PossDupFlag and OrigSendingTime; administrative or missing messages are replaced by a
+//!   SequenceReset-GapFill (`35=4`, `123=Y`).
+//! - A session given a [`Dictionary`] rejects messages with missing required fields, values
+//!   outside their enumeration or values that do not parse, with a session-level Reject
+//!   (`35=3`). The MsgSeqNum of a rejected message is still consumed.
+//! - A received message flagged PossDupFlag must carry an OrigSendingTime (tag 122) no later
+//!   than its SendingTime, or it is answered with a session-level Reject (`35=3`).
+//! - A Logon carrying ResetSeqNumFlag (`141=Y`) resets both directions to MsgSeqNum 1 and
+//!   empties the store; it must itself be numbered 1 and is answered with a Logon that also
+//!   carries the flag. [`Session::reset_seq_nums`] starts such a reset, either on the next
+//!   Logon or, on an active session, straight away. With [`SessionConfig::reset_on_logon`] an
+//!   initiator resets on every Logon, and with [`SessionConfig::reset_time`] either side resets
+//!   once a day (see [`Session::on_clock`]).
+//! - Both sequence numbers are kept in the session's [`MessageStore`], so a session given a
+//!   persistent store (see [`Session::with_store`]) resumes numbering after a restart. If the
+//!   store cannot persist a message or sequence number, the session ends without sending
+//!   anything further, since it could no longer answer a ResendRequest.
+//! - A SequenceReset-GapFill is sequenced like any other message and moves the expected number
+//!   forward to NewSeqNo (tag 36). A SequenceReset-Reset (GapFillFlag absent or `N`) is applied
+//!   whatever its MsgSeqNum, for recovery when messages can no longer be resent. Either mode
+//!   is rejected if NewSeqNo would move the expected number backwards.
+//!
+//! ## Flood protection
+//! A session given a [`RateLimit`] counts the messages it receives once logged on in windows of
+//! fixed length. Messages beyond the limit of a window are not acted on: each is answered with a
+//! session-level Reject (`35=3`, reason 99) warning the counterparty, though its MsgSeqNum is
+//! still consumed. After the limit has been exceeded in `max_violations` consecutive windows the
+//! session logs the counterparty out. Logout is always processed.
+//!
+//! ## Latency
+//! The SendingTime (tag 52) of every inbound message is compared with the local time it was
+//! received, and round trips are timed from Logon and TestRequest, to estimate the one-way
+//! latency and clock skew of the counterparty (see [`LatencyMetrics`]).
+
+use std::{collections::BTreeMap, io, sync::Arc, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+    time,
+};
+use crate::audit::AuditLog;
+use crate::config::TimeOfDay;
+use crate::dictionary::Dictionary;
+use crate::fix::{tags, FixMessage, FixMessageBuilder, Tag};
+use crate::latency::LatencyMetrics;
+use crate::store::{MemoryStore, MessageStore};
+use crate::wirelog::{Direction, WireLog};
+
+/// Administrative message types handled by the session layer.
+pub mod msg_types {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const LOGON: &str = "A";
+
+    /// Returns `true` for session-level message types, which are never replayed on resend.
+    pub fn is_admin(msg_type: &str) -> bool {
+        matches!(msg_type, HEARTBEAT | TEST_REQUEST | RESEND_REQUEST | REJECT | SEQUENCE_RESET | LOGOUT | LOGON)
+    }
+}
+
+/// BeginString of the FIXT transport, which carries FIX 5.0 application messages.
+pub const FIXT_1_1: &str = "FIXT.1.1";
+
+/// ApplVerID (tag 1128) and DefaultApplVerID (tag 1137) values.
+pub mod appl_ver_id {
+    pub const FIX_4_2: &str = "4";
+    pub const FIX_4_4: &str = "6";
+    pub const FIX_5_0: &str = "7";
+    pub const FIX_5_0_SP1: &str = "8";
+    pub const FIX_5_0_SP2: &str = "9";
+}
+
+/// SessionRejectReason (tag 373) values sent by the session layer.
+pub mod reject_reason {
+    pub const REQUIRED_TAG_MISSING: u32 = 1;
+    pub const VALUE_INCORRECT: u32 = 5;
+    pub const INCORRECT_DATA_FORMAT: u32 = 6;
+    pub const SENDING_TIME_ACCURACY: u32 = 10;
+    pub const REPEATING_GROUP_FIELDS_OUT_OF_ORDER: u32 = 15;
+    pub const INCORRECT_NUM_IN_GROUP_COUNT: u32 = 16;
+    pub const OTHER: u32 = 99;
+}
+
+/// Which side of the connection a session is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionRole {
+    /// Connects and sends the first Logon (the client).
+    Initiator,
+    /// Accepts connections and answers Logon (the server).
+    Acceptor,
+}
+
+/// Static parameters of a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionConfig {
+    /// BeginString (tag 8), e.g. `FIX.4.4`.
+    pub begin_string: String,
+    /// Our CompID, sent as SenderCompID (tag 49).
+    pub sender_comp_id: String,
+    /// The counterparty's CompID, sent as TargetCompID (tag 56).
+    pub target_comp_id: String,
+    /// HeartBtInt (tag 108) proposed by an initiator. An acceptor uses the peer's value.
+    pub heartbeat_interval: Duration,
+    /// Silence tolerated beyond the heartbeat interval before a TestRequest is sent, in
+    /// percent of the interval.
+    pub heartbeat_tolerance: u32,
+    /// DefaultApplVerID (tag 1137) exchanged at Logon; required when `begin_string` is
+    /// [`FIXT_1_1`] and ignored otherwise.
+    pub default_appl_ver_id: Option<String>,
+    /// Inbound flood protection; unlimited if `None`.
+    pub rate_limit: Option<RateLimit>,
+    /// Send ResetSeqNumFlag (tag 141) on every Logon of an initiator.
+    pub reset_on_logon: bool,
+    /// UTC time of day at which sequence numbers are reset.
+    pub reset_time: Option<TimeOfDay>,
+}
+
+/// How many messages a session accepts from its counterparty, see the
+/// [module docs](self#flood-protection).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Messages accepted per window.
+    pub max_messages: u32,
+    pub window: Duration,
+    /// Consecutive windows over the limit after which the counterparty is logged out.
+    pub max_violations: u32,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            begin_string: "FIX.4.4".to_string(),
+            sender_comp_id: "CLIENT".to_string(),
+            target_comp_id: "SERVER".to_string(),
+            heartbeat_interval: Duration::from_secs(30),
+            heartbeat_tolerance: 20,
+            default_appl_ver_id: None,
+            rate_limit: None,
+            reset_on_logon: false,
+            reset_time: None,
+        }
+    }
+}
+
+/// Where a session is in its lifecycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionState {
+    /// Connected, waiting for the peer's Logon (acceptor) or not yet started (initiator).
+    AwaitingLogon,
+    /// Logon sent, waiting for the acceptor's reply.
+    LogonSent,
+    /// Logged on; application messages may flow.
+    Active,
+    /// Logout sent, waiting for confirmation.
+    LogoutSent,
+    /// Finished; the connection should be closed.
+    Disconnected,
+}
+
+/// Something the owner of a session should know about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent {
+    /// The logon handshake completed.
+    LoggedOn,
+    /// An application-level message arrived.
+    Message(FixMessage),
+    /// The session ended; the connection should be closed.
+    Disconnected(String),
+}
+
+/// The FIX session state machine for one connection.
+pub struct Session {
+    config: SessionConfig,
+    role: SessionRole,
+    state: SessionState,
+    heartbeat_interval: Duration,
+    next_outgoing: u64,
+    next_incoming: u64,
+    /// Highest MsgSeqNum seen when the outstanding ResendRequest was sent.
+    resend_target: Option<u64>,
+    store: Box<dyn MessageStore>,
+    dictionary: Option<Arc<Dictionary>>,
+    wire_log: Option<WireLog>,
+    audit_log: Option<AuditLog>,
+    latency: LatencyMetrics,
+    last_sent: Instant,
+    last_received: Instant,
+    /// When the current state was entered; used for logon/logout timeouts.
+    state_since: Instant,
+    /// TestReqID of the unanswered TestRequest and when it was sent.
+    pending_test_request: Option<(String, Instant)>,
+    test_requests_sent: u64,
+    /// Start of the current rate-limit window and the messages received in it.
+    window_start: Instant,
+    window_count: u32,
+    /// Consecutive windows in which the rate limit was exceeded.
+    violations: u32,
+    /// Our next Logon carries ResetSeqNumFlag, or we are waiting for the peer to confirm one.
+    reset_requested: bool,
+    /// When the sequence numbers were last reset by schedule, or the session first saw the
+    /// clock.
+    last_scheduled_reset: Option<SystemTime>,
+    /// Why the store failed, until the owner takes it with [`Session::take_failure`].
+    failure: Option<String>,
+    outbound: Vec<Vec<u8>>,
+}
+
+impl Session {
+    /// Creates a session in [`SessionState::AwaitingLogon`].
+    pub fn new(config: SessionConfig, role: SessionRole, now: Instant) -> Self {
+        Self {
+            heartbeat_interval: config.heartbeat_interval,
+            config,
+            role,
+            state: SessionState::AwaitingLogon,
+            next_outgoing: 1,
+            next_incoming: 1,
+            resend_target: None,
+            store: Box::new(MemoryStore::new()),
+            dictionary: None,
+            wire_log: None,
+            audit_log: None,
+            latency: LatencyMetrics::new(),
+            last_sent: now,
+            last_received: now,
+            state_since: now,
+            pending_test_request: None,
+            test_requests_sent: 0,
+            window_start: now,
+            window_count: 0,
+            violations: 0,
+            reset_requested: false,
+            last_scheduled_reset: None,
+            failure: None,
+            outbound: Vec::new(),
+        }
+    }
+
+    /// Replaces the default in-memory store of sent messages, continuing from the sequence
+    /// numbers it holds.
+    pub fn with_store(mut self, store: Box<dyn MessageStore>) -> Self {
+        self.next_outgoing = store.get_next_sender_seq();
+        self.next_incoming = store.get_next_target_seq();
+        self.store = store;
+        self
+    }
+
+    /// Validates inbound messages against `dictionary` once logged on, answering violations
+    /// with a session-level Reject instead of delivering them.
+    pub fn with_dictionary(mut self, dictionary: Arc<Dictionary>) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// Records every message read from and written to the connection in `wire_log`.
+    pub fn with_wire_log(mut self, wire_log: WireLog) -> Self {
+        self.wire_log = Some(wire_log);
+        self
+    }
+
+    /// Records every application message read from and written to the connection in
+    /// `audit_log`.
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Records latency estimates in `latency`, e.g. metrics the application already holds.
+    pub fn with_latency_metrics(mut self, latency: LatencyMetrics) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Returns the latency estimates of the session.
+    pub fn get_latency_metrics(&self) -> &LatencyMetrics {
+        &self.latency
+    }
+
+    /// Returns the session configuration.
+    pub fn get_config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Returns the current state.
+    pub fn get_state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Returns the heartbeat interval in force (negotiated once logged on).
+    pub fn get_heartbeat_interval(&self) -> Duration {
+        self.heartbeat_interval
+    }
+
+    /// Returns how long the peer may stay silent before it is probed with a TestRequest: the
+    /// heartbeat interval plus the configured tolerance.
+    pub fn get_probe_delay(&self) -> Duration {
+        self.heartbeat_interval + self.heartbeat_interval * self.config.heartbeat_tolerance / 100
+    }
+
+    /// Returns the MsgSeqNum the next outbound message will carry.
+    pub fn get_next_outgoing(&self) -> u64 {
+        self.next_outgoing
+    }
+
+    /// Returns the MsgSeqNum expected on the next inbound message.
+    pub fn get_next_incoming(&self) -> u64 {
+        self.next_incoming
+    }
+
+    /// Returns `true` while a ResendRequest is outstanding.
+    pub fn is_resending(&self) -> bool {
+        self.resend_target.is_some()
+    }
+
+    /// Returns `true` once the session has ended.
+    pub fn is_disconnected(&self) -> bool {
+        self.state == SessionState::Disconnected
+    }
+
+    /// Returns why the session ended if it failed (see [`Session::send`]), once.
+    pub fn take_failure(&mut self) -> Option<String> {
+        self.failure.take()
+    }
+
+    /// Appends raw message bytes to the wire log and, for application messages, to the audit
+    /// log, if they are attached.
+    ///
+    /// # Errors
+    /// Returns an error if either log cannot be written.
+    pub fn log_wire(&mut self, direction: Direction, message: &[u8]) -> io::Result<()> {
+        if let Some(wire_log) = &mut self.wire_log {
+            wire_log.record(direction, message)?;
+        }
+        match &mut self.audit_log {
+            Some(audit_log) => audit_log.record(direction, message, SystemTime::now()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the session runs over the FIXT.1.1 transport.
+    pub fn is_fixt(&self) -> bool {
+        self.config.begin_string == FIXT_1_1
+    }
+
+    /// Returns the application version of `message`: its ApplVerID (tag 1128) if present,
+    /// otherwise the DefaultApplVerID agreed at Logon. `None` for classic FIX 4.x sessions.
+    pub fn get_appl_ver_id<'a>(&'a self, message: &'a FixMessage) -> Option<&'a str> {
+        if !self.is_fixt() {
+            return None;
+        }
+        message.get_field(tags::APPL_VER_ID).or(self.config.default_appl_ver_id.as_deref())
+    }
+
+    /// Drains the serialized messages waiting to be written to the connection.
+    pub fn take_outbound(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.outbound)
+    }
+
+    /// Prepares the session for a new connection after the previous one ended, keeping the
+    /// sequence numbers and stored messages so the peer can recover anything it missed.
+    pub fn reconnect(&mut self, now: Instant) {
+        self.heartbeat_interval = self.config.heartbeat_interval;
+        self.resend_target = None;
+        self.failure = None;
+        self.pending_test_request = None;
+        self.outbound.clear();
+        self.last_sent = now;
+        self.last_received = now;
+        self.set_state(SessionState::AwaitingLogon, now);
+    }
+
+    /// Called once the transport is connected. An initiator sends its Logon.
+    pub fn on_connect(&mut self, now: Instant) {
+        if self.role == SessionRole::Initiator && self.state == SessionState::AwaitingLogon {
+            if self.config.reset_on_logon {
+                self.reset_seq_nums(now);
+            }
+            let logon = self.logon_fields();
+            self.send_admin(msg_types::LOGON, now, logon);
+            self.set_state(SessionState::LogonSent, now);
+        }
+    }
+
+    /// Stamps and queues an application message.
+    ///
+    /// # Errors
+    /// Returns an error if the session is not logged on or a value contains SOH, in which case
+    /// nothing is sent, or if the message cannot be stored, which ends the session.
+    pub fn send(&mut self, builder: FixMessageBuilder, now: Instant) -> Result<(), String> {
+        if self.state != SessionState::Active {
+            return Err(format!("Cannot send {} while session is {:?}", builder.get_msg_type(), self.state));
+        }
+        builder.check().map_err(|err| format!("Cannot send {}: {}", builder.get_msg_type(), err))?;
+        self.queue(builder, now);
+        match self.take_failure() {
+            Some(failure) => Err(failure),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends a SequenceReset-Reset so the peer expects `new_seq_no` next, skipping messages that
+    /// can no longer be resent.
+    ///
+    /// # Errors
+    /// Returns an error if the session is not logged on or `new_seq_no` is not ahead of the
+    /// next outbound MsgSeqNum.
+    pub fn reset_sequence(&mut self, new_seq_no: u64, now: Instant) -> Result<(), String> {
+        if self.state != SessionState::Active {
+            return Err(format!("Cannot reset sequence numbers while session is {:?}", self.state));
+        }
+        if new_seq_no <= self.next_outgoing {
+            return Err(format!("NewSeqNo {} must be greater than {}", new_seq_no, self.next_outgoing));
+        }
+        self.send_admin(msg_types::SEQUENCE_RESET, now, |builder| {
+            builder.field(tags::GAP_FILL_FLAG, 'N').field(tags::NEW_SEQ_NO, new_seq_no)
+        });
+        self.next_outgoing = new_seq_no;
+        self.save_seq_nums(now);
+        Ok(())
+    }
+
+    /// Resets both directions to MsgSeqNum 1 with ResetSeqNumFlag (tag 141).
+    ///
+    /// On an active session a Logon with the flag is sent now and the peer's confirming Logon
+    /// is expected next; otherwise the reset is applied locally and the next Logon carries the
+    /// flag.
+    pub fn reset_seq_nums(&mut self, now: Instant) {
+        self.reset_requested = true;
+        self.reset_outgoing(now);
+        self.next_incoming = 1;
+        if self.state == SessionState::Active {
+            let logon = self.logon_fields();
+            self.send_admin(msg_types::LOGON, now, logon);
+        }
+    }
+
+    /// Applies the daily reset of [`SessionConfig::reset_time`] once the wall clock `time`
+    /// has passed it. Call this regularly, e.g. together with [`Session::on_timer`].
+    pub fn on_clock(&mut self, time: SystemTime, now: Instant) {
+        let Some(reset_time) = self.config.reset_time else {
+            return;
+        };
+        let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut due = UNIX_EPOCH + Duration::from_secs(seconds - seconds % 86_400 + u64::from(reset_time.get_seconds()));
+        if due > time {
+            due -= Duration::from_secs(86_400);
+        }
+        match self.last_scheduled_reset {
+            Some(last) if last < due => {
+                self.last_scheduled_reset = Some(time);
+                self.reset_seq_nums(now);
+            }
+            Some(_) => {}
+            // A session that starts after today's reset time has nothing to catch up on.
+            None => self.last_scheduled_reset = Some(time),
+        }
+    }
+
+    /// Starts a graceful logout.
+    pub fn logout(&mut self, text: &str, now: Instant) {
+        match self.state {
+            SessionState::Active => {
+                self.send_admin(msg_types::LOGOUT, now, |builder| builder.field(tags::TEXT, text));
+                self.set_state(SessionState::LogoutSent, now);
+            }
+            SessionState::LogoutSent | SessionState::Disconnected => {}
+            _ => self.set_state(SessionState::Disconnected, now),
+        }
+    }
+
+    /// Processes one inbound message.
+    pub fn on_message(&mut self, message: FixMessage, now: Instant) -> Vec<SessionEvent> {
+        let mut events = self.receive(message, now);
+        self.save_seq_nums(now);
+        events.extend(self.take_failure().map(SessionEvent::Disconnected));
+        events
+    }
+
+    fn receive(&mut self, message: FixMessage, now: Instant) -> Vec<SessionEvent> {
+        self.last_received = now;
+        if let Err(reason) = self.check_identity(&message) {
+            return self.terminate(&reason, now);
+        }
+        if self.state == SessionState::Disconnected {
+            return Vec::new();
+        }
+        if let Some(sent) = message.get_field(tags::SENDING_TIME).and_then(parse_utc_timestamp) {
+            self.latency.record_transit(sent, SystemTime::now());
+        }
+        let msg_type = message.get_msg_type().unwrap_or_default().to_string();
+        let Ok(seq_num) = message.get_uint(tags::MSG_SEQ_NUM) else {
+            return self.terminate("MsgSeqNum (34) missing or invalid", now);
+        };
+
+        if msg_type == msg_types::LOGON && self.state == SessionState::Active && message.get_bool(tags::RESET_SEQ_NUM_FLAG) == Ok(true) {
+            return self.on_reset_logon(seq_num, now);
+        }
+        if msg_type == msg_types::LOGON && self.state != SessionState::Active {
+            let mut events = self.on_logon(&message, now);
+            if self.state == SessionState::Active {
+                match self.check_sequence(seq_num, &message, now) {
+                    SequenceCheck::InOrder => self.next_incoming += 1,
+                    SequenceCheck::Fatal(reason) => events.extend(self.terminate(&reason, now)),
+                    SequenceCheck::Gap | SequenceCheck::Ignore => {}
+                }
+            }
+            return events;
+        }
+        if matches!(self.state, SessionState::AwaitingLogon | SessionState::LogonSent) {
+            return self.terminate(&format!("Expected Logon, received MsgType {}", msg_type), now);
+        }
+
+        // A SequenceReset-Reset is applied whatever its MsgSeqNum.
+        let is_reset = msg_type == msg_types::SEQUENCE_RESET && message.get_bool(tags::GAP_FILL_FLAG) != Ok(true);
+        if !is_reset {
+            match self.check_sequence(seq_num, &message, now) {
+                SequenceCheck::InOrder => self.next_incoming += 1,
+                SequenceCheck::Ignore => return Vec::new(),
+                SequenceCheck::Fatal(reason) => return self.terminate(&reason, now),
+                // While a gap is open only Logout and ResendRequest are acted on.
+                SequenceCheck::Gap if msg_type == msg_types::LOGOUT || msg_type == msg_types::RESEND_REQUEST => {}
+                SequenceCheck::Gap => return Vec::new(),
+            }
+        }
+
+        let invalid = self.dictionary.as_ref().and_then(|dictionary| dictionary.validate(&message).err());
+        let flooded = msg_type != msg_types::LOGOUT && self.state == SessionState::Active && self.is_flooded(now);
+        let events = if flooded {
+            self.on_flood(seq_num, &msg_type, now);
+            Vec::new()
+        } else if let Some((reason, text)) = self.check_poss_dup(&message) {
+            self.send_reject(seq_num, &msg_type, Some(tags::ORIG_SENDING_TIME), reason, text, now);
+            if reason == reject_reason::SENDING_TIME_ACCURACY {
+                self.terminate(text, now)
+            } else {
+                Vec::new()
+            }
+        } else if let Some(err) = invalid {
+            // A Reject is never answered with another Reject.
+            if msg_type != msg_types::REJECT {
+                self.send_reject(seq_num, &msg_type, Some(err.get_tag()), err.get_reason(), &err.to_string(), now);
+            }
+            Vec::new()
+        } else {
+            self.dispatch(message, seq_num, &msg_type, now)
+        };
+        if self.resend_target.is_some_and(|target| self.next_incoming > target) {
+            self.resend_target = None;
+        }
+        events
+    }
+
+    /// Acts on a message that has passed the sequence checks.
+    fn dispatch(&mut self, message: FixMessage, seq_num: u64, msg_type: &str, now: Instant) -> Vec<SessionEvent> {
+        match (self.state, msg_type) {
+            (SessionState::LogoutSent, msg_types::LOGOUT) => {
+                self.set_state(SessionState::Disconnected, now);
+                vec![SessionEvent::Disconnected("Logout confirmed".to_string())]
+            }
+            (_, msg_types::LOGOUT) => {
+                self.send_admin(msg_types::LOGOUT, now, |builder| builder);
+                self.set_state(SessionState::Disconnected, now);
+                let text = message.get_field(tags::TEXT).unwrap_or("Logout requested by peer");
+                vec![SessionEvent::Disconnected(text.to_string())]
+            }
+            (_, msg_types::HEARTBEAT) => {
+                if let Some((id, sent_at)) = &self.pending_test_request {
+                    if message.get_field(tags::TEST_REQ_ID).is_none_or(|received| received == id) {
+                        self.latency.record_round_trip(self.since(*sent_at, now));
+                        self.pending_test_request = None;
+                    }
+                }
+                Vec::new()
+            }
+            (_, msg_types::TEST_REQUEST) => {
+                let id = message.get_field(tags::TEST_REQ_ID).unwrap_or_default().to_string();
+                self.send_admin(msg_types::HEARTBEAT, now, |builder| builder.field(tags::TEST_REQ_ID, id));
+                Vec::new()
+            }
+            (_, msg_types::RESEND_REQUEST) => {
+                self.on_resend_request(&message, now);
+                Vec::new()
+            }
+            (_, msg_types::SEQUENCE_RESET) => {
+                match message.get_uint(tags::NEW_SEQ_NO) {
+                    Ok(new_seq_no) if new_seq_no >= self.next_incoming => self.next_incoming = new_seq_no,
+                    Ok(_) => self.send_reject(seq_num, msg_type, Some(tags::NEW_SEQ_NO), reject_reason::VALUE_INCORRECT, "NewSeqNo (36) is lower than the expected MsgSeqNum", now),
+                    Err(_) => self.send_reject(seq_num, msg_type, Some(tags::NEW_SEQ_NO), reject_reason::REQUIRED_TAG_MISSING, "NewSeqNo (36) missing or invalid", now),
+                }
+                Vec::new()
+            }
+            (_, msg_types::REJECT) => Vec::new(),
+            (SessionState::Active, msg_types::LOGON) => self.terminate("Unexpected Logon on an active session", now),
+            (SessionState::Active, _) => vec![SessionEvent::Message(message)],
+            // Application messages that cross our Logout are dropped; the other states were
+            // handled before the sequence check.
+            _ => Vec::new(),
+        }
+    }
+
+    /// Runs the heartbeat and timeout checks. Call this regularly (a few times per second).
+    pub fn on_timer(&mut self, now: Instant) -> Vec<SessionEvent> {
+        let mut events = self.check_timers(now);
+        events.extend(self.take_failure().map(SessionEvent::Disconnected));
+        events
+    }
+
+    fn check_timers(&mut self, now: Instant) -> Vec<SessionEvent> {
+        let interval = self.heartbeat_interval;
+        match self.state {
+            SessionState::AwaitingLogon | SessionState::LogonSent if self.since(self.state_since, now) >= interval => {
+                self.terminate("Logon timed out", now)
+            }
+            SessionState::LogoutSent if self.since(self.state_since, now) >= interval => {
+                self.set_state(SessionState::Disconnected, now);
+                vec![SessionEvent::Disconnected("Logout not confirmed".to_string())]
+            }
+            SessionState::Active => {
+                if let Some((_, sent_at)) = &self.pending_test_request {
+                    if self.since(*sent_at, now) >= interval {
+                        return self.terminate("TestRequest not answered", now);
+                    }
+                } else if self.since(self.last_received, now) >= self.get_probe_delay() {
+                    self.test_requests_sent += 1;
+                    let id = format!("TEST-{}", self.test_requests_sent);
+                    self.pending_test_request = Some((id.clone(), now));
+                    self.send_admin(msg_types::TEST_REQUEST, now, |builder| builder.field(tags::TEST_REQ_ID, id));
+                }
+                if self.since(self.last_sent, now) >= interval {
+                    self.send_admin(msg_types::HEARTBEAT, now, |builder| builder);
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Compares an inbound MsgSeqNum with the expected one, requesting a resend on a gap.
+    fn check_sequence(&mut self, seq_num: u64, message: &FixMessage, now: Instant) -> SequenceCheck {
+        if seq_num == self.next_incoming {
+            return SequenceCheck::InOrder;
+        }
+        if seq_num < self.next_incoming {
+            if message.get_bool(tags::POSS_DUP_FLAG) == Ok(true) {
+                return SequenceCheck::Ignore;
+            }
+            return SequenceCheck::Fatal(format!("MsgSeqNum too low, expecting {} but received {}", self.next_incoming, seq_num));
+        }
+        match self.resend_target {
+            Some(target) => self.resend_target = Some(target.max(seq_num)),
+            None => {
+                let begin = self.next_incoming;
+                self.send_admin(msg_types::RESEND_REQUEST, now, |builder| {
+                    builder.field(tags::BEGIN_SEQ_NO, begin).field(tags::END_SEQ_NO, 0)
+                });
+                self.resend_target = Some(seq_num);
+            }
+        }
+        SequenceCheck::Gap
+    }
+
+    /// Replays stored application messages in the requested range and gap-fills the rest.
+    fn on_resend_request(&mut self, message: &FixMessage, now: Instant) {
+        let last_sent = self.next_outgoing - 1;
+        let begin = message.get_uint(tags::BEGIN_SEQ_NO).unwrap_or(1).max(1);
+        let end = match message.get_uint(tags::END_SEQ_NO) {
+            Ok(end) if end != 0 && end < last_sent => end,
+            _ => last_sent,
+        };
+        let stored: BTreeMap<u64, Vec<u8>> = self.store.get_range(begin, end).into_iter().collect();
+
+        let mut gap_start = None;
+        for seq_num in begin..=end {
+            let replay = stored
+                .get(&seq_num)
+                .and_then(|bytes| FixMessage::parse(bytes).ok())
+                .filter(|stored| !msg_types::is_admin(stored.get_msg_type().unwrap_or_default()));
+            match replay {
+                Some(original) => {
+                    if let Some(start) = gap_start.take() {
+                        self.send_gap_fill(start, seq_num, now);
+                    }
+                    let mut builder = FixMessageBuilder::from_message(&original);
+                    builder.set_field(tags::POSS_DUP_FLAG, 'Y');
+                    if let Some(sending_time) = original.get_field(tags::SENDING_TIME) {
+                        builder.set_field(tags::ORIG_SENDING_TIME, sending_time);
+                    }
+                    if let Some(bytes) = self.stamp(builder, seq_num, now) {
+                        self.outbound.push(bytes);
+                    }
+                }
+                None => {
+                    gap_start.get_or_insert(seq_num);
+                }
+            }
+        }
+        if let Some(start) = gap_start {
+            self.send_gap_fill(start, end + 1, now);
+        }
+        self.last_sent = now;
+    }
+
+    /// Sends a SequenceReset-GapFill numbered `seq_num` that skips the peer to `new_seq_no`.
+    fn send_gap_fill(&mut self, seq_num: u64, new_seq_no: u64, now: Instant) {
+        let builder = FixMessageBuilder::new(&self.config.begin_string, msg_types::SEQUENCE_RESET)
+            .field(tags::POSS_DUP_FLAG, 'Y')
+            .field(tags::ORIG_SENDING_TIME, format_utc_timestamp(SystemTime::now()))
+            .field(tags::GAP_FILL_FLAG, 'Y')
+            .field(tags::NEW_SEQ_NO, new_seq_no);
+        if let Some(bytes) = self.stamp(builder, seq_num, now) {
+            self.outbound.push(bytes);
+        }
+        self.last_sent = now;
+    }
+
+    fn on_logon(&mut self, message: &FixMessage, now: Instant) -> Vec<SessionEvent> {
+        let heartbeat = match message.get_uint(tags::HEART_BT_INT) {
+            Ok(seconds) if seconds > 0 => seconds,
+            _ => return self.terminate("Logon without a valid HeartBtInt (108)", now),
+        };
+        if message.get_field(tags::ENCRYPT_METHOD).is_some_and(|method| method != "0") {
+            return self.terminate("Unsupported EncryptMethod (98)", now);
+        }
+        if self.is_fixt() {
+            let expected = self.config.default_appl_ver_id.as_deref();
+            match message.get_field(tags::DEFAULT_APPL_VER_ID) {
+                None => return self.terminate("Logon without DefaultApplVerID (1137)", now),
+                Some(value) if Some(value) != expected => {
+                    return self.terminate(&format!("Unsupported DefaultApplVerID (1137) {}", value), now);
+                }
+                Some(_) => {}
+            }
+        }
+        if message.get_bool(tags::RESET_SEQ_NUM_FLAG) == Ok(true) {
+            self.next_incoming = 1;
+            if self.role == SessionRole::Acceptor {
+                self.reset_requested = true;
+                self.reset_outgoing(now);
+            }
+        }
+        if self.role == SessionRole::Acceptor {
+            self.heartbeat_interval = Duration::from_secs(heartbeat);
+            let logon = self.logon_fields();
+            self.send_admin(msg_types::LOGON, now, logon);
+        } else {
+            self.latency.record_round_trip(self.since(self.state_since, now));
+        }
+        self.reset_requested = false;
+        if self.is_disconnected() {
+            return Vec::new();
+        }
+        self.set_state(SessionState::Active, now);
+        vec![SessionEvent::LoggedOn]
+    }
+
+    /// Handles a Logon with ResetSeqNumFlag on an active session: the peer's confirmation of
+    /// our reset, or a reset it starts, which we confirm.
+    fn on_reset_logon(&mut self, seq_num: u64, now: Instant) -> Vec<SessionEvent> {
+        if seq_num != 1 {
+            return self.terminate(&format!("Logon with ResetSeqNumFlag (141) must have MsgSeqNum 1, received {}", seq_num), now);
+        }
+        if !self.reset_requested {
+            self.reset_outgoing(now);
+            self.reset_requested = true;
+            let logon = self.logon_fields();
+            self.send_admin(msg_types::LOGON, now, logon);
+        }
+        self.reset_requested = false;
+        self.next_incoming = 2;
+        self.resend_target = None;
+        Vec::new()
+    }
+
+    /// Restarts outbound numbering from 1, forgetting the messages that can no longer be resent.
+    fn reset_outgoing(&mut self, now: Instant) {
+        self.next_outgoing = 1;
+        if let Err(err) = self.store.reset() {
+            self.fail(format!("Cannot reset the message store: {}", err), now);
+        }
+    }
+
+    /// Persists the sequence numbers the store does not hold yet.
+    fn save_seq_nums(&mut self, now: Instant) {
+        let result = if self.store.get_next_sender_seq() != self.next_outgoing {
+            self.store.set_next_sender_seq(self.next_outgoing)
+        } else {
+            Ok(())
+        };
+        let result = result.and_then(|_| match self.store.get_next_target_seq() != self.next_incoming {
+            true => self.store.set_next_target_seq(self.next_incoming),
+            false => Ok(()),
+        });
+        if let Err(err) = result {
+            self.fail(format!("Cannot store the sequence numbers: {}", err), now);
+        }
+    }
+
+    /// Ends the session after its store failed or a message could not be built, without sending
+    /// anything further.
+    fn fail(&mut self, reason: String, now: Instant) {
+        self.set_state(SessionState::Disconnected, now);
+        self.failure.get_or_insert(reason);
+    }
+
+    /// Returns the fields of our Logon: EncryptMethod, HeartBtInt, ResetSeqNumFlag while a reset
+    /// is requested and, over FIXT, DefaultApplVerID.
+    fn logon_fields(&self) -> impl FnOnce(FixMessageBuilder) -> FixMessageBuilder {
+        let heartbeat = self.heartbeat_interval.as_secs();
+        let reset = self.reset_requested;
+        let default_appl_ver_id = self.config.default_appl_ver_id.clone().filter(|_| self.is_fixt());
+        move |builder| {
+            let builder = builder.field(tags::ENCRYPT_METHOD, 0).field(tags::HEART_BT_INT, heartbeat);
+            let builder = if reset { builder.field(tags::RESET_SEQ_NUM_FLAG, 'Y') } else { builder };
+            match default_appl_ver_id {
+                Some(version) => builder.field(tags::DEFAULT_APPL_VER_ID, version),
+                None => builder,
+            }
+        }
+    }
+
+    /// Checks that BeginString and the CompIDs match this session.
+    fn check_identity(&self, message: &FixMessage) -> Result<(), String> {
+        let expect = |tag, expected: &str| match message.get_field(tag) {
+            Some(value) if value == expected => Ok(()),
+            value => Err(format!("Field {} is {:?}, expected {}", tag, value, expected)),
+        };
+        expect(tags::BEGIN_STRING, &self.config.begin_string)?;
+        expect(tags::SENDER_COMP_ID, &self.config.target_comp_id)?;
+        expect(tags::TARGET_COMP_ID, &self.config.sender_comp_id)
+    }
+
+    /// Sends a Logout with `reason` (if logged on) and ends the session.
+    fn terminate(&mut self, reason: &str, now: Instant) -> Vec<SessionEvent> {
+        if matches!(self.state, SessionState::Active | SessionState::LogonSent) {
+            self.send_admin(msg_types::LOGOUT, now, |builder| builder.field(tags::TEXT, reason));
+        }
+        self.set_state(SessionState::Disconnected, now);
+        vec![SessionEvent::Disconnected(reason.to_string())]
+    }
+
+    /// Counts an inbound message against the rate limit, returning true if it is over the limit.
+    fn is_flooded(&mut self, now: Instant) -> bool {
+        let Some(limit) = self.config.rate_limit else {
+            return false;
+        };
+        let elapsed = self.since(self.window_start, now);
+        if elapsed >= limit.window {
+            // Violations only add up over consecutive windows.
+            if self.window_count <= limit.max_messages || elapsed >= limit.window * 2 {
+                self.violations = 0;
+            }
+            self.window_start = now;
+            self.window_count = 0;
+        }
+        self.window_count += 1;
+        if self.window_count == limit.max_messages + 1 {
+            self.violations += 1;
+        }
+        self.window_count > limit.max_messages
+    }
+
+    /// Answers a message over the rate limit with a warning, or logs the counterparty out once
+    /// the limit has been exceeded for too long.
+    fn on_flood(&mut self, seq_num: u64, msg_type: &str, now: Instant) {
+        let Some(limit) = self.config.rate_limit else {
+            return;
+        };
+        if self.violations >= limit.max_violations {
+            let text = format!("Message rate above {} per {:?} in {} consecutive windows", limit.max_messages, limit.window, self.violations);
+            self.logout(&text, now);
+        } else {
+            let text = format!("Message rate limit of {} per {:?} exceeded, message ignored", limit.max_messages, limit.window);
+            self.send_reject(seq_num, msg_type, None, reject_reason::OTHER, &text, now);
+        }
+    }
+
+    /// Checks the OrigSendingTime of a message flagged PossDupFlag, returning the
+    /// SessionRejectReason and text if it is unacceptable.
+    fn check_poss_dup(&self, message: &FixMessage) -> Option<(u32, &'static str)> {
+        if message.get_bool(tags::POSS_DUP_FLAG) != Ok(true) {
+            return None;
+        }
+        let Some(original) = message.get_field(tags::ORIG_SENDING_TIME) else {
+            return Some((reject_reason::REQUIRED_TAG_MISSING, "OrigSendingTime (122) required with PossDupFlag"));
+        };
+        // Compare to the second, since the two may be sent with different precisions.
+        let sending = message.get_field(tags::SENDING_TIME).unwrap_or_default();
+        match (original.get(..17), sending.get(..17)) {
+            (Some(original), Some(sending)) if original > sending => {
+                Some((reject_reason::SENDING_TIME_ACCURACY, "OrigSendingTime (122) is later than SendingTime (52)"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sends a session-level Reject for the message numbered `ref_seq_num`.
+    fn send_reject(&mut self, ref_seq_num: u64, ref_msg_type: &str, ref_tag: Option<Tag>, reason: u32, text: &str, now: Instant) {
+        let ref_msg_type = ref_msg_type.to_string();
+        self.send_admin(msg_types::REJECT, now, |builder| {
+            builder
+                .field(tags::REF_SEQ_NUM, ref_seq_num)
+                .optional_field(tags::REF_TAG_ID, ref_tag)
+                .field(tags::REF_MSG_TYPE, ref_msg_type)
+                .field(tags::SESSION_REJECT_REASON, reason)
+                .field(tags::TEXT, text)
+        });
+    }
+
+    fn send_admin(&mut self, msg_type: &str, now: Instant, fields: impl FnOnce(FixMessageBuilder) -> FixMessageBuilder) {
+        let builder = fields(FixMessageBuilder::new(&self.config.begin_string, msg_type));
+        self.queue(builder, now);
+    }
+
+    /// Stamps `builder` with the next MsgSeqNum, stores it and queues it for sending.
+    ///
+    /// A message that cannot be stored is not sent and ends the session.
+    fn queue(&mut self, builder: FixMessageBuilder, now: Instant) {
+        if self.failure.is_some() {
+            return;
+        }
+        let Some(bytes) = self.stamp(builder, self.next_outgoing, now) else {
+            return;
+        };
+        if let Err(err) = self.store.add(self.next_outgoing, &bytes) {
+            self.fail(format!("Cannot store MsgSeqNum {}: {}", self.next_outgoing, err), now);
+            return;
+        }
+        self.next_outgoing += 1;
+        self.last_sent = now;
+        self.outbound.push(bytes);
+    }
+
+    /// Sets the standard header fields and serializes the message, ending the session if it
+    /// cannot be serialized.
+    fn stamp(&mut self, builder: FixMessageBuilder, seq_num: u64, now: Instant) -> Option<Vec<u8>> {
+        let built = builder
+            .field(tags::SENDER_COMP_ID, &self.config.sender_comp_id)
+            .field(tags::TARGET_COMP_ID, &self.config.target_comp_id)
+            .field(tags::MSG_SEQ_NUM, seq_num)
+            .field(tags::SENDING_TIME, format_utc_timestamp(SystemTime::now()))
+            .build();
+        built.map_err(|err| self.fail(format!("Cannot build MsgSeqNum {}: {}", seq_num, err), now)).ok()
+    }
+
+    fn set_state(&mut self, state: SessionState, now: Instant) {
+        // A failed session stays disconnected until it reconnects.
+        if self.failure.is_some() {
+            return;
+        }
+        self.state = state;
+        self.state_since = now;
+    }
+
+    fn since(&self, earlier: Instant, now: Instant) -> Duration {
+        now.saturating_duration_since(earlier)
+    }
+}
+
+/// Outcome of comparing an inbound MsgSeqNum with the expected one.
+enum SequenceCheck {
+    InOrder,
+    /// Ahead of the expected number; a resend has been requested.
+    Gap,
+    /// A possible duplicate of something already processed.
+    Ignore,
+    Fatal(String),
+}
+
+/// Formats `time` as a FIX UTCTimestamp with milliseconds: `YYYYMMDD-HH:MM:SS.sss`.
+pub fn format_utc_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Parses a FIX UTCTimestamp, `YYYYMMDD-HH:MM:SS` optionally followed by up to nine digits of
+/// fractional seconds.
+pub fn parse_utc_timestamp(value: &str) -> Option<SystemTime> {
+    let digits = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = value.get(range)?;
+        part.bytes().all(|byte| byte.is_ascii_digit()).then(|| part.parse().ok())?
+    };
+    if value.get(8..9)? != "-" || value.get(11..12)? != ":" || value.get(14..15)? != ":" {
+        return None;
+    }
+    let (year, month, day) = (digits(0..4)?, digits(4..6)?, digits(6..8)?);
+    let (hours, minutes, seconds) = (digits(9..11)?, digits(12..14)?, digits(15..17)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let nanos = match value.get(17..) {
+        Some("") => 0,
+        Some(fraction) if fraction.len() >= 2 && fraction.len() <= 10 && fraction.starts_with('.') => {
+            digits(18..value.len())? * 10i64.pow(10 - fraction.len() as u32)
+        }
+        _ => return None,
+    };
+
+    // Days-from-civil, the inverse of the conversion in format_utc_timestamp.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = u64::try_from(days * 86_400 + hours * 3600 + minutes * 60 + seconds).ok()?;
+    Some(UNIX_EPOCH + Duration::new(seconds, nanos as u32))
+}
+
+/// Requests from the application to a running [`run_session`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionCommand {
+    /// Send an application message.
+    Send(FixMessageBuilder),
+    /// Log out gracefully.
+    Logout(String),
+    /// Send a SequenceReset-Reset to the given NewSeqNo.
+    ResetSequence(u64),
+    /// Reset both directions to MsgSeqNum 1 with ResetSeqNumFlag, see
+    /// [`Session::reset_seq_nums`].
+    ResetSeqNums,
+}
+
+/// Drives `session` over `stream` until it disconnects.
+///
+/// Inbound application messages and lifecycle changes are published on `events`; messages to
+/// send are taken from `commands`. Dropping the command sender logs the session out.
+///
+/// # Errors
+/// Returns an error if the stream fails, the peer sends bytes that are not valid FIX or a
+/// message cannot be written to the wire or audit log.
+pub async fn run_session<S>(
+    stream: S,
+    session: Session,
+    commands: mpsc::UnboundedReceiver<SessionCommand>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    run_session_with(stream, Vec::new(), session, commands, events).await
+}
+
+/// Like [`run_session`], for a stream from which `received` has already been read, e.g. by an
+/// acceptor that inspected the Logon to pick the session configuration.
+///
+/// # Errors
+/// Returns an error if the stream fails, the peer sends bytes that are not valid FIX or a
+/// message cannot be written to the wire or audit log.
+pub async fn run_session_with<S>(
+    stream: S,
+    received: Vec<u8>,
+    mut session: Session,
+    mut commands: mpsc::UnboundedReceiver<SessionCommand>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    drive_session(stream, received, &mut session, &mut commands, &events).await
+}
+
+/// Like [`run_session_with`], borrowing the session and channels so they can be reused on a
+/// new connection once this one ends (see [`Session::reconnect`]).
+///
+/// # Errors
+/// Returns an error if the stream fails, the peer sends bytes that are not valid FIX or a
+/// message cannot be written to the wire or audit log.
+pub async fn drive_session<S>(
+    mut stream: S,
+    received: Vec<u8>,
+    session: &mut Session,
+    commands: &mut mpsc::UnboundedReceiver<SessionCommand>,
+    events: &mpsc::UnboundedSender<SessionEvent>,
+) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut buffer = received;
+    let mut chunk = [0u8; 4096];
+    let mut ticker = time::interval(Duration::from_millis(100));
+    let mut commands_open = true;
+    session.on_connect(Instant::now());
+
+    loop {
+        while let Some((message, consumed)) = FixMessage::decode(&buffer).map_err(|err| err.to_string())? {
+            session.log_wire(Direction::Inbound, &buffer[..consumed]).map_err(|err| format!("Logging failed: {}", err))?;
+            buffer.drain(..consumed);
+            for event in session.on_message(message, Instant::now()) {
+                let _ = events.send(event);
+            }
+        }
+        for bytes in session.take_outbound() {
+            session.log_wire(Direction::Outbound, &bytes).map_err(|err| format!("Logging failed: {}", err))?;
+            stream.write_all(&bytes).await.map_err(|err| format!("Write failed: {}", err))?;
+        }
+        if session.is_disconnected() {
+            if let Some(failure) = session.take_failure() {
+                let _ = events.send(SessionEvent::Disconnected(failure));
+            }
+            let _ = stream.shutdown().await;
+            return Ok(());
+        }
+
+        let emitted = tokio::select! {
+            read = stream.read(&mut chunk) => {
+                let read = read.map_err(|err| format!("Read failed: {}", err))?;
+                if read == 0 {
+                    let _ = events.send(SessionEvent::Disconnected("Connection closed by peer".to_string()));
+                    return Ok(());
+                }
+                buffer.extend_from_slice(&chunk[..read]);
+                Vec::new()
+            }
+            command = commands.recv(), if commands_open => {
+                match command {
+                    Some(SessionCommand::Send(builder)) => {
+                        if let Err(err) = session.send(builder, Instant::now()) {
+                            let _ = events.send(SessionEvent::Disconnected(err));
+                        }
+                    }
+                    Some(SessionCommand::Logout(text)) => session.logout(&text, Instant::now()),
+                    Some(SessionCommand::ResetSequence(new_seq_no)) => {
+                        if let Err(err) = session.reset_sequence(new_seq_no, Instant::now()) {
+                            let _ = events.send(SessionEvent::Disconnected(err));
+                        }
+                    }
+                    Some(SessionCommand::ResetSeqNums) => session.reset_seq_nums(Instant::now()),
+                    None => {
+                        commands_open = false;
+                        session.logout("Application shut down", Instant::now());
+                    }
+                }
+                Vec::new()
+            }
+            _ = ticker.tick() => {
+                session.on_clock(SystemTime::now(), Instant::now());
+                session.on_timer(Instant::now())
+            }
+        };
+
+        for event in emitted {
+            let _ = events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::store::FileStore;
+
+    fn config(sender: &str, target: &str, heartbeat: u64) -> SessionConfig {
+        SessionConfig {
+            sender_comp_id: sender.to_string(),
+            target_comp_id: target.to_string(),
+            heartbeat_interval: Duration::from_secs(heartbeat),
+            ..Default::default()
+        }
+    }
+
+    fn parse_all(session: &mut Session) -> Vec<FixMessage> {
+        session.take_outbound().iter().map(|bytes| FixMessage::parse(bytes).unwrap()).collect()
+    }
+
+    /// Delivers everything `from` has queued to `to`, returning `to`'s events.
+    fn pump(from: &mut Session, to: &mut Session, now: Instant) -> Vec<SessionEvent> {
+        parse_all(from).into_iter().flat_map(|message| to.on_message(message, now)).collect()
+    }
+
+    fn logged_on_pair(now: Instant) -> (Session, Session) {
+        let mut client = Session::new(config("CLIENT", "SERVER", 10), SessionRole::Initiator, now);
+        let mut server = Session::new(config("SERVER", "CLIENT", 30), SessionRole::Acceptor, now);
+        client.on_connect(now);
+        assert_eq!(pump(&mut client, &mut server, now), vec![SessionEvent::LoggedOn]);
+        assert_eq!(pump(&mut server, &mut client, now), vec![SessionEvent::LoggedOn]);
+        (client, server)
+    }
+
+    #[test]
+    fn test_logon_negotiates_heartbeat_interval(){
+        let now = Instant::now();
+        let (client, server) = logged_on_pair(now);
+
+        assert_eq!(client.get_state(), SessionState::Active);
+        assert_eq!(server.get_state(), SessionState::Active);
+        assert_eq!(server.get_heartbeat_interval(), Duration::from_secs(10));
+        assert_eq!(client.get_next_outgoing(), 2);
+        assert_eq!(server.get_next_outgoing(), 2);
+    }
+
+    #[test]
+    fn test_fixt_logon_exchanges_default_appl_ver_id(){
+        let now = Instant::now();
+        let fixt = |sender, target, version: &str| SessionConfig {
+            begin_string: FIXT_1_1.to_string(),
+            default_appl_ver_id: Some(version.to_string()),
+            ..config(sender, target, 30)
+        };
+        let mut client = Session::new(fixt("CLIENT", "SERVER", appl_ver_id::FIX_5_0_SP2), SessionRole::Initiator, now);
+        let mut server = Session::new(fixt("SERVER", "CLIENT", appl_ver_id::FIX_5_0_SP2), SessionRole::Acceptor, now);
+        client.on_connect(now);
+        let logon = parse_all(&mut client).remove(0);
+        assert_eq!(logon.get_begin_string(), Some(FIXT_1_1));
+        assert_eq!(logon.get_field(tags::DEFAULT_APPL_VER_ID), Some(appl_ver_id::FIX_5_0_SP2));
+        assert_eq!(server.on_message(logon, now), vec![SessionEvent::LoggedOn]);
+        assert_eq!(pump(&mut server, &mut client, now), vec![SessionEvent::LoggedOn]);
+
+        let order = FixMessageBuilder::new(FIXT_1_1, "D").field(tags::CL_ORD_ID, "1");
+        client.send(order.clone(), now).unwrap();
+        client.send(order.field(tags::APPL_VER_ID, appl_ver_id::FIX_5_0_SP1), now).unwrap();
+        let received = parse_all(&mut client);
+        assert_eq!(server.get_appl_ver_id(&received[0]), Some(appl_ver_id::FIX_5_0_SP2));
+        assert_eq!(server.get_appl_ver_id(&received[1]), Some(appl_ver_id::FIX_5_0_SP1));
+
+        // A peer on another application version is refused.
+        let mut client = Session::new(fixt("CLIENT", "SERVER", appl_ver_id::FIX_5_0), SessionRole::Initiator, now);
+        let mut server = Session::new(fixt("SERVER", "CLIENT", appl_ver_id::FIX_5_0_SP2), SessionRole::Acceptor, now);
+        client.on_connect(now);
+        assert_eq!(pump(&mut client, &mut server, now), vec![SessionEvent::Disconnected("Unsupported DefaultApplVerID (1137) 7".to_string())]);
+        assert_eq!(Session::new(config("CLIENT", "SERVER", 30), SessionRole::Initiator, now).get_appl_ver_id(&received[0]), None);
+    }
+
+    #[test]
+    fn test_heartbeat_and_test_request(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+
+        // Quiet for one interval: both sides heartbeat.
+        let later = now + Duration::from_secs(10);
+        client.on_timer(later);
+        let sent = parse_all(&mut client);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].get_msg_type(), Some(msg_types::HEARTBEAT));
+        server.on_message(sent[0].clone(), later);
+
+        // The server then hears nothing for interval + grace: it probes the client.
+        let later = later + Duration::from_secs(12);
+        server.on_timer(later);
+        let probe = parse_all(&mut server);
+        assert_eq!(probe[0].get_msg_type(), Some(msg_types::TEST_REQUEST));
+        assert_eq!(probe[0].get_field(tags::TEST_REQ_ID), Some("TEST-1"));
+
+        // The client answers with a Heartbeat echoing the TestReqID, which clears the probe.
+        client.on_message(probe[0].clone(), later);
+        let answer = parse_all(&mut client);
+        assert_eq!(answer[0].get_field(tags::TEST_REQ_ID), Some("TEST-1"));
+        server.on_message(answer[0].clone(), later);
+        assert!(server.on_timer(later + Duration::from_secs(10)).is_empty());
+        assert_eq!(server.get_state(), SessionState::Active);
+    }
+
+    #[test]
+    fn test_unanswered_test_request_disconnects(){
+        let now = Instant::now();
+        let (_, mut server) = logged_on_pair(now);
+
+        server.on_timer(now + Duration::from_secs(12));
+        let events = server.on_timer(now + Duration::from_secs(22));
+
+        assert_eq!(events, vec![SessionEvent::Disconnected("TestRequest not answered".to_string())]);
+        let sent = parse_all(&mut server);
+        assert_eq!(sent.last().unwrap().get_msg_type(), Some(msg_types::LOGOUT));
+    }
+
+    #[test]
+    fn test_heartbeat_tolerance_is_configurable(){
+        let now = Instant::now();
+        let (_, mut server) = logged_on_pair(now);
+        server.config.heartbeat_tolerance = 50;
+        assert_eq!(server.get_probe_delay(), Duration::from_secs(15));
+
+        server.on_timer(now + Duration::from_secs(12));
+        assert!(parse_all(&mut server).iter().all(|message| message.get_msg_type() == Some(msg_types::HEARTBEAT)));
+        server.on_timer(now + Duration::from_secs(15));
+        assert_eq!(parse_all(&mut server)[0].get_msg_type(), Some(msg_types::TEST_REQUEST));
+        assert!(server.on_timer(now + Duration::from_secs(24)).is_empty());
+        assert_eq!(server.on_timer(now + Duration::from_secs(25)), vec![SessionEvent::Disconnected("TestRequest not answered".to_string())]);
+    }
+
+    #[test]
+    fn test_rate_limit_warns_then_logs_out(){
+        let now = Instant::now();
+        let mut client = Session::new(config("CLIENT", "SERVER", 30), SessionRole::Initiator, now);
+        let limit = RateLimit { max_messages: 3, window: Duration::from_secs(1), max_violations: 2 };
+        let mut server = Session::new(SessionConfig { rate_limit: Some(limit), ..config("SERVER", "CLIENT", 30) }, SessionRole::Acceptor, now);
+        client.on_connect(now);
+        pump(&mut client, &mut server, now);
+        pump(&mut server, &mut client, now);
+        let mut send = |count: usize, at: Instant| {
+            for index in 0..count {
+                client.send(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, index), at).unwrap();
+            }
+            let delivered = pump(&mut client, &mut server, at).len();
+            (delivered, parse_all(&mut server))
+        };
+
+        let (delivered, answers) = send(5, now);
+        assert_eq!(delivered, 3);
+        assert_eq!(answers.len(), 2);
+        assert!(answers.iter().all(|answer| answer.get_uint(tags::SESSION_REJECT_REASON) == Ok(reject_reason::OTHER as u64)));
+        assert_eq!(answers[0].get_uint(tags::REF_SEQ_NUM), Ok(5));
+
+        // A quiet window clears the violation.
+        let (delivered, answers) = send(3, now + Duration::from_secs(3));
+        assert_eq!((delivered, answers.len()), (3, 0));
+
+        // Two windows over the limit in a row end the session.
+        let (delivered, answers) = send(4, now + Duration::from_secs(5));
+        assert_eq!((delivered, answers.len()), (3, 1));
+        let (delivered, answers) = send(4, now + Duration::from_secs(6));
+        assert_eq!(delivered, 3);
+        assert_eq!(answers[0].get_msg_type(), Some(msg_types::LOGOUT));
+        assert!(answers[0].get_field(tags::TEXT).unwrap().contains("2 consecutive windows"));
+        assert_eq!(server.get_state(), SessionState::LogoutSent);
+        assert_eq!(server.get_next_incoming(), client.get_next_outgoing());
+    }
+
+    #[test]
+    fn test_reset_seq_num_flag_on_logon(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+        client.send(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "1"), now).unwrap();
+        pump(&mut client, &mut server, now);
+        client.logout("bye", now);
+        pump(&mut client, &mut server, now);
+        pump(&mut server, &mut client, now);
+        assert_eq!((client.get_next_outgoing(), server.get_next_outgoing()), (4, 3));
+
+        // The client comes back resetting; the acceptor resets too and echoes the flag.
+        client.config.reset_on_logon = true;
+        client.reconnect(now);
+        server.reconnect(now);
+        client.on_connect(now);
+        let logon = parse_all(&mut client).remove(0);
+        assert_eq!((logon.get_uint(tags::MSG_SEQ_NUM), logon.get_bool(tags::RESET_SEQ_NUM_FLAG)), (Ok(1), Ok(true)));
+        assert_eq!(server.on_message(logon, now), vec![SessionEvent::LoggedOn]);
+        let reply = parse_all(&mut server).remove(0);
+        assert_eq!((reply.get_uint(tags::MSG_SEQ_NUM), reply.get_bool(tags::RESET_SEQ_NUM_FLAG)), (Ok(1), Ok(true)));
+        assert_eq!(client.on_message(reply, now), vec![SessionEvent::LoggedOn]);
+        for session in [&client, &server] {
+            assert_eq!((session.get_next_outgoing(), session.get_next_incoming()), (2, 2));
+        }
+        assert_eq!(server.store.get_range(1, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_file_store_resumes_sequence_numbers_after_restart(){
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("client.log");
+        let now = Instant::now();
+        let start_client = || {
+            let store = FileStore::open(&path).unwrap();
+            Session::new(config("CLIENT", "SERVER", 10), SessionRole::Initiator, now).with_store(Box::new(store))
+        };
+        let mut client = start_client();
+        let mut server = Session::new(config("SERVER", "CLIENT", 30), SessionRole::Acceptor, now);
+        client.on_connect(now);
+        pump(&mut client, &mut server, now);
+        pump(&mut server, &mut client, now);
+        client.send(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "1"), now).unwrap();
+        pump(&mut client, &mut server, now);
+        assert_eq!((client.get_next_outgoing(), client.get_next_incoming()), (3, 2));
+        drop(client);
+
+        // The client process restarts on the same store; the server only sees a reconnect.
+        let mut client = start_client();
+        assert_eq!((client.get_next_outgoing(), client.get_next_incoming()), (3, 2));
+        server.reconnect(now);
+        client.on_connect(now);
+        let logon = parse_all(&mut client).remove(0);
+        assert_eq!(logon.get_uint(tags::MSG_SEQ_NUM), Ok(3));
+        assert_eq!(server.on_message(logon, now), vec![SessionEvent::LoggedOn]);
+        assert_eq!(pump(&mut server, &mut client, now), vec![SessionEvent::LoggedOn]);
+
+        // The order sent before the restart is still the one replayed for MsgSeqNum 2.
+        server.send_admin(msg_types::RESEND_REQUEST, now, |builder| builder.field(tags::BEGIN_SEQ_NO, 2).field(tags::END_SEQ_NO, 2));
+        pump(&mut server, &mut client, now);
+        let replayed = parse_all(&mut client).remove(0);
+        assert_eq!((replayed.get_uint(tags::MSG_SEQ_NUM), replayed.get_field(tags::CL_ORD_ID)), (Ok(2), Some("1")));
+        assert_eq!(client.get_state(), SessionState::Active);
+    }
+
+    #[test]
+    fn test_send_rejects_soh_in_values(){
+        let now = Instant::now();
+        let (mut client, _server) = logged_on_pair(now);
+        let order = FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "1\x0135=5");
+        assert_eq!(client.send(order, now), Err("Cannot send D: value of field 11 contains SOH".to_string()));
+        assert!(client.take_outbound().is_empty());
+        assert_eq!((client.get_state(), client.get_next_outgoing()), (SessionState::Active, 2));
+    }
+
+    #[test]
+    fn test_store_failure_ends_session(){
+        struct FullStore {
+            messages: MemoryStore,
+            full: Arc<AtomicBool>,
+        }
+
+        impl MessageStore for FullStore {
+            fn add(&mut self, seq_num: u64, message: &[u8]) -> io::Result<()> {
+                if self.full.load(Ordering::SeqCst) {
+                    return Err(io::Error::other("disk full"));
+                }
+                self.messages.add(seq_num, message)
+            }
+
+            fn get_range(&self, begin: u64, end: u64) -> Vec<(u64, Vec<u8>)> {
+                self.messages.get_range(begin, end)
+            }
+
+            fn reset(&mut self) -> io::Result<()> {
+                self.messages.reset()
+            }
+
+            fn get_next_sender_seq(&self) -> u64 {
+                self.messages.get_next_sender_seq()
+            }
+
+            fn get_next_target_seq(&self) -> u64 {
+                self.messages.get_next_target_seq()
+            }
+
+            fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()> {
+                self.messages.set_next_sender_seq(seq_num)
+            }
+
+            fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()> {
+                self.messages.set_next_target_seq(seq_num)
+            }
+        }
+
+        let now = Instant::now();
+        let full = Arc::new(AtomicBool::new(false));
+        let store = FullStore { messages: MemoryStore::new(), full: full.clone() };
+        let mut client = Session::new(config("CLIENT", "SERVER", 10), SessionRole::Initiator, now).with_store(Box::new(store));
+        let mut server = Session::new(config("SERVER", "CLIENT", 30), SessionRole::Acceptor, now);
+        client.on_connect(now);
+        pump(&mut client, &mut server, now);
+        pump(&mut server, &mut client, now);
+
+        // The Heartbeat answering a TestRequest cannot be stored, so it is not sent.
+        full.store(true, Ordering::SeqCst);
+        server.send_admin(msg_types::TEST_REQUEST, now, |builder| builder.field(tags::TEST_REQ_ID, "1"));
+        let events = pump(&mut server, &mut client, now);
+        assert_eq!(events, vec![SessionEvent::Disconnected("Cannot store MsgSeqNum 2: disk full".to_string())]);
+        assert!(client.take_outbound().is_empty());
+        assert!(client.is_disconnected());
+        assert!(client.send(FixMessageBuilder::new("FIX.4.4", "D"), now).is_err());
+
+        // Nor can the Logon of the next connection.
+        client.reconnect(now);
+        client.on_connect(now);
+        assert!(client.is_disconnected());
+        assert_eq!(client.take_failure().as_deref(), Some("Cannot store MsgSeqNum 2: disk full"));
+        assert!(client.take_outbound().is_empty());
+    }
+
+    #[test]
+    fn test_reset_seq_nums_on_active_session(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+        for cl_ord_id in ["1", "2"] {
+            client.send(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, cl_ord_id), now).unwrap();
+        }
+        pump(&mut client, &mut server, now);
+
+        // Started by the server; the client confirms and neither side sees an event.
+        server.reset_seq_nums(now);
+        assert_eq!(pump(&mut server, &mut client, now), vec![]);
+        assert_eq!(pump(&mut client, &mut server, now), vec![]);
+        client.send(FixMessageBuilder::new("FIX.4.4", "D").field(tags::CL_ORD_ID, "3"), now).unwrap();
+        let order = parse_all(&mut client).remove(0);
+        assert_eq!(order.get_uint(tags::MSG_SEQ_NUM), Ok(2));
+        assert!(matches!(&server.on_message(order, now)[..], [SessionEvent::Message(_)]));
+        assert_eq!(server.get_state(), SessionState::Active);
+
+        // A reset Logon numbered other than 1 is fatal.
+        let bad = FixMessageBuilder::new("FIX.4.4", msg_types::LOGON)
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, 3)
+            .field(tags::HEART_BT_INT, 10)
+            .field(tags::RESET_SEQ_NUM_FLAG, 'Y')
+            .build_message();
+        assert!(matches!(&server.on_message(bad, now)[..], [SessionEvent::Disconnected(reason)] if reason.contains("MsgSeqNum 1")));
+    }
+
+    #[test]
+    fn test_scheduled_daily_reset(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+        server.config.reset_time = TimeOfDay::new(22, 0, 0);
+        let day = |hours: u64, minutes: u64| UNIX_EPOCH + Duration::from_secs(19_000 * 86_400 + hours * 3600 + minutes * 60);
+
+        // Starting after today's reset time does not trigger it.
+        server.on_clock(day(22, 30), now);
+        server.on_clock(day(23, 59), now);
+        assert!(server.take_outbound().is_empty());
+
+        server.on_clock(day(46, 1), now);
+        let logon = parse_all(&mut server).remove(0);
+        assert_eq!((logon.get_uint(tags::MSG_SEQ_NUM), logon.get_bool(tags::RESET_SEQ_NUM_FLAG)), (Ok(1), Ok(true)));
+        client.on_message(logon, now);
+        pump(&mut client, &mut server, now);
+        assert_eq!((server.get_next_outgoing(), server.get_next_incoming()), (2, 2));
+
+        // Only once per day.
+        server.on_clock(day(47, 0), now);
+        assert!(server.take_outbound().is_empty());
+    }
+
+    #[test]
+    fn test_logout_handshake(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+
+        client.logout("bye", now);
+        assert_eq!(client.get_state(), SessionState::LogoutSent);
+        let events = pump(&mut client, &mut server, now);
+        assert_eq!(events, vec![SessionEvent::Disconnected("bye".to_string())]);
+        let events = pump(&mut server, &mut client, now);
+        assert_eq!(events, vec![SessionEvent::Disconnected("Logout confirmed".to_string())]);
+        assert!(client.is_disconnected() && server.is_disconnected());
+    }
+
+    #[test]
+    fn test_reconnect_resumes_sequence_numbers(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+        client.send(order("1"), now).unwrap();
+        pump(&mut client, &mut server, now);
+
+        // The connection drops without a Logout; both sides keep their numbers.
+        client.on_timer(now + Duration::from_secs(12));
+        let events = client.on_timer(now + Duration::from_secs(22));
+        assert_eq!(events, vec![SessionEvent::Disconnected("TestRequest not answered".to_string())]);
+        client.take_outbound();
+        server.logout("Connection lost", now);
+        server.take_outbound();
+
+        let later = now + Duration::from_secs(30);
+        client.reconnect(later);
+        server.reconnect(later);
+        assert_eq!(client.get_state(), SessionState::AwaitingLogon);
+        client.on_connect(later);
+        let logon = parse_all(&mut client).remove(0);
+        assert_eq!(logon.get_uint(tags::MSG_SEQ_NUM), Ok(5));
+        assert_eq!(server.on_message(logon, later), vec![SessionEvent::LoggedOn]);
+
+        // Admin messages sent into the dead connection are recovered with gap fills both ways.
+        assert!(server.is_resending());
+        assert_eq!(pump(&mut server, &mut client, later), vec![SessionEvent::LoggedOn]);
+        assert!(client.is_resending());
+        assert!(pump(&mut client, &mut server, later).is_empty());
+        assert!(pump(&mut server, &mut client, later).is_empty());
+        assert!(!client.is_resending() && !server.is_resending());
+        assert_eq!(server.get_next_incoming(), client.get_next_outgoing());
+        assert_eq!(client.get_next_incoming(), server.get_next_outgoing());
+    }
+
+    #[test]
+    fn test_rejects_wrong_comp_id_and_non_logon(){
+        let now = Instant::now();
+        let mut server = Session::new(config("SERVER", "CLIENT", 30), SessionRole::Acceptor, now);
+        let mut intruder = Session::new(config("OTHER", "SERVER", 30), SessionRole::Initiator, now);
+        intruder.on_connect(now);
+        assert!(matches!(pump(&mut intruder, &mut server, now)[..], [SessionEvent::Disconnected(_)]));
+
+        let mut server = Session::new(config("SERVER", "CLIENT", 30), SessionRole::Acceptor, now);
+        let heartbeat = FixMessageBuilder::new("FIX.4.4", msg_types::HEARTBEAT)
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, 1)
+            .build_message();
+        assert_eq!(
+            server.on_message(heartbeat, now),
+            vec![SessionEvent::Disconnected("Expected Logon, received MsgType 0".to_string())]
+        );
+    }
+
+    fn order(id: &str) -> FixMessageBuilder {
+        FixMessageBuilder::new("FIX.4.4", "D").field(11, id)
+    }
+
+    #[test]
+    fn test_gap_triggers_resend_and_recovery(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+
+        client.send(order("A"), now).unwrap();
+        client.on_timer(now + Duration::from_secs(10));
+        client.send(order("B"), now).unwrap();
+        let mut sent = parse_all(&mut client);
+        assert_eq!(sent.iter().map(|m| m.get_uint(tags::MSG_SEQ_NUM).unwrap()).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        // Lose the first order: the heartbeat reveals the gap and the second order is dropped.
+        sent.remove(0);
+        for message in sent {
+            assert!(server.on_message(message, now).is_empty());
+        }
+        assert!(server.is_resending());
+        assert_eq!(server.get_next_incoming(), 2);
+
+        let request = parse_all(&mut server);
+        assert_eq!(request.len(), 1);
+        assert_eq!(request[0].get_msg_type(), Some(msg_types::RESEND_REQUEST));
+        assert_eq!((request[0].get_uint(tags::BEGIN_SEQ_NO), request[0].get_uint(tags::END_SEQ_NO)), (Ok(2), Ok(0)));
+
+        // The client replays both orders and gap-fills the heartbeat.
+        client.on_message(request[0].clone(), now);
+        let replay = parse_all(&mut client);
+        let summary: Vec<_> = replay.iter().map(|m| (m.get_msg_type().unwrap(), m.get_uint(tags::MSG_SEQ_NUM).unwrap())).collect();
+        assert_eq!(summary, vec![("D", 2), (msg_types::SEQUENCE_RESET, 3), ("D", 4)]);
+        assert!(replay.iter().all(|m| m.get_bool(tags::POSS_DUP_FLAG) == Ok(true)));
+        assert!(replay[0].has_field(tags::ORIG_SENDING_TIME));
+        assert_eq!(replay[1].get_uint(tags::NEW_SEQ_NO), Ok(4));
+
+        let events: Vec<_> = replay.into_iter().flat_map(|message| server.on_message(message, now)).collect();
+        let ids: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                SessionEvent::Message(message) => message.get_field(11).unwrap(),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(ids, vec!["A", "B"]);
+        assert_eq!(server.get_next_incoming(), 5);
+        assert!(!server.is_resending());
+        assert_eq!(client.get_next_outgoing(), 5);
+    }
+
+    #[test]
+    fn test_sequence_too_low(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+        client.send(order("A"), now).unwrap();
+        let first = parse_all(&mut client).remove(0);
+        server.on_message(first.clone(), now);
+
+        // A flagged duplicate is ignored.
+        let mut duplicate = FixMessageBuilder::from_message(&first);
+        duplicate.set_field(tags::POSS_DUP_FLAG, 'Y');
+        assert!(server.on_message(duplicate.build_message(), now).is_empty());
+        assert_eq!(server.get_state(), SessionState::Active);
+
+        // An unflagged one is fatal.
+        let events = server.on_message(first, now);
+        assert_eq!(events, vec![SessionEvent::Disconnected("MsgSeqNum too low, expecting 3 but received 2".to_string())]);
+        assert_eq!(parse_all(&mut server).last().unwrap().get_msg_type(), Some(msg_types::LOGOUT));
+    }
+
+    /// Builds a message from CLIENT to SERVER with the given MsgSeqNum.
+    fn from_client(msg_type: &str, seq_num: u64) -> FixMessageBuilder {
+        FixMessageBuilder::new("FIX.4.4", msg_type)
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, seq_num)
+            .field(tags::SENDING_TIME, "20240101-12:00:00.000")
+    }
+
+    fn rejection(session: &mut Session) -> (Option<u64>, Option<u64>, Option<u64>) {
+        let sent = parse_all(session);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].get_msg_type(), Some(msg_types::REJECT));
+        (sent[0].get_uint(tags::REF_SEQ_NUM).ok(), sent[0].get_uint(tags::REF_TAG_ID).ok(), sent[0].get_uint(tags::SESSION_REJECT_REASON).ok())
+    }
+
+    #[test]
+    fn test_sequence_reset_modes(){
+        let now = Instant::now();
+        let (_, mut server) = logged_on_pair(now);
+
+        // Reset mode is applied whatever its MsgSeqNum and requests no resend.
+        let reset = from_client(msg_types::SEQUENCE_RESET, 9).field(tags::NEW_SEQ_NO, 5).build_message();
+        assert!(server.on_message(reset, now).is_empty());
+        assert_eq!(server.get_next_incoming(), 5);
+        assert!(parse_all(&mut server).is_empty());
+
+        // Moving backwards is rejected in either mode.
+        let reset = from_client(msg_types::SEQUENCE_RESET, 3).field(tags::GAP_FILL_FLAG, 'N').field(tags::NEW_SEQ_NO, 3).build_message();
+        server.on_message(reset, now);
+        assert_eq!(rejection(&mut server), (Some(3), Some(tags::NEW_SEQ_NO as u64), Some(reject_reason::VALUE_INCORRECT as u64)));
+        assert_eq!(server.get_next_incoming(), 5);
+
+        let gap_fill = from_client(msg_types::SEQUENCE_RESET, 5).field(tags::GAP_FILL_FLAG, 'Y').field(tags::NEW_SEQ_NO, 4).build_message();
+        server.on_message(gap_fill, now);
+        assert_eq!(rejection(&mut server), (Some(5), Some(tags::NEW_SEQ_NO as u64), Some(reject_reason::VALUE_INCORRECT as u64)));
+        assert_eq!(server.get_next_incoming(), 6);
+
+        // A gap fill ahead of the expected number is a gap like any other message.
+        let gap_fill = from_client(msg_types::SEQUENCE_RESET, 8).field(tags::GAP_FILL_FLAG, 'Y').field(tags::NEW_SEQ_NO, 10).build_message();
+        server.on_message(gap_fill, now);
+        assert!(server.is_resending());
+        assert_eq!(server.get_next_incoming(), 6);
+
+        // A reset past the gap closes it.
+        let reset = from_client(msg_types::SEQUENCE_RESET, 1).field(tags::NEW_SEQ_NO, 9).build_message();
+        server.on_message(reset, now);
+        assert!(!server.is_resending());
+        assert_eq!(server.get_next_incoming(), 9);
+    }
+
+    #[test]
+    fn test_reset_sequence_outbound(){
+        let now = Instant::now();
+        let (mut client, mut server) = logged_on_pair(now);
+
+        assert!(client.reset_sequence(2, now).is_err());
+        client.reset_sequence(10, now).unwrap();
+        client.send(order("A"), now).unwrap();
+        let sent = parse_all(&mut client);
+        assert_eq!(sent[0].get_msg_type(), Some(msg_types::SEQUENCE_RESET));
+        assert_eq!((sent[0].get_uint(tags::MSG_SEQ_NUM), sent[0].get_bool(tags::GAP_FILL_FLAG), sent[0].get_uint(tags::NEW_SEQ_NO)), (Ok(2), Ok(false), Ok(10)));
+        assert_eq!(sent[1].get_uint(tags::MSG_SEQ_NUM), Ok(10));
+
+        let events: Vec<_> = sent.into_iter().flat_map(|message| server.on_message(message, now)).collect();
+        assert!(matches!(&events[..], [SessionEvent::Message(message)] if message.get_field(11) == Some("A")));
+        assert_eq!(server.get_next_incoming(), 11);
+    }
+
+    #[test]
+    fn test_poss_dup_requires_orig_sending_time(){
+        let now = Instant::now();
+        let (_, mut server) = logged_on_pair(now);
+
+        let unflagged = from_client("D", 2).field(tags::POSS_DUP_FLAG, 'Y').field(11, "A").build_message();
+        assert!(server.on_message(unflagged, now).is_empty());
+        assert_eq!(rejection(&mut server), (Some(2), Some(tags::ORIG_SENDING_TIME as u64), Some(reject_reason::REQUIRED_TAG_MISSING as u64)));
+        assert_eq!(server.get_next_incoming(), 3);
+
+        let replayed = from_client("D", 3).field(tags::POSS_DUP_FLAG, 'Y').field(tags::ORIG_SENDING_TIME, "20240101-11:59:59.500").field(11, "B").build_message();
+        assert_eq!(server.on_message(replayed.clone(), now), vec![SessionEvent::Message(replayed)]);
+
+        let from_the_future = from_client("D", 4).field(tags::POSS_DUP_FLAG, 'Y').field(tags::ORIG_SENDING_TIME, "20240101-12:00:01").field(11, "C").build_message();
+        let events = server.on_message(from_the_future, now);
+        assert!(matches!(&events[..], [SessionEvent::Disconnected(_)]));
+        let sent = parse_all(&mut server);
+        assert_eq!(sent[0].get_uint(tags::SESSION_REJECT_REASON), Ok(reject_reason::SENDING_TIME_ACCURACY as u64));
+        assert_eq!(sent[1].get_msg_type(), Some(msg_types::LOGOUT));
+    }
+
+    #[test]
+    fn test_dictionary_rejects_invalid_messages(){
+        let now = Instant::now();
+        let (_, server) = logged_on_pair(now);
+        let mut server = server.with_dictionary(Arc::new(Dictionary::fix44()));
+
+        let incomplete = from_client("D", 2).field(11, "A").field(tags::SIDE, 1).field(tags::ORDER_QTY, 5).build_message();
+        assert!(server.on_message(incomplete, now).is_empty());
+        assert_eq!(rejection(&mut server), (Some(2), Some(tags::ORD_TYPE as u64), Some(reject_reason::REQUIRED_TAG_MISSING as u64)));
+
+        let garbled = from_client("D", 3).field(11, "B").field(tags::SIDE, 1).field(tags::ORDER_QTY, "ten").field(tags::ORD_TYPE, 1).build_message();
+        assert!(server.on_message(garbled, now).is_empty());
+        assert_eq!(rejection(&mut server), (Some(3), Some(tags::ORDER_QTY as u64), Some(reject_reason::INCORRECT_DATA_FORMAT as u64)));
+
+        let valid = from_client("D", 4).field(11, "C").field(tags::SIDE, 1).field(tags::ORDER_QTY, 5).field(tags::ORD_TYPE, 1).build_message();
+        assert_eq!(server.on_message(valid.clone(), now), vec![SessionEvent::Message(valid)]);
+        assert_eq!(server.get_next_incoming(), 5);
+
+        // An invalid Reject is dropped rather than rejected.
+        assert!(server.on_message(from_client(msg_types::REJECT, 5).build_message(), now).is_empty());
+        assert!(parse_all(&mut server).is_empty());
+    }
+
+    #[test]
+    fn test_format_utc_timestamp(){
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(format_utc_timestamp(time), "20240229-12:34:56.789");
+        assert_eq!(format_utc_timestamp(UNIX_EPOCH), "19700101-00:00:00.000");
+    }
+
+    #[test]
+    fn test_parse_utc_timestamp(){
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(parse_utc_timestamp(&format_utc_timestamp(time)), Some(time));
+        assert_eq!(parse_utc_timestamp("19700101-00:00:00"), Some(UNIX_EPOCH));
+        assert_eq!(parse_utc_timestamp("20240229-12:34:56.789123"), Some(time + Duration::from_micros(123)));
+        assert_eq!(parse_utc_timestamp("20240229-12:34:56.789123456"), Some(time + Duration::from_nanos(123_456)));
+        for invalid in ["", "20240229", "20241329-12:34:56", "20240229 12:34:56", "20240229-12:34:56.", "20240229-12:34:56.1234567890", "20240229-12:34:5x"] {
+            assert_eq!(parse_utc_timestamp(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_latency_from_sending_time_and_round_trips(){
+        let now = Instant::now();
+        let mut client = Session::new(config("CLIENT", "SERVER", 10), SessionRole::Initiator, now);
+        let mut server = Session::new(config("SERVER", "CLIENT", 30), SessionRole::Acceptor, now);
+        client.on_connect(now);
+        pump(&mut client, &mut server, now);
+
+        // The initiator times its Logon; the acceptor only gets a transit sample.
+        pump(&mut server, &mut client, now + Duration::from_millis(8));
+        assert_eq!(client.get_latency_metrics().get_stats().get_round_trip(), Some(Duration::from_millis(8)));
+        assert_eq!(server.get_latency_metrics().get_stats().get_samples(), 1);
+        assert_eq!(server.get_latency_metrics().get_stats().get_round_trip(), None);
+
+        // The acceptor times its TestRequest.
+        let probe_at = now + Duration::from_secs(12);
+        server.on_timer(probe_at);
+        pump(&mut server, &mut client, probe_at);
+        pump(&mut client, &mut server, probe_at + Duration::from_millis(6));
+        let stats = server.get_latency_metrics().get_stats();
+        assert_eq!((stats.get_samples(), stats.get_round_trip()), (2, Some(Duration::from_millis(6))));
+        assert!(stats.get_clock_skew().is_some());
+
+        // A counterparty an hour behind shows up in the transit.
+        let late = FixMessageBuilder::new("FIX.4.4", msg_types::HEARTBEAT)
+            .field(tags::SENDER_COMP_ID, "CLIENT")
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, server.get_next_incoming())
+            .field(tags::SENDING_TIME, format_utc_timestamp(SystemTime::now() - Duration::from_secs(3600)))
+            .build_message();
+        server.on_message(late, probe_at);
+        assert!(server.get_latency_metrics().get_stats().get_last_transit().unwrap() >= 3_600_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_run_session_over_stream(){
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let now = Instant::now();
+        let (client_commands, client_rx) = mpsc::unbounded_channel();
+        let (client_events_tx, mut client_events) = mpsc::unbounded_channel();
+        let (_server_commands, server_rx) = mpsc::unbounded_channel();
+        let (server_events_tx, mut server_events) = mpsc::unbounded_channel();
+
+        let client = Session::new(config("CLIENT", "SERVER", 5), SessionRole::Initiator, now);
+        let server = Session::new(config("SERVER", "CLIENT", 5), SessionRole::Acceptor, now);
+        let client_task = tokio::spawn(run_session(client_io, client, client_rx, client_events_tx));
+        let server_task = tokio::spawn(run_session(server_io, server, server_rx, server_events_tx));
+
+        assert_eq!(client_events.recv().await, Some(SessionEvent::LoggedOn));
+        assert_eq!(server_events.recv().await, Some(SessionEvent::LoggedOn));
+
+        client_commands.send(SessionCommand::Send(FixMessageBuilder::new("FIX.4.4", "D").field(11, "ORD-1"))).unwrap();
+        match server_events.recv().await {
+            Some(SessionEvent::Message(message)) => assert_eq!(message.get_field(11), Some("ORD-1")),
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        drop(client_commands);
+        assert!(matches!(server_events.recv().await, Some(SessionEvent::Disconnected(_))));
+        assert!(matches!(client_events.recv().await, Some(SessionEvent::Disconnected(_))));
+        client_task.await.unwrap().unwrap();
+        server_task.await.unwrap().unwrap();
+    }
+}
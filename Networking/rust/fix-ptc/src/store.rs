@@ -0,0 +1,247 @@
+//! # Store Module
+//!
+//! Outbound message storage used to answer ResendRequests.
+//!
+//! A [`Session`](crate::session::Session) hands every message it sends to its store, keyed by
+//! MsgSeqNum. When the peer asks for a range again the session reads the stored messages back
+//! and either replays them or replaces them with a gap fill.
+//!
+//! The store also keeps the next MsgSeqNum in each direction, so a session created with it
+//! carries on where the last one stopped.
+//!
+//! [`MemoryStore`] forgets everything when the process exits. [`FileStore`] also appends each
+//! message to a file, one `<MsgSeqNum> <message>` line per message, keeps the sequence numbers
+//! in a `.seqnums` file next to it and reloads both when opened.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Storage for sent messages, keyed by MsgSeqNum, and for the sequence numbers of a session.
+pub trait MessageStore: Send {
+    /// Stores the serialized message sent with `seq_num`; the next one to send is `seq_num + 1`.
+    ///
+    /// # Errors
+    /// Returns an error if the message cannot be persisted.
+    fn add(&mut self, seq_num: u64, message: &[u8]) -> io::Result<()>;
+
+    /// Returns the stored messages with `begin <= seq_num <= end`, in sequence order.
+    fn get_range(&self, begin: u64, end: u64) -> Vec<(u64, Vec<u8>)>;
+
+    /// Forgets every stored message and restarts both directions at MsgSeqNum 1 (used when
+    /// sequence numbers are reset).
+    ///
+    /// # Errors
+    /// Returns an error if the persisted messages or sequence numbers cannot be cleared.
+    fn reset(&mut self) -> io::Result<()>;
+
+    /// Returns the MsgSeqNum of the next message to send.
+    fn get_next_sender_seq(&self) -> u64;
+
+    /// Returns the MsgSeqNum expected on the next message received.
+    fn get_next_target_seq(&self) -> u64;
+
+    /// Sets the MsgSeqNum of the next message to send, e.g. after a SequenceReset.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence number cannot be persisted.
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()>;
+
+    /// Sets the MsgSeqNum expected on the next message received.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence number cannot be persisted.
+    fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()>;
+}
+
+/// A [`MessageStore`] that keeps messages in memory for the lifetime of the session.
+#[derive(Clone, Debug)]
+pub struct MemoryStore {
+    messages: BTreeMap<u64, Vec<u8>>,
+    next_sender_seq: u64,
+    next_target_seq: u64,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self { messages: BTreeMap::new(), next_sender_seq: 1, next_target_seq: 1 }
+    }
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MessageStore for MemoryStore {
+    fn add(&mut self, seq_num: u64, message: &[u8]) -> io::Result<()> {
+        self.messages.insert(seq_num, message.to_vec());
+        self.next_sender_seq = seq_num + 1;
+        Ok(())
+    }
+
+    fn get_range(&self, begin: u64, end: u64) -> Vec<(u64, Vec<u8>)> {
+        if begin > end {
+            return Vec::new();
+        }
+        self.messages.range(begin..=end).map(|(seq_num, message)| (*seq_num, message.clone())).collect()
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        *self = Self::default();
+        Ok(())
+    }
+
+    fn get_next_sender_seq(&self) -> u64 {
+        self.next_sender_seq
+    }
+
+    fn get_next_target_seq(&self) -> u64 {
+        self.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_sender_seq = seq_num;
+        Ok(())
+    }
+
+    fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_target_seq = seq_num;
+        Ok(())
+    }
+}
+
+/// A [`MessageStore`] backed by files, so sent messages and sequence numbers survive a restart.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    seq_nums_path: PathBuf,
+    file: File,
+    messages: MemoryStore,
+}
+
+impl FileStore {
+    /// Opens (or creates) the store at `path` and loads the messages and sequence numbers
+    /// already in it.
+    ///
+    /// # Errors
+    /// Returns an error if a file cannot be opened or read, or holds a malformed line.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let malformed = |path: &Path| io::Error::new(io::ErrorKind::InvalidData, format!("Malformed line in {}", path.display()));
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        let mut messages = MemoryStore::new();
+        for line in contents.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()) {
+            let space = line.iter().position(|byte| *byte == b' ').ok_or_else(|| malformed(&path))?;
+            let seq_num = std::str::from_utf8(&line[..space]).ok().and_then(|seq_num| seq_num.parse().ok()).ok_or_else(|| malformed(&path))?;
+            messages.add(seq_num, &line[space + 1..])?;
+        }
+
+        let mut seq_nums_path = path.clone().into_os_string();
+        seq_nums_path.push(".seqnums");
+        let seq_nums_path = PathBuf::from(seq_nums_path);
+        match fs::read_to_string(&seq_nums_path) {
+            Ok(contents) => {
+                let mut seq_nums = contents.split_whitespace().map(str::parse::<u64>);
+                let (Some(Ok(sender)), Some(Ok(target)), None) = (seq_nums.next(), seq_nums.next(), seq_nums.next()) else {
+                    return Err(malformed(&seq_nums_path));
+                };
+                // Messages appended after the sequence numbers were last written are newer.
+                messages.next_sender_seq = messages.next_sender_seq.max(sender);
+                messages.next_target_seq = target;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(Self { path, seq_nums_path, file, messages })
+    }
+
+    /// Returns the path of the backing file.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes both sequence numbers to the `.seqnums` file.
+    fn write_seq_nums(&self) -> io::Result<()> {
+        fs::write(&self.seq_nums_path, format!("{} {}\n", self.messages.next_sender_seq, self.messages.next_target_seq))
+    }
+}
+
+impl MessageStore for FileStore {
+    fn add(&mut self, seq_num: u64, message: &[u8]) -> io::Result<()> {
+        let mut line = format!("{} ", seq_num).into_bytes();
+        line.extend_from_slice(message);
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.messages.add(seq_num, message)
+    }
+
+    fn get_range(&self, begin: u64, end: u64) -> Vec<(u64, Vec<u8>)> {
+        self.messages.get_range(begin, end)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.messages.reset()?;
+        self.write_seq_nums()
+    }
+
+    fn get_next_sender_seq(&self) -> u64 {
+        self.messages.next_sender_seq
+    }
+
+    fn get_next_target_seq(&self) -> u64 {
+        self.messages.next_target_seq
+    }
+
+    fn set_next_sender_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.messages.next_sender_seq = seq_num;
+        self.write_seq_nums()
+    }
+
+    fn set_next_target_seq(&mut self, seq_num: u64) -> io::Result<()> {
+        self.messages.next_target_seq = seq_num;
+        self.write_seq_nums()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_store_reloads_messages(){
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.log");
+
+        let mut store = FileStore::open(&path).unwrap();
+        assert_eq!((store.get_next_sender_seq(), store.get_next_target_seq()), (1, 1));
+        store.add(1, b"8=FIX.4.4\x0135=A\x01").unwrap();
+        store.set_next_target_seq(4).unwrap();
+        store.add(2, b"8=FIX.4.4\x0135=0\x01").unwrap();
+        drop(store);
+
+        let mut store = FileStore::open(&path).unwrap();
+        assert_eq!(store.get_range(2, 5), vec![(2, b"8=FIX.4.4\x0135=0\x01".to_vec())]);
+        assert_eq!((store.get_next_sender_seq(), store.get_next_target_seq()), (3, 4));
+        store.reset().unwrap();
+        store.add(1, b"8=FIX.4.4\x0135=5\x01").unwrap();
+        drop(store);
+
+        let mut store = FileStore::open(&path).unwrap();
+        assert_eq!(store.get_range(1, 5).len(), 1);
+        assert_eq!((store.get_next_sender_seq(), store.get_next_target_seq()), (2, 1));
+        store.set_next_sender_seq(10).unwrap();
+        drop(store);
+
+        assert_eq!(FileStore::open(&path).unwrap().get_next_sender_seq(), 10);
+        fs::write(dir.path().join("store.log.seqnums"), "10\n").unwrap();
+        assert!(FileStore::open(&path).is_err());
+    }
+}
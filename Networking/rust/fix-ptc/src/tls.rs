@@ -0,0 +1,227 @@
+//! # TLS Module
+//!
+//! Optional TLS for [`fix_client`](crate::fix::fix_client) and
+//! [`fix_server`](crate::fix::fix_server), configured per session with the `tls_*` settings of
+//! [`SessionSettings`]. Certificates, keys and CAs are read from PEM files.
+//!
+//! - An initiator whose session sets `tls_ca` connects over TLS and only accepts an acceptor
+//!   certificate issued by that CA for the session's `host`. If the session also sets
+//!   `tls_cert` and `tls_key`, it presents them as its client certificate.
+//! - An acceptor whose sessions set `tls_cert` and `tls_key` only accepts TLS connections.
+//!   The sessions of a server share its listener, so they must all name the same certificate
+//!   and key. Client certificates are requested if any session sets `tls_ca`.
+//! - Once the Logon names the session, the connection is closed if the session sets
+//!   `tls_require_client_cert` and the client presented no certificate, or if the client's
+//!   certificate was not issued by the session's `tls_ca` or does not name the counterparty's
+//!   CompID (the session's `target_comp_id`) as a DNS subjectAltName.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+use crate::config::{ConfigError, SessionSettings};
+
+/// The connection a session runs over, plain TCP or TLS.
+pub trait SessionStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SessionStream for T {}
+
+/// The TLS side of an initiator.
+#[derive(Clone)]
+pub struct TlsClient {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl TlsClient {
+    /// Loads the TLS settings of an initiator, or returns `None` if `settings` has no `tls_ca`.
+    ///
+    /// # Errors
+    /// Returns an error if a PEM file cannot be read or holds no usable certificate or key, or
+    /// if `host` cannot be checked against a certificate.
+    pub fn from_settings(settings: &SessionSettings) -> Result<Option<Self>, ConfigError> {
+        let Some(ca) = &settings.tls_ca else {
+            return Ok(None);
+        };
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid)?
+            .with_root_certificates(load_roots(ca)?);
+        let config = match (&settings.tls_cert, &settings.tls_key) {
+            (Some(cert), Some(key)) => builder.with_client_auth_cert(load_certificates(cert)?, load_key(key)?).map_err(invalid)?,
+            _ => builder.with_no_client_auth(),
+        };
+        let server_name = ServerName::try_from(settings.host.clone())
+            .map_err(|_| ConfigError::Invalid(format!("Session {}->{}: host {} is not a TLS server name", settings.sender_comp_id, settings.target_comp_id, settings.host)))?;
+        Ok(Some(Self { connector: TlsConnector::from(Arc::new(config)), server_name }))
+    }
+
+    /// Performs the TLS handshake over a new connection.
+    ///
+    /// # Errors
+    /// Returns an error if the handshake fails, including when the acceptor's certificate is
+    /// not trusted.
+    pub async fn connect(&self, stream: TcpStream) -> io::Result<client::TlsStream<TcpStream>> {
+        self.connector.connect(self.server_name.clone(), stream).await
+    }
+}
+
+/// The TLS side of an acceptor: its certificate and the CAs of its sessions.
+pub struct TlsServer {
+    acceptor: TlsAcceptor,
+    roots: HashMap<PathBuf, RootCertStore>,
+}
+
+impl TlsServer {
+    /// Loads the TLS settings shared by the sessions of one listener, or returns `None` if
+    /// they do not use TLS.
+    ///
+    /// # Errors
+    /// Returns an error if only some sessions set `tls_cert`, if they name different
+    /// certificates or keys, or if a PEM file cannot be read or holds nothing usable.
+    pub fn from_sessions(sessions: &[SessionSettings]) -> Result<Option<Self>, ConfigError> {
+        let Some((cert, key)) = sessions.iter().find_map(|settings| settings.tls_cert.as_ref().zip(settings.tls_key.as_ref())) else {
+            return match sessions.iter().find(|settings| settings.tls_ca.is_some()) {
+                Some(settings) => Err(ConfigError::Invalid(format!("Session {}->{}: an acceptor needs tls_cert and tls_key to use tls_ca", settings.sender_comp_id, settings.target_comp_id))),
+                None => Ok(None),
+            };
+        };
+        if let Some(other) = sessions.iter().find(|settings| settings.tls_cert.as_ref() != Some(cert) || settings.tls_key.as_ref() != Some(key)) {
+            return Err(ConfigError::Invalid(format!(
+                "Session {}->{}: sessions sharing a listener must use the same tls_cert and tls_key",
+                other.sender_comp_id, other.target_comp_id
+            )));
+        }
+
+        let mut roots = HashMap::new();
+        let mut all_roots = RootCertStore::empty();
+        for ca in sessions.iter().filter_map(|settings| settings.tls_ca.as_ref()) {
+            if !roots.contains_key(ca) {
+                let store = load_roots(ca)?;
+                all_roots.roots.extend(store.roots.iter().cloned());
+                roots.insert(ca.clone(), store);
+            }
+        }
+        let builder = ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions().map_err(invalid)?;
+        let builder = if all_roots.is_empty() {
+            builder.with_no_client_auth()
+        } else {
+            // Which session a client belongs to, and so whether it needs a certificate, is
+            // only known from its Logon; check_client_certificate enforces the rest.
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(all_roots), provider())
+                .allow_unauthenticated()
+                .build()
+                .map_err(invalid)?;
+            builder.with_client_cert_verifier(verifier)
+        };
+        let config = builder.with_single_cert(load_certificates(cert)?, load_key(key)?).map_err(invalid)?;
+        Ok(Some(Self { acceptor: TlsAcceptor::from(Arc::new(config)), roots }))
+    }
+
+    /// Performs the TLS handshake over an accepted connection.
+    ///
+    /// # Errors
+    /// Returns an error if the handshake fails, including when a client certificate is not
+    /// issued by any session's CA.
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<server::TlsStream<TcpStream>> {
+        self.acceptor.accept(stream).await
+    }
+
+    /// Checks the certificate chain a client presented, if any, against the session its Logon
+    /// named.
+    ///
+    /// # Errors
+    /// Returns an error describing why the client may not use the session.
+    pub fn check_client_certificate(&self, settings: &SessionSettings, certificates: Option<&[CertificateDer<'_>]>) -> Result<(), String> {
+        let name = format!("{}->{}", settings.sender_comp_id, settings.target_comp_id);
+        let Some((end_entity, intermediates)) = certificates.and_then(<[_]>::split_first) else {
+            if settings.tls_require_client_cert {
+                return Err(format!("Session {} requires a client certificate", name));
+            }
+            return Ok(());
+        };
+        let roots = settings
+            .tls_ca
+            .as_ref()
+            .and_then(|ca| self.roots.get(ca))
+            .ok_or_else(|| format!("Session {} has no tls_ca to check the client certificate against", name))?;
+        let certificate = webpki::EndEntityCert::try_from(end_entity).map_err(|err| format!("Invalid client certificate: {}", err))?;
+        certificate
+            .verify_for_usage(webpki::ALL_VERIFICATION_ALGS, &roots.roots, intermediates, UnixTime::now(), webpki::KeyUsage::client_auth(), None, None)
+            .map_err(|err| format!("Client certificate is not valid for session {}: {}", name, err))?;
+        let comp_id = ServerName::try_from(settings.target_comp_id.as_str())
+            .map_err(|_| format!("CompID {} cannot be matched against a certificate", settings.target_comp_id))?;
+        certificate
+            .verify_is_valid_for_subject_name(&comp_id)
+            .map_err(|_| format!("Client certificate does not name {}", settings.target_comp_id))
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn invalid(err: impl std::fmt::Display) -> ConfigError {
+    ConfigError::Invalid(format!("TLS: {}", err))
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, ConfigError> {
+    File::open(path).map(BufReader::new).map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, ConfigError> {
+    let certificates = rustls_pemfile::certs(&mut open(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })?;
+    if certificates.is_empty() {
+        return Err(ConfigError::Invalid(format!("No certificate in {}", path.display())));
+    }
+    Ok(certificates)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, ConfigError> {
+    rustls_pemfile::private_key(&mut open(path)?)
+        .map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })?
+        .ok_or_else(|| ConfigError::Invalid(format!("No private key in {}", path.display())))
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore, ConfigError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in load_certificates(path)? {
+        roots.add(certificate).map_err(|err| ConfigError::Invalid(format!("Invalid CA certificate in {}: {}", path.display(), err)))?;
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    fn error(contents: &str) -> String {
+        let config: Config = contents.parse().unwrap();
+        TlsServer::from_sessions(config.get_sessions()).err().map(|err| err.to_string()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_listener_sessions_share_tls_settings(){
+        let session = |target_comp_id: &str, tls: &str| format!("[[session]]\nsender_comp_id = \"SERVER\"\ntarget_comp_id = \"{}\"\nport = 1\n{}\n", target_comp_id, tls);
+        let plain: Config = (session("A", "") + &session("B", "")).parse().unwrap();
+        assert!(TlsServer::from_sessions(plain.get_sessions()).unwrap().is_none());
+
+        let tls = "tls_cert = \"server.pem\"\ntls_key = \"server.key\"";
+        assert!(error(&(session("A", tls) + &session("B", ""))).contains("SERVER->B: sessions sharing a listener"));
+        let other = "tls_cert = \"other.pem\"\ntls_key = \"server.key\"";
+        assert!(error(&(session("A", tls) + &session("B", other))).contains("same tls_cert and tls_key"));
+        assert!(error(&session("A", "tls_ca = \"ca.pem\"")).contains("needs tls_cert and tls_key"));
+        assert!(error(&session("A", tls)).contains("Cannot read server.pem"));
+    }
+}
@@ -0,0 +1,147 @@
+//! # Wire Log Module
+//!
+//! Per-session log of every FIX message sent and received, kept apart from application
+//! logging.
+//!
+//! Each line holds the UTC time, the direction and the raw message with SOH rendered as `|`:
+//!
+//! ```text
+//! 20240101-12:00:00.000 IN  8=FIX.4.4|9=65|35=A|49=CLIENT|56=SERVER|34=1|...|10=062|
+//! 20240101-12:00:00.002 OUT 8=FIX.4.4|9=65|35=A|49=SERVER|56=CLIENT|34=1|...|10=061|
+//! ```
+//!
+//! The log is written to `<dir>/<SenderCompID>-<TargetCompID>.log`. When a line would take the
+//! file past its size limit, the file is rotated: `.log` becomes `.log.1`, `.log.1` becomes
+//! `.log.2` and so on, and the oldest file beyond the configured count is deleted.
+//!
+//! [`WireLog::record`] returns rotation and write errors; a session ends its connection on
+//! them, as it does when its audit log fails.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::fix::SOH;
+use crate::session::format_utc_timestamp;
+
+/// Which way a logged message travelled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A size-rotated wire log for one session.
+#[derive(Debug)]
+pub struct WireLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl WireLog {
+    /// Opens (appending to) the log of `session_id` in `dir`, creating the directory if needed.
+    ///
+    /// `max_files` counts the rotated files kept besides the current one.
+    ///
+    /// # Errors
+    /// Returns an error if the directory or file cannot be created.
+    pub fn open(dir: impl AsRef<Path>, session_id: &str, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(format!("{}.log", session_id));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size, max_bytes, max_files })
+    }
+
+    /// Returns the path of the current log file.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one message, rotating the file first if it would grow past its limit.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be rotated or written.
+    pub fn record(&mut self, direction: Direction, message: &[u8]) -> io::Result<()> {
+        let label = match direction {
+            Direction::Inbound => "IN ",
+            Direction::Outbound => "OUT",
+        };
+        let line = format!("{} {} {}\n", format_utc_timestamp(SystemTime::now()), label, render(message));
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |index: usize| PathBuf::from(format!("{}.{}", self.path.display(), index));
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else {
+            let _ = fs::remove_file(rotated(self.max_files));
+            for index in (1..self.max_files).rev() {
+                if rotated(index).exists() {
+                    fs::rename(rotated(index), rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Renders a raw message for display, with SOH shown as `|`.
+pub fn render(message: &[u8]) -> String {
+    String::from_utf8_lossy(message).replace(SOH as char, "|")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render(){
+        assert_eq!(render(b"8=FIX.4.4\x0135=0\x01"), "8=FIX.4.4|35=0|");
+    }
+
+    #[test]
+    fn test_rotation(){
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let message = b"8=FIX.4.4\x019=5\x0135=0\x0110=000\x01";
+
+        // Each line is ~62 bytes, so every file holds two lines.
+        let mut log = WireLog::open(dir, "SERVER-CLIENT", 130, 2).unwrap();
+        for index in 0..7 {
+            let direction = if index % 2 == 0 { Direction::Inbound } else { Direction::Outbound };
+            log.record(direction, message).unwrap();
+        }
+
+        let current = fs::read_to_string(log.get_path()).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains(" IN  8=FIX.4.4|9=5|35=0|10=000|"));
+        let first_rotation = fs::read_to_string(dir.join("SERVER-CLIENT.log.1")).unwrap();
+        assert_eq!(first_rotation.lines().count(), 2);
+        assert!(first_rotation.lines().next().unwrap().contains(" IN  "));
+        assert!(first_rotation.lines().nth(1).unwrap().contains(" OUT "));
+        assert!(dir.join("SERVER-CLIENT.log.2").exists());
+        assert!(!dir.join("SERVER-CLIENT.log.3").exists());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_record_reports_write_errors(){
+        let temp = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink("/dev/full", temp.path().join("SERVER-CLIENT.log")).unwrap();
+        let mut log = WireLog::open(temp.path(), "SERVER-CLIENT", 1_000, 1).unwrap();
+        assert!(log.record(Direction::Inbound, b"8=FIX.4.4\x0135=0\x01").is_err());
+    }
+}
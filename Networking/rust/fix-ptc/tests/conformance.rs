@@ -0,0 +1,406 @@
+//! # Conformance Tests
+//!
+//! Runs the acceptor against a scripted counterparty. Each test starts a [`fix_server`] with an
+//! [`OrderBridge`] behind it on an ephemeral port, connects a [`Counterparty`] over TCP and
+//! plays one scenario message by message, asserting on every message the server sends back.
+//!
+//! The counterparty writes raw wire messages with whatever MsgSeqNum the scenario asks for, so
+//! gaps, duplicates and resends can be scripted exactly. It checks that every inbound message
+//! carries the next expected MsgSeqNum, except possible duplicates, which must carry
+//! PossDupFlag (43) and OrigSendingTime (122).
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+use fix_ptc::bridge::OrderBridge;
+use fix_ptc::config::Config;
+use fix_ptc::fix::{fix_server, tags, FixMessage, FixMessageBuilder, SessionHandle, SessionHandler, SessionTable, Tag};
+use fix_ptc::session::{format_utc_timestamp, msg_types};
+
+/// How long the counterparty waits for the next message before failing the scenario.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The acceptor under test: routes every order to one shared book, as the `server` binary does.
+struct Exchange {
+    sessions: SessionTable,
+    bridge: Mutex<OrderBridge>,
+}
+
+impl SessionHandler for Exchange {
+    fn on_message(&self, session: &SessionHandle, message: FixMessage) {
+        let reports = self.bridge.lock().unwrap().on_message(session.get_target_comp_id(), &message);
+        for report in reports {
+            if let Some(owner) = self.sessions.get_by_target(report.get_owner()) {
+                let _ = owner.send(report.to_builder(&owner.get_config().begin_string));
+            }
+        }
+    }
+}
+
+/// Starts an acceptor for the sessions SERVER->CLIENT and SERVER->OTHER, returning its address.
+async fn start_server() -> (String, fix_server) {
+    let config: Config = "
+        [[session]]
+        sender_comp_id = \"SERVER\"
+        target_comp_id = \"CLIENT\"
+        port = 1
+        validate = true
+        [[session]]
+        sender_comp_id = \"SERVER\"
+        target_comp_id = \"OTHER\"
+        port = 1
+    "
+    .parse()
+    .unwrap();
+    let server = fix_server::from_config(&config).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let exchange = Exchange { sessions: server.get_session_table(), bridge: Mutex::new(OrderBridge::new().with_symbol("ACME")) };
+    let serving = server.clone();
+    tokio::spawn(async move { serving.serve(listener, exchange).await.unwrap() });
+    (addr, server)
+}
+
+/// The scripted side of a session.
+struct Counterparty {
+    stream: TcpStream,
+    received: Vec<u8>,
+    sender_comp_id: String,
+    /// MsgSeqNum of the next message sent by [`Counterparty::send`].
+    next_outgoing: u64,
+    /// MsgSeqNum the server's next message must carry.
+    next_incoming: u64,
+}
+
+impl Counterparty {
+    async fn connect(addr: &str, sender_comp_id: &str) -> Self {
+        Self {
+            stream: TcpStream::connect(addr).await.unwrap(),
+            received: Vec::new(),
+            sender_comp_id: sender_comp_id.to_string(),
+            next_outgoing: 1,
+            next_incoming: 1,
+        }
+    }
+
+    /// Connects as CLIENT and completes the Logon exchange with HeartBtInt `heartbeat`.
+    async fn logged_on(addr: &str, heartbeat: u64) -> Self {
+        let mut counterparty = Self::connect(addr, "CLIENT").await;
+        counterparty.send(msg_types::LOGON, &[(tags::ENCRYPT_METHOD, "0"), (tags::HEART_BT_INT, &heartbeat.to_string())]).await;
+        counterparty.expect(msg_types::LOGON, &[(tags::ENCRYPT_METHOD, "0"), (tags::HEART_BT_INT, &heartbeat.to_string())]).await;
+        counterparty
+    }
+
+    /// Returns a message of type `msg_type` with the session header and `fields`.
+    fn message(&self, msg_type: &str, seq_num: u64, fields: &[(Tag, &str)]) -> FixMessageBuilder {
+        let mut builder = FixMessageBuilder::new("FIX.4.4", msg_type)
+            .field(tags::SENDER_COMP_ID, &self.sender_comp_id)
+            .field(tags::TARGET_COMP_ID, "SERVER")
+            .field(tags::MSG_SEQ_NUM, seq_num)
+            .field(tags::SENDING_TIME, format_utc_timestamp(SystemTime::now()));
+        for (tag, value) in fields {
+            builder = builder.field(*tag, *value);
+        }
+        builder
+    }
+
+    /// Sends the next message in sequence.
+    async fn send(&mut self, msg_type: &str, fields: &[(Tag, &str)]) {
+        let builder = self.message(msg_type, self.next_outgoing, fields);
+        self.next_outgoing += 1;
+        self.send_raw(builder).await;
+    }
+
+    /// Sends `builder` as is, without touching the outgoing sequence.
+    async fn send_raw(&mut self, builder: FixMessageBuilder) {
+        self.stream.write_all(&builder.build().unwrap()).await.unwrap();
+    }
+
+    /// Reads the next message, or `None` once the server has closed the connection.
+    async fn read(&mut self) -> Option<FixMessage> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some((message, consumed)) = FixMessage::decode(&self.received).unwrap() {
+                self.received.drain(..consumed);
+                return Some(message);
+            }
+            let read = time::timeout(TIMEOUT, self.stream.read(&mut chunk)).await.expect("no message from the server");
+            match read {
+                Ok(0) | Err(_) => return None,
+                Ok(read) => self.received.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    /// Reads the next message and checks its type, header, sequence number and `fields`.
+    async fn expect(&mut self, msg_type: &str, fields: &[(Tag, &str)]) -> FixMessage {
+        let message = self.read().await.unwrap_or_else(|| panic!("connection closed, expected MsgType {}", msg_type));
+        assert_eq!(message.get_msg_type(), Some(msg_type), "{:?}", message);
+        assert_eq!(message.get_field(tags::SENDER_COMP_ID), Some("SERVER"));
+        assert_eq!(message.get_field(tags::TARGET_COMP_ID), Some(self.sender_comp_id.as_str()));
+        assert!(message.has_field(tags::SENDING_TIME));
+        let seq_num = message.get_uint(tags::MSG_SEQ_NUM).unwrap();
+        if message.get_bool(tags::POSS_DUP_FLAG) == Ok(true) {
+            assert!(message.has_field(tags::ORIG_SENDING_TIME), "{:?}", message);
+            assert!(seq_num < self.next_incoming, "possible duplicate {} not below {}", seq_num, self.next_incoming);
+        } else {
+            assert_eq!(seq_num, self.next_incoming, "{:?}", message);
+            self.next_incoming += 1;
+        }
+        for (tag, value) in fields {
+            assert_eq!(message.get_field(*tag), Some(*value), "field {} of {:?}", tag, message);
+        }
+        message
+    }
+
+    /// Checks that the server closes the connection without sending anything else.
+    async fn expect_closed(&mut self) {
+        if let Some(message) = self.read().await {
+            panic!("expected the connection to close, received {:?}", message);
+        }
+    }
+
+    /// Logs out and waits for the server's confirmation.
+    async fn logout(&mut self) {
+        self.send(msg_types::LOGOUT, &[]).await;
+        self.expect(msg_types::LOGOUT, &[]).await;
+        self.expect_closed().await;
+    }
+}
+
+/// Fields of a limit NewOrderSingle for ACME.
+fn new_order<'a>(cl_ord_id: &'a str, side: &'a str, quantity: &'a str, price: &'a str) -> [(Tag, &'a str); 7] {
+    [
+        (tags::CL_ORD_ID, cl_ord_id),
+        (tags::SYMBOL, "ACME"),
+        (tags::SIDE, side),
+        (tags::ORDER_QTY, quantity),
+        (tags::ORD_TYPE, "2"),
+        (tags::PRICE, price),
+        (tags::TIME_IN_FORCE, "1"),
+    ]
+}
+
+#[tokio::test]
+async fn test_logon_and_logout(){
+    let (addr, server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+    assert!(server.is_active("SERVER", "CLIENT"));
+
+    client.logout().await;
+    time::sleep(Duration::from_millis(50)).await;
+    assert!(!server.is_active("SERVER", "CLIENT"));
+}
+
+#[tokio::test]
+async fn test_logon_refused(){
+    let (addr, _server) = start_server().await;
+
+    // Unknown CompIDs are dropped without a reply.
+    let mut stranger = Counterparty::connect(&addr, "STRANGER").await;
+    stranger.send(msg_types::LOGON, &[(tags::ENCRYPT_METHOD, "0"), (tags::HEART_BT_INT, "30")]).await;
+    stranger.expect_closed().await;
+
+    // So is a first message that is not a Logon.
+    let mut eager = Counterparty::connect(&addr, "CLIENT").await;
+    eager.send("D", &new_order("1", "1", "10", "100")).await;
+    eager.expect_closed().await;
+
+    // A Logon without HeartBtInt is not accepted.
+    let mut careless = Counterparty::connect(&addr, "CLIENT").await;
+    careless.send(msg_types::LOGON, &[(tags::ENCRYPT_METHOD, "0")]).await;
+    careless.expect_closed().await;
+
+    // A second connection for a logged-on session is dropped and leaves the first alone.
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+    let mut duplicate = Counterparty::connect(&addr, "CLIENT").await;
+    duplicate.send(msg_types::LOGON, &[(tags::ENCRYPT_METHOD, "0"), (tags::HEART_BT_INT, "30")]).await;
+    duplicate.expect_closed().await;
+    client.logout().await;
+}
+
+#[tokio::test]
+async fn test_test_request_and_heartbeats(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 1).await;
+
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "PING")]).await;
+    client.expect(msg_types::HEARTBEAT, &[(tags::TEST_REQ_ID, "PING")]).await;
+
+    // Left idle, the server heartbeats every second and probes the silent counterparty.
+    let mut heartbeats = 0;
+    let test_request = loop {
+        let message = client.read().await.expect("connection closed");
+        client.next_incoming += 1;
+        match message.get_msg_type() {
+            Some(msg_types::HEARTBEAT) => heartbeats += 1,
+            Some(msg_types::TEST_REQUEST) => break message,
+            other => panic!("unexpected MsgType {:?}", other),
+        }
+    };
+    assert!(heartbeats >= 1);
+    let id = test_request.get_field(tags::TEST_REQ_ID).unwrap().to_string();
+    client.send(msg_types::HEARTBEAT, &[(tags::TEST_REQ_ID, &id)]).await;
+
+    // The answered probe keeps the session up.
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "AGAIN")]).await;
+    let answer = loop {
+        let message = client.read().await.expect("connection closed");
+        client.next_incoming += 1;
+        if message.get_field(tags::TEST_REQ_ID) == Some("AGAIN") {
+            break message;
+        }
+    };
+    assert_eq!(answer.get_msg_type(), Some(msg_types::HEARTBEAT));
+}
+
+#[tokio::test]
+async fn test_gap_is_recovered_with_resend_and_gap_fill(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+
+    // MsgSeqNum 2 and 3 are lost; 4 opens the gap and is not processed.
+    client.next_outgoing = 4;
+    client.send("D", &new_order("LOST", "1", "10", "100")).await;
+    client.expect(msg_types::RESEND_REQUEST, &[(tags::BEGIN_SEQ_NO, "2"), (tags::END_SEQ_NO, "0")]).await;
+
+    // 2 was a heartbeat, so it is gap-filled; 3 and 4 are resent.
+    let original = format_utc_timestamp(SystemTime::now());
+    let resent = [(tags::POSS_DUP_FLAG, "Y"), (tags::ORIG_SENDING_TIME, original.as_str())];
+    client.send_raw(client.message(msg_types::SEQUENCE_RESET, 2, &resent).field(tags::GAP_FILL_FLAG, 'Y').field(tags::NEW_SEQ_NO, 3)).await;
+    client.send_raw(client.message("D", 3, &[&resent[..], &new_order("1", "1", "10", "100")].concat())).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "1"), (tags::EXEC_TYPE, "0"), (tags::ORD_STATUS, "0")]).await;
+    client.send_raw(client.message("D", 4, &[&resent[..], &new_order("2", "1", "5", "99")].concat())).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "2"), (tags::EXEC_TYPE, "0")]).await;
+
+    // Back in sequence, and a duplicate of something already processed is ignored.
+    client.next_outgoing = 5;
+    client.send_raw(client.message("D", 3, &[&resent[..], &new_order("1", "1", "10", "100")].concat())).await;
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "SYNC")]).await;
+    client.expect(msg_types::HEARTBEAT, &[(tags::TEST_REQ_ID, "SYNC")]).await;
+    client.logout().await;
+}
+
+#[tokio::test]
+async fn test_resend_request_replays_application_messages(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+
+    client.send("D", &new_order("1", "1", "10", "100")).await;
+    let report = client.expect("8", &[(tags::CL_ORD_ID, "1"), (tags::EXEC_TYPE, "0")]).await;
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "PING")]).await;
+    client.expect(msg_types::HEARTBEAT, &[(tags::TEST_REQ_ID, "PING")]).await;
+
+    // The Logon and Heartbeat are gap-filled around the replayed ExecutionReport.
+    client.send(msg_types::RESEND_REQUEST, &[(tags::BEGIN_SEQ_NO, "1"), (tags::END_SEQ_NO, "0")]).await;
+    client.expect(msg_types::SEQUENCE_RESET, &[(tags::MSG_SEQ_NUM, "1"), (tags::GAP_FILL_FLAG, "Y"), (tags::NEW_SEQ_NO, "2")]).await;
+    let replayed = client.expect("8", &[(tags::MSG_SEQ_NUM, "2"), (tags::CL_ORD_ID, "1")]).await;
+    assert_eq!(replayed.get_field(tags::ORIG_SENDING_TIME), report.get_field(tags::SENDING_TIME));
+    assert_eq!(replayed.get_field(tags::EXEC_ID), report.get_field(tags::EXEC_ID));
+    client.expect(msg_types::SEQUENCE_RESET, &[(tags::MSG_SEQ_NUM, "3"), (tags::GAP_FILL_FLAG, "Y"), (tags::NEW_SEQ_NO, "4")]).await;
+
+    // The replay does not consume sequence numbers.
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "AFTER")]).await;
+    client.expect(msg_types::HEARTBEAT, &[(tags::MSG_SEQ_NUM, "4"), (tags::TEST_REQ_ID, "AFTER")]).await;
+    client.logout().await;
+}
+
+#[tokio::test]
+async fn test_sequence_errors(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+
+    // A SequenceReset-GapFill that moves backwards is rejected.
+    client.send_raw(client.message(msg_types::SEQUENCE_RESET, 2, &[(tags::GAP_FILL_FLAG, "Y"), (tags::NEW_SEQ_NO, "1")])).await;
+    client.expect(msg_types::REJECT, &[(tags::REF_SEQ_NUM, "2"), (tags::REF_TAG_ID, "36"), (tags::SESSION_REJECT_REASON, "5")]).await;
+
+    // A SequenceReset-Reset moves the expected sequence number forward whatever its own.
+    client.send_raw(client.message(msg_types::SEQUENCE_RESET, 99, &[(tags::NEW_SEQ_NO, "10")])).await;
+    client.next_outgoing = 10;
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "PING")]).await;
+    client.expect(msg_types::HEARTBEAT, &[(tags::TEST_REQ_ID, "PING")]).await;
+
+    // A MsgSeqNum lower than expected without PossDupFlag ends the session.
+    client.next_outgoing = 5;
+    client.send(msg_types::HEARTBEAT, &[]).await;
+    let logout = client.expect(msg_types::LOGOUT, &[]).await;
+    assert!(logout.get_field(tags::TEXT).unwrap().contains("MsgSeqNum too low"), "{:?}", logout);
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn test_invalid_messages_are_rejected(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+
+    // The session validates against the FIX 4.4 dictionary: OrdType (40) is missing.
+    client.send("D", &[(tags::CL_ORD_ID, "1"), (tags::SYMBOL, "ACME"), (tags::SIDE, "1"), (tags::ORDER_QTY, "10")]).await;
+    client.expect(msg_types::REJECT, &[(tags::REF_SEQ_NUM, "2"), (tags::REF_TAG_ID, "40"), (tags::REF_MSG_TYPE, "D"), (tags::SESSION_REJECT_REASON, "1")]).await;
+
+    // A possible duplicate needs OrigSendingTime (122).
+    client.send(msg_types::TEST_REQUEST, &[(tags::POSS_DUP_FLAG, "Y"), (tags::TEST_REQ_ID, "PING")]).await;
+    client.expect(msg_types::REJECT, &[(tags::REF_SEQ_NUM, "3"), (tags::REF_TAG_ID, "122"), (tags::SESSION_REJECT_REASON, "1")]).await;
+
+    // Rejected messages still count, so the session stays in sequence.
+    client.send(msg_types::TEST_REQUEST, &[(tags::TEST_REQ_ID, "PING")]).await;
+    client.expect(msg_types::HEARTBEAT, &[(tags::TEST_REQ_ID, "PING")]).await;
+    client.logout().await;
+}
+
+#[tokio::test]
+async fn test_order_lifecycle(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+
+    client.send("D", &new_order("1", "1", "10", "100")).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "1"), (tags::EXEC_TYPE, "0"), (tags::ORD_STATUS, "0"), (tags::LEAVES_QTY, "10")]).await;
+
+    client.send("G", &[(tags::ORIG_CL_ORD_ID, "1"), (tags::CL_ORD_ID, "2"), (tags::SYMBOL, "ACME"), (tags::SIDE, "1"), (tags::ORDER_QTY, "8"), (tags::ORD_TYPE, "2"), (tags::PRICE, "101"), (tags::TIME_IN_FORCE, "1")]).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "2"), (tags::ORIG_CL_ORD_ID, "1"), (tags::EXEC_TYPE, "5"), (tags::LEAVES_QTY, "8")]).await;
+
+    client.send("F", &[(tags::ORIG_CL_ORD_ID, "2"), (tags::CL_ORD_ID, "3"), (tags::SYMBOL, "ACME"), (tags::SIDE, "1")]).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "3"), (tags::ORIG_CL_ORD_ID, "2"), (tags::EXEC_TYPE, "4"), (tags::ORD_STATUS, "4")]).await;
+
+    // Cancelling it again is refused, and so is a symbol the exchange does not trade.
+    client.send("F", &[(tags::ORIG_CL_ORD_ID, "2"), (tags::CL_ORD_ID, "4"), (tags::SYMBOL, "ACME"), (tags::SIDE, "1")]).await;
+    client.expect("9", &[(tags::CL_ORD_ID, "4"), (tags::ORIG_CL_ORD_ID, "2"), (tags::CXL_REJ_RESPONSE_TO, "1")]).await;
+    let mut order = new_order("5", "1", "10", "100");
+    order[1] = (tags::SYMBOL, "OTHER");
+    client.send("D", &order).await;
+    client.expect("j", &[(tags::REF_MSG_TYPE, "D"), (tags::BUSINESS_REJECT_REF_ID, "5")]).await;
+    client.logout().await;
+}
+
+#[tokio::test]
+async fn test_orders_cross_between_sessions(){
+    let (addr, _server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+    let mut other = Counterparty::connect(&addr, "OTHER").await;
+    other.send(msg_types::LOGON, &[(tags::ENCRYPT_METHOD, "0"), (tags::HEART_BT_INT, "30")]).await;
+    other.expect(msg_types::LOGON, &[]).await;
+
+    client.send("D", &new_order("BUY", "1", "10", "100")).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "BUY"), (tags::EXEC_TYPE, "0")]).await;
+
+    other.send("D", &new_order("SELL", "2", "4", "100")).await;
+    other.expect("8", &[(tags::CL_ORD_ID, "SELL"), (tags::EXEC_TYPE, "0")]).await;
+    other.expect("8", &[(tags::CL_ORD_ID, "SELL"), (tags::EXEC_TYPE, "F"), (tags::ORD_STATUS, "2"), (tags::LAST_QTY, "4"), (tags::LAST_PX, "100")]).await;
+    client.expect("8", &[(tags::CL_ORD_ID, "BUY"), (tags::EXEC_TYPE, "F"), (tags::ORD_STATUS, "1"), (tags::LAST_QTY, "4"), (tags::CUM_QTY, "4"), (tags::LEAVES_QTY, "6")]).await;
+
+    other.logout().await;
+    client.logout().await;
+}
+
+#[tokio::test]
+async fn test_server_shutdown_logs_out(){
+    let (addr, server) = start_server().await;
+    let mut client = Counterparty::logged_on(&addr, 30).await;
+
+    server.disconnect();
+    let logout = client.expect(msg_types::LOGOUT, &[]).await;
+    assert_eq!(logout.get_field(tags::TEXT), Some("Server shutting down"));
+    client.send(msg_types::LOGOUT, &[]).await;
+    client.expect_closed().await;
+}
@@ -0,0 +1,55 @@
+/* C interface to the orderbook matching engine (liborderbook_ffi).
+ *
+ * Sides: 0 buy, 1 sell.
+ * Order types: 0 GoodTillCancel, 1 GoodForDay, 2 FillAndKill, 3 FillOrKill, 4 Market.
+ * Calls on the same handle must not overlap.
+ */
+#ifndef ORDERBOOK_H
+#define ORDERBOOK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define OB_ERR_NULL (-1)
+#define OB_ERR_SIDE (-2)
+#define OB_ERR_ORDER_TYPE (-3)
+
+typedef struct ObBook ObBook;
+
+typedef struct {
+    uint32_t bid_order_id;
+    int32_t bid_price;
+    uint32_t ask_order_id;
+    int32_t ask_price;
+    uint32_t quantity;
+} ObTrade;
+
+typedef struct {
+    int32_t price;
+    uint32_t quantity;
+} ObLevel;
+
+ObBook *ob_book_new(void);
+void ob_book_free(ObBook *book);
+
+/* Returns the number of trades queued for ob_poll_trade, or OB_ERR_*. */
+int32_t ob_submit(ObBook *book, uint8_t order_type, uint32_t order_id, uint8_t side, int32_t price, uint32_t quantity);
+int32_t ob_cancel(ObBook *book, uint32_t order_id);
+
+/* Returns 1 if a trade was written, 0 if none is queued, or OB_ERR_*. */
+int32_t ob_poll_trade(ObBook *book, ObTrade *trade);
+
+size_t ob_size(const ObBook *book);
+
+/* Copies up to capacity levels, best price first. Returns the count written, or OB_ERR_*. */
+int32_t ob_depth(const ObBook *book, uint8_t side, ObLevel *levels, size_t capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ORDERBOOK_H */
@@ -0,0 +1,264 @@
+//! # Orderbook FFI
+//!
+//! A C ABI over [`Orderbook`] so C++ and Python harnesses can embed this matching engine,
+//! e.g. to compare it against another implementation order by order. The declarations are
+//! in `include/orderbook.h`; the library builds as `liborderbook_ffi.so`.
+//!
+//! ## Model
+//! - [`ob_book_new`] returns an opaque handle that must be released with [`ob_book_free`].
+//! - [`ob_submit`] and [`ob_cancel`] drive the book. Trades caused by a submission are
+//!   queued on the handle and drained one at a time with [`ob_poll_trade`].
+//! - [`ob_depth`] copies aggregated levels into a caller-provided array.
+//!
+//! Sides are coded `0` buy, `1` sell, and order types `0` GoodTillCancel, `1` GoodForDay,
+//! `2` FillAndKill, `3` FillOrKill, `4` Market, as in the [snapshot](orderbook::snapshot)
+//! format. Functions that can fail return a negative `OB_ERR_*` code.
+//!
+//! A handle is not thread-safe from C: calls on the same handle must not overlap.
+//!
+//! SBE encode/decode entry points are not offered, as this tree has no SBE codecs.
+
+use std::{cmp::Reverse, collections::{BTreeMap, VecDeque}, ptr, slice};
+use orderbook::{Order, OrderId, OrderType, Orderbook, Price, Quantity, Side};
+
+/// A null pointer was passed for a required argument.
+pub const OB_ERR_NULL: i32 = -1;
+/// The side code is neither `0` nor `1`.
+pub const OB_ERR_SIDE: i32 = -2;
+/// The order type code is not one of `0..=4`.
+pub const OB_ERR_ORDER_TYPE: i32 = -3;
+
+/// One trade as seen from C.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ObTrade {
+    pub bid_order_id: OrderId,
+    pub bid_price: Price,
+    pub ask_order_id: OrderId,
+    pub ask_price: Price,
+    pub quantity: Quantity,
+}
+
+/// One aggregated price level as seen from C.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ObLevel {
+    pub price: Price,
+    pub quantity: Quantity,
+}
+
+/// Opaque handle owning a book and its queue of unpolled trades.
+pub struct ObBook {
+    orderbook: Orderbook,
+    trades: VecDeque<ObTrade>,
+}
+
+/// Creates an empty book.
+#[no_mangle]
+pub extern "C" fn ob_book_new() -> *mut ObBook {
+    let book = ObBook {
+        orderbook: Orderbook::new(BTreeMap::new(), BTreeMap::new()),
+        trades: VecDeque::new(),
+    };
+    Box::into_raw(Box::new(book))
+}
+
+/// Releases a book created by [`ob_book_new`]. Passing null does nothing.
+///
+/// # Safety
+/// `book` must be null or a handle from [`ob_book_new`] that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_free(book: *mut ObBook) {
+    if !book.is_null() {
+        drop(Box::from_raw(book));
+    }
+}
+
+/// Submits an order and queues the trades it causes.
+///
+/// `price` is ignored for market orders. Duplicate order ids and unfillable
+/// FillAndKill/FillOrKill orders are dropped by the book and cause no trades.
+///
+/// # Returns
+/// The number of trades queued, or a negative `OB_ERR_*` code.
+///
+/// # Safety
+/// `book` must be null or a live handle from [`ob_book_new`].
+#[no_mangle]
+pub unsafe extern "C" fn ob_submit(book: *mut ObBook, order_type: u8, order_id: OrderId, side: u8, price: Price, quantity: Quantity) -> i32 {
+    let Some(book) = book.as_mut() else {
+        return OB_ERR_NULL;
+    };
+    let Some(side) = side_from_code(side) else {
+        return OB_ERR_SIDE;
+    };
+    let order = match order_type_from_code(order_type) {
+        Some(OrderType::Market) => Order::new_market(order_id, side, quantity),
+        Some(order_type) => Order::new(order_type, order_id, side, price, quantity),
+        None => return OB_ERR_ORDER_TYPE,
+    };
+    let trades = book.orderbook.add_order(order);
+    book.trades.extend(trades.iter().map(|trade| {
+        let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+        ObTrade { bid_order_id: bid.order_id, bid_price: bid.price, ask_order_id: ask.order_id, ask_price: ask.price, quantity: bid.quantity }
+    }));
+    trades.len() as i32
+}
+
+/// Cancels a resting order. Unknown ids are ignored.
+///
+/// # Returns
+/// `0`, or [`OB_ERR_NULL`].
+///
+/// # Safety
+/// `book` must be null or a live handle from [`ob_book_new`].
+#[no_mangle]
+pub unsafe extern "C" fn ob_cancel(book: *mut ObBook, order_id: OrderId) -> i32 {
+    let Some(book) = book.as_mut() else {
+        return OB_ERR_NULL;
+    };
+    book.orderbook.cancel_order(order_id);
+    0
+}
+
+/// Pops the oldest unpolled trade into `trade`.
+///
+/// # Returns
+/// `1` if a trade was written, `0` if the queue is empty, or [`OB_ERR_NULL`].
+///
+/// # Safety
+/// `book` must be null or a live handle from [`ob_book_new`], and `trade` must be null or
+/// valid for writing one [`ObTrade`].
+#[no_mangle]
+pub unsafe extern "C" fn ob_poll_trade(book: *mut ObBook, trade: *mut ObTrade) -> i32 {
+    let Some(book) = book.as_mut() else {
+        return OB_ERR_NULL;
+    };
+    if trade.is_null() {
+        return OB_ERR_NULL;
+    }
+    match book.trades.pop_front() {
+        Some(next) => {
+            ptr::write(trade, next);
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Returns the number of resting orders, or `0` for a null handle.
+///
+/// # Safety
+/// `book` must be null or a live handle from [`ob_book_new`].
+#[no_mangle]
+pub unsafe extern "C" fn ob_size(book: *const ObBook) -> usize {
+    book.as_ref().map_or(0, |book| book.orderbook.size())
+}
+
+/// Copies up to `capacity` levels of `side`, best price first, into `levels`.
+///
+/// # Returns
+/// The number of levels written, or a negative `OB_ERR_*` code.
+///
+/// # Safety
+/// `book` must be null or a live handle from [`ob_book_new`], and `levels` must be null or
+/// valid for writing `capacity` [`ObLevel`]s.
+#[no_mangle]
+pub unsafe extern "C" fn ob_depth(book: *const ObBook, side: u8, levels: *mut ObLevel, capacity: usize) -> i32 {
+    let Some(book) = book.as_ref() else {
+        return OB_ERR_NULL;
+    };
+    let Some(side) = side_from_code(side) else {
+        return OB_ERR_SIDE;
+    };
+    if levels.is_null() && capacity > 0 {
+        return OB_ERR_NULL;
+    }
+    let infos = book.orderbook.get_order_infos();
+    let mut depth: Vec<ObLevel> = match side {
+        Side::Buy => infos.get_bids(),
+        Side::Sell => infos.get_asks(),
+    }
+    .iter()
+    .map(|level| ObLevel { price: level.price, quantity: level.quantity })
+    .collect();
+    match side {
+        Side::Buy => depth.sort_by_key(|level| Reverse(level.price)),
+        Side::Sell => depth.sort_by_key(|level| level.price),
+    }
+    let count = depth.len().min(capacity);
+    if count > 0 {
+        slice::from_raw_parts_mut(levels, count).copy_from_slice(&depth[..count]);
+    }
+    count as i32
+}
+
+fn side_from_code(code: u8) -> Option<Side> {
+    match code {
+        0 => Some(Side::Buy),
+        1 => Some(Side::Sell),
+        _ => None,
+    }
+}
+
+fn order_type_from_code(code: u8) -> Option<OrderType> {
+    match code {
+        0 => Some(OrderType::GoodTillCancel),
+        1 => Some(OrderType::GoodForDay),
+        2 => Some(OrderType::FillAndKill),
+        3 => Some(OrderType::FillOrKill),
+        4 => Some(OrderType::Market),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_submit_poll_and_depth(){
+        unsafe {
+            let book = ob_book_new();
+            assert_eq!(ob_submit(book, 0, 1, 1, 101, 5), 0);
+            assert_eq!(ob_submit(book, 0, 2, 1, 102, 5), 0);
+            assert_eq!(ob_submit(book, 0, 3, 0, 99, 4), 0);
+            assert_eq!(ob_submit(book, 0, 4, 0, 100, 2), 0);
+            assert_eq!(ob_submit(book, 4, 5, 0, 0, 7), 2);
+            assert_eq!(ob_size(book), 3);
+
+            let mut trade = ObTrade::default();
+            assert_eq!(ob_poll_trade(book, &mut trade), 1);
+            assert_eq!((trade.ask_order_id, trade.ask_price, trade.quantity), (1, 101, 5));
+            assert_eq!(ob_poll_trade(book, &mut trade), 1);
+            assert_eq!((trade.bid_order_id, trade.ask_order_id, trade.quantity), (5, 2, 2));
+            assert_eq!(ob_poll_trade(book, &mut trade), 0);
+
+            let mut levels = [ObLevel::default(); 4];
+            assert_eq!(ob_depth(book, 0, levels.as_mut_ptr(), levels.len()), 2);
+            assert_eq!(levels[..2], [ObLevel { price: 100, quantity: 2 }, ObLevel { price: 99, quantity: 4 }]);
+            assert_eq!(ob_depth(book, 1, levels.as_mut_ptr(), 1), 1);
+            assert_eq!(levels[0], ObLevel { price: 102, quantity: 3 });
+
+            assert_eq!(ob_cancel(book, 3), 0);
+            assert_eq!(ob_size(book), 2);
+            ob_book_free(book);
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments(){
+        unsafe {
+            let book = ob_book_new();
+            assert_eq!(ob_submit(ptr::null_mut(), 0, 1, 0, 100, 1), OB_ERR_NULL);
+            assert_eq!(ob_submit(book, 0, 1, 2, 100, 1), OB_ERR_SIDE);
+            assert_eq!(ob_submit(book, 9, 1, 0, 100, 1), OB_ERR_ORDER_TYPE);
+            assert_eq!(ob_poll_trade(book, ptr::null_mut()), OB_ERR_NULL);
+            assert_eq!(ob_depth(book, 0, ptr::null_mut(), 1), OB_ERR_NULL);
+            assert_eq!(ob_depth(book, 0, ptr::null_mut(), 0), 0);
+            assert_eq!(ob_size(ptr::null()), 0);
+            ob_book_free(book);
+            ob_book_free(ptr::null_mut());
+        }
+    }
+}
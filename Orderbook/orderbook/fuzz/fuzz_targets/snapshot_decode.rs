@@ -0,0 +1,15 @@
+//! Feeds arbitrary bytes to the book snapshot decoder, compressed or not. Anything it
+//! accepts must re-encode to a snapshot that decodes to the same value.
+//! Run with `cargo +nightly fuzz run snapshot_decode`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orderbook::snapshot::BookSnapshot;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(snapshot) = BookSnapshot::from_bytes(data) {
+        assert_eq!(BookSnapshot::from_bytes(&snapshot.to_bytes()).unwrap(), snapshot);
+        let _ = snapshot.restore();
+    }
+});
@@ -0,0 +1,131 @@
+//! # Auction Module
+//!
+//! Call auctions: a phase in which orders rest without matching, and the single price they are
+//! then executed at.
+//!
+//! ## Phases
+//! A book trades continuously until [`Orderbook::start_auction`](crate::Orderbook::start_auction)
+//! puts it in its [`TradingPhase::Auction`], e.g. ahead of one of an instrument's
+//! [`auction_times`](crate::instrument::Instrument::auction_times). During the auction:
+//! - GoodTillCancel and GoodForDay orders rest even if they cross the other side,
+//! - Market, FillAndKill and FillOrKill orders are dropped, since nothing executes until the
+//!   auction ends,
+//! - the book keeps its [`IndicativeUncross`] up to date as orders arrive, are modified or
+//!   cancelled, for the market data feed to publish.
+//!
+//! [`Orderbook::uncross`](crate::Orderbook::uncross) ends the auction: every order that
+//! crosses at the indicative price is executed at that price, best price and then time first,
+//! and the book trades continuously again.
+//!
+//! ## Equilibrium Price
+//! Among the prices of the resting levels, [`compute_uncross`] picks the one that:
+//! 1. executes the largest quantity,
+//! 2. leaves the smallest imbalance between what is bid and offered at it,
+//! 3. is closest to the reference price, if there is one,
+//! 4. is the lowest.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Order, OrderType, Orderbook, Side};
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.start_auction();
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 102, 10));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 100, 6));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 101, 6));
+//!
+//! let indicative = ob.indicative_uncross().unwrap();
+//! assert_eq!((indicative.price, indicative.matched_quantity, indicative.imbalance), (101, 10, Some((Side::Sell, 2))));
+//!
+//! let trades = ob.uncross();
+//! assert!(trades.iter().all(|trade| trade.get_bid_trade().price == 101 && trade.get_ask_trade().price == 101));
+//! assert_eq!(ob.get_best_prices(), (None, Some(101)));
+//! ```
+
+use std::{cmp::{Ordering, Reverse}, collections::BTreeMap};
+use crate::orderbook::{Price, Quantity, Side};
+
+/// Whether a book matches orders as they arrive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TradingPhase {
+    /// Orders are matched on arrival.
+    #[default]
+    Continuous,
+    /// Orders rest without matching until the book is uncrossed.
+    Auction,
+}
+
+/// The outcome of uncrossing the book as it stands.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct IndicativeUncross {
+    /// Equilibrium price every execution would take place at.
+    pub price: Price,
+    /// Quantity that would execute.
+    pub matched_quantity: u64,
+    /// Side with quantity left over at `price` and how much; `None` if both sides balance.
+    pub imbalance: Option<(Side, u64)>,
+}
+
+/// Computes the uncross of the remaining quantity per level of `bids` and `asks`, or `None`
+/// if they do not cross.
+///
+/// `reference` breaks ties between prices that execute as much with the same imbalance.
+pub fn compute_uncross(bids: &BTreeMap<Price, Quantity>, asks: &BTreeMap<Price, Quantity>, reference: Option<Price>) -> Option<IndicativeUncross> {
+    let mut prices: Vec<Price> = bids.keys().chain(asks.keys()).copied().collect();
+    prices.sort_unstable();
+    prices.dedup();
+
+    // Offered at or below each price, lowest price first; bid at or above it, highest first.
+    let mut supply = Vec::with_capacity(prices.len());
+    let mut offered = 0;
+    for price in &prices {
+        offered += asks.get(price).map_or(0, |quantity| u64::from(*quantity));
+        supply.push(offered);
+    }
+    let mut demand = vec![0; prices.len()];
+    let mut bid = 0;
+    for (index, price) in prices.iter().enumerate().rev() {
+        bid += bids.get(price).map_or(0, |quantity| u64::from(*quantity));
+        demand[index] = bid;
+    }
+
+    let (index, matched_quantity) = (0..prices.len())
+        .map(|index| (index, supply[index].min(demand[index])))
+        .filter(|(_, matched_quantity)| *matched_quantity > 0)
+        .min_by_key(|(index, matched_quantity)| {
+            let price = prices[*index];
+            let distance = reference.map_or(0, |reference| (i64::from(price) - i64::from(reference)).abs());
+            (Reverse(*matched_quantity), supply[*index].abs_diff(demand[*index]), distance, price)
+        })?;
+    let imbalance = match demand[index].cmp(&supply[index]) {
+        Ordering::Greater => Some((Side::Buy, demand[index] - supply[index])),
+        Ordering::Less => Some((Side::Sell, supply[index] - demand[index])),
+        Ordering::Equal => None,
+    };
+    Some(IndicativeUncross { price: prices[index], matched_quantity, imbalance })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn levels(levels: &[(Price, Quantity)]) -> BTreeMap<Price, Quantity> {
+        levels.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_compute_uncross(){
+        let bids = levels(&[(99, 5), (101, 4), (103, 3)]);
+        let asks = levels(&[(98, 2), (100, 6), (102, 5)]);
+        // At 100: 7 bid, 8 offered; at 101: 7 bid, 8 offered; at 99: 12 bid, 2 offered.
+        let uncross = compute_uncross(&bids, &asks, None).unwrap();
+        assert_eq!(uncross, IndicativeUncross { price: 100, matched_quantity: 7, imbalance: Some((Side::Sell, 1)) });
+        assert_eq!(compute_uncross(&bids, &asks, Some(105)).unwrap().price, 101);
+
+        assert_eq!(compute_uncross(&levels(&[(99, 5)]), &levels(&[(100, 5)]), None), None);
+        assert_eq!(compute_uncross(&levels(&[(100, 5)]), &BTreeMap::new(), None), None);
+        let balanced = compute_uncross(&levels(&[(100, 5)]), &levels(&[(100, 5)]), None).unwrap();
+        assert_eq!((balanced.matched_quantity, balanced.imbalance), (5, None));
+    }
+}
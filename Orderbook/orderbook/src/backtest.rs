@@ -0,0 +1,521 @@
+//! # Backtest Module
+//!
+//! Replays a recorded command file through a fresh [`Orderbook`] in virtual time and lets a
+//! pluggable [`Strategy`] insert its own orders, reporting the strategy's fills and P&L.
+//!
+//! ## Command File Format
+//! One command per line, comma separated. Blank lines and lines starting with `#` are ignored.
+//! Timestamps are virtual microseconds since the start of the recording and must not decrease.
+//!
+//! ```text
+//! # timestamp_us,NEW,order_id,side,price,quantity,order_type[,flags]
+//! 1000,NEW,1,BUY,100,10,GTC
+//! 1100,NEW,3,SELL,102,10,GTC,SA
+//! # timestamp_us,MARKET,order_id,side,quantity
+//! 1200,MARKET,2,SELL,5
+//! # timestamp_us,MODIFY,order_id,side,price,quantity
+//! 1500,MODIFY,1,BUY,101,10
+//! # timestamp_us,CANCEL,order_id
+//! 2000,CANCEL,1
+//! ```
+//!
+//! Order types are `GTC`, `GFD`, `FAK`, `FOK` and `MKT`. The optional flags of a new order
+//! are letters: `S` for a short sale and `A` or `P` for agency or principal capacity.
+//! Orders without flags leave the field out. Recorded order ids must be below
+//! [`STRATEGY_ORDER_ID_BASE`]; ids from that value up are reserved for strategy orders.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::backtest::{parse_commands, Backtester, Strategy, StrategyContext};
+//!
+//! struct Idle;
+//! impl Strategy for Idle {
+//!     fn on_tick(&mut self, _context: &mut StrategyContext) {}
+//! }
+//!
+//! let commands = parse_commands("1000,NEW,1,BUY,100,10,GTC\n2000,CANCEL,1").unwrap();
+//! let report = Backtester::new(commands).run(&mut Idle);
+//! assert!(report.fills.is_empty());
+//! ```
+
+use std::{collections::{BTreeMap, HashSet}, fs, path::Path, time::Duration};
+use log::{debug, info, warn};
+use crate::orderbook::{Capacity, Order, OrderFlags, OrderId, OrderModify, OrderType, Orderbook, Price, Quantity, Side, Trade};
+
+/// First order id handed out to strategy orders. Recorded order ids must stay below it.
+pub const STRATEGY_ORDER_ID_BASE: OrderId = 1 << 31;
+
+/// A single instruction to the book, either recorded or issued by a strategy.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    /// Submit a new limit order (any non-market [`OrderType`]).
+    New { order_id: OrderId, side: Side, price: Price, quantity: Quantity, order_type: OrderType, flags: OrderFlags },
+    /// Submit a new market order.
+    Market { order_id: OrderId, side: Side, quantity: Quantity },
+    /// Replace an existing order via [`OrderModify`].
+    Modify { order_id: OrderId, side: Side, price: Price, quantity: Quantity },
+    /// Cancel an existing order.
+    Cancel { order_id: OrderId },
+}
+
+/// A [`Command`] tagged with the virtual time at which it was recorded.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RecordedCommand {
+    /// Virtual time since the start of the recording.
+    pub timestamp: Duration,
+    pub command: Command,
+}
+
+/// One execution of a strategy order.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fill {
+    /// Virtual time of the recorded command (or strategy tick) that caused the fill.
+    pub timestamp: Duration,
+    pub order_id: OrderId,
+    pub side: Side,
+    /// Execution price as reported by the book for this side of the trade.
+    pub price: Price,
+    pub quantity: Quantity,
+}
+
+/// Summary of a backtest run from the strategy's point of view.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BacktestReport {
+    /// Every fill of a strategy order, in execution order.
+    pub fills: Vec<Fill>,
+    /// Net position: bought minus sold quantity.
+    pub position: i64,
+    /// Cash flow: proceeds of sells minus cost of buys.
+    pub cash: i64,
+    /// Price used to mark the final position (last traded price in the book), if any trade happened.
+    pub mark_price: Option<Price>,
+    /// Recorded commands replayed.
+    pub commands_replayed: usize,
+    /// Trades generated by the book, including trades not involving the strategy.
+    pub total_trades: usize,
+}
+
+impl BacktestReport {
+    /// Mark-to-market profit and loss: `cash + position * mark_price`.
+    ///
+    /// Falls back to `cash` when nothing traded and the position is therefore flat.
+    pub fn get_pnl(&self) -> i64 {
+        self.cash + self.position * self.mark_price.unwrap_or(0) as i64
+    }
+}
+
+/// A trading strategy plugged into the [`Backtester`].
+pub trait Strategy {
+    /// Called after each recorded command has been applied, at that command's virtual time.
+    ///
+    /// Orders submitted through `context` are applied right after this call returns.
+    fn on_tick(&mut self, context: &mut StrategyContext);
+
+    /// Called once for every fill of one of the strategy's own orders.
+    fn on_fill(&mut self, _fill: &Fill) {}
+}
+
+/// Handle passed to [`Strategy::on_tick`] for inspecting the book and issuing orders.
+pub struct StrategyContext<'a> {
+    now: Duration,
+    orderbook: &'a Orderbook,
+    next_order_id: &'a mut OrderId,
+    pending: Vec<Command>,
+}
+
+impl StrategyContext<'_> {
+    /// Returns the current virtual time.
+    pub const fn get_time(&self) -> Duration {
+        self.now
+    }
+
+    /// Returns read access to the book being replayed.
+    pub const fn get_orderbook(&self) -> &Orderbook {
+        self.orderbook
+    }
+
+    /// Queues a new limit order and returns the id assigned to it.
+    pub fn submit(&mut self, order_type: OrderType, side: Side, price: Price, quantity: Quantity) -> OrderId {
+        let order_id = self.allocate_order_id();
+        self.pending.push(Command::New { order_id, side, price, quantity, order_type, flags: OrderFlags::default() });
+        order_id
+    }
+
+    /// Queues a new market order and returns the id assigned to it.
+    pub fn submit_market(&mut self, side: Side, quantity: Quantity) -> OrderId {
+        let order_id = self.allocate_order_id();
+        self.pending.push(Command::Market { order_id, side, quantity });
+        order_id
+    }
+
+    /// Queues a modification of one of the strategy's orders.
+    pub fn modify(&mut self, order_id: OrderId, side: Side, price: Price, quantity: Quantity) {
+        self.pending.push(Command::Modify { order_id, side, price, quantity });
+    }
+
+    /// Queues a cancel of one of the strategy's orders.
+    pub fn cancel(&mut self, order_id: OrderId) {
+        self.pending.push(Command::Cancel { order_id });
+    }
+
+    fn allocate_order_id(&mut self) -> OrderId {
+        let order_id = *self.next_order_id;
+        *self.next_order_id += 1;
+        order_id
+    }
+}
+
+/// Replays recorded commands against a fresh book and drives a [`Strategy`].
+pub struct Backtester {
+    commands: Vec<RecordedCommand>,
+}
+
+impl Backtester {
+    /// Creates a backtester over `commands`, which must be ordered by timestamp.
+    pub fn new(commands: Vec<RecordedCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Loads a command file (see the module docs for the format) into a backtester.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or any line fails to parse.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        Ok(Self::new(parse_commands(&contents)?))
+    }
+
+    /// Runs the replay to completion.
+    ///
+    /// For each recorded command: apply it, invoke [`Strategy::on_tick`], then apply the orders
+    /// the strategy queued. Fills of strategy orders are reported through [`Strategy::on_fill`].
+    pub fn run(&self, strategy: &mut dyn Strategy) -> BacktestReport {
+        let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut report = BacktestReport::default();
+        let mut next_order_id = STRATEGY_ORDER_ID_BASE;
+        let mut strategy_orders: HashSet<OrderId> = HashSet::new();
+
+        info!("Backtester: replaying {} commands", self.commands.len());
+        for recorded in &self.commands {
+            let now = recorded.timestamp;
+            let trades = apply_command(&orderbook, recorded.command);
+            report.commands_replayed += 1;
+            self.settle(now, &trades, &strategy_orders, strategy, &mut report);
+
+            let mut context = StrategyContext { now, orderbook: &orderbook, next_order_id: &mut next_order_id, pending: Vec::new() };
+            strategy.on_tick(&mut context);
+
+            for command in context.pending {
+                match command {
+                    Command::New { order_id, .. } | Command::Market { order_id, .. } => {
+                        strategy_orders.insert(order_id);
+                    }
+                    Command::Modify { order_id, .. } | Command::Cancel { order_id } if !strategy_orders.contains(&order_id) => {
+                        warn!("Backtester: strategy tried to touch foreign order#{}, ignoring.", order_id);
+                        continue;
+                    }
+                    _ => {}
+                }
+                let trades = apply_command(&orderbook, command);
+                self.settle(now, &trades, &strategy_orders, strategy, &mut report);
+            }
+        }
+        info!("Backtester: finished with {} fills, position {}, P&L {}", report.fills.len(), report.position, report.get_pnl());
+        report
+    }
+
+    /// Books the strategy's side of `trades` into `report` and notifies the strategy.
+    fn settle(
+        &self,
+        now: Duration,
+        trades: &[Trade],
+        strategy_orders: &HashSet<OrderId>,
+        strategy: &mut dyn Strategy,
+        report: &mut BacktestReport,
+    ) {
+        for trade in trades {
+            report.total_trades += 1;
+            let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+            report.mark_price = Some(ask.price);
+
+            for (info, side) in [(bid, Side::Buy), (ask, Side::Sell)] {
+                if !strategy_orders.contains(&info.order_id) {
+                    continue;
+                }
+                let fill = Fill { timestamp: now, order_id: info.order_id, side, price: info.price, quantity: info.quantity };
+                let notional = info.price as i64 * info.quantity as i64;
+                match side {
+                    Side::Buy => {
+                        report.position += info.quantity as i64;
+                        report.cash -= notional;
+                    }
+                    Side::Sell => {
+                        report.position -= info.quantity as i64;
+                        report.cash += notional;
+                    }
+                }
+                debug!("Backtester: strategy fill {:?}", fill);
+                strategy.on_fill(&fill);
+                report.fills.push(fill);
+            }
+        }
+    }
+}
+
+/// Applies one command to `orderbook`, returning any trades it produced.
+pub fn apply_command(orderbook: &Orderbook, command: Command) -> Vec<Trade> {
+    match command {
+        Command::New { order_id, side, price, quantity, order_type, flags } => {
+            let order = Order::new(order_type, order_id, side, price, quantity);
+            order.lock().unwrap().set_flags(flags);
+            orderbook.add_order(order)
+        }
+        Command::Market { order_id, side, quantity } => orderbook.add_order(Order::new_market(order_id, side, quantity)),
+        Command::Modify { order_id, side, price, quantity } => {
+            orderbook.modify_order(OrderModify::new(order_id, side, price, quantity))
+        }
+        Command::Cancel { order_id } => {
+            orderbook.cancel_order(order_id);
+            vec![]
+        }
+    }
+}
+
+/// Parses a whole command file.
+///
+/// # Errors
+/// Returns the first parse error, prefixed with its 1-based line number, or an error if
+/// timestamps decrease.
+pub fn parse_commands(contents: &str) -> Result<Vec<RecordedCommand>, String> {
+    let mut commands = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let recorded = parse_command_line(line).map_err(|err| format!("line {}: {}", index + 1, err))?;
+        if commands.last().is_some_and(|last: &RecordedCommand| last.timestamp > recorded.timestamp) {
+            return Err(format!("line {}: timestamp goes backwards", index + 1));
+        }
+        commands.push(recorded);
+    }
+    Ok(commands)
+}
+
+/// Parses a single command line such as `1000,NEW,1,BUY,100,10,GTC`.
+///
+/// # Errors
+/// Returns a description of the first malformed field.
+pub fn parse_command_line(line: &str) -> Result<RecordedCommand, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let field = |index: usize, name: &str| fields.get(index).copied().ok_or(format!("missing {}", name));
+    let number = |index: usize, name: &str| -> Result<i64, String> {
+        field(index, name)?.parse().map_err(|_| format!("invalid {}: {}", name, fields[index]))
+    };
+
+    let timestamp = Duration::from_micros(number(0, "timestamp")?.try_into().map_err(|_| "negative timestamp".to_string())?);
+    let order_id: OrderId = number(2, "order_id")?.try_into().map_err(|_| "order_id out of range".to_string())?;
+    if order_id >= STRATEGY_ORDER_ID_BASE {
+        return Err(format!("order_id {} is reserved for strategy orders", order_id));
+    }
+    let quantity = |index: usize| -> Result<Quantity, String> {
+        number(index, "quantity")?.try_into().map_err(|_| "quantity out of range".to_string())
+    };
+    let price = |index: usize| -> Result<Price, String> {
+        number(index, "price")?.try_into().map_err(|_| "price out of range".to_string())
+    };
+
+    let expected_len = |len: usize| {
+        if fields.len() == len { Ok(()) } else { Err(format!("expected {} fields, got {}", len, fields.len())) }
+    };
+    let command = match field(1, "command")?.to_ascii_uppercase().as_str() {
+        "NEW" => {
+            if fields.len() != 8 {
+                expected_len(7)?;
+            }
+            let order_type = parse_order_type(fields[6])?;
+            if order_type == OrderType::Market {
+                return Err("use MARKET for market orders".to_string());
+            }
+            let flags = fields.get(7).map_or(Ok(OrderFlags::default()), |value| parse_flags(value))?;
+            Command::New { order_id, side: parse_side(fields[3])?, price: price(4)?, quantity: quantity(5)?, order_type, flags }
+        }
+        "MARKET" => {
+            expected_len(5)?;
+            Command::Market { order_id, side: parse_side(fields[3])?, quantity: quantity(4)? }
+        }
+        "MODIFY" => {
+            expected_len(6)?;
+            Command::Modify { order_id, side: parse_side(fields[3])?, price: price(4)?, quantity: quantity(5)? }
+        }
+        "CANCEL" => {
+            expected_len(3)?;
+            Command::Cancel { order_id }
+        }
+        other => return Err(format!("unknown command: {}", other)),
+    };
+    Ok(RecordedCommand { timestamp, command })
+}
+
+/// Parses `BUY`/`SELL` (case-insensitive).
+fn parse_side(value: &str) -> Result<Side, String> {
+    match value.to_ascii_uppercase().as_str() {
+        "BUY" => Ok(Side::Buy),
+        "SELL" => Ok(Side::Sell),
+        _ => Err(format!("invalid side: {}", value)),
+    }
+}
+
+/// Parses the short order type codes used in command files.
+fn parse_order_type(value: &str) -> Result<OrderType, String> {
+    match value.to_ascii_uppercase().as_str() {
+        "GTC" => Ok(OrderType::GoodTillCancel),
+        "GFD" => Ok(OrderType::GoodForDay),
+        "FAK" => Ok(OrderType::FillAndKill),
+        "FOK" => Ok(OrderType::FillOrKill),
+        "MKT" => Ok(OrderType::Market),
+        _ => Err(format!("invalid order type: {}", value)),
+    }
+}
+
+/// Parses the flag letters of a new order, e.g. `SA` for an agency short sale.
+fn parse_flags(value: &str) -> Result<OrderFlags, String> {
+    let mut flags = OrderFlags::default();
+    for letter in value.chars() {
+        match letter.to_ascii_uppercase() {
+            'S' => flags.short_sell = true,
+            'A' if flags.capacity.is_none() => flags.capacity = Some(Capacity::Agency),
+            'P' if flags.capacity.is_none() => flags.capacity = Some(Capacity::Principal),
+            _ => return Err(format!("invalid flags: {}", value)),
+        }
+    }
+    Ok(flags)
+}
+
+/// Returns the flag letters of `flags`, empty if none are set.
+fn flags_code(flags: OrderFlags) -> String {
+    let capacity = match flags.capacity {
+        Some(Capacity::Agency) => "A",
+        Some(Capacity::Principal) => "P",
+        None => "",
+    };
+    format!("{}{}", if flags.short_sell { "S" } else { "" }, capacity)
+}
+
+/// Formats a command as a command file line; the inverse of [`parse_command_line`].
+pub fn format_command_line(recorded: &RecordedCommand) -> String {
+    let timestamp = recorded.timestamp.as_micros();
+    match recorded.command {
+        Command::New { order_id, side, price, quantity, order_type, flags } => {
+            let line = format!("{},NEW,{},{},{},{},{}", timestamp, order_id, side_code(side), price, quantity, order_type_code(order_type));
+            match flags_code(flags) {
+                code if code.is_empty() => line,
+                code => format!("{},{}", line, code),
+            }
+        }
+        Command::Market { order_id, side, quantity } => format!("{},MARKET,{},{},{}", timestamp, order_id, side_code(side), quantity),
+        Command::Modify { order_id, side, price, quantity } => {
+            format!("{},MODIFY,{},{},{},{}", timestamp, order_id, side_code(side), price, quantity)
+        }
+        Command::Cancel { order_id } => format!("{},CANCEL,{}", timestamp, order_id),
+    }
+}
+
+/// Returns the command file code for `side`.
+pub(crate) const fn side_code(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "BUY",
+        Side::Sell => "SELL",
+    }
+}
+
+/// Returns the command file code for `order_type`.
+const fn order_type_code(order_type: OrderType) -> &'static str {
+    match order_type {
+        OrderType::GoodTillCancel => "GTC",
+        OrderType::GoodForDay => "GFD",
+        OrderType::FillAndKill => "FAK",
+        OrderType::FillOrKill => "FOK",
+        OrderType::Market => "MKT",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Joins the best bid once, then does nothing.
+    struct JoinBid {
+        order_id: Option<OrderId>,
+        fills: usize,
+    }
+
+    impl Strategy for JoinBid {
+        fn on_tick(&mut self, context: &mut StrategyContext) {
+            if self.order_id.is_some() {
+                return;
+            }
+            let infos = context.get_orderbook().get_order_infos();
+            if let Some(best_bid) = infos.get_bids().last() {
+                self.order_id = Some(context.submit(OrderType::GoodTillCancel, Side::Buy, best_bid.price, 5));
+            }
+        }
+
+        fn on_fill(&mut self, _fill: &Fill) {
+            self.fills += 1;
+        }
+    }
+
+    #[test]
+    fn test_parse_commands(){
+        let commands = parse_commands("# header\n\n1000,NEW,1,BUY,100,10,GTC\n1200,MARKET,2,SELL,5\n1500,MODIFY,1,BUY,101,10\n2000,CANCEL,1").unwrap();
+
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands[0].timestamp, Duration::from_micros(1000));
+        assert_eq!(commands[0].command, Command::New { order_id: 1, side: Side::Buy, price: 100, quantity: 10, order_type: OrderType::GoodTillCancel, flags: OrderFlags::default() });
+        assert_eq!(commands[3].command, Command::Cancel { order_id: 1 });
+    }
+
+    #[test]
+    fn test_format_command_line_round_trips(){
+        let contents = "1000,NEW,1,BUY,100,10,GFD\n1100,NEW,3,SELL,102,10,GTC,SA\n1200,MARKET,2,SELL,5\n1500,MODIFY,1,BUY,101,10\n2000,CANCEL,1";
+        let formatted: Vec<String> = parse_commands(contents).unwrap().iter().map(format_command_line).collect();
+
+        assert_eq!(formatted.join("\n"), contents);
+    }
+
+    #[test]
+    fn test_parse_commands_rejects_bad_input(){
+        assert!(parse_commands("1000,NEW,1,BUY,100,10").is_err());
+        assert!(parse_commands("1000,NEW,1,HOLD,100,10,GTC").is_err());
+        assert!(parse_commands("1000,NEW,1,SELL,100,10,GTC,AP").is_err());
+        assert!(parse_commands("2000,CANCEL,1\n1000,CANCEL,2").is_err());
+        assert!(parse_commands(&format!("1000,CANCEL,{}", STRATEGY_ORDER_ID_BASE)).is_err());
+    }
+
+    #[test]
+    fn test_backtest_reports_strategy_fills_and_pnl(){
+        // The strategy joins the 100 bid behind order 1; the 101 ask is lifted later
+        // and a seller hits both bids at 100.
+        let commands = parse_commands(
+            "1000,NEW,1,BUY,100,5,GTC\n\
+             2000,NEW,2,SELL,105,5,GTC\n\
+             3000,NEW,3,SELL,100,10,GTC\n\
+             4000,NEW,4,BUY,105,5,GTC",
+        ).unwrap();
+        let mut strategy = JoinBid { order_id: None, fills: 0 };
+        let report = Backtester::new(commands).run(&mut strategy);
+
+        assert_eq!(report.commands_replayed, 4);
+        assert_eq!(report.total_trades, 3);
+        assert_eq!(strategy.fills, 1);
+        assert_eq!(report.fills[0].order_id, STRATEGY_ORDER_ID_BASE);
+        assert_eq!(report.fills[0].timestamp, Duration::from_micros(3000));
+        assert_eq!(report.position, 5);
+        assert_eq!(report.cash, -500);
+        assert_eq!(report.mark_price, Some(105));
+        assert_eq!(report.get_pnl(), 25);
+    }
+}
@@ -0,0 +1,36 @@
+use std::{env, process};
+use orderbook::replay::verify_recording;
+
+fn main() {
+    env_logger::init();
+
+    let Some(directory) = env::args().nth(1) else {
+        eprintln!("Usage: replay <recording directory>");
+        process::exit(2);
+    };
+
+    let report = match verify_recording(&directory) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    println!("Commands replayed: {}", report.commands);
+    println!("Trades replayed:   {}", report.trades);
+    if let Some(mismatch) = &report.trade_mismatch {
+        println!("Trade tape diverges at row {}:", mismatch.index);
+        println!("  recorded: {}", mismatch.expected.as_deref().unwrap_or("<none>"));
+        println!("  replayed: {}", mismatch.actual.as_deref().unwrap_or("<none>"));
+    }
+    for mismatch in &report.depth_mismatches {
+        println!("Depth mismatch: {}", mismatch);
+    }
+
+    if report.is_consistent() {
+        println!("Replay matches the recording.");
+    } else {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,35 @@
+use std::{env, fs, process};
+use orderbook::scenario::{parse_scenario, ScenarioRunner};
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let realtime = args.iter().any(|arg| arg == "--realtime");
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: scenario <file> [--realtime]");
+        process::exit(2);
+    };
+
+    let steps = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path, err))
+        .and_then(|source| parse_scenario(&source));
+    let steps = match steps {
+        Ok(steps) => steps,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    match ScenarioRunner::new().realtime(realtime).run(&steps) {
+        Ok(report) => println!(
+            "PASS {}: {} steps, {} expectations, {} trades",
+            path, report.steps, report.expectations, report.trades
+        ),
+        Err(err) => {
+            println!("FAIL {}: {}", path, err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, env, path::PathBuf, process, time::Duration};
+use orderbook::Orderbook;
+#[cfg(feature = "parquet")]
+use orderbook::columnar::{ParquetExportConfig, ParquetExporter};
+use orderbook::harness::DeterministicSimulation;
+use orderbook::recorder::{Recorder, RecorderConfig};
+use orderbook::simulator::{OrderTypeMix, PriceDistribution, Simulator, SimulatorConfig};
+use orderbook::tape::{TradeTape, TradeTapeConfig};
+
+const USAGE: &str = "\
+Usage: simulator [OPTIONS]
+
+Options:
+  --seed <u64>             RNG seed for a reproducible run
+  --events <n>             Number of events to generate (default 1000)
+  --rate <f64>             Mean events per second (default 100)
+  --realtime               Sleep between events instead of running flat out
+  --fair-value <price>     Starting fair value (default 100)
+  --volatility <f64>       Std dev of each fair-value step in ticks (default 0.5)
+  --distribution <kind>    Price offset distribution: normal | uniform (default normal)
+  --spread <f64>           Scale of the price offset in ticks (default 3)
+  --min-qty <n>            Smallest order quantity (default 1)
+  --max-qty <n>            Largest order quantity (default 20)
+  --cancel-ratio <f64>     Probability an event is a cancel (default 0.2)
+  --mix <gtc,gfd,fak,fok,mkt>
+                           Order type weights (default 70,10,10,5,5)
+  --session <secs>         Prune GFD orders every <secs> of simulated time
+  --verify                 Run the seed twice and check both runs match
+                           (needs --seed)
+  --record <dir>           Record commands, trades and depth as CSV into <dir>
+  --tape <dir>             Write the trade tape as CSV into <dir>
+  --parquet <dir>          Export trades and depth as Parquet into <dir>
+                           (needs the `parquet` feature)
+  -h, --help               Print this help";
+
+/// Parsed command line.
+struct Args {
+    config: SimulatorConfig,
+    record_directory: Option<PathBuf>,
+    tape_directory: Option<PathBuf>,
+    parquet_directory: Option<PathBuf>,
+    verify: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut config = SimulatorConfig::default();
+    let mut record_directory = None;
+    let mut tape_directory = None;
+    let mut parquet_directory = None;
+    let mut verify = false;
+    let mut args = env::args().skip(1);
+
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        if flag == "--realtime" {
+            config.realtime = true;
+            continue;
+        }
+        if flag == "--verify" {
+            verify = true;
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("Missing value for {}", flag))?;
+        let invalid = || format!("Invalid value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--seed" => config.seed = Some(value.parse().map_err(|_| invalid())?),
+            "--events" => config.max_events = value.parse().map_err(|_| invalid())?,
+            "--rate" => config.arrival_rate = value.parse().map_err(|_| invalid())?,
+            "--fair-value" => config.initial_fair_value = value.parse().map_err(|_| invalid())?,
+            "--volatility" => config.volatility = value.parse().map_err(|_| invalid())?,
+            "--spread" => config.price_spread = value.parse().map_err(|_| invalid())?,
+            "--min-qty" => config.min_quantity = value.parse().map_err(|_| invalid())?,
+            "--max-qty" => config.max_quantity = value.parse().map_err(|_| invalid())?,
+            "--cancel-ratio" => config.cancel_ratio = value.parse().map_err(|_| invalid())?,
+            "--session" => config.session_length = Some(Duration::from_secs_f64(value.parse().map_err(|_| invalid())?)),
+            "--record" => record_directory = Some(PathBuf::from(value)),
+            "--tape" => tape_directory = Some(PathBuf::from(value)),
+            "--parquet" => parquet_directory = Some(PathBuf::from(value)),
+            "--distribution" => {
+                config.price_distribution = match value.as_str() {
+                    "normal" => PriceDistribution::Normal,
+                    "uniform" => PriceDistribution::Uniform,
+                    _ => return Err(format!("Unknown distribution: {}", value)),
+                }
+            }
+            "--mix" => {
+                let weights = value
+                    .split(',')
+                    .map(|weight| weight.trim().parse::<u32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("Invalid value for {}: {}", flag, value))?;
+                let [good_till_cancel, good_for_day, fill_and_kill, fill_or_kill, market] = weights[..] else {
+                    return Err(format!("--mix expects 5 weights, got {}", weights.len()));
+                };
+                config.order_type_mix = OrderTypeMix { good_till_cancel, good_for_day, fill_and_kill, fill_or_kill, market };
+            }
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    if verify && config.seed.is_none() {
+        return Err("--verify needs --seed".to_string());
+    }
+    Ok(Args { config, record_directory, tape_directory, parquet_directory, verify })
+}
+
+fn main() {
+    env_logger::init();
+
+    let Args { config, record_directory, tape_directory, parquet_directory, verify } = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let (true, Some(seed)) = (verify, config.seed) {
+        match DeterministicSimulation::new(seed, config).verify() {
+            Ok(run) => println!("Seed {} is reproducible: {} journal entries, {} trades", seed, run.entries.len(), run.stats.trades),
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+    let mut simulator = Simulator::new(config);
+    if let Some(directory) = record_directory {
+        match Recorder::new(RecorderConfig { directory, ..Default::default() }) {
+            Ok(recorder) => simulator.add_sink(recorder),
+            Err(err) => {
+                eprintln!("Failed to start recorder: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(directory) = tape_directory {
+        match TradeTape::new(TradeTapeConfig { directory, ..Default::default() }) {
+            Ok(tape) => simulator.add_sink(tape),
+            Err(err) => {
+                eprintln!("Failed to start trade tape: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(directory) = parquet_directory {
+        #[cfg(feature = "parquet")]
+        match ParquetExporter::new(ParquetExportConfig { directory, ..Default::default() }) {
+            Ok(exporter) => simulator.add_sink(exporter),
+            Err(err) => {
+                eprintln!("Failed to start Parquet export: {}", err);
+                process::exit(1);
+            }
+        }
+        #[cfg(not(feature = "parquet"))]
+        {
+            eprintln!("Cannot export {}: built without the `parquet` feature", directory.display());
+            process::exit(2);
+        }
+    }
+    let stats = simulator.run(&orderbook).clone();
+
+    let infos = orderbook.get_order_infos();
+    println!("Submitted:       {}", stats.submitted);
+    println!("Cancelled:       {}", stats.cancelled);
+    println!("Pruned (GFD):    {}", stats.pruned);
+    println!("Trades:          {}", stats.trades);
+    println!("Traded quantity: {}", stats.traded_quantity);
+    println!("Simulated time:  {:.3}s", stats.elapsed.as_secs_f64());
+    println!("Final fair value: {:.2}", simulator.get_fair_value());
+    println!("Resting orders:  {} ({} bid levels, {} ask levels)", orderbook.size(), infos.get_bids().len(), infos.get_asks().len());
+}
@@ -0,0 +1,273 @@
+//! # Bus Module
+//!
+//! Fans engine output out to several in-process consumers, e.g. a recorder, a metrics
+//! collector, a market data publisher and a risk check, each on its own thread.
+//!
+//! ## Model
+//! - [`EventBus::subscribe`] hands out a [`Subscriber`] with its own bounded queue, so a slow
+//!   consumer never delays the others.
+//! - [`EventBus::publish`] delivers one [`BusEvent`] to every subscriber;
+//!   [`EventBus::publish_book`] publishes the trades of a command together with the
+//!   [events](crate::events) and [end-of-day reports](crate::session) the book buffered
+//!   while running it.
+//! - What happens when a queue is full is chosen per subscriber with [`Overflow`]: the
+//!   engine either waits for the consumer or the event is dropped and counted.
+//! - Dropping a [`Subscriber`] unsubscribes it.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Order, OrderType, Orderbook, Side};
+//! use orderbook::bus::{EventBus, Overflow};
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.set_publish_events(true);
+//! let bus = EventBus::new();
+//! let metrics = bus.subscribe("metrics", 1024, Overflow::Drop);
+//!
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 5));
+//! let trades = ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 101, 5));
+//! bus.publish_book(&ob, &trades);
+//! let published = metrics.try_iter().count();
+//! assert!(published > 0);
+//! ```
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use log::debug;
+use crate::backtest::Command;
+use crate::events::{DepthDelta, SequencedEvent};
+use crate::orderbook::{Orderbook, Trade};
+use crate::session::EndOfDayReport;
+use crate::simulator::SimulationSink;
+
+/// One message on the bus.
+#[derive(Clone, Debug)]
+pub enum BusEvent {
+    Trade(Trade),
+    Order(SequencedEvent),
+    Depth(DepthDelta),
+    EndOfDay(EndOfDayReport),
+}
+
+/// What [`EventBus::publish`] does when a subscriber's queue is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    /// Wait until the subscriber makes room. Suited to consumers that must see every event,
+    /// such as a recorder, at the cost of stalling the engine behind them.
+    Block,
+    /// Drop the event for this subscriber and count it in [`Subscriber::get_dropped`].
+    Drop,
+}
+
+struct Subscription {
+    name: String,
+    sender: Sender<BusEvent>,
+    overflow: Overflow,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Receiving end of one subscription.
+pub struct Subscriber {
+    name: String,
+    receiver: Receiver<BusEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscriber {
+    /// Returns the name the subscriber registered with.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns how many events were dropped because the queue was full.
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the next event; `None` once the bus is gone and the queue is drained.
+    pub fn recv(&self) -> Option<BusEvent> {
+        self.receiver.recv().ok()
+    }
+
+    /// Returns the next event if one is queued.
+    pub fn try_recv(&self) -> Option<BusEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Iterates over the events queued right now without waiting.
+    pub fn try_iter(&self) -> impl Iterator<Item = BusEvent> + '_ {
+        self.receiver.try_iter()
+    }
+
+    /// Iterates over events until the bus is gone.
+    pub fn iter(&self) -> impl Iterator<Item = BusEvent> + '_ {
+        self.receiver.iter()
+    }
+}
+
+/// Publishes engine output to any number of [`Subscriber`]s.
+///
+/// Shared between threads behind an `Arc`; subscribing and publishing take `&self`.
+#[derive(Default)]
+pub struct EventBus {
+    subscriptions: Mutex<Vec<Subscription>>,
+}
+
+impl EventBus {
+    /// Creates a bus without subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes a consumer with a queue of `capacity` events.
+    ///
+    /// The subscriber only sees events published after this call.
+    ///
+    /// # Panics
+    /// If `capacity` is `0`.
+    pub fn subscribe(&self, name: &str, capacity: usize, overflow: Overflow) -> Subscriber {
+        assert!(capacity > 0, "EventBus: subscriber {} needs a queue", name);
+        let (sender, receiver) = bounded(capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.subscriptions.lock().unwrap().push(Subscription {
+            name: name.to_string(),
+            sender,
+            overflow,
+            dropped: dropped.clone(),
+        });
+        debug!("EventBus: {} subscribed with capacity {} ({:?})", name, capacity, overflow);
+        Subscriber { name: name.to_string(), receiver, dropped }
+    }
+
+    /// Returns the number of live subscribers.
+    pub fn get_subscriber_count(&self) -> usize {
+        self.subscriptions.lock().unwrap().len()
+    }
+
+    /// Delivers `event` to every subscriber, dropping subscriptions whose [`Subscriber`] is gone.
+    ///
+    /// A [`Overflow::Block`] subscriber that is full holds up the call, and every subscriber
+    /// after it, until it catches up.
+    ///
+    /// # Returns
+    /// The number of subscribers the event was queued for.
+    pub fn publish(&self, event: BusEvent) -> usize {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let mut delivered = 0;
+        subscriptions.retain(|subscription| {
+            let result = match subscription.overflow {
+                Overflow::Block => subscription.sender.send(event.clone()).map_err(|err| TrySendError::Disconnected(err.into_inner())),
+                Overflow::Drop => subscription.sender.try_send(event.clone()),
+            };
+            match result {
+                Ok(()) => {
+                    delivered += 1;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    subscription.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    debug!("EventBus: {} unsubscribed", subscription.name);
+                    false
+                }
+            }
+        });
+        delivered
+    }
+
+    /// Publishes `trades`, then the order events, depth deltas and end-of-day reports buffered
+    /// in `orderbook`.
+    ///
+    /// `orderbook` needs [`set_publish_events`](Orderbook::set_publish_events) turned on for
+    /// anything but trades to show up.
+    pub fn publish_book(&self, orderbook: &Orderbook, trades: &[Trade]) {
+        for trade in trades {
+            self.publish(BusEvent::Trade(trade.clone()));
+        }
+        for event in orderbook.take_events() {
+            self.publish(BusEvent::Order(event));
+        }
+        for delta in orderbook.take_depth_deltas() {
+            self.publish(BusEvent::Depth(delta));
+        }
+        for report in orderbook.take_session_reports() {
+            self.publish(BusEvent::EndOfDay(report));
+        }
+    }
+}
+
+/// Publishes every trade, and the book's buffered events, after each command.
+impl SimulationSink for Arc<EventBus> {
+    fn on_applied(&mut self, orderbook: &Orderbook, _timestamp: Duration, _command: Command, trades: &[Trade]) -> Result<(), String> {
+        self.publish_book(orderbook, trades);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::BTreeMap, thread};
+    use crate::orderbook::{Order, OrderType, Side};
+
+    fn count(subscriber: &Subscriber) -> (usize, usize, usize) {
+        subscriber.try_iter().fold((0, 0, 0), |(trades, orders, depth), event| match event {
+            BusEvent::Trade(_) => (trades + 1, orders, depth),
+            BusEvent::Order(_) => (trades, orders + 1, depth),
+            BusEvent::Depth(_) => (trades, orders, depth + 1),
+            BusEvent::EndOfDay(_) => (trades, orders, depth),
+        })
+    }
+
+    #[test]
+    fn test_every_subscriber_sees_every_event(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        let bus = EventBus::new();
+        let recorder = bus.subscribe("recorder", 64, Overflow::Block);
+        let risk = bus.subscribe("risk", 64, Overflow::Drop);
+
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 5));
+        bus.publish_book(&ob, &[]);
+        let trades = ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 101, 3));
+        bus.publish_book(&ob, &trades);
+
+        // One trade; both orders added and executed; each level added, then changed.
+        assert_eq!(count(&recorder), (1, 4, 4));
+        assert_eq!(count(&risk), (1, 4, 4));
+        assert_eq!((recorder.get_dropped(), risk.get_dropped()), (0, 0));
+    }
+
+    #[test]
+    fn test_full_queues_drop_or_block(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        let bus = Arc::new(EventBus::new());
+        let metrics = bus.subscribe("metrics", 2, Overflow::Drop);
+        let recorder = bus.subscribe("recorder", 2, Overflow::Block);
+        let gone = bus.subscribe("gone", 2, Overflow::Drop);
+        drop(gone);
+
+        let consumer = thread::spawn(move || recorder.iter().count());
+        for order_id in 1..=5 {
+            ob.add_order(Order::new(OrderType::GoodTillCancel, order_id, Side::Buy, 100 - order_id as i32, 1));
+            bus.publish_book(&ob, &[]);
+        }
+        assert_eq!(bus.get_subscriber_count(), 2);
+        drop(bus);
+
+        // The recorder got all ten events; metrics kept the first two.
+        assert_eq!(consumer.join().unwrap(), 10);
+        assert_eq!(metrics.try_iter().count(), 2);
+        assert_eq!(metrics.get_dropped(), 8);
+    }
+}
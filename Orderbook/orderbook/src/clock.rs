@@ -0,0 +1,190 @@
+//! # Clock Module
+//!
+//! Where time-dependent logic, such as the book's end-of-day prune of GoodForDay orders, gets
+//! the time from, so it can run on something other than the wall clock.
+//!
+//! ## Clocks
+//! - [`SystemClock`]: the wall clock.
+//! - [`MockClock`]: stands still until a test sets or advances it.
+//! - [`VirtualClock`]: time since the start of a simulated run, moved forward by the
+//!   [`simulator`](crate::simulator); a run takes the same course however fast it executes.
+//!
+//! Clones of a mock or virtual clock share the same time, so one component can drive the
+//! clock while others read it.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use orderbook::clock::{Clock, VirtualClock};
+//!
+//! let clock = VirtualClock::new();
+//! let reader = clock.clone();
+//! clock.advance(Duration::from_millis(5));
+//! assert_eq!(reader.elapsed(), Duration::from_millis(5));
+//! assert_eq!(reader.now().timestamp_millis(), 5);
+//! ```
+
+use std::{fmt::Debug, sync::{Arc, Mutex}, time::Duration};
+use chrono::{DateTime, TimeDelta, Utc};
+use crate::orderbook::Orderbook;
+use crate::simulator::SimulationSink;
+
+/// Longest real wait on a clock that real time does not move, before looking at it again.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Returns how long to really wait for `duration` to pass on this clock.
+    ///
+    /// Clocks that real time does not move return at most [`POLL_INTERVAL`], so waiters notice
+    /// when the clock is moved.
+    fn real_wait(&self, duration: Duration) -> Duration {
+        duration
+    }
+}
+
+/// The wall clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, and may be moved back.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    /// Creates a clock standing at `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now: Arc::new(Mutex::new(now)) }
+    }
+
+    /// Moves the clock to `now`.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn real_wait(&self, duration: Duration) -> Duration {
+        duration.min(POLL_INTERVAL)
+    }
+}
+
+/// Manually driven time since the start of a run, shared between clones.
+///
+/// As a [`Clock`] it reads as its epoch plus the time elapsed.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    epoch: DateTime<Utc>,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::starting_at(DateTime::UNIX_EPOCH)
+    }
+}
+
+impl VirtualClock {
+    /// Creates a clock at time zero, reading as the Unix epoch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a clock at time zero, reading as `epoch`.
+    pub fn starting_at(epoch: DateTime<Utc>) -> Self {
+        Self { epoch, elapsed: Arc::new(Mutex::new(Duration::ZERO)) }
+    }
+
+    /// Returns the virtual time since the start of the run.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+
+    /// Moves the clock forward by `duration`.
+    ///
+    /// # Returns
+    /// The new time since the start of the run.
+    pub fn advance(&self, duration: Duration) -> Duration {
+        let mut elapsed = self.elapsed.lock().unwrap();
+        *elapsed += duration;
+        *elapsed
+    }
+
+    /// Moves the clock to `elapsed` since the start of the run, which must not be in the past.
+    ///
+    /// # Errors
+    /// If `elapsed` is in the past; the clock is left unchanged.
+    pub fn set(&self, elapsed: Duration) -> Result<(), String> {
+        let mut now = self.elapsed.lock().unwrap();
+        if elapsed < *now {
+            return Err(format!("Cannot move the clock back from {:?} to {:?}", *now, elapsed));
+        }
+        *now = elapsed;
+        Ok(())
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.epoch + TimeDelta::from_std(self.elapsed()).unwrap_or(TimeDelta::MAX)
+    }
+
+    fn real_wait(&self, duration: Duration) -> Duration {
+        duration.min(POLL_INTERVAL)
+    }
+}
+
+/// Moves forward with the simulated time, so other components can read it.
+impl SimulationSink for VirtualClock {
+    fn on_advance(&mut self, _orderbook: &Orderbook, _timestamp: Duration, wait: Duration) -> Result<(), String> {
+        self.advance(wait);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock_never_goes_back(){
+        let clock = VirtualClock::new();
+        clock.set(Duration::from_secs(10)).unwrap();
+        assert_eq!(clock.advance(Duration::from_secs(1)), Duration::from_secs(11));
+        assert!(clock.set(Duration::from_secs(5)).is_err());
+        assert_eq!(clock.elapsed(), Duration::from_secs(11));
+    }
+
+    #[test]
+    fn test_mock_clock_is_shared_between_clones(){
+        let start = DateTime::parse_from_rfc3339("2026-10-16T15:59:00Z").unwrap().to_utc();
+        let clock = MockClock::new(start);
+        let reader: Arc<dyn Clock> = Arc::new(clock.clone());
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(reader.now(), start + TimeDelta::seconds(90));
+        assert_eq!(reader.real_wait(Duration::from_secs(3600)), POLL_INTERVAL);
+        assert_eq!(SystemClock.real_wait(Duration::from_secs(3600)), Duration::from_secs(3600));
+    }
+}
@@ -0,0 +1,378 @@
+//! # Columnar Module
+//!
+//! Exports trades and depth changes as Parquet files for large-scale analysis of simulator
+//! and load-test output in pandas, Polars, DuckDB or Spark. Only built with the `parquet`
+//! feature.
+//!
+//! ## Files
+//! Rows are buffered into Arrow record batches of [`ParquetExportConfig::rows_per_batch`]
+//! rows, and every batch is written as one file of a Hive-style partition, so readers can
+//! prune by date and symbol:
+//!
+//! ```text
+//! <directory>/trades/date=2026-10-16/symbol=ACME/part-0001.parquet
+//! <directory>/depth/date=2026-10-16/symbol=ACME/part-0001.parquet
+//! ```
+//!
+//! - `trades`: `timestamp,bid_order_id,bid_price,ask_order_id,ask_price,quantity`
+//! - `depth`: `timestamp,side,price,quantity`, one row per changed level;
+//!   a quantity of `0` means the level was removed.
+//!
+//! The columns match the [recorder](crate::recorder)'s CSV files. `timestamp` is a UTC
+//! microsecond timestamp: the caller's clock offset added to [`ParquetExportConfig::start`].
+//! A batch is cut early when the date changes so that every file belongs to one partition.
+//!
+//! The [`Simulator`](crate::simulator::Simulator) writes one with
+//! [`Simulator::add_sink`](crate::simulator::Simulator::add_sink).
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use arrow_array::{ArrayRef, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt32Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use log::info;
+use parquet::arrow::ArrowWriter;
+use crate::backtest::{side_code, Command};
+use crate::orderbook::{Orderbook, OrderbookLevelInfos, Price, Quantity, Side, Trade};
+use crate::recorder::changed_levels;
+use crate::simulator::SimulationSink;
+
+/// Where and how a [`ParquetExporter`] writes its files.
+#[derive(Clone, Debug)]
+pub struct ParquetExportConfig {
+    /// Output directory; created if missing.
+    pub directory: PathBuf,
+    /// Symbol partition every file is written to.
+    pub symbol: String,
+    /// Wall-clock time of timestamp zero on the caller's clock.
+    pub start: DateTime<Utc>,
+    /// Rows buffered before a batch is written out as a file.
+    pub rows_per_batch: usize,
+}
+
+impl Default for ParquetExportConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("parquet"),
+            symbol: "SIM".to_string(),
+            start: Utc::now(),
+            rows_per_batch: 100_000,
+        }
+    }
+}
+
+/// Batches trades and depth changes of one book into partitioned Parquet files.
+pub struct ParquetExporter {
+    directory: PathBuf,
+    symbol: String,
+    start: DateTime<Utc>,
+    rows_per_batch: usize,
+    trades: TradeBatch,
+    depth: DepthBatch,
+    /// Files written so far per partition directory, used to number the next one.
+    parts: BTreeMap<PathBuf, usize>,
+    /// Last exported quantity per bid level, used to emit only changed levels.
+    last_bids: BTreeMap<Price, Quantity>,
+    /// Last exported quantity per ask level, used to emit only changed levels.
+    last_asks: BTreeMap<Price, Quantity>,
+}
+
+impl ParquetExporter {
+    /// Creates the output directory.
+    ///
+    /// # Errors
+    /// Returns any I/O error from creating the directory.
+    pub fn new(config: ParquetExportConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.directory)?;
+        info!("ParquetExporter: writing to {}", config.directory.display());
+        Ok(Self {
+            directory: config.directory,
+            symbol: config.symbol,
+            start: config.start,
+            rows_per_batch: config.rows_per_batch.max(1),
+            trades: TradeBatch::default(),
+            depth: DepthBatch::default(),
+            parts: BTreeMap::new(),
+            last_bids: BTreeMap::new(),
+            last_asks: BTreeMap::new(),
+        })
+    }
+
+    /// Buffers one row per trade, writing out the batch whenever it fills up or the date changes.
+    ///
+    /// # Errors
+    /// Returns any error from writing a full batch.
+    pub fn record_trades(&mut self, timestamp: Duration, trades: &[Trade]) -> io::Result<()> {
+        let (date, micros) = self.resolve(timestamp)?;
+        for trade in trades {
+            if self.trades.date.is_some_and(|current| current != date) || self.trades.len() == self.rows_per_batch {
+                self.write_trades()?;
+            }
+            self.trades.push(date, micros, trade);
+        }
+        Ok(())
+    }
+
+    /// Buffers a row for every level whose quantity changed since the last exported depth.
+    ///
+    /// # Errors
+    /// Returns any error from writing a full batch.
+    pub fn record_depth(&mut self, timestamp: Duration, infos: &OrderbookLevelInfos) -> io::Result<()> {
+        let (date, micros) = self.resolve(timestamp)?;
+        let bids = changed_levels(&mut self.last_bids, infos.get_bids());
+        let asks = changed_levels(&mut self.last_asks, infos.get_asks());
+
+        for (side, changes) in [(Side::Buy, bids), (Side::Sell, asks)] {
+            for (price, quantity) in changes {
+                if self.depth.date.is_some_and(|current| current != date) || self.depth.len() == self.rows_per_batch {
+                    self.write_depth()?;
+                }
+                self.depth.push(date, micros, side, price, quantity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes out both partially filled batches.
+    ///
+    /// # Errors
+    /// Returns any I/O or Parquet error from writing the files.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_trades()?;
+        self.write_depth()
+    }
+
+    /// Returns the date partition and UTC microsecond timestamp of `timestamp`.
+    fn resolve(&self, timestamp: Duration) -> io::Result<(NaiveDate, i64)> {
+        let time = TimeDelta::from_std(timestamp)
+            .ok()
+            .and_then(|offset| self.start.checked_add_signed(offset))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Timestamp {:?} is out of range", timestamp)))?;
+        Ok((time.date_naive(), time.timestamp_micros()))
+    }
+
+    fn write_trades(&mut self) -> io::Result<()> {
+        let Some(date) = self.trades.date else {
+            return Ok(());
+        };
+        let batch = std::mem::take(&mut self.trades).into_record_batch().map_err(io::Error::other)?;
+        self.write_batch("trades", date, &batch)
+    }
+
+    fn write_depth(&mut self) -> io::Result<()> {
+        let Some(date) = self.depth.date else {
+            return Ok(());
+        };
+        let batch = std::mem::take(&mut self.depth).into_record_batch().map_err(io::Error::other)?;
+        self.write_batch("depth", date, &batch)
+    }
+
+    /// Writes `batch` as the next file of the `kind`/`date`/symbol partition.
+    fn write_batch(&mut self, kind: &str, date: NaiveDate, batch: &RecordBatch) -> io::Result<()> {
+        let partition = partition_directory(&self.directory, kind, date, &self.symbol);
+        fs::create_dir_all(&partition)?;
+        let part = self.parts.entry(partition.clone()).or_insert(0);
+        *part += 1;
+        let path = partition.join(format!("part-{:04}.parquet", part));
+
+        let mut writer = ArrowWriter::try_new(File::create(&path)?, batch.schema(), None).map_err(io::Error::other)?;
+        writer.write(batch).map_err(io::Error::other)?;
+        writer.close().map_err(io::Error::other)?;
+        info!("ParquetExporter: wrote {} rows to {}", batch.num_rows(), path.display());
+        Ok(())
+    }
+}
+
+/// Exports every trade and depth change.
+impl SimulationSink for ParquetExporter {
+    fn on_applied(&mut self, orderbook: &Orderbook, timestamp: Duration, _command: Command, trades: &[Trade]) -> Result<(), String> {
+        self.record_trades(timestamp, trades)
+            .and_then(|_| self.record_depth(timestamp, &orderbook.get_order_infos()))
+            .map_err(|err| format!("Parquet export failed: {}", err))
+    }
+}
+
+impl Drop for ParquetExporter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Returns the Hive-style directory of the `kind` files for `date` and `symbol`.
+pub fn partition_directory(directory: &Path, kind: &str, date: NaiveDate, symbol: &str) -> PathBuf {
+    directory.join(kind).join(format!("date={}", date.format("%Y-%m-%d"))).join(format!("symbol={}", symbol))
+}
+
+/// Schema of the `timestamp` column shared by both file kinds.
+fn timestamp_field() -> Field {
+    Field::new("timestamp", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false)
+}
+
+/// Schema of the trades files.
+pub fn trade_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        timestamp_field(),
+        Field::new("bid_order_id", DataType::UInt32, false),
+        Field::new("bid_price", DataType::Int32, false),
+        Field::new("ask_order_id", DataType::UInt32, false),
+        Field::new("ask_price", DataType::Int32, false),
+        Field::new("quantity", DataType::UInt32, false),
+    ]))
+}
+
+/// Schema of the depth files.
+pub fn depth_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        timestamp_field(),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("price", DataType::Int32, false),
+        Field::new("quantity", DataType::UInt32, false),
+    ]))
+}
+
+/// Column buffers of the trades batch being filled.
+#[derive(Default)]
+struct TradeBatch {
+    date: Option<NaiveDate>,
+    timestamps: Vec<i64>,
+    bid_order_ids: Vec<u32>,
+    bid_prices: Vec<Price>,
+    ask_order_ids: Vec<u32>,
+    ask_prices: Vec<Price>,
+    quantities: Vec<Quantity>,
+}
+
+impl TradeBatch {
+    fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    fn push(&mut self, date: NaiveDate, micros: i64, trade: &Trade) {
+        let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+        self.date = Some(date);
+        self.timestamps.push(micros);
+        self.bid_order_ids.push(bid.order_id);
+        self.bid_prices.push(bid.price);
+        self.ask_order_ids.push(ask.order_id);
+        self.ask_prices.push(ask.price);
+        self.quantities.push(bid.quantity);
+    }
+
+    fn into_record_batch(self) -> Result<RecordBatch, arrow_schema::ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMicrosecondArray::from(self.timestamps).with_timezone("UTC")),
+            Arc::new(UInt32Array::from(self.bid_order_ids)),
+            Arc::new(Int32Array::from(self.bid_prices)),
+            Arc::new(UInt32Array::from(self.ask_order_ids)),
+            Arc::new(Int32Array::from(self.ask_prices)),
+            Arc::new(UInt32Array::from(self.quantities)),
+        ];
+        RecordBatch::try_new(trade_schema(), columns)
+    }
+}
+
+/// Column buffers of the depth batch being filled.
+#[derive(Default)]
+struct DepthBatch {
+    date: Option<NaiveDate>,
+    timestamps: Vec<i64>,
+    sides: Vec<&'static str>,
+    prices: Vec<Price>,
+    quantities: Vec<Quantity>,
+}
+
+impl DepthBatch {
+    fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    fn push(&mut self, date: NaiveDate, micros: i64, side: Side, price: Price, quantity: Quantity) {
+        self.date = Some(date);
+        self.timestamps.push(micros);
+        self.sides.push(side_code(side));
+        self.prices.push(price);
+        self.quantities.push(quantity);
+    }
+
+    fn into_record_batch(self) -> Result<RecordBatch, arrow_schema::ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMicrosecondArray::from(self.timestamps).with_timezone("UTC")),
+            Arc::new(StringArray::from(self.sides)),
+            Arc::new(Int32Array::from(self.prices)),
+            Arc::new(UInt32Array::from(self.quantities)),
+        ];
+        RecordBatch::try_new(depth_schema(), columns)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use crate::orderbook::{LevelInfo, OrderFlags, TradeInfo};
+
+    fn read_batches(path: &Path) -> Vec<RecordBatch> {
+        ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_trades_are_batched_and_partitioned_by_date(){
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let start = DateTime::parse_from_rfc3339("2026-10-16T23:59:59Z").unwrap().with_timezone(&Utc);
+        let mut exporter = ParquetExporter::new(ParquetExportConfig { directory: directory.to_path_buf(), symbol: "ACME".to_string(), start, rows_per_batch: 2 }).unwrap();
+        let trade = |bid, ask, quantity| Trade::new(
+            TradeInfo { order_id: bid, price: 100, quantity, flags: OrderFlags::default() },
+            TradeInfo { order_id: ask, price: 99, quantity, flags: OrderFlags::default() },
+        );
+        exporter.record_trades(Duration::ZERO, &[trade(1, 2, 5), trade(1, 3, 4), trade(1, 4, 3)]).unwrap();
+        exporter.record_trades(Duration::from_secs(2), &[trade(5, 6, 1)]).unwrap();
+        exporter.flush().unwrap();
+
+        let first_day = partition_directory(directory, "trades", NaiveDate::from_ymd_opt(2026, 10, 16).unwrap(), "ACME");
+        let second_day = partition_directory(directory, "trades", NaiveDate::from_ymd_opt(2026, 10, 17).unwrap(), "ACME");
+        assert_eq!(read_batches(&first_day.join("part-0001.parquet"))[0].num_rows(), 2);
+        assert_eq!(read_batches(&first_day.join("part-0002.parquet"))[0].num_rows(), 1);
+        assert!(!first_day.join("part-0003.parquet").exists());
+
+        let batch = &read_batches(&second_day.join("part-0001.parquet"))[0];
+        assert_eq!(batch.schema(), trade_schema());
+        let timestamps = batch.column(0).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+        assert_eq!(timestamps.value(0), start.timestamp_micros() + 2_000_000);
+        let ask_order_ids = batch.column(3).as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!(ask_order_ids.value(0), 6);
+    }
+
+    #[test]
+    fn test_depth_exports_only_changed_levels(){
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let start = DateTime::parse_from_rfc3339("2026-10-16T09:30:00Z").unwrap().with_timezone(&Utc);
+        let mut exporter = ParquetExporter::new(ParquetExportConfig { directory: directory.to_path_buf(), symbol: "ACME".to_string(), start, rows_per_batch: 100 }).unwrap();
+        let level = |price, quantity| LevelInfo { price, quantity };
+
+        exporter.record_depth(Duration::from_micros(1), &OrderbookLevelInfos::new(vec![level(99, 10), level(100, 5)], vec![level(101, 7)])).unwrap();
+        exporter.record_depth(Duration::from_micros(2), &OrderbookLevelInfos::new(vec![level(99, 10), level(100, 8)], vec![])).unwrap();
+        drop(exporter);
+
+        let path = partition_directory(directory, "depth", start.date_naive(), "ACME").join("part-0001.parquet");
+        let batch = &read_batches(&path)[0];
+        let sides = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        let quantities = batch.column(3).as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!(batch.num_rows(), 5);
+        assert_eq!(sides.iter().flatten().collect::<Vec<_>>(), vec!["BUY", "BUY", "SELL", "BUY", "SELL"]);
+        assert_eq!(quantities.values().to_vec(), vec![10, 5, 7, 8, 0]);
+    }
+}
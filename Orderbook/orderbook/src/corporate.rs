@@ -0,0 +1,182 @@
+//! # Corporate Actions Module
+//!
+//! Re-pricing a whole book for a split, a reverse split or a re-denomination.
+//!
+//! ## Model
+//! A [`PriceAdjustment`] multiplies prices and quantities by fractions. A 2-for-1 split halves
+//! prices and doubles quantities; a re-denomination from units to cents multiplies prices by
+//! 100 and leaves quantities alone.
+//!
+//! [`Orderbook::adjust_orders`](crate::Orderbook::adjust_orders) applies one to every resting
+//! order under a single lock, so no order is matched against a half-adjusted book:
+//! - bids are rounded down and asks up, so the adjusted book never crosses,
+//! - remaining and filled quantities are rounded down; an order left with nothing is cancelled,
+//! - levels are rebuilt with their aggregates and depth, keeping price-time priority;
+//!   orders at levels that merge keep the priority of their old level,
+//! - every adjusted order is published as [`BookEvent::OrderReplaced`](crate::events::BookEvent::OrderReplaced)
+//!   in its new queue order, every cancelled one as `OrderCancelled`,
+//! - reference prices and the last sale are adjusted to the nearest price.
+//!
+//! The adjustment is checked against every order first and nothing changes if a price or
+//! quantity would not fit.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Order, OrderType, Orderbook, Side};
+//! use orderbook::corporate::PriceAdjustment;
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 101, 10));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 105, 10));
+//!
+//! let report = ob.adjust_orders(&PriceAdjustment::split(2, 1)).unwrap();
+//! assert_eq!(report.adjusted, 2);
+//! assert_eq!(ob.get_depth_snapshot().bids, [(50, 20)]);
+//! assert_eq!(ob.get_depth_snapshot().asks, [(53, 20)]);
+//! ```
+
+use crate::orderbook::{OrderId, Price, Quantity, Side};
+
+/// Factors a corporate action applies to prices and quantities.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceAdjustment {
+    pub price_numerator: u32,
+    pub price_denominator: u32,
+    pub quantity_numerator: u32,
+    pub quantity_denominator: u32,
+}
+
+impl PriceAdjustment {
+    /// A split of `old` shares into `new`, e.g. `split(3, 2)` for a 3-for-2 split; a reverse
+    /// split has `new < old`.
+    pub const fn split(new: u32, old: u32) -> Self {
+        Self { price_numerator: old, price_denominator: new, quantity_numerator: new, quantity_denominator: old }
+    }
+
+    /// Multiplies prices by `numerator / denominator`, e.g. `redenominate(100, 1)` to quote in
+    /// cents instead of units.
+    pub const fn redenominate(numerator: u32, denominator: u32) -> Self {
+        Self { price_numerator: numerator, price_denominator: denominator, quantity_numerator: 1, quantity_denominator: 1 }
+    }
+
+    /// Checks that every factor is positive.
+    ///
+    /// # Errors
+    /// Names the first factor that is zero.
+    pub fn validate(&self) -> Result<(), String> {
+        let factors = [
+            (self.price_numerator, "price numerator"),
+            (self.price_denominator, "price denominator"),
+            (self.quantity_numerator, "quantity numerator"),
+            (self.quantity_denominator, "quantity denominator"),
+        ];
+        match factors.into_iter().find(|(factor, _)| *factor == 0) {
+            Some((_, name)) => Err(format!("Adjustment {} must be positive", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the adjusted price of an order on `side`: bids rounded down, asks up.
+    ///
+    /// # Errors
+    /// Returns an error if the adjusted price does not fit a [`Price`].
+    pub fn adjust_price(&self, side: Side, price: Price) -> Result<Price, String> {
+        let (scaled, denominator) = (i64::from(price) * i64::from(self.price_numerator), i64::from(self.price_denominator));
+        let adjusted = match side {
+            Side::Buy => scaled.div_euclid(denominator),
+            Side::Sell => -(-scaled).div_euclid(denominator),
+        };
+        Price::try_from(adjusted).map_err(|_| format!("Adjusted price of {} is out of range", price))
+    }
+
+    /// Returns the adjusted reference price, rounded to the nearest price.
+    ///
+    /// # Errors
+    /// Returns an error if the adjusted price does not fit a [`Price`].
+    pub fn adjust_reference(&self, price: Price) -> Result<Price, String> {
+        let (scaled, denominator) = (i64::from(price) * i64::from(self.price_numerator), i64::from(self.price_denominator));
+        let adjusted = (2 * scaled + denominator).div_euclid(2 * denominator);
+        Price::try_from(adjusted).map_err(|_| format!("Adjusted price of {} is out of range", price))
+    }
+
+    /// Returns the adjusted quantity, rounded down.
+    ///
+    /// # Errors
+    /// Returns an error if the adjusted quantity does not fit a [`Quantity`].
+    pub fn adjust_quantity(&self, quantity: Quantity) -> Result<Quantity, String> {
+        let adjusted = u64::from(quantity) * u64::from(self.quantity_numerator) / u64::from(self.quantity_denominator);
+        Quantity::try_from(adjusted).map_err(|_| format!("Adjusted quantity of {} is out of range", quantity))
+    }
+}
+
+/// What [`Orderbook::adjust_orders`](crate::Orderbook::adjust_orders) did to the book.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct AdjustmentReport {
+    /// Orders re-priced and resized.
+    pub adjusted: usize,
+    /// Orders cancelled because nothing was left of them, in the order they were found.
+    pub cancelled: Vec<OrderId>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::events::BookEvent;
+    use crate::orderbook::{Order, OrderType, Orderbook, ReferencePrices};
+
+    #[test]
+    fn test_rounding(){
+        let reverse = PriceAdjustment::split(1, 3);
+        assert_eq!((reverse.adjust_price(Side::Buy, 101), reverse.adjust_price(Side::Sell, 101)), (Ok(303), Ok(303)));
+        assert_eq!(reverse.adjust_quantity(8), Ok(2));
+        let split = PriceAdjustment::split(3, 1);
+        assert_eq!((split.adjust_price(Side::Buy, -5), split.adjust_price(Side::Sell, -5)), (Ok(-2), Ok(-1)));
+        assert_eq!(split.adjust_reference(-5), Ok(-2));
+        assert_eq!(split.adjust_reference(5), Ok(2));
+        assert!(PriceAdjustment::redenominate(100, 1).adjust_price(Side::Buy, Price::MAX).is_err());
+        assert_eq!(PriceAdjustment::redenominate(1, 0).validate(), Err("Adjustment price denominator must be positive".to_string()));
+    }
+
+    #[test]
+    fn test_reverse_split_rebuilds_the_book(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 10, 7));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 11, 4));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 9, 2));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 12, 6));
+        ob.add_order(Order::new(OrderType::FillAndKill, 5, Side::Buy, 12, 1)); // leaves order 4 with 5
+        ob.set_reference_prices(ReferencePrices { previous_close: Some(13), ..ob.get_reference_prices() });
+        ob.set_publish_events(true);
+
+        // Nothing changes if one order does not fit.
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 6, Side::Sell, Price::MAX / 2, 1));
+        assert!(ob.adjust_orders(&PriceAdjustment::split(1, 3)).is_err());
+        ob.cancel_order(6);
+        let _ = ob.take_events();
+
+        // 1-for-3: prices triple, quantities shrink to a third.
+        let report = ob.adjust_orders(&PriceAdjustment::split(1, 3)).unwrap();
+        assert_eq!(report, AdjustmentReport { adjusted: 3, cancelled: vec![3] });
+        let snapshot = ob.get_depth_snapshot();
+        assert_eq!((snapshot.bids, snapshot.asks), (vec![(33, 1), (30, 2)], vec![(36, 1)]));
+        let ask = &ob.get_resting_orders(Side::Sell)[0];
+        assert_eq!((ask.get_initial_quantity(), ask.get_filled_quantity()), (1, 0));
+        assert_eq!(ob.get_reference_prices(), ReferencePrices { previous_close: Some(39), open: Some(36), last: Some(36) });
+        assert_eq!(ob.get_last_sale().unwrap().price, 36);
+
+        let events: Vec<BookEvent> = ob.take_events().into_iter().map(|event| event.event).collect();
+        assert_eq!(events, [
+            BookEvent::OrderReplaced { order_id: 2, side: Side::Buy, price: 33, quantity: 1 },
+            BookEvent::OrderReplaced { order_id: 1, side: Side::Buy, price: 30, quantity: 2 },
+            BookEvent::OrderCancelled { order_id: 3, side: Side::Buy, price: 9, quantity: 2 },
+            BookEvent::OrderReplaced { order_id: 4, side: Side::Sell, price: 36, quantity: 1 },
+        ]);
+
+        // The adjusted book matches as usual.
+        let trades = ob.add_order(Order::new(OrderType::GoodTillCancel, 7, Side::Sell, 30, 3));
+        assert_eq!(trades.iter().map(|trade| trade.get_bid_trade().order_id).collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(ob.size(), 1);
+    }
+}
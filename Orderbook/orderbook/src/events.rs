@@ -0,0 +1,233 @@
+//! # Events Module
+//!
+//! Market data published by the matching engine: order-by-order (Level 3) events and
+//! per-level (Level 2) depth deltas.
+//!
+//! Once [`Orderbook::set_publish_events`](crate::Orderbook::set_publish_events) is on, every
+//! change to a resting order is buffered in the book as a [`SequencedEvent`] and handed out by
+//! [`Orderbook::take_events`](crate::Orderbook::take_events). Replaying the events in sequence
+//! order gives every order's price, remaining quantity and position in its level's queue.
+//! Every change to a level's total quantity is buffered as a [`DepthDelta`] and handed out by
+//! [`Orderbook::take_depth_deltas`](crate::Orderbook::take_depth_deltas).
+//!
+//! ## Order events
+//! - [`BookEvent::OrderAdded`]: an order entered the book; it joins the back of its level.
+//!   It carries the order's [`OrderFlags`], which stay with it until it leaves the book.
+//!   Market orders are reported at the price they were converted to. FillAndKill and
+//!   FillOrKill orders that are rejected up front never appear.
+//! - [`BookEvent::OrderExecuted`]: a resting or incoming order traded. An order whose
+//!   `remaining` is `0` has left the book.
+//! - [`BookEvent::OrderCancelled`]: an order left the book unfilled, by request, by the
+//!   end-of-day prune, or as the unfilled rest of a FillAndKill order.
+//! - [`BookEvent::OrderReplaced`]: an order was modified; it left its old place and rejoined
+//!   the back of the level at its new price and quantity, keeping its id.
+//!
+//! Order ids are the ids the orders were submitted with, so they are stable for the life of
+//! an order and unique within a book. Sequence numbers start at 1 and have no gaps, so a
+//! consumer can tell when it missed an event.
+//!
+//! ## Depth deltas
+//! Each [`DepthDelta`] carries the new total quantity of one level and whether the level
+//! appeared, changed or disappeared. An incoming order that crosses shows up on its own side
+//! first and is then reduced by its executions, as it happens inside the engine.
+//!
+//! Depth deltas have their own sequence, which advances even while publishing is off. A
+//! consumer that joins late starts from
+//! [`Orderbook::get_depth_snapshot`](crate::Orderbook::get_depth_snapshot) and applies only
+//! deltas with a higher sequence. [`DepthFeed`] interleaves deltas with such snapshots at a
+//! fixed interval, for feeds that late joiners pick up mid-stream. The
+//! [FlatBuffers module](crate::flatbuf) encodes its messages for the wire.
+
+use std::collections::BTreeMap;
+use crate::orderbook::{OrderFlags, OrderId, Orderbook, Price, Quantity, Side};
+
+/// A change to one order in the book.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookEvent {
+    /// An order joined the back of the queue at `price`.
+    OrderAdded { order_id: OrderId, side: Side, price: Price, quantity: Quantity, flags: OrderFlags },
+    /// An order traded `quantity` at its own `price`, leaving `remaining` in the book.
+    OrderExecuted { order_id: OrderId, side: Side, price: Price, quantity: Quantity, remaining: Quantity },
+    /// An order left the book with `quantity` unfilled.
+    OrderCancelled { order_id: OrderId, side: Side, price: Price, quantity: Quantity },
+    /// An order moved to the back of the queue at `price` with `quantity` remaining.
+    OrderReplaced { order_id: OrderId, side: Side, price: Price, quantity: Quantity },
+}
+
+impl BookEvent {
+    /// Returns the id of the order the event is about.
+    pub const fn get_order_id(&self) -> OrderId {
+        match *self {
+            BookEvent::OrderAdded { order_id, .. }
+            | BookEvent::OrderExecuted { order_id, .. }
+            | BookEvent::OrderCancelled { order_id, .. }
+            | BookEvent::OrderReplaced { order_id, .. } => order_id,
+        }
+    }
+}
+
+/// A [`BookEvent`] numbered in the order the book produced it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SequencedEvent {
+    /// Position of the event in the book's stream, starting at 1.
+    pub sequence: u64,
+    pub event: BookEvent,
+}
+
+/// How a [`DepthDelta`] changed its level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthAction {
+    /// The level did not exist before.
+    Added,
+    /// The level's quantity changed.
+    Updated,
+    /// The level is gone; its quantity is `0`.
+    Removed,
+}
+
+/// A change to the total quantity of one price level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthDelta {
+    /// Position of the delta in the book's depth stream, starting at 1.
+    pub sequence: u64,
+    pub side: Side,
+    pub price: Price,
+    /// Total remaining quantity at the level after the change.
+    pub quantity: Quantity,
+    pub action: DepthAction,
+}
+
+/// Full depth of a book, both sides best price first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DepthSnapshot {
+    /// Sequence of the last [`DepthDelta`] included, `0` if none.
+    pub sequence: u64,
+    pub bids: Vec<(Price, Quantity)>,
+    pub asks: Vec<(Price, Quantity)>,
+}
+
+impl DepthSnapshot {
+    /// Applies `delta` if it is newer than the snapshot, keeping both sides sorted.
+    pub fn apply(&mut self, delta: &DepthDelta) {
+        if delta.sequence <= self.sequence {
+            return;
+        }
+        let levels = match delta.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let mut book: BTreeMap<Price, Quantity> = levels.iter().copied().collect();
+        match delta.action {
+            DepthAction::Removed => book.remove(&delta.price),
+            DepthAction::Added | DepthAction::Updated => book.insert(delta.price, delta.quantity),
+        };
+        *levels = match delta.side {
+            Side::Buy => book.into_iter().rev().collect(),
+            Side::Sell => book.into_iter().collect(),
+        };
+        self.sequence = delta.sequence;
+    }
+}
+
+/// One message of a [`DepthFeed`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum DepthMessage {
+    Snapshot(DepthSnapshot),
+    Delta(DepthDelta),
+}
+
+/// Turns a book's depth deltas into a feed with a full snapshot every `snapshot_interval`
+/// deltas, so a consumer joining mid-stream waits for at most that many deltas.
+pub struct DepthFeed {
+    snapshot_interval: usize,
+    deltas_since_snapshot: usize,
+}
+
+impl DepthFeed {
+    /// Creates a feed that starts with a snapshot. An interval of `0` sends only that one.
+    pub fn new(snapshot_interval: usize) -> Self {
+        Self { snapshot_interval, deltas_since_snapshot: usize::MAX }
+    }
+
+    /// Takes the deltas buffered in `orderbook` and returns the messages to send, with a
+    /// snapshot appended whenever the interval has passed.
+    ///
+    /// `orderbook` must have publishing turned on.
+    pub fn poll(&mut self, orderbook: &Orderbook) -> Vec<DepthMessage> {
+        let deltas = orderbook.take_depth_deltas();
+        let mut messages: Vec<DepthMessage> = Vec::with_capacity(deltas.len() + 1);
+        if self.deltas_since_snapshot == usize::MAX {
+            self.deltas_since_snapshot = 0;
+            messages.push(DepthMessage::Snapshot(orderbook.get_depth_snapshot()));
+        }
+        self.deltas_since_snapshot += deltas.len();
+        messages.extend(deltas.into_iter().map(DepthMessage::Delta));
+        if self.snapshot_interval > 0 && self.deltas_since_snapshot >= self.snapshot_interval {
+            self.deltas_since_snapshot = 0;
+            messages.push(DepthMessage::Snapshot(orderbook.get_depth_snapshot()));
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::orderbook::{Order, OrderModify, OrderType};
+
+    #[test]
+    fn test_depth_deltas_track_levels(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 5));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 101, 3));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 101, 6));
+        ob.modify_order(OrderModify::new(2, Side::Sell, 102, 2));
+
+        let delta = |sequence, side, price, quantity, action| DepthDelta { sequence, side, price, quantity, action };
+        assert_eq!(ob.take_depth_deltas(), vec![
+            delta(1, Side::Sell, 101, 5, DepthAction::Added),
+            delta(2, Side::Sell, 101, 8, DepthAction::Updated),
+            delta(3, Side::Buy, 101, 6, DepthAction::Added),
+            delta(4, Side::Buy, 101, 1, DepthAction::Updated),
+            delta(5, Side::Sell, 101, 3, DepthAction::Updated),
+            delta(6, Side::Buy, 101, 0, DepthAction::Removed),
+            delta(7, Side::Sell, 101, 2, DepthAction::Updated),
+            delta(8, Side::Sell, 101, 0, DepthAction::Removed),
+            delta(9, Side::Sell, 102, 2, DepthAction::Added),
+        ]);
+        assert_eq!(ob.get_depth_snapshot(), DepthSnapshot { sequence: 9, bids: vec![], asks: vec![(102, 2)] });
+    }
+
+    #[test]
+    fn test_depth_feed_snapshots_rebuild_the_book(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 99, 4));
+        ob.set_publish_events(true);
+        let mut feed = DepthFeed::new(3);
+
+        let mut messages = feed.poll(&ob);
+        for (order_id, side, price) in [(2, Side::Buy, 100), (3, Side::Sell, 102), (4, Side::Sell, 100), (5, Side::Sell, 103)] {
+            ob.add_order(Order::new(OrderType::GoodTillCancel, order_id, side, price, 2));
+            messages.extend(feed.poll(&ob));
+        }
+        let snapshots = messages.iter().filter(|message| matches!(message, DepthMessage::Snapshot(_))).count();
+        assert_eq!(snapshots, 2);
+
+        // A consumer joining at any snapshot ends up with the book's depth.
+        let expected = ob.get_depth_snapshot();
+        assert_eq!(expected.bids, vec![(99, 4)]);
+        for start in 0..messages.len() {
+            let DepthMessage::Snapshot(mut view) = messages[start].clone() else {
+                continue;
+            };
+            for message in &messages[start..] {
+                if let DepthMessage::Delta(delta) = message {
+                    view.apply(delta);
+                }
+            }
+            assert_eq!(view, expected);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! # FlatBuffers Module
+//!
+//! FlatBuffers encoding of the [`DepthMessage`]s of a [`DepthFeed`](crate::events::DepthFeed),
+//! so browser/JS and Python consumers of the market data feed can read snapshots and deltas in
+//! place with code generated by `flatc`, instead of parsing JSON.
+//!
+//! ## Schema
+//! The schema is [`SCHEMA`], shipped as `schema/market_data.fbs`. Every message is a
+//! `DepthMessage` table, file identifier [`FILE_IDENTIFIER`], whose `payload` union holds
+//! either a `DepthSnapshot`, with each side as a vector of `Level { price, quantity }`
+//! structs best price first, or a `DepthDelta`. Fields mirror [`DepthSnapshot`] and
+//! [`DepthDelta`]; fields at their default (`0`, `Buy`, `Added`) are left out of the buffer,
+//! as FlatBuffers does, and read back as that default.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Orderbook, Order, OrderType, Side};
+//! use orderbook::events::DepthFeed;
+//! use orderbook::flatbuf::DepthEncoder;
+//!
+//! let book = Orderbook::new(Default::default(), Default::default());
+//! book.set_publish_events(true);
+//! let mut feed = DepthFeed::new(100);
+//! let mut encoder = DepthEncoder::new();
+//!
+//! book.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 10));
+//! for message in feed.poll(&book) {
+//!     let frame: &[u8] = encoder.encode(&message);
+//!     assert!(flatbuffers::buffer_has_identifier(frame, "OBMD", false));
+//! }
+//! ```
+
+use flatbuffers::{FlatBufferBuilder, Push, PushAlignment, UnionWIPOffset, VOffsetT, WIPOffset};
+use crate::events::{DepthAction, DepthDelta, DepthMessage, DepthSnapshot};
+use crate::orderbook::{Price, Quantity, Side};
+
+/// The FlatBuffers schema of the feed, for consumers to generate readers from.
+pub const SCHEMA: &str = include_str!("../schema/market_data.fbs");
+/// File identifier of every encoded message.
+pub const FILE_IDENTIFIER: &str = "OBMD";
+
+// Union type codes of `DepthPayload`; `0` is FlatBuffers' NONE.
+const PAYLOAD_SNAPSHOT: u8 = 1;
+const PAYLOAD_DELTA: u8 = 2;
+
+// Field slots: the vtable offset of field `n` is `4 + 2 * n`.
+const MESSAGE_PAYLOAD_TYPE: VOffsetT = 4;
+const MESSAGE_PAYLOAD: VOffsetT = 6;
+const SNAPSHOT_SEQUENCE: VOffsetT = 4;
+const SNAPSHOT_BIDS: VOffsetT = 6;
+const SNAPSHOT_ASKS: VOffsetT = 8;
+const DELTA_SEQUENCE: VOffsetT = 4;
+const DELTA_SIDE: VOffsetT = 6;
+const DELTA_PRICE: VOffsetT = 8;
+const DELTA_QUANTITY: VOffsetT = 10;
+const DELTA_ACTION: VOffsetT = 12;
+
+/// The `Level` struct of the schema: a little-endian `int` price and `uint` quantity.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Level {
+    price: Price,
+    quantity: Quantity,
+}
+
+impl Push for Level {
+    type Output = Level;
+
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        dst[..4].copy_from_slice(&self.price.to_le_bytes());
+        dst[4..8].copy_from_slice(&self.quantity.to_le_bytes());
+    }
+
+    fn size() -> usize {
+        8
+    }
+
+    fn alignment() -> PushAlignment {
+        PushAlignment::new(4)
+    }
+}
+
+/// Encodes depth messages, reusing one buffer from message to message.
+pub struct DepthEncoder {
+    builder: FlatBufferBuilder<'static>,
+}
+
+impl Default for DepthEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DepthEncoder {
+    pub fn new() -> Self {
+        Self { builder: FlatBufferBuilder::new() }
+    }
+
+    /// Encodes `message` as a finished `DepthMessage` buffer, valid until the next call.
+    pub fn encode(&mut self, message: &DepthMessage) -> &[u8] {
+        self.builder.reset();
+        let (payload_type, payload) = match message {
+            DepthMessage::Snapshot(snapshot) => (PAYLOAD_SNAPSHOT, self.snapshot(snapshot)),
+            DepthMessage::Delta(delta) => (PAYLOAD_DELTA, self.delta(delta)),
+        };
+        let start = self.builder.start_table();
+        self.builder.push_slot_always(MESSAGE_PAYLOAD, payload);
+        self.builder.push_slot::<u8>(MESSAGE_PAYLOAD_TYPE, payload_type, 0);
+        let root = self.builder.end_table(start);
+        self.builder.finish(root, Some(FILE_IDENTIFIER));
+        self.builder.finished_data()
+    }
+
+    fn snapshot(&mut self, snapshot: &DepthSnapshot) -> WIPOffset<UnionWIPOffset> {
+        let bids = self.levels(&snapshot.bids);
+        let asks = self.levels(&snapshot.asks);
+        let start = self.builder.start_table();
+        self.builder.push_slot::<u64>(SNAPSHOT_SEQUENCE, snapshot.sequence, 0);
+        self.builder.push_slot_always(SNAPSHOT_BIDS, bids);
+        self.builder.push_slot_always(SNAPSHOT_ASKS, asks);
+        self.builder.end_table(start).as_union_value()
+    }
+
+    fn levels(&mut self, levels: &[(Price, Quantity)]) -> WIPOffset<flatbuffers::Vector<'static, Level>> {
+        // Buffers are built back to front, so the last level goes in first.
+        self.builder.start_vector::<Level>(levels.len());
+        for &(price, quantity) in levels.iter().rev() {
+            self.builder.push(Level { price, quantity });
+        }
+        self.builder.end_vector(levels.len())
+    }
+
+    fn delta(&mut self, delta: &DepthDelta) -> WIPOffset<UnionWIPOffset> {
+        let start = self.builder.start_table();
+        self.builder.push_slot::<u64>(DELTA_SEQUENCE, delta.sequence, 0);
+        self.builder.push_slot::<Price>(DELTA_PRICE, delta.price, 0);
+        self.builder.push_slot::<Quantity>(DELTA_QUANTITY, delta.quantity, 0);
+        self.builder.push_slot::<u8>(DELTA_SIDE, side_code(delta.side), 0);
+        self.builder.push_slot::<u8>(DELTA_ACTION, action_code(delta.action), 0);
+        self.builder.end_table(start).as_union_value()
+    }
+}
+
+/// Encodes one depth message; see [`DepthEncoder`] to reuse the buffer across messages.
+pub fn depth_message_to_flatbuffer(message: &DepthMessage) -> Vec<u8> {
+    DepthEncoder::new().encode(message).to_vec()
+}
+
+fn side_code(side: Side) -> u8 {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+fn action_code(action: DepthAction) -> u8 {
+    match action {
+        DepthAction::Added => 0,
+        DepthAction::Updated => 1,
+        DepthAction::Removed => 2,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use flatbuffers::{ForwardsUOffset, Follow, InvalidFlatbuffer, SimpleToVerifyInSlice, Table, Verifiable, Verifier, Vector};
+    use crate::events::DepthFeed;
+    use crate::orderbook::{Order, OrderType, Orderbook};
+
+    // A reader in the shape of flatc's generated Rust code, to check buffers against the schema.
+
+    struct Message<'a>(Table<'a>);
+    // Payload tables are only verified here; `decode` reads them as plain tables.
+    struct Snapshot;
+    struct Delta;
+
+    impl<'a> Follow<'a> for Message<'a> {
+        type Inner = Self;
+        unsafe fn follow(buf: &'a [u8], loc: usize) -> Self {
+            Message(Table::new(buf, loc))
+        }
+    }
+
+    impl<'a> Follow<'a> for Snapshot {
+        type Inner = Table<'a>;
+        unsafe fn follow(buf: &'a [u8], loc: usize) -> Table<'a> {
+            Table::new(buf, loc)
+        }
+    }
+
+    impl<'a> Follow<'a> for Delta {
+        type Inner = Table<'a>;
+        unsafe fn follow(buf: &'a [u8], loc: usize) -> Table<'a> {
+            Table::new(buf, loc)
+        }
+    }
+
+    impl<'a> Follow<'a> for Level {
+        type Inner = Level;
+        unsafe fn follow(buf: &'a [u8], loc: usize) -> Level {
+            let field = |at: usize| buf[at..at + 4].try_into().unwrap();
+            Level { price: Price::from_le_bytes(field(loc)), quantity: Quantity::from_le_bytes(field(loc + 4)) }
+        }
+    }
+
+    impl SimpleToVerifyInSlice for Level {}
+
+    impl Verifiable for Message<'_> {
+        fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+            v.visit_table(pos)?
+                .visit_union::<u8, _>("payload_type", MESSAGE_PAYLOAD_TYPE, "payload", MESSAGE_PAYLOAD, true, |key, v, pos| match key {
+                    PAYLOAD_SNAPSHOT => v.verify_union_variant::<ForwardsUOffset<Snapshot>>("DepthSnapshot", pos),
+                    PAYLOAD_DELTA => v.verify_union_variant::<ForwardsUOffset<Delta>>("DepthDelta", pos),
+                    _ => Ok(()),
+                })?
+                .finish();
+            Ok(())
+        }
+    }
+
+    impl Verifiable for Snapshot {
+        fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+            v.visit_table(pos)?
+                .visit_field::<u64>("sequence", SNAPSHOT_SEQUENCE, false)?
+                .visit_field::<ForwardsUOffset<Vector<Level>>>("bids", SNAPSHOT_BIDS, false)?
+                .visit_field::<ForwardsUOffset<Vector<Level>>>("asks", SNAPSHOT_ASKS, false)?
+                .finish();
+            Ok(())
+        }
+    }
+
+    impl Verifiable for Delta {
+        fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+            v.visit_table(pos)?
+                .visit_field::<u64>("sequence", DELTA_SEQUENCE, false)?
+                .visit_field::<u8>("side", DELTA_SIDE, false)?
+                .visit_field::<Price>("price", DELTA_PRICE, false)?
+                .visit_field::<Quantity>("quantity", DELTA_QUANTITY, false)?
+                .visit_field::<u8>("action", DELTA_ACTION, false)?
+                .finish();
+            Ok(())
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> DepthMessage {
+        assert!(flatbuffers::buffer_has_identifier(bytes, FILE_IDENTIFIER, false));
+        let message = flatbuffers::root::<Message>(bytes).unwrap();
+        // Safety: the verifier checked every field read below against the schema.
+        unsafe {
+            let table = message.0.get::<ForwardsUOffset<Table>>(MESSAGE_PAYLOAD, None).unwrap();
+            match message.0.get::<u8>(MESSAGE_PAYLOAD_TYPE, Some(0)).unwrap() {
+                PAYLOAD_SNAPSHOT => {
+                    let levels = |slot| table.get::<ForwardsUOffset<Vector<Level>>>(slot, None).unwrap().iter().map(|level| (level.price, level.quantity)).collect();
+                    DepthMessage::Snapshot(DepthSnapshot {
+                        sequence: table.get::<u64>(SNAPSHOT_SEQUENCE, Some(0)).unwrap(),
+                        bids: levels(SNAPSHOT_BIDS),
+                        asks: levels(SNAPSHOT_ASKS),
+                    })
+                }
+                PAYLOAD_DELTA => DepthMessage::Delta(DepthDelta {
+                    sequence: table.get::<u64>(DELTA_SEQUENCE, Some(0)).unwrap(),
+                    side: [Side::Buy, Side::Sell][table.get::<u8>(DELTA_SIDE, Some(0)).unwrap() as usize],
+                    price: table.get::<Price>(DELTA_PRICE, Some(0)).unwrap(),
+                    quantity: table.get::<Quantity>(DELTA_QUANTITY, Some(0)).unwrap(),
+                    action: [DepthAction::Added, DepthAction::Updated, DepthAction::Removed][table.get::<u8>(DELTA_ACTION, Some(0)).unwrap() as usize],
+                }),
+                code => panic!("unknown payload type {}", code),
+            }
+        }
+    }
+
+    #[test]
+    fn test_depth_messages_round_trip(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, -2, 4));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 99, 6));
+        ob.set_publish_events(true);
+        let mut feed = DepthFeed::new(3);
+        let mut encoder = DepthEncoder::new();
+
+        let mut messages = feed.poll(&ob);
+        for (order_id, side, price) in [(3, Side::Sell, 102), (4, Side::Sell, 103), (5, Side::Buy, 102)] {
+            ob.add_order(Order::new(OrderType::GoodTillCancel, order_id, side, price, 2));
+            messages.extend(feed.poll(&ob));
+        }
+        assert!(messages.iter().any(|message| matches!(message, DepthMessage::Delta(DepthDelta { action: DepthAction::Removed, .. }))));
+        assert_eq!(messages.iter().filter(|message| matches!(message, DepthMessage::Snapshot(_))).count(), 2);
+        for message in &messages {
+            assert_eq!(&decode(encoder.encode(message)), message);
+            assert_eq!(depth_message_to_flatbuffer(message), encoder.encode(message));
+        }
+
+        let empty = DepthMessage::Snapshot(DepthSnapshot::default());
+        assert_eq!(decode(&depth_message_to_flatbuffer(&empty)), empty);
+    }
+
+    #[test]
+    fn test_schema_matches_the_encoder(){
+        assert!(SCHEMA.contains(&format!("file_identifier \"{}\";", FILE_IDENTIFIER)));
+        assert!(SCHEMA.contains("union DepthPayload { DepthSnapshot, DepthDelta }"));
+        assert!(SCHEMA.contains("root_type DepthMessage;"));
+    }
+}
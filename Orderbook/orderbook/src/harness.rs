@@ -0,0 +1,190 @@
+//! # Harness Module
+//!
+//! Deterministic simulation: a seeded [`Simulator`] on a [`VirtualClock`], driving a book with
+//! no background threads and logging to a [`Journal`], so a whole run is reproducible from its
+//! seed. A failing seed can be rerun as often as needed to chase a rare matching bug.
+//!
+//! ## Model
+//! - **Randomness:** one RNG, seeded from the seed and nothing else.
+//! - **Time:** virtual and never slept. Trading sessions end when the virtual time crosses
+//!   `session_length`, not at the wall-clock cutoff.
+//! - **Engine:** a book built with [`Orderbook::new`], so nothing but the simulator touches it.
+//!
+//! [`DeterministicSimulation::verify`] checks all of this holds: it runs the seed twice,
+//! compares the journals entry by entry, and rebuilds the final book from the journal.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::harness::DeterministicSimulation;
+//! use orderbook::simulator::SimulatorConfig;
+//!
+//! let simulation = DeterministicSimulation::new(7, SimulatorConfig { max_events: 200, ..Default::default() });
+//! let run = simulation.verify().unwrap();
+//! assert_eq!(run.stats.submitted + run.stats.cancelled, 200);
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+use log::info;
+use crate::clock::VirtualClock;
+use crate::journal::{Journal, JournalEntry};
+use crate::orderbook::Orderbook;
+use crate::simulator::{Simulator, SimulatorConfig, SimulatorStats};
+use crate::snapshot::BookSnapshot;
+
+/// Session length used when the config does not set one: one simulated minute.
+pub const DEFAULT_SESSION_LENGTH: Duration = Duration::from_secs(60);
+
+/// Everything a deterministic run produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationRun {
+    /// Seed the run was generated from.
+    pub seed: u64,
+    /// Counters of the simulator.
+    pub stats: SimulatorStats,
+    /// Every command and event, in the order they happened.
+    pub entries: Vec<JournalEntry>,
+    /// The book at the end of the run.
+    pub snapshot: BookSnapshot,
+}
+
+impl SimulationRun {
+    /// Returns the index of the first journal entry where `self` and `other` differ, if any.
+    ///
+    /// When one journal is a prefix of the other, that is the length of the shorter one.
+    pub fn first_divergence(&self, other: &SimulationRun) -> Option<usize> {
+        let common = self.entries.iter().zip(&other.entries).position(|(ours, theirs)| ours != theirs);
+        match common {
+            Some(index) => Some(index),
+            None if self.entries.len() != other.entries.len() => Some(self.entries.len().min(other.entries.len())),
+            None => None,
+        }
+    }
+}
+
+/// Runs the [`Simulator`] in a fully reproducible setup.
+#[derive(Clone, Debug)]
+pub struct DeterministicSimulation {
+    seed: u64,
+    config: SimulatorConfig,
+}
+
+impl DeterministicSimulation {
+    /// Creates a simulation of `config` seeded with `seed`.
+    ///
+    /// The config's own seed is replaced, `realtime` is turned off, and a missing
+    /// `session_length` defaults to [`DEFAULT_SESSION_LENGTH`].
+    pub fn new(seed: u64, config: SimulatorConfig) -> Self {
+        let config = SimulatorConfig {
+            seed: Some(seed),
+            realtime: false,
+            session_length: config.session_length.or(Some(DEFAULT_SESSION_LENGTH)),
+            ..config
+        };
+        Self { seed, config }
+    }
+
+    /// Returns the seed of the simulation.
+    pub const fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Runs the simulation once against a fresh book.
+    pub fn run(&self) -> SimulationRun {
+        let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        orderbook.set_publish_events(true);
+        let clock = VirtualClock::new();
+        let mut journal = Journal::new();
+        let mut simulator = Simulator::new(self.config.clone());
+        simulator.add_sink(clock.clone());
+        simulator.add_sink(&mut journal);
+
+        let stats = simulator.run(&orderbook).clone();
+        drop(simulator);
+        journal.record_events(&orderbook);
+        info!("Harness: seed {} ran {} events over {:?}", self.seed, self.config.max_events, clock.elapsed());
+        SimulationRun { seed: self.seed, stats, entries: journal.into_entries(), snapshot: BookSnapshot::capture(&orderbook) }
+    }
+
+    /// Runs the simulation twice and checks both runs, and the journal, agree.
+    ///
+    /// # Returns
+    /// The first run.
+    ///
+    /// # Errors
+    /// Names the seed and the first journal entry at which the runs diverge, or explains why
+    /// the journal does not rebuild the final book.
+    pub fn verify(&self) -> Result<SimulationRun, String> {
+        let run = self.run();
+        let rerun = self.run();
+        if let Some(index) = run.first_divergence(&rerun) {
+            return Err(format!(
+                "Seed {}: runs diverge at journal entry {}: {:?} vs {:?}",
+                self.seed, index, run.entries.get(index), rerun.entries.get(index)
+            ));
+        }
+        if run.stats != rerun.stats || run.snapshot != rerun.snapshot {
+            return Err(format!("Seed {}: identical journals left different books", self.seed));
+        }
+
+        let rebuilt = Orderbook::from_events(run.entries.iter().copied()).map_err(|err| format!("Seed {}: {}", self.seed, err))?;
+        if BookSnapshot::capture(&rebuilt) != run.snapshot {
+            return Err(format!("Seed {}: the journal does not rebuild the final book", self.seed));
+        }
+        Ok(run)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::backtest::Command;
+    use crate::events::{BookEvent, SequencedEvent};
+    use crate::orderbook::OrderType;
+
+    fn config() -> SimulatorConfig {
+        SimulatorConfig { max_events: 2_000, session_length: Some(Duration::from_secs(2)), ..Default::default() }
+    }
+
+    #[test]
+    fn test_seeds_verify_across_sessions(){
+        for seed in 0..5 {
+            let run = DeterministicSimulation::new(seed, config()).verify().unwrap();
+            assert!(run.stats.trades > 0);
+            assert!(run.stats.pruned > 0);
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_diverge(){
+        let first = DeterministicSimulation::new(1, config()).run();
+        let second = DeterministicSimulation::new(2, config()).run();
+        assert_eq!(first.first_divergence(&first.clone()), None);
+        assert_eq!(second.first_divergence(&first), first.first_divergence(&second));
+        assert!(first.first_divergence(&second).is_some());
+    }
+
+    #[test]
+    fn test_journal_explains_session_prunes(){
+        let run = DeterministicSimulation::new(3, config()).run();
+
+        // Pruned orders are the GoodForDay orders cancelled without a cancel command.
+        let mut good_for_day = HashSet::new();
+        let mut unprompted = 0;
+        for entry in &run.entries {
+            match entry {
+                JournalEntry::Command(Command::New { order_id, order_type: OrderType::GoodForDay, .. }) => {
+                    good_for_day.insert(*order_id);
+                }
+                JournalEntry::Command(Command::Cancel { order_id }) => {
+                    good_for_day.remove(order_id);
+                }
+                JournalEntry::Event(SequencedEvent { event: BookEvent::OrderCancelled { order_id, .. }, .. })
+                    if good_for_day.remove(order_id) => unprompted += 1,
+                _ => {}
+            }
+        }
+        assert_eq!(unprompted, run.stats.pruned);
+    }
+}
@@ -0,0 +1,356 @@
+//! # Implied Module
+//!
+//! Calendar spreads whose books trade against the books of their legs.
+//!
+//! ## Model
+//! A [`CalendarSpread`] buys one lot of its near leg `N` and sells one lot of its far leg `F`,
+//! so its price is the near price minus the far price. Any two of the three books imply a
+//! price in the third one:
+//!
+//! | Incoming order | Implied from | Implied ask, for a buy | Implied bid, for a sell |
+//! |----------------|--------------|------------------------|-------------------------|
+//! | spread `S`     | `N` and `F`  | ask `N` − bid `F`      | bid `N` − ask `F`       |
+//! | near leg `N`   | `S` and `F`  | ask `S` + ask `F`      | bid `S` + bid `F`       |
+//! | far leg `F`    | `N` and `S`  | ask `N` − bid `S`      | bid `N` − ask `S`       |
+//!
+//! An implied level holds the smaller quantity of the two best levels it comes from.
+//!
+//! ## Matching
+//! [`ImpliedMatcher::add_order`] fills an incoming order level by level from the better of
+//! its own book and the prices implied for it, its own book first at the same price. A fill
+//! at an implied price trades the best levels of both books it comes from, through orders the
+//! matcher creates with ids from [`IMPLIED_ORDER_ID_BASE`] up; incoming orders must use lower
+//! ids. All books are updated under the same `&mut` call, so a fill and its leg trades happen
+//! together. What is left rests if the order is GoodTillCancel or GoodForDay and is dropped
+//! otherwise; FillOrKill orders are rejected on books with implied prices.
+//!
+//! An order only rests once nothing crosses it, directly or implied, so the three books never
+//! cross each other and resting orders never need to trade against implied prices.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{LevelInfo, Order, OrderType, Side};
+//! use orderbook::implied::{CalendarSpread, ImpliedMatcher};
+//! use orderbook::instrument::{Currency, Instrument};
+//! use orderbook::manager::OrderbookManager;
+//!
+//! let usd = Currency::new("USD").unwrap();
+//! let mut manager = OrderbookManager::new();
+//! for symbol in ["CLZ6", "CLF7", "CLZ6-CLF7"] {
+//!     manager.add_instrument(Instrument::new(symbol, usd)).unwrap();
+//! }
+//! let mut matcher = ImpliedMatcher::new(manager);
+//! matcher.add_spread(CalendarSpread::new("CLZ6-CLF7", "CLZ6", "CLF7")).unwrap();
+//!
+//! matcher.add_order("CLZ6", Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 7_010, 5)).unwrap();
+//! matcher.add_order("CLF7", Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 6_990, 5)).unwrap();
+//! assert_eq!(matcher.get_implied_quote("CLZ6-CLF7"), (None, Some(LevelInfo { price: 20, quantity: 5 })));
+//!
+//! // Buying the spread buys the near leg and sells the far leg.
+//! let executions = matcher.add_order("CLZ6-CLF7", Order::new(OrderType::FillAndKill, 3, Side::Buy, 20, 2)).unwrap();
+//! assert_eq!(executions.get_filled_quantity(), 2);
+//! assert_eq!(executions.implied[0].leg_trades.len(), 2);
+//! ```
+
+use crate::manager::OrderbookManager;
+use crate::orderbook::{LevelInfo, Order, OrderId, OrderPointer, OrderType, Price, Quantity, Side, Trade, Trades};
+
+/// Ids from this value up are reserved for the orders that trade the legs of implied fills.
+pub const IMPLIED_ORDER_ID_BASE: OrderId = 3 << 30;
+
+/// A spread buying `near` and selling `far`, one lot each.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CalendarSpread {
+    pub symbol: String,
+    pub near: String,
+    pub far: String,
+}
+
+impl CalendarSpread {
+    pub fn new(symbol: &str, near: &str, far: &str) -> Self {
+        Self { symbol: symbol.to_string(), near: near.to_string(), far: far.to_string() }
+    }
+
+    /// Returns `symbol` as one lot of the two other instruments of the spread, bought for
+    /// `1` and sold for `-1`, if it is one of the three.
+    fn get_recipe(&self, symbol: &str) -> Option<[(&str, i8); 2]> {
+        if symbol == self.symbol {
+            Some([(&self.near, 1), (&self.far, -1)])
+        } else if symbol == self.near {
+            Some([(&self.symbol, 1), (&self.far, 1)])
+        } else if symbol == self.far {
+            Some([(&self.near, 1), (&self.symbol, -1)])
+        } else {
+            None
+        }
+    }
+}
+
+/// A fill of an incoming order at an implied price.
+#[derive(Clone, Debug)]
+pub struct ImpliedFill {
+    pub price: Price,
+    pub quantity: Quantity,
+    /// Trades in the books the price was implied from, with their symbol.
+    pub leg_trades: Vec<(String, Trade)>,
+}
+
+/// What [`ImpliedMatcher::add_order`] did with an order.
+#[derive(Clone, Debug, Default)]
+pub struct Executions {
+    /// Trades against the order's own book.
+    pub trades: Trades,
+    pub implied: Vec<ImpliedFill>,
+    /// Whether what is left of the order rests in its book.
+    pub rested: bool,
+}
+
+impl Executions {
+    /// Returns the quantity filled directly and at implied prices.
+    pub fn get_filled_quantity(&self) -> Quantity {
+        let direct: Quantity = self.trades.iter().map(|trade| trade.get_bid_trade().quantity).sum();
+        direct + self.implied.iter().map(|fill| fill.quantity).sum::<Quantity>()
+    }
+}
+
+/// A price implied for an incoming order and the leg orders that would trade it.
+struct Implied {
+    price: Price,
+    quantity: Quantity,
+    /// Symbol, side and price of each leg order.
+    legs: Vec<(String, Side, Price)>,
+}
+
+/// Matches orders across the books of calendar spreads and their legs.
+pub struct ImpliedMatcher {
+    manager: OrderbookManager,
+    spreads: Vec<CalendarSpread>,
+    next_order_id: OrderId,
+}
+
+impl ImpliedMatcher {
+    pub fn new(manager: OrderbookManager) -> Self {
+        Self { manager, spreads: Vec::new(), next_order_id: IMPLIED_ORDER_ID_BASE }
+    }
+
+    /// Returns the manager owning the books. Orders sent to it directly bypass implied matching.
+    pub fn get_manager(&self) -> &OrderbookManager {
+        &self.manager
+    }
+
+    /// Links the book of `spread` with the books of its legs.
+    ///
+    /// # Errors
+    /// Returns an error if one of the three symbols is not listed, two are the same, or the
+    /// spread is already defined.
+    pub fn add_spread(&mut self, spread: CalendarSpread) -> Result<(), String> {
+        for symbol in [&spread.symbol, &spread.near, &spread.far] {
+            if self.manager.get_instrument(symbol).is_none() {
+                return Err(format!("Unknown instrument {}", symbol));
+            }
+        }
+        if spread.symbol == spread.near || spread.symbol == spread.far || spread.near == spread.far {
+            return Err(format!("Spread {} needs two different legs", spread.symbol));
+        }
+        if self.spreads.iter().any(|defined| defined.symbol == spread.symbol) {
+            return Err(format!("Spread {} is already defined", spread.symbol));
+        }
+        self.spreads.push(spread);
+        Ok(())
+    }
+
+    /// Returns the best implied bid and ask of `symbol`.
+    pub fn get_implied_quote(&self, symbol: &str) -> (Option<LevelInfo>, Option<LevelInfo>) {
+        let level = |implied: Implied| LevelInfo { price: implied.price, quantity: implied.quantity };
+        (self.get_best_implied(symbol, Side::Sell).map(level), self.get_best_implied(symbol, Side::Buy).map(level))
+    }
+
+    /// Checks `order` against the instrument of `symbol` and matches it against its book and
+    /// the prices implied for it.
+    ///
+    /// # Errors
+    /// Returns an error if the manager rejects the order, its id is taken or reserved, or it
+    /// is FillOrKill on a book with implied prices.
+    pub fn add_order(&mut self, symbol: &str, order: OrderPointer) -> Result<Executions, String> {
+        let order_id = order.lock().unwrap().get_order_id();
+        if !self.spreads.iter().any(|spread| spread.get_recipe(symbol).is_some()) {
+            let trades = self.manager.add_order(symbol, order)?;
+            let rested = self.manager.get_orderbook(symbol).is_some_and(|book| book.contains_order(order_id));
+            return Ok(Executions { trades, implied: Vec::new(), rested });
+        }
+
+        let (order_type, side, limit, flags) = {
+            let ord = order.lock().unwrap();
+            self.manager.check_order(symbol, &ord)?;
+            let limit = (ord.get_order_type() != OrderType::Market).then(|| ord.get_price());
+            (ord.get_order_type(), ord.get_side(), limit, ord.get_flags())
+        };
+        if order_id >= IMPLIED_ORDER_ID_BASE {
+            return Err(format!("Order#{} is in the range reserved for implied orders", order_id));
+        }
+        if order_type == OrderType::FillOrKill {
+            return Err(format!("{} has implied prices, which FillOrKill orders cannot use", symbol));
+        }
+        if self.get_book(symbol).contains_order(order_id) {
+            return Err(format!("Order#{} already exists", order_id));
+        }
+
+        let crosses = |price: Price| limit.is_none_or(|limit| if side == Side::Buy { price <= limit } else { price >= limit });
+        let is_better = |price: Price, than: Price| if side == Side::Buy { price < than } else { price > than };
+        let opposite = if side == Side::Buy { Side::Sell } else { Side::Buy };
+        let mut executions = Executions::default();
+        loop {
+            let remaining = order.lock().unwrap().get_remaining_quantity();
+            if remaining == 0 {
+                break;
+            }
+            let direct = self.get_best_level(symbol, opposite).filter(|(price, _)| crosses(*price));
+            let implied = self.get_best_implied(symbol, side).filter(|implied| crosses(implied.price));
+            let filled = match (direct, implied) {
+                (Some((price, quantity)), implied) if implied.as_ref().is_none_or(|implied| !is_better(implied.price, price)) => {
+                    // A slice of the order sized to the level fills there and leaves nothing behind.
+                    let slice = Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity.min(remaining));
+                    slice.lock().unwrap().set_flags(flags);
+                    let trades = self.get_book(symbol).add_order(slice);
+                    let filled = trades.iter().map(|trade| trade.get_bid_trade().quantity).sum();
+                    executions.trades.extend(trades);
+                    filled
+                }
+                (_, Some(implied)) => {
+                    let fill = self.trade_implied(&implied, implied.quantity.min(remaining));
+                    executions.implied.push(fill);
+                    implied.quantity.min(remaining)
+                }
+                _ => break,
+            };
+            if filled == 0 {
+                break;
+            }
+            order.lock().unwrap().fill(filled)?;
+        }
+
+        let remaining = order.lock().unwrap().get_remaining_quantity();
+        if remaining > 0 && matches!(order_type, OrderType::GoodTillCancel | OrderType::GoodForDay) {
+            self.get_book(symbol).restore_order(order)?;
+            executions.rested = true;
+        }
+        Ok(executions)
+    }
+
+    fn get_book(&self, symbol: &str) -> &crate::Orderbook {
+        self.manager.get_orderbook(symbol).expect("spread instruments are listed")
+    }
+
+    /// Returns the best level on `side` of the book of `symbol`.
+    fn get_best_level(&self, symbol: &str, side: Side) -> Option<(Price, Quantity)> {
+        let snapshot = self.get_book(symbol).get_depth_snapshot();
+        match side {
+            Side::Buy => snapshot.bids.first().copied(),
+            Side::Sell => snapshot.asks.first().copied(),
+        }
+    }
+
+    /// Returns the best price implied for an incoming order on `side` of `symbol`: an ask for
+    /// a buy, a bid for a sell.
+    fn get_best_implied(&self, symbol: &str, side: Side) -> Option<Implied> {
+        let implied = self.spreads.iter().filter_map(|spread| spread.get_recipe(symbol)).filter_map(|recipe| {
+            let (mut price, mut quantity, mut legs) = (0i64, Quantity::MAX, Vec::new());
+            for (leg, coefficient) in recipe {
+                // Buying `symbol` buys what it is long of and sells what it is short of.
+                let leg_side = if (coefficient > 0) == (side == Side::Buy) { Side::Buy } else { Side::Sell };
+                let (leg_price, leg_quantity) = self.get_best_level(leg, if leg_side == Side::Buy { Side::Sell } else { Side::Buy })?;
+                price += i64::from(coefficient) * i64::from(leg_price);
+                quantity = quantity.min(leg_quantity);
+                legs.push((leg.to_string(), leg_side, leg_price));
+            }
+            Some(Implied { price: Price::try_from(price).ok()?, quantity, legs })
+        });
+        implied.reduce(|best, implied| {
+            let is_better = if side == Side::Buy { implied.price < best.price } else { implied.price > best.price };
+            if is_better { implied } else { best }
+        })
+    }
+
+    /// Trades `quantity` of every leg of `implied` at its best level.
+    fn trade_implied(&mut self, implied: &Implied, quantity: Quantity) -> ImpliedFill {
+        let mut leg_trades = Vec::new();
+        for (symbol, side, price) in &implied.legs {
+            let order = Order::new(OrderType::GoodTillCancel, self.next_order_id, *side, *price, quantity);
+            self.next_order_id += 1;
+            let trades = self.get_book(symbol).add_order(order);
+            leg_trades.extend(trades.into_iter().map(|trade| (symbol.clone(), trade)));
+        }
+        ImpliedFill { price: implied.price, quantity, leg_trades }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instrument::{Currency, Instrument};
+
+    fn matcher() -> ImpliedMatcher {
+        let mut manager = OrderbookManager::new();
+        for symbol in ["N", "F", "S"] {
+            manager.add_instrument(Instrument::new(symbol, Currency::new("USD").unwrap())).unwrap();
+        }
+        let mut matcher = ImpliedMatcher::new(manager);
+        matcher.add_spread(CalendarSpread::new("S", "N", "F")).unwrap();
+        matcher
+    }
+
+    #[test]
+    fn test_implied_in_and_out(){
+        let mut matcher = matcher();
+        let gtc = |order_id, side, price, quantity| Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity);
+        matcher.add_order("N", gtc(1, Side::Sell, 101, 5)).unwrap();
+        matcher.add_order("F", gtc(2, Side::Buy, 95, 3)).unwrap();
+        matcher.add_order("S", gtc(3, Side::Sell, 7, 10)).unwrap();
+        assert_eq!(matcher.get_implied_quote("S"), (None, Some(LevelInfo { price: 6, quantity: 3 })));
+
+        // Implied in: 3 at 6 from the legs, then 2 at 7 from the spread book.
+        let executions = matcher.add_order("S", gtc(10, Side::Buy, 7, 5)).unwrap();
+        assert_eq!((executions.implied.len(), executions.implied[0].price, executions.implied[0].quantity), (1, 6, 3));
+        let legs: Vec<_> = executions.implied[0].leg_trades.iter()
+            .map(|(symbol, trade)| (symbol.as_str(), trade.get_ask_trade().order_id, trade.get_bid_trade().order_id, trade.get_bid_trade().price))
+            .collect();
+        assert_eq!(legs, [("N", 1, IMPLIED_ORDER_ID_BASE, 101), ("F", IMPLIED_ORDER_ID_BASE + 1, 2, 95)]);
+        assert_eq!((executions.trades.len(), executions.trades[0].get_bid_trade().order_id), (1, 10));
+        assert_eq!(executions.get_filled_quantity(), 5);
+        assert!(!executions.rested);
+        assert_eq!(matcher.get_manager().get_orderbook("F").unwrap().size(), 0);
+
+        // Implied out: N ask 101 direct and 7 + 94 implied; the book goes first at the same price.
+        matcher.add_order("F", gtc(4, Side::Sell, 94, 4)).unwrap();
+        let executions = matcher.add_order("N", gtc(11, Side::Buy, 101, 6)).unwrap();
+        assert_eq!((executions.trades.len(), executions.implied[0].price, executions.implied[0].quantity), (1, 101, 4));
+        assert_eq!(executions.get_filled_quantity(), 6);
+        let spread = matcher.get_manager().get_orderbook("S").unwrap();
+        assert_eq!(spread.get_depth_snapshot().asks, [(7, 4)]);
+        assert_eq!(matcher.get_manager().get_orderbook("F").unwrap().size(), 0);
+    }
+
+    #[test]
+    fn test_remainders_and_rejections(){
+        let mut matcher = matcher();
+        matcher.add_order("N", Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 2)).unwrap();
+        matcher.add_order("S", Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 4, 2)).unwrap();
+        assert_eq!(matcher.get_implied_quote("F"), (Some(LevelInfo { price: 96, quantity: 2 }), None));
+
+        // Far leg sell: 2 at the implied bid of 100 - 4, the rest rests.
+        let executions = matcher.add_order("F", Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 95, 5)).unwrap();
+        assert_eq!((executions.implied[0].price, executions.get_filled_quantity(), executions.rested), (96, 2, true));
+        let resting = &matcher.get_manager().get_orderbook("F").unwrap().get_resting_orders(Side::Sell)[0];
+        assert_eq!((resting.get_order_id(), resting.get_remaining_quantity()), (3, 3));
+
+        // Market orders drop what they cannot fill.
+        let executions = matcher.add_order("S", Order::new_market(4, Side::Buy, 1)).unwrap();
+        assert_eq!((executions.get_filled_quantity(), executions.rested), (0, false));
+
+        assert!(matcher.add_order("S", Order::new(OrderType::FillOrKill, 5, Side::Buy, 4, 1)).is_err());
+        assert!(matcher.add_order("S", Order::new(OrderType::GoodTillCancel, IMPLIED_ORDER_ID_BASE, Side::Buy, 4, 1)).is_err());
+        assert!(matcher.add_order("F", Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 99, 1)).is_err());
+        assert!(matcher.add_spread(CalendarSpread::new("S", "N", "N")).is_err());
+    }
+}
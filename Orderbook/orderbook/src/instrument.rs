@@ -0,0 +1,479 @@
+//! # Instrument Module
+//!
+//! Static data of a traded instrument and the money amounts its trades are worth.
+//!
+//! ## Model
+//! A book knows prices and quantities only as integers. An [`Instrument`] says what they mean:
+//! - a [`Price`] counts units of `10^price_exponent` of the instrument's [`Currency`], e.g.
+//!   cents with an exponent of `-2`,
+//! - prices must be a multiple of the tick size and quantities of the lot size,
+//! - one unit of quantity is worth `contract_multiplier` times the price, e.g. 100 shares for an
+//!   equity option.
+//!
+//! An option also carries its [`OptionTerms`]: underlying, strike, expiry and call or put. It
+//! trades up to and including its expiry date and is
+//! [expired](crate::manager::OrderbookManager::expire_instruments) after it. Options are listed
+//! under symbols made from their terms, such as `ACME-20261218-C-10000`, and a chain of them
+//! can be made from a template with [`option_chain`]. Pricing them is out of scope.
+//!
+//! Notionals and fees are [`Money`] in the instrument's currency. Accounts that settle in
+//! another currency convert them through an [`FxRates`] hook; [`FixedRates`] is a table of
+//! rates set by hand.
+//!
+//! ## Instrument Files
+//! The instrument universe of a venue can be kept in a CSV file, one instrument per line, read
+//! by [`parse_instruments`] and loaded by
+//! [`OrderbookManager::from_file`](crate::manager::OrderbookManager::from_file). `#` starts a
+//! comment; the header is one:
+//!
+//! ```text
+//! # symbol,currency,tick_size,lot_size,contract_multiplier,price_exponent,open,close,auctions[,underlying,expiry,kind,strike]
+//! ACME,USD,1,1,1,-2,13:30,20:00,13:30;20:00
+//! FESX,EUR,5,1,10,-2,,,
+//! ,USD,5,1,100,-2,13:30,20:00,,ACME,2026-12-18,C,10000
+//! ```
+//!
+//! `open` and `close` are the UTC [`TradingHours`]; both empty means the instrument always
+//! trades. `auctions` lists the UTC times of the day's auctions, separated by `;`. Options add
+//! their terms, the kind being `C` or `P`, and may leave the symbol empty to have it made from
+//! them.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::instrument::{Currency, FixedRates, Instrument};
+//!
+//! let eur = Currency::new("EUR").unwrap();
+//! let usd = Currency::new("USD").unwrap();
+//! let future = Instrument { contract_multiplier: 10, tick_size: 5, ..Instrument::new("FESX", eur) };
+//! assert!(future.check_order(4_203, 1).is_err());
+//!
+//! let notional = future.get_notional(4_205, 2); // 2 × 10 × 42.05 EUR
+//! assert_eq!(notional.to_string(), "841.00 EUR");
+//! let rates = FixedRates::new().with_rate(eur, usd, 1.1);
+//! assert_eq!(notional.convert(usd, &rates).unwrap().to_string(), "925.10 USD");
+//! ```
+
+use std::{collections::{HashMap, HashSet}, fmt, fmt::Debug};
+use chrono::{NaiveDate, NaiveTime};
+use crate::orderbook::{Price, Quantity};
+
+/// Header line of an instrument file.
+pub const INSTRUMENTS_HEADER: &str = "# symbol,currency,tick_size,lot_size,contract_multiplier,price_exponent,open,close,auctions[,underlying,expiry,kind,strike]";
+
+/// An ISO 4217 currency code.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    /// Creates a currency from its three-letter code.
+    ///
+    /// # Errors
+    /// Returns an error if `code` is not three ASCII uppercase letters.
+    pub fn new(code: &str) -> Result<Self, String> {
+        match code.as_bytes() {
+            &[a, b, c] if [a, b, c].iter().all(u8::is_ascii_uppercase) => Ok(Self([a, b, c])),
+            _ => Err(format!("Invalid currency code {:?}", code)),
+        }
+    }
+
+    /// Returns the three-letter code.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An amount in a currency.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: Currency,
+}
+
+impl Money {
+    /// Converts the amount to `currency` at the rate `rates` quote.
+    ///
+    /// # Errors
+    /// Returns an error if `rates` has no rate between the two currencies.
+    pub fn convert(&self, currency: Currency, rates: &dyn FxRates) -> Result<Money, String> {
+        if currency == self.currency {
+            return Ok(*self);
+        }
+        let rate = rates
+            .get_rate(self.currency, currency)
+            .ok_or_else(|| format!("No rate from {} to {}", self.currency, currency))?;
+        Ok(Money { amount: self.amount * rate, currency })
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} {}", self.amount, self.currency)
+    }
+}
+
+/// Quotes exchange rates for cross-currency accounts.
+pub trait FxRates: Debug + Send + Sync {
+    /// Returns how many units of `to` one unit of `from` buys, if the rate is known.
+    fn get_rate(&self, from: Currency, to: Currency) -> Option<f64>;
+}
+
+/// Exchange rates set by hand; each rate also quotes its inverse.
+#[derive(Clone, Debug, Default)]
+pub struct FixedRates {
+    rates: HashMap<(Currency, Currency), f64>,
+}
+
+impl FixedRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the rate from `from` to `to`.
+    pub fn with_rate(mut self, from: Currency, to: Currency, rate: f64) -> Self {
+        self.rates.insert((from, to), rate);
+        self
+    }
+}
+
+impl FxRates for FixedRates {
+    fn get_rate(&self, from: Currency, to: Currency) -> Option<f64> {
+        self.rates
+            .get(&(from, to))
+            .copied()
+            .or_else(|| self.rates.get(&(to, from)).map(|rate| 1.0 / rate))
+    }
+}
+
+/// The daily window in which an instrument trades, in UTC.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TradingHours {
+    pub open: NaiveTime,
+    /// First time of the day the instrument no longer trades.
+    pub close: NaiveTime,
+}
+
+impl TradingHours {
+    /// Returns `true` if `time` falls in the window, which may span midnight.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.open <= self.close {
+            self.open <= time && time < self.close
+        } else {
+            time >= self.open || time < self.close
+        }
+    }
+}
+
+/// Whether an option gives the right to buy or to sell its underlying.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+impl OptionKind {
+    /// Returns `C` for a call and `P` for a put.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            OptionKind::Call => "C",
+            OptionKind::Put => "P",
+        }
+    }
+
+    /// Parses `C` or `P`.
+    ///
+    /// # Errors
+    /// Returns an error for anything else.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "C" => Ok(OptionKind::Call),
+            "P" => Ok(OptionKind::Put),
+            _ => Err(format!("invalid option kind: {}", value)),
+        }
+    }
+}
+
+/// What makes an instrument an option.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OptionTerms {
+    /// Symbol of the underlying instrument.
+    pub underlying: String,
+    /// In price units of the option.
+    pub strike: Price,
+    /// Last day the option trades.
+    pub expiry: NaiveDate,
+    pub kind: OptionKind,
+}
+
+impl OptionTerms {
+    /// Returns the symbol the option is listed under, `underlying-YYYYMMDD-kind-strike`.
+    pub fn to_symbol(&self) -> String {
+        format!("{}-{}-{}-{}", self.underlying, self.expiry.format("%Y%m%d"), self.kind.as_str(), self.strike)
+    }
+
+    /// Parses a symbol made by [`to_symbol`](Self::to_symbol); the underlying may contain `-`.
+    ///
+    /// # Errors
+    /// Returns an error if the symbol does not have the four parts or one is malformed.
+    pub fn from_symbol(symbol: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid option symbol: {}", symbol);
+        let mut parts = symbol.rsplitn(4, '-');
+        let (strike, kind, expiry, underlying) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(strike), Some(kind), Some(expiry), Some(underlying)) if !underlying.is_empty() => (strike, kind, expiry, underlying),
+            _ => return Err(invalid()),
+        };
+        let terms = Self {
+            underlying: underlying.to_string(),
+            strike: strike.parse().map_err(|_| invalid())?,
+            expiry: NaiveDate::parse_from_str(expiry, "%Y%m%d").map_err(|_| invalid())?,
+            kind: OptionKind::parse(kind).map_err(|_| invalid())?,
+        };
+        if terms.strike <= 0 {
+            return Err(invalid());
+        }
+        Ok(terms)
+    }
+
+    /// Returns `true` once `date` is past the expiry date.
+    pub fn is_expired(&self, date: NaiveDate) -> bool {
+        date > self.expiry
+    }
+}
+
+/// Static data of a traded instrument.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Instrument {
+    pub symbol: String,
+    pub currency: Currency,
+    /// Smallest price increment, in price units.
+    pub tick_size: Price,
+    /// Smallest quantity increment.
+    pub lot_size: Quantity,
+    /// Units of the underlying per unit of quantity.
+    pub contract_multiplier: u32,
+    /// A price counts units of `10^price_exponent` of the currency.
+    pub price_exponent: i8,
+    /// The instrument always trades if `None`.
+    pub trading_hours: Option<TradingHours>,
+    /// UTC times of the day's auctions.
+    pub auction_times: Vec<NaiveTime>,
+    /// Set if the instrument is an option.
+    pub option: Option<OptionTerms>,
+}
+
+impl Instrument {
+    /// Creates an instrument priced in hundredths of `currency`, with a tick, lot and
+    /// multiplier of 1, that always trades.
+    pub fn new(symbol: &str, currency: Currency) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            currency,
+            tick_size: 1,
+            lot_size: 1,
+            contract_multiplier: 1,
+            price_exponent: -2,
+            trading_hours: None,
+            auction_times: Vec::new(),
+            option: None,
+        }
+    }
+
+    /// Creates an option like [`new`](Self::new) does, listed under the symbol of its terms.
+    pub fn new_option(terms: OptionTerms, currency: Currency) -> Self {
+        Self { option: Some(terms.clone()), ..Self::new(&terms.to_symbol(), currency) }
+    }
+
+    /// Returns `true` if the instrument is an option that expired before `date`.
+    pub fn is_expired(&self, date: NaiveDate) -> bool {
+        self.option.as_ref().is_some_and(|terms| terms.is_expired(date))
+    }
+
+    /// Checks that `price` is on the tick grid and `quantity` a whole number of lots.
+    ///
+    /// # Errors
+    /// Describes the first check that failed.
+    pub fn check_order(&self, price: Price, quantity: Quantity) -> Result<(), String> {
+        if price % self.tick_size != 0 {
+            return Err(format!("Price {} of {} is not a multiple of the tick size {}", price, self.symbol, self.tick_size));
+        }
+        if !quantity.is_multiple_of(self.lot_size) {
+            return Err(format!("Quantity {} of {} is not a multiple of the lot size {}", quantity, self.symbol, self.lot_size));
+        }
+        Ok(())
+    }
+
+    /// Returns `price` in units of the currency.
+    pub fn to_decimal(&self, price: Price) -> f64 {
+        f64::from(price) * 10f64.powi(i32::from(self.price_exponent))
+    }
+
+    /// Returns the value of `quantity` at `price`, multiplier included.
+    pub fn get_notional(&self, price: Price, quantity: Quantity) -> Money {
+        let amount = self.to_decimal(price) * f64::from(quantity) * f64::from(self.contract_multiplier);
+        Money { amount, currency: self.currency }
+    }
+
+    /// Returns the fee of `rate` times the notional of `quantity` at `price`.
+    pub fn get_fee(&self, price: Price, quantity: Quantity, rate: f64) -> Money {
+        let notional = self.get_notional(price, quantity);
+        Money { amount: notional.amount * rate, ..notional }
+    }
+}
+
+/// Returns a call and a put at each of `strikes`, in that order, on `underlying` expiring on
+/// `expiry`. Everything but the symbol and the terms is copied from `template`.
+pub fn option_chain(template: &Instrument, underlying: &str, expiry: NaiveDate, strikes: impl IntoIterator<Item = Price>) -> Vec<Instrument> {
+    let mut chain = Vec::new();
+    for strike in strikes {
+        for kind in [OptionKind::Call, OptionKind::Put] {
+            let terms = OptionTerms { underlying: underlying.to_string(), strike, expiry, kind };
+            chain.push(Instrument { symbol: terms.to_symbol(), option: Some(terms), ..template.clone() });
+        }
+    }
+    chain
+}
+
+/// Parses a whole instrument file.
+///
+/// # Errors
+/// Returns the first parse error, prefixed with its 1-based line number, or an error if a
+/// symbol is listed twice.
+pub fn parse_instruments(contents: &str) -> Result<Vec<Instrument>, String> {
+    let mut instruments = Vec::new();
+    let mut symbols = HashSet::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let instrument = parse_instrument_line(line).map_err(|err| format!("line {}: {}", index + 1, err))?;
+        if !symbols.insert(instrument.symbol.clone()) {
+            return Err(format!("line {}: {} is listed twice", index + 1, instrument.symbol));
+        }
+        instruments.push(instrument);
+    }
+    Ok(instruments)
+}
+
+/// Parses a single instrument line such as `ACME,USD,1,1,1,-2,13:30,20:00,13:30;20:00`, or
+/// `,USD,5,1,100,-2,,,,ACME,2026-12-18,C,10000` for an option.
+///
+/// # Errors
+/// Returns a description of the first malformed field.
+pub fn parse_instrument_line(line: &str) -> Result<Instrument, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if fields.len() != 9 && fields.len() != 13 {
+        return Err(format!("expected 9 or 13 fields, got {}", fields.len()));
+    }
+    fn number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+        value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+    }
+    let time = |value: &str| NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| format!("invalid time: {}", value));
+
+    let option = match fields.get(9..) {
+        Some(&[underlying, expiry, kind, strike]) => {
+            if underlying.is_empty() {
+                return Err("missing underlying".to_string());
+            }
+            let strike: Price = number(strike, "strike")?;
+            if strike <= 0 {
+                return Err("strike must be positive".to_string());
+            }
+            Some(OptionTerms {
+                underlying: underlying.to_string(),
+                strike,
+                expiry: NaiveDate::parse_from_str(expiry, "%Y-%m-%d").map_err(|_| format!("invalid expiry: {}", expiry))?,
+                kind: OptionKind::parse(kind)?,
+            })
+        }
+        _ => None,
+    };
+    let symbol = match (fields[0], &option) {
+        ("", Some(terms)) => terms.to_symbol(),
+        ("", None) => return Err("missing symbol".to_string()),
+        (symbol, _) => symbol.to_string(),
+    };
+    let tick_size: Price = number(fields[2], "tick_size")?;
+    let lot_size: Quantity = number(fields[3], "lot_size")?;
+    if tick_size <= 0 || lot_size == 0 {
+        return Err("tick_size and lot_size must be positive".to_string());
+    }
+    let trading_hours = match (fields[6], fields[7]) {
+        ("", "") => None,
+        (open, close) => Some(TradingHours { open: time(open)?, close: time(close)? }),
+    };
+    let auction_times = fields[8].split(';').filter(|value| !value.is_empty()).map(time).collect::<Result<_, _>>()?;
+    Ok(Instrument {
+        symbol,
+        currency: Currency::new(fields[1])?,
+        tick_size,
+        lot_size,
+        contract_multiplier: number(fields[4], "contract_multiplier")?,
+        price_exponent: number(fields[5], "price_exponent")?,
+        trading_hours,
+        auction_times,
+        option,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_notional_fees_and_conversion(){
+        let jpy = Currency::new("JPY").unwrap();
+        let usd = Currency::new("USD").unwrap();
+        assert!(Currency::new("usd").is_err());
+        let stock = Instrument { lot_size: 100, price_exponent: 0, ..Instrument::new("7203", jpy) };
+        assert_eq!(stock.check_order(2_500, 100), Ok(()));
+        assert_eq!(stock.check_order(2_500, 150), Err("Quantity 150 of 7203 is not a multiple of the lot size 100".to_string()));
+
+        assert_eq!(stock.get_notional(2_500, 200), Money { amount: 500_000.0, currency: jpy });
+        assert_eq!(stock.get_fee(2_500, 200, 0.0002).amount, 100.0);
+
+        // Only the USD/JPY rate is set; JPY → USD uses its inverse.
+        let rates = FixedRates::new().with_rate(usd, jpy, 125.0);
+        assert_eq!(stock.get_notional(2_500, 200).convert(usd, &rates).unwrap().to_string(), "4000.00 USD");
+        assert!(stock.get_notional(2_500, 200).convert(Currency::new("EUR").unwrap(), &rates).is_err());
+    }
+
+    #[test]
+    fn test_parse_instruments(){
+        let instruments = parse_instruments(&format!("{}\nACME,USD,1,1,1,-2,13:30,20:00,13:30;20:00\n\nN225,JPY,5,1,1000,0,23:00,06:00,\n", INSTRUMENTS_HEADER)).unwrap();
+        let at = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        assert_eq!(instruments[0].auction_times, [at(13, 30), at(20, 0)]);
+        let hours = instruments[1].trading_hours.unwrap();
+        assert!(hours.contains(at(2, 0)) && !hours.contains(at(12, 0)) && !hours.contains(at(6, 0)));
+        assert_eq!((instruments[1].contract_multiplier, instruments[1].price_exponent), (1000, 0));
+
+        assert_eq!(parse_instruments("ACME,USD,0,1,1,-2,,,"), Err("line 1: tick_size and lot_size must be positive".to_string()));
+        assert_eq!(parse_instruments("ACME,USD,1,1,1,-2,,,\nACME,EUR,1,1,1,-2,,,"), Err("line 2: ACME is listed twice".to_string()));
+        assert_eq!(parse_instruments("ACME,USD,1,1,1,-2,9am,,"), Err("line 1: invalid time: 9am".to_string()));
+    }
+
+    #[test]
+    fn test_option_terms(){
+        let instruments = parse_instruments(",USD,5,1,100,-2,,,,BRK-B,2026-12-18,P,45000\nCUSTOM,USD,5,1,100,-2,,,,ACME,2026-12-18,C,10000").unwrap();
+        let terms = instruments[0].option.clone().unwrap();
+        assert_eq!(instruments[0].symbol, "BRK-B-20261218-P-45000");
+        assert_eq!(OptionTerms::from_symbol(&instruments[0].symbol), Ok(terms.clone()));
+        assert_eq!(instruments[1].symbol, "CUSTOM");
+        assert_eq!(Instrument { contract_multiplier: 100, tick_size: 5, ..Instrument::new_option(terms.clone(), Currency::new("USD").unwrap()) }, instruments[0]);
+
+        let expiry = terms.expiry;
+        assert!(!instruments[0].is_expired(expiry) && instruments[0].is_expired(expiry.succ_opt().unwrap()));
+        assert!(!Instrument::new("ACME", Currency::new("USD").unwrap()).is_expired(expiry.succ_opt().unwrap()));
+
+        assert!(OptionTerms::from_symbol("ACME-20261218-X-10000").is_err());
+        assert!(OptionTerms::from_symbol("20261218-C-10000").is_err());
+        assert_eq!(parse_instruments(",USD,5,1,100,-2,,,,ACME,2026-12-18,C,0"), Err("line 1: strike must be positive".to_string()));
+        assert_eq!(parse_instruments(",USD,5,1,100,-2,,,"), Err("line 1: missing symbol".to_string()));
+    }
+}
@@ -0,0 +1,316 @@
+//! # Journal Module
+//!
+//! An ordered log of every command applied to a book together with the
+//! [order events](crate::events) each one produced, and the means to rebuild the book from it.
+//!
+//! ## Model
+//! - [`Journal::apply`] runs a [`Command`] against a book and appends it to the log, followed
+//!   by the events it caused. Events raised outside a command, such as the end-of-day prune
+//!   of GoodForDay orders, are picked up by [`Journal::record_events`].
+//! - [`Orderbook::from_events`] replays the events of a log into a fresh book, order flags
+//!   included. Commands are only consulted for the order type of the orders they submit, so
+//!   the log doubles as an audit trail: every change of state is an event, every event is
+//!   explained by the command before it.
+//!
+//! The book must have [`set_publish_events`](Orderbook::set_publish_events) turned on, or
+//! the log holds commands only and rebuilds an empty book.
+//!
+//! Orders that were [restored](Orderbook::restore_order) with partial fills come back with
+//! their remaining quantity as their initial quantity, since that is all their event carries.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{OrderType, Orderbook, Side};
+//! use orderbook::backtest::Command;
+//! use orderbook::journal::Journal;
+//! use orderbook::snapshot::BookSnapshot;
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.set_publish_events(true);
+//! let mut journal = Journal::new();
+//! journal.apply(&ob, Command::New { order_id: 1, side: Side::Sell, price: 101, quantity: 5, order_type: OrderType::GoodTillCancel, flags: Default::default() });
+//! journal.apply(&ob, Command::Market { order_id: 2, side: Side::Buy, quantity: 2 });
+//!
+//! let rebuilt = Orderbook::from_events(journal.into_entries()).unwrap();
+//! assert_eq!(BookSnapshot::capture(&rebuilt), BookSnapshot::capture(&ob));
+//! ```
+
+use std::{collections::{BTreeMap, HashMap}, time::Duration};
+use log::debug;
+use crate::backtest::{apply_command, Command};
+use crate::events::{BookEvent, SequencedEvent};
+use crate::orderbook::{Order, OrderFlags, OrderId, OrderType, Orderbook, Price, Quantity, Side, Trade};
+use crate::simulator::SimulationSink;
+
+/// One line of a [`Journal`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JournalEntry {
+    /// A command as it was applied to the book.
+    Command(Command),
+    /// A change the book made, in the book's own sequence.
+    Event(SequencedEvent),
+}
+
+/// Records commands and the events they cause, in the order they happened.
+#[derive(Clone, Debug, Default)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Creates an empty journal.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `command` to `orderbook` and logs it, followed by the events it produced.
+    ///
+    /// Events still buffered in the book are logged first, so they stay ahead of the command.
+    ///
+    /// # Returns
+    /// The trades produced by the command.
+    pub fn apply(&mut self, orderbook: &Orderbook, command: Command) -> Vec<Trade> {
+        self.record_command(orderbook, command);
+        let trades = apply_command(orderbook, command);
+        self.record_events(orderbook);
+        trades
+    }
+
+    /// Logs `command` that the caller is about to apply to `orderbook` itself.
+    ///
+    /// Events still buffered in the book are logged first; call [`record_events`](Self::record_events)
+    /// once the command has been applied.
+    pub fn record_command(&mut self, orderbook: &Orderbook, command: Command) {
+        self.record_events(orderbook);
+        self.entries.push(JournalEntry::Command(command));
+    }
+
+    /// Logs the events buffered in `orderbook` that no command accounts for.
+    pub fn record_events(&mut self, orderbook: &Orderbook) {
+        self.entries.extend(orderbook.take_events().into_iter().map(JournalEntry::Event));
+    }
+
+    /// Returns the logged entries, oldest first.
+    pub fn get_entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Consumes the journal, returning its entries oldest first.
+    pub fn into_entries(self) -> Vec<JournalEntry> {
+        self.entries
+    }
+}
+
+/// An order as rebuilt from events.
+struct RebuiltOrder {
+    order_type: OrderType,
+    side: Side,
+    price: Price,
+    initial_quantity: Quantity,
+    remaining_quantity: Quantity,
+    flags: OrderFlags,
+}
+
+/// Order ids resting at each price, in queue order.
+type Queues = BTreeMap<Price, Vec<OrderId>>;
+
+/// Replays the events in `entries` into a new book.
+///
+/// # Errors
+/// If the events do not start at sequence 1, skip a sequence number, or refer to orders
+/// that are not, or are already, in the book.
+pub(crate) fn rebuild(entries: impl IntoIterator<Item = JournalEntry>) -> Result<Orderbook, String> {
+    let mut order_types: HashMap<OrderId, OrderType> = HashMap::new();
+    let mut orders: HashMap<OrderId, RebuiltOrder> = HashMap::new();
+    let (mut bids, mut asks) = (Queues::new(), Queues::new());
+    let mut last_sequence = 0;
+
+    for entry in entries {
+        let SequencedEvent { sequence, event } = match entry {
+            JournalEntry::Command(Command::New { order_id, order_type, .. }) => {
+                order_types.insert(order_id, order_type);
+                continue;
+            }
+            JournalEntry::Command(Command::Market { order_id, .. }) => {
+                // Market orders only ever rest after conversion to GoodTillCancel.
+                order_types.insert(order_id, OrderType::GoodTillCancel);
+                continue;
+            }
+            JournalEntry::Command(_) => continue,
+            JournalEntry::Event(event) => event,
+        };
+        if sequence != last_sequence + 1 {
+            return Err(format!("Event {} follows event {}", sequence, last_sequence));
+        }
+        last_sequence = sequence;
+
+        match event {
+            BookEvent::OrderAdded { order_id, side, price, quantity, flags } => {
+                if orders.contains_key(&order_id) {
+                    return Err(format!("Event {}: Order#{} already exists", sequence, order_id));
+                }
+                // Orders restored from a snapshot have no command; only resting types get restored.
+                let order_type = order_types.remove(&order_id).unwrap_or(OrderType::GoodTillCancel);
+                queues(&mut bids, &mut asks, side).entry(price).or_default().push(order_id);
+                orders.insert(order_id, RebuiltOrder { order_type, side, price, initial_quantity: quantity, remaining_quantity: quantity, flags });
+            }
+            BookEvent::OrderExecuted { order_id, remaining, .. } => {
+                let Some(order) = orders.get_mut(&order_id) else {
+                    return Err(format!("Event {}: Order#{} is not in the book", sequence, order_id));
+                };
+                order.remaining_quantity = remaining;
+                if remaining == 0 {
+                    remove(&mut orders, &mut bids, &mut asks, order_id);
+                }
+            }
+            BookEvent::OrderCancelled { order_id, .. } => {
+                if !orders.contains_key(&order_id) {
+                    return Err(format!("Event {}: Order#{} is not in the book", sequence, order_id));
+                }
+                remove(&mut orders, &mut bids, &mut asks, order_id);
+            }
+            BookEvent::OrderReplaced { order_id, side, price, quantity } => {
+                let Some(replaced) = remove(&mut orders, &mut bids, &mut asks, order_id) else {
+                    return Err(format!("Event {}: Order#{} is not in the book", sequence, order_id));
+                };
+                queues(&mut bids, &mut asks, side).entry(price).or_default().push(order_id);
+                orders.insert(order_id, RebuiltOrder { side, price, initial_quantity: quantity, remaining_quantity: quantity, ..replaced });
+            }
+        }
+    }
+
+    let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+    for order_id in bids.values().chain(asks.values()).flatten() {
+        let order = &orders[order_id];
+        let pointer = Order::new(order.order_type, *order_id, order.side, order.price, order.initial_quantity);
+        pointer.lock().unwrap().set_flags(order.flags);
+        let filled = order.initial_quantity - order.remaining_quantity;
+        if filled > 0 {
+            pointer.lock().unwrap().fill(filled)?;
+        }
+        orderbook.restore_order(pointer)?;
+    }
+    debug!("Journal: rebuilt {} orders from {} events", orders.len(), last_sequence);
+    Ok(orderbook)
+}
+
+fn queues<'a>(bids: &'a mut Queues, asks: &'a mut Queues, side: Side) -> &'a mut Queues {
+    match side {
+        Side::Buy => bids,
+        Side::Sell => asks,
+    }
+}
+
+/// Takes `order_id` out of the book, returning it if it was there.
+///
+/// The engine swaps the last order of a level into the vacated slot, so this does too.
+fn remove(orders: &mut HashMap<OrderId, RebuiltOrder>, bids: &mut Queues, asks: &mut Queues, order_id: OrderId) -> Option<RebuiltOrder> {
+    let order = orders.remove(&order_id)?;
+    let queues = queues(bids, asks, order.side);
+    if let Some(queue) = queues.get_mut(&order.price) {
+        if let Some(location) = queue.iter().position(|id| *id == order_id) {
+            queue.swap_remove(location);
+        }
+        if queue.is_empty() {
+            queues.remove(&order.price);
+        }
+    }
+    Some(order)
+}
+
+/// Logs every command and the book's events.
+///
+/// The book must publish events. The journal takes them as they happen, so an
+/// [`EventBus`](crate::bus::EventBus) added after it only receives trades and depth deltas.
+impl SimulationSink for Journal {
+    fn on_command(&mut self, orderbook: &Orderbook, _timestamp: Duration, command: Command) -> Result<(), String> {
+        self.record_command(orderbook, command);
+        Ok(())
+    }
+
+    fn on_applied(&mut self, orderbook: &Orderbook, _timestamp: Duration, _command: Command, _trades: &[Trade]) -> Result<(), String> {
+        self.record_events(orderbook);
+        Ok(())
+    }
+
+    fn on_advance(&mut self, orderbook: &Orderbook, _timestamp: Duration, _wait: Duration) -> Result<(), String> {
+        self.record_events(orderbook);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use crate::orderbook::Capacity;
+    use crate::snapshot::BookSnapshot;
+
+    fn random_command(rng: &mut StdRng, order_id: OrderId) -> Command {
+        let side = if rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
+        let (price, quantity) = (rng.gen_range(95..=105), rng.gen_range(1..=10));
+        let existing = rng.gen_range(1..=order_id);
+        let flags = OrderFlags { capacity: None, short_sell: side == Side::Sell && rng.gen_bool(0.3) };
+        match rng.gen_range(0..10) {
+            0 => Command::Market { order_id, side, quantity },
+            1 => Command::Cancel { order_id: existing },
+            2 => Command::Modify { order_id: existing, side, price, quantity },
+            3 => Command::New { order_id, side, price, quantity, order_type: OrderType::FillAndKill, flags },
+            4 => Command::New { order_id, side, price, quantity, order_type: OrderType::FillOrKill, flags },
+            5 => Command::New { order_id, side, price, quantity, order_type: OrderType::GoodForDay, flags },
+            _ => Command::New { order_id, side, price, quantity, order_type: OrderType::GoodTillCancel, flags },
+        }
+    }
+
+    #[test]
+    fn test_rebuilt_book_matches_the_original(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        let mut journal = Journal::new();
+        let mut rng = StdRng::seed_from_u64(11);
+        for order_id in 1..=500 {
+            journal.apply(&ob, random_command(&mut rng, order_id));
+
+            // Replaying any prefix gives the book as it was at that point.
+            if order_id % 100 == 0 {
+                let rebuilt = Orderbook::from_events(journal.get_entries().iter().copied()).unwrap();
+                assert_eq!(BookSnapshot::capture(&rebuilt), BookSnapshot::capture(&ob));
+            }
+        }
+        assert!(ob.size() > 0);
+    }
+
+    #[test]
+    fn test_rebuilt_book_keeps_order_flags(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        let mut journal = Journal::new();
+        let flags = OrderFlags { capacity: Some(Capacity::Agency), short_sell: true };
+        journal.apply(&ob, Command::New { order_id: 1, side: Side::Sell, price: 101, quantity: 5, order_type: OrderType::GoodTillCancel, flags });
+        journal.apply(&ob, Command::Modify { order_id: 1, side: Side::Sell, price: 102, quantity: 8 });
+
+        let rebuilt = Orderbook::from_events(journal.get_entries().iter().copied()).unwrap();
+        assert_eq!(BookSnapshot::capture(&rebuilt), BookSnapshot::capture(&ob));
+        assert_eq!(BookSnapshot::capture(&rebuilt).asks[0].orders[0].flags, flags);
+    }
+
+    #[test]
+    fn test_rejects_incomplete_logs(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        let mut journal = Journal::new();
+        journal.apply(&ob, Command::New { order_id: 1, side: Side::Buy, price: 100, quantity: 5, order_type: OrderType::GoodTillCancel, flags: OrderFlags::default() });
+        journal.apply(&ob, Command::Cancel { order_id: 1 });
+        journal.apply(&ob, Command::New { order_id: 2, side: Side::Buy, price: 100, quantity: 5, order_type: OrderType::GoodTillCancel, flags: OrderFlags::default() });
+
+        let entries = journal.into_entries();
+        assert_eq!(entries.len(), 6);
+        let gap = [&entries[..2], &entries[5..]].concat();
+        assert_eq!(Orderbook::from_events(gap).unwrap_err(), "Event 3 follows event 1");
+
+        let cancel = BookEvent::OrderCancelled { order_id: 9, side: Side::Buy, price: 100, quantity: 5 };
+        let unknown = JournalEntry::Event(SequencedEvent { sequence: 1, event: cancel });
+        assert_eq!(Orderbook::from_events([unknown]).unwrap_err(), "Event 1: Order#9 is not in the book");
+    }
+}
@@ -0,0 +1,115 @@
+//! # JSON Module
+//!
+//! JSON views of book depth and of the trade tape, so web dashboards and the websocket feed
+//! share one format. Documents are written with `serde_json` from the book's own types.
+//!
+//! ## Depth
+//! [`Orderbook::to_json_depth`](crate::Orderbook::to_json_depth) and [`depth_to_json`] give
+//! both sides best price first, one [`LevelInfo`] per level:
+//!
+//! ```text
+//! {"bids":[{"price":100,"quantity":15},{"price":99,"quantity":10}],"asks":[{"price":101,"quantity":7}]}
+//! ```
+//!
+//! ## Trade tape
+//! [`trade_to_json`] gives one trade, timestamped in microseconds on the caller's clock, with
+//! the [`TradeInfo`] of each side:
+//!
+//! ```text
+//! {"timestamp_us":1500,"quantity":5,"bid":{"order_id":1,"price":100,"quantity":5,"flags":{"capacity":null,"short_sell":false}},"ask":{...}}
+//! ```
+//!
+//! [`trades_to_json`] gives an array of those, for a batch of trades from one command.
+
+use std::{cmp::Reverse, time::Duration};
+use serde::Serialize;
+use crate::orderbook::{LevelInfo, OrderbookLevelInfos, Quantity, Trade, TradeInfo};
+
+#[derive(Serialize)]
+struct Depth<'a> {
+    bids: Vec<&'a LevelInfo>,
+    asks: Vec<&'a LevelInfo>,
+}
+
+#[derive(Serialize)]
+struct TapeEntry {
+    timestamp_us: u128,
+    quantity: Quantity,
+    bid: TradeInfo,
+    ask: TradeInfo,
+}
+
+impl TapeEntry {
+    fn new(timestamp: Duration, trade: &Trade) -> Self {
+        Self {
+            timestamp_us: timestamp.as_micros(),
+            quantity: trade.get_bid_trade().quantity,
+            bid: trade.get_bid_trade(),
+            ask: trade.get_ask_trade(),
+        }
+    }
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("book types serialize to JSON")
+}
+
+/// Formats depth as a JSON object with `bids` and `asks`, best price first.
+pub fn depth_to_json(infos: &OrderbookLevelInfos) -> String {
+    let mut depth = Depth { bids: infos.get_bids().iter().collect(), asks: infos.get_asks().iter().collect() };
+    depth.bids.sort_by_key(|level| Reverse(level.price));
+    depth.asks.sort_by_key(|level| level.price);
+    to_json(&depth)
+}
+
+/// Formats a trade as a JSON object, stamped with `timestamp`.
+pub fn trade_to_json(timestamp: Duration, trade: &Trade) -> String {
+    to_json(&TapeEntry::new(timestamp, trade))
+}
+
+/// Formats trades that happened at `timestamp` as a JSON array.
+pub fn trades_to_json(timestamp: Duration, trades: &[Trade]) -> String {
+    to_json(&trades.iter().map(|trade| TapeEntry::new(timestamp, trade)).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::orderbook::{Capacity, Order, OrderFlags, OrderType, Orderbook, Side};
+
+    #[test]
+    fn test_depth_to_json(){
+        let book = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        assert_eq!(book.to_json_depth(), "{\"bids\":[],\"asks\":[]}");
+
+        book.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 99, 10));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 100, 5));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 100, 10));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 102, 3));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 5, Side::Sell, 101, 7));
+        assert_eq!(
+            book.to_json_depth(),
+            "{\"bids\":[{\"price\":100,\"quantity\":15},{\"price\":99,\"quantity\":10}],\"asks\":[{\"price\":101,\"quantity\":7},{\"price\":102,\"quantity\":3}]}"
+        );
+    }
+
+    #[test]
+    fn test_trades_to_json(){
+        let trade = |bid_id, ask_id, price, quantity| Trade::new(
+            TradeInfo { order_id: bid_id, price, quantity, flags: OrderFlags::default() },
+            TradeInfo { order_id: ask_id, price, quantity, flags: OrderFlags { capacity: Some(Capacity::Principal), short_sell: true } },
+        );
+        let timestamp = Duration::from_micros(1500);
+        assert_eq!(
+            trade_to_json(timestamp, &trade(1, 2, -5, 4)),
+            "{\"timestamp_us\":1500,\"quantity\":4,\
+             \"bid\":{\"order_id\":1,\"price\":-5,\"quantity\":4,\"flags\":{\"capacity\":null,\"short_sell\":false}},\
+             \"ask\":{\"order_id\":2,\"price\":-5,\"quantity\":4,\"flags\":{\"capacity\":\"Principal\",\"short_sell\":true}}}"
+        );
+        assert_eq!(trades_to_json(timestamp, &[]), "[]");
+        let tape = trades_to_json(timestamp, &[trade(1, 2, 100, 5), trade(3, 2, 100, 1)]);
+        assert!(tape.starts_with("[{\"timestamp_us\":1500,\"quantity\":5,"), "{}", tape);
+        assert_eq!(tape.matches("timestamp_us").count(), 2);
+    }
+}
@@ -0,0 +1,27 @@
+pub mod orderbook;
+pub mod simulator;
+pub mod auction;
+pub mod backtest;
+pub mod bus;
+pub mod clock;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod corporate;
+pub mod events;
+pub mod flatbuf;
+pub mod harness;
+pub mod implied;
+pub mod instrument;
+pub mod journal;
+pub mod json;
+pub mod manager;
+pub mod recorder;
+pub mod regulation;
+pub mod replay;
+pub mod scenario;
+pub mod session;
+pub mod snapshot;
+pub mod tape;
+pub mod triggers;
+
+pub use crate::orderbook::*;
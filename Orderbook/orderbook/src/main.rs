@@ -1,15 +1,8 @@
-mod orderbook;
-use std::{
-    rc::Rc,
-    cell::RefCell,
-    collections::{BTreeMap, HashMap}
-};
-use crate::orderbook::{Orderbook, Order, OrderType, Side};
-use log::{info, warn, error, debug, trace};
+use std::collections::BTreeMap;
+use orderbook::{Orderbook, Order, OrderType, Side};
 use std::thread;
 use std::time::Duration;
 use colored::*;
-use fern::Dispatch;
 
 
 
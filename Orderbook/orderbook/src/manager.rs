@@ -0,0 +1,333 @@
+//! # Manager Module
+//!
+//! Runs one [`Orderbook`] per [`Instrument`], keyed by symbol.
+//!
+//! ## Model
+//! The [`OrderbookManager`] owns the instruments and their books. Orders are routed by
+//! symbol and checked against the instrument's tick and lot size before they reach the book;
+//! market orders have no price to check. Money amounts are computed in the instrument's
+//! currency and, for accounts that settle in another one, converted through the manager's
+//! [`FxRates`].
+//!
+//! ## Instrument Files
+//! At startup, [`OrderbookManager::from_file`] lists every instrument of an
+//! [instrument file](crate::instrument#instrument-files). [`OrderbookManager::reload`] reads the
+//! file again and lists the instruments added since, with empty books. Instruments already
+//! listed keep their definition and their live book, even if the file changed them; the
+//! reload reports them, so such changes are rolled out by restarting.
+//!
+//! Orders for an instrument with [`TradingHours`](crate::instrument::TradingHours) are only
+//! accepted while it trades, as told by the manager's [`Clock`].
+//!
+//! ## Options
+//! Options are listed like any instrument, e.g. a whole
+//! [`option_chain`](crate::instrument::option_chain) at once, and found again by underlying
+//! with [`OrderbookManager::get_option_chain`]. Expired options take no orders and cannot be
+//! listed, so reloads skip them. [`OrderbookManager::expire_instruments`], run once a day after
+//! the close, cancels what rests in their books and delists them.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Order, OrderType, Side};
+//! use orderbook::instrument::{Currency, Instrument};
+//! use orderbook::manager::OrderbookManager;
+//!
+//! let mut manager = OrderbookManager::new();
+//! manager.add_instrument(Instrument { tick_size: 5, ..Instrument::new("ACME", Currency::new("USD").unwrap()) }).unwrap();
+//! assert!(manager.add_order("ACME", Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 10_005, 1)).is_ok());
+//! assert!(manager.add_order("ACME", Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 10_001, 1)).is_err());
+//! assert!(manager.add_order("OTHER", Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 10_005, 1)).is_err());
+//! ```
+
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, sync::Arc};
+use log::{info, warn};
+use crate::clock::{Clock, SystemClock};
+use crate::instrument::{parse_instruments, Currency, FixedRates, FxRates, Instrument, Money, OptionTerms};
+use crate::orderbook::{Order, OrderId, OrderPointer, OrderType, Side, Orderbook, Price, Quantity, Trades};
+
+/// Outcome of [`OrderbookManager::reload`].
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Reload {
+    /// Symbols listed by the reload.
+    pub added: Vec<String>,
+    /// Listed symbols whose definition in the file differs; they were left as they are.
+    pub changed: Vec<String>,
+}
+
+/// An instrument delisted by [`OrderbookManager::expire_instruments`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Expiry {
+    pub symbol: String,
+    /// Orders that rested in its book, bids first.
+    pub cancelled: Vec<OrderId>,
+}
+
+/// Owns the instruments of a venue and a book for each.
+pub struct OrderbookManager {
+    books: BTreeMap<String, (Instrument, Orderbook)>,
+    fx_rates: Arc<dyn FxRates>,
+    clock: Arc<dyn Clock>,
+    /// The instrument file, if the instruments were loaded from one.
+    instruments_path: Option<PathBuf>,
+}
+
+impl OrderbookManager {
+    /// Creates a manager without instruments and without exchange rates.
+    pub fn new() -> Self {
+        Self {
+            books: BTreeMap::new(),
+            fx_rates: Arc::new(FixedRates::new()),
+            clock: Arc::new(SystemClock),
+            instruments_path: None,
+        }
+    }
+
+    /// Creates a manager listing every instrument of the file at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let mut manager = Self::new();
+        manager.instruments_path = Some(path.as_ref().to_path_buf());
+        manager.reload()?;
+        Ok(manager)
+    }
+
+    /// Reads the instrument file again and lists the instruments that are new in it.
+    ///
+    /// # Errors
+    /// Returns an error, and lists nothing, if the manager was not loaded from a file or the
+    /// file cannot be read or parsed.
+    pub fn reload(&mut self) -> Result<Reload, String> {
+        let path = self.instruments_path.as_ref().ok_or("Instruments were not loaded from a file")?;
+        let contents = fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        let instruments = parse_instruments(&contents).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+        let mut reload = Reload::default();
+        for instrument in instruments {
+            match self.get_instrument(&instrument.symbol) {
+                None if instrument.is_expired(self.clock.now().date_naive()) => {}
+                Some(listed) if *listed != instrument => {
+                    warn!("Instrument {} changed in the file; it keeps its definition until a restart", instrument.symbol);
+                    reload.changed.push(instrument.symbol);
+                }
+                Some(_) => {}
+                None => {
+                    reload.added.push(instrument.symbol.clone());
+                    self.add_instrument(instrument)?;
+                }
+            }
+        }
+        Ok(reload)
+    }
+
+    /// Sets the clock trading hours are checked on.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Sets the rates cross-currency amounts are converted at.
+    pub fn set_fx_rates(&mut self, fx_rates: Arc<dyn FxRates>) {
+        self.fx_rates = fx_rates;
+    }
+
+    /// Lists `instrument` with an empty book.
+    ///
+    /// # Errors
+    /// Returns an error if the symbol is already listed, the tick or lot size is not positive,
+    /// or the instrument is an expired option.
+    pub fn add_instrument(&mut self, instrument: Instrument) -> Result<(), String> {
+        if self.books.contains_key(&instrument.symbol) {
+            return Err(format!("Instrument {} is already listed", instrument.symbol));
+        }
+        if instrument.tick_size <= 0 || instrument.lot_size == 0 {
+            return Err(format!("Instrument {} needs a positive tick and lot size", instrument.symbol));
+        }
+        if let Some(terms) = instrument.option.as_ref().filter(|terms| terms.is_expired(self.clock.now().date_naive())) {
+            return Err(format!("Instrument {} expired on {}", instrument.symbol, terms.expiry));
+        }
+        let book = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        self.books.insert(instrument.symbol.clone(), (instrument, book));
+        Ok(())
+    }
+
+    /// Returns the listed symbols in order.
+    pub fn get_symbols(&self) -> Vec<&str> {
+        self.books.keys().map(String::as_str).collect()
+    }
+
+    /// Returns the instrument listed as `symbol`, if any.
+    pub fn get_instrument(&self, symbol: &str) -> Option<&Instrument> {
+        self.books.get(symbol).map(|(instrument, _)| instrument)
+    }
+
+    /// Returns the listed options on `underlying`, by expiry, strike and kind.
+    pub fn get_option_chain(&self, underlying: &str) -> Vec<&Instrument> {
+        let mut chain: Vec<(&OptionTerms, &Instrument)> = self
+            .books
+            .values()
+            .filter_map(|(instrument, _)| instrument.option.as_ref().map(|terms| (terms, instrument)))
+            .filter(|(terms, _)| terms.underlying == underlying)
+            .collect();
+        chain.sort_by_key(|(terms, _)| (terms.expiry, terms.strike, terms.kind));
+        chain.into_iter().map(|(_, instrument)| instrument).collect()
+    }
+
+    /// Cancels the resting orders of every option that expired before today and delists it.
+    pub fn expire_instruments(&mut self) -> Vec<Expiry> {
+        let today = self.clock.now().date_naive();
+        let expired: Vec<String> = self
+            .books
+            .values()
+            .filter(|(instrument, _)| instrument.is_expired(today))
+            .map(|(instrument, _)| instrument.symbol.clone())
+            .collect();
+        let mut expiries = Vec::new();
+        for symbol in expired {
+            let (_, book) = self.books.remove(&symbol).expect("expired symbols are listed");
+            let cancelled: Vec<OrderId> = [Side::Buy, Side::Sell]
+                .into_iter()
+                .flat_map(|side| book.get_resting_orders(side))
+                .map(|order| order.get_order_id())
+                .collect();
+            for order_id in &cancelled {
+                book.cancel_order(*order_id);
+            }
+            info!("Delisted {} at expiry, cancelling {} orders", symbol, cancelled.len());
+            expiries.push(Expiry { symbol, cancelled });
+        }
+        expiries
+    }
+
+    /// Returns the book of `symbol`, if it is listed.
+    pub fn get_orderbook(&self, symbol: &str) -> Option<&Orderbook> {
+        self.books.get(symbol).map(|(_, book)| book)
+    }
+
+    /// Checks `order` against the instrument of `symbol` and adds it to its book.
+    ///
+    /// # Errors
+    /// Returns an error if [`check_order`](Self::check_order) rejects the order.
+    pub fn add_order(&self, symbol: &str, order: OrderPointer) -> Result<Trades, String> {
+        self.check_order(symbol, &order.lock().unwrap())?;
+        let (_, book) = &self.books[symbol];
+        Ok(book.add_order(order))
+    }
+
+    /// Checks that `order` may be sent to the book of `symbol`.
+    ///
+    /// # Errors
+    /// Returns an error if the symbol is not listed, has expired or does not trade at the
+    /// moment, or the order is off the tick or lot grid.
+    pub fn check_order(&self, symbol: &str, order: &Order) -> Result<(), String> {
+        let instrument = self.get_instrument(symbol).ok_or_else(|| format!("Unknown instrument {}", symbol))?;
+        let now = self.clock.now();
+        if let Some(terms) = instrument.option.as_ref().filter(|terms| terms.is_expired(now.date_naive())) {
+            return Err(format!("{} expired on {}", symbol, terms.expiry));
+        }
+        let now = now.time();
+        if instrument.trading_hours.is_some_and(|hours| !hours.contains(now)) {
+            return Err(format!("{} does not trade at {}", symbol, now.format("%H:%M:%S")));
+        }
+        // Market orders are priced by the book.
+        let price = if order.get_order_type() == OrderType::Market { 0 } else { order.get_price() };
+        instrument.check_order(price, order.get_initial_quantity())
+    }
+
+    /// Returns the value of `quantity` of `symbol` at `price` in `currency`, e.g. an account's
+    /// settlement currency.
+    ///
+    /// # Errors
+    /// Returns an error if the symbol is not listed or no rate converts its currency.
+    pub fn get_notional(&self, symbol: &str, price: Price, quantity: Quantity, currency: Currency) -> Result<Money, String> {
+        let instrument = self.get_instrument(symbol).ok_or_else(|| format!("Unknown instrument {}", symbol))?;
+        instrument.get_notional(price, quantity).convert(currency, self.fx_rates.as_ref())
+    }
+}
+
+impl Default for OrderbookManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use chrono::{DateTime, NaiveDate};
+    use crate::clock::MockClock;
+    use crate::instrument::option_chain;
+
+    #[test]
+    fn test_routes_and_converts_by_instrument(){
+        let (usd, gbp) = (Currency::new("USD").unwrap(), Currency::new("GBP").unwrap());
+        let mut manager = OrderbookManager::new();
+        manager.add_instrument(Instrument::new("ACME", usd)).unwrap();
+        manager.add_instrument(Instrument { lot_size: 10, ..Instrument::new("VOD", gbp) }).unwrap();
+        assert!(manager.add_instrument(Instrument::new("ACME", gbp)).is_err());
+        assert_eq!(manager.get_symbols(), ["ACME", "VOD"]);
+
+        manager.add_order("VOD", Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 7_500, 20)).unwrap();
+        assert!(manager.add_order("VOD", Order::new_market(2, Side::Buy, 5)).is_err());
+        assert_eq!(manager.add_order("VOD", Order::new_market(3, Side::Buy, 10)).unwrap().len(), 1);
+        assert_eq!((manager.get_orderbook("VOD").unwrap().size(), manager.get_orderbook("ACME").unwrap().size()), (1, 0));
+
+        assert!(manager.get_notional("VOD", 7_500, 10, usd).is_err());
+        manager.set_fx_rates(Arc::new(FixedRates::new().with_rate(gbp, usd, 1.25)));
+        assert_eq!(manager.get_notional("VOD", 7_500, 10, usd).unwrap().to_string(), "937.50 USD");
+        assert_eq!(manager.get_notional("VOD", 7_500, 10, gbp).unwrap().to_string(), "750.00 GBP");
+    }
+
+    #[test]
+    fn test_reload_adds_without_touching_live_books(){
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("instruments.csv");
+        fs::write(&path, "ACME,USD,1,1,1,-2,13:30,20:00,\n").unwrap();
+        let mut manager = OrderbookManager::from_file(&path).unwrap();
+        let clock = Arc::new(MockClock::new(DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z").unwrap().to_utc()));
+        manager.set_clock(clock.clone());
+        assert_eq!(manager.add_order("ACME", Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 1)).unwrap_err(), "ACME does not trade at 12:00:00");
+        clock.advance(Duration::from_secs(2 * 3600));
+        manager.add_order("ACME", Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 1)).unwrap();
+
+        fs::write(&path, "ACME,USD,5,1,1,-2,,,\nBETA,EUR,1,1,1,-2,,,\n").unwrap();
+        assert_eq!(manager.reload(), Ok(Reload { added: vec!["BETA".to_string()], changed: vec!["ACME".to_string()] }));
+        assert_eq!(manager.get_instrument("ACME").unwrap().tick_size, 1);
+        assert_eq!(manager.get_orderbook("ACME").unwrap().size(), 1);
+
+        fs::write(&path, "GAMMA,usd,1,1,1,-2,,,\n").unwrap();
+        assert!(manager.reload().unwrap_err().ends_with("line 1: Invalid currency code \"usd\""));
+        assert_eq!(manager.get_symbols(), ["ACME", "BETA"]);
+    }
+
+    #[test]
+    fn test_option_chain_expires(){
+        let mut manager = OrderbookManager::new();
+        let clock = Arc::new(MockClock::new(DateTime::parse_from_rfc3339("2026-12-18T21:00:00Z").unwrap().to_utc()));
+        manager.set_clock(clock.clone());
+        let template = Instrument { contract_multiplier: 100, ..Instrument::new("", Currency::new("USD").unwrap()) };
+        let (december, january) = (NaiveDate::from_ymd_opt(2026, 12, 18).unwrap(), NaiveDate::from_ymd_opt(2027, 1, 15).unwrap());
+        for instrument in option_chain(&template, "ACME", january, [10_000]).into_iter().chain(option_chain(&template, "ACME", december, [10_500, 10_000])) {
+            manager.add_instrument(instrument).unwrap();
+        }
+        let symbols: Vec<&str> = manager.get_option_chain("ACME").iter().map(|instrument| instrument.symbol.as_str()).collect();
+        assert_eq!(symbols, [
+            "ACME-20261218-C-10000", "ACME-20261218-P-10000", "ACME-20261218-C-10500", "ACME-20261218-P-10500",
+            "ACME-20270115-C-10000", "ACME-20270115-P-10000",
+        ]);
+
+        // Expiry day still trades.
+        manager.add_order("ACME-20261218-C-10000", Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 250, 1)).unwrap();
+        assert!(manager.expire_instruments().is_empty());
+
+        clock.advance(Duration::from_secs(12 * 3600));
+        assert_eq!(manager.add_order("ACME-20261218-P-10000", Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 250, 1)).unwrap_err(), "ACME-20261218-P-10000 expired on 2026-12-18");
+        let expiries = manager.expire_instruments();
+        assert_eq!(expiries.len(), 4);
+        assert_eq!(expiries[0], Expiry { symbol: "ACME-20261218-C-10000".to_string(), cancelled: vec![1] });
+        assert_eq!(manager.get_option_chain("ACME").len(), 2);
+        assert!(manager.add_instrument(option_chain(&template, "ACME", december, [11_000]).remove(0)).is_err());
+    }
+}
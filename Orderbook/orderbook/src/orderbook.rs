@@ -7,9 +7,15 @@
 //! - **Bid/Ask Management:** Uses price levels and order queues for efficient bid/ask tracking.
 //! - **Matching Engine:** Matches buy and sell orders, generating [`Trade`] records.
 //! - **Order Modification & Cancellation:** Allows modification via [`OrderModify`] and cancellation by order ID.
-//! - **Automatic Pruning:** GoodForDay orders are automatically pruned at market close.
+//! - **Automatic Pruning:** GoodForDay orders are automatically pruned at market close, as
+//!   told by the book's [`Clock`](crate::clock::Clock), when the book
+//!   [closes the session](crate::session).
 //! - **Thread Safety:** All operations are thread-safe using `Arc<Mutex<_>>`.
 //! - **Query Utilities:** Provides methods for querying orderbook state and trade history.
+//! - **Market Data Events:** Optionally publishes every change to a resting order and to each
+//!   price level (see [`events`](crate::events)).
+//! - **Auctions:** Rests orders without matching during an auction and executes them at a
+//!   single price when it ends (see [`auction`](crate::auction)).
 //! - **Extensibility & Testability:** Designed for easy extension and includes comprehensive unit tests.
 //!
 //! ## Main Types
@@ -53,8 +59,16 @@ use std::{
     sync::atomic::{AtomicBool, Ordering},
     time::{Duration, SystemTime, UNIX_EPOCH}
 };
-use chrono::{Local, NaiveDateTime, TimeDelta, DateTime, Timelike};
+use chrono::{Local, NaiveDateTime, TimeDelta, DateTime, Timelike, Utc};
 use log::{info, trace, warn, debug, error};
+use serde::Serialize;
+use crate::auction::{compute_uncross, IndicativeUncross, TradingPhase};
+use crate::clock::{Clock, SystemClock};
+use crate::corporate::{AdjustmentReport, PriceAdjustment};
+use crate::events::{BookEvent, DepthAction, DepthDelta, DepthSnapshot, SequencedEvent};
+use crate::journal::JournalEntry;
+use crate::regulation::{LastSale, ShortSaleRule};
+use crate::session::{DailyStats, EndOfDayReport};
 
 
 
@@ -81,6 +95,49 @@ pub enum Side {
     Sell,
 }
 
+/// Whose account an order trades for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+pub enum Capacity {
+    /// On behalf of a client.
+    Agency,
+    /// For the firm's own account.
+    Principal,
+}
+
+/// Optional regulatory fields of an order, passed on to its [`TradeInfo`]s.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize)]
+pub struct OrderFlags {
+    /// Capacity the order was entered in, if the venue asks for it.
+    pub capacity: Option<Capacity>,
+    /// Marks a sell of shares the seller does not own; only valid on [`Side::Sell`].
+    pub short_sell: bool,
+}
+
+/// Prices of a book that price checks and valuations are measured against.
+///
+/// The engine keeps `open` and `last` up to date as it trades; all three can also be set,
+/// e.g. to carry the previous close over from another system at start-up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ReferencePrices {
+    /// Closing price of the previous session.
+    pub previous_close: Option<Price>,
+    /// Price of the first trade of the current session.
+    pub open: Option<Price>,
+    /// Price of the last trade.
+    pub last: Option<Price>,
+}
+
+impl ReferencePrices {
+    /// Returns the most recent of the prices: the last trade, else the open, else the
+    /// previous close.
+    pub const fn get_current(&self) -> Option<Price> {
+        match (self.last, self.open) {
+            (Some(price), _) | (None, Some(price)) => Some(price),
+            (None, None) => self.previous_close,
+        }
+    }
+}
+
 /// Represents actions that can be performed on a price level's data in the orderbook.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum LevelDataAction {
@@ -92,17 +149,17 @@ pub enum LevelDataAction {
     Match,
 }
 
-type Price = i32;
-type Quantity = u32;
-type OrderId = u32;
+pub type Price = i32;
+pub type Quantity = u32;
+pub type OrderId = u32;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
 pub struct LevelInfo {
     pub price: Price,
     pub quantity: Quantity,
 }
 
-type LevelInfos = Vec<LevelInfo>;
+pub type LevelInfos = Vec<LevelInfo>;
 #[derive(Debug)]
 pub struct OrderbookLevelInfos {
     bid_infos: LevelInfos,
@@ -125,7 +182,7 @@ impl OrderbookLevelInfos {
 ///
 /// Tracks identity, side, price, and quantity lifecycle:
 /// initial → remaining/filled, with a convenience flag `filled`.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Order {
     /// Limit/market/GTC classification for matching behavior.
     order_type: OrderType,
@@ -144,6 +201,8 @@ pub struct Order {
     filled_quantity: Quantity,
     /// Convenience flag set when `remaining_quantity == 0`.
     filled: bool,
+    /// Capacity and short-sell indicator.
+    flags: OrderFlags,
 }
 
 impl Order {
@@ -174,6 +233,7 @@ impl Order {
             remaining_quantity: quantity,
             filled_quantity: 0,
             filled: false,
+            flags: OrderFlags::default(),
         }))
     }
 
@@ -199,6 +259,7 @@ impl Order {
     ///
     /// # Errors
     /// Returns an error if the order is not currently `OrderType::Market`.
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_good_till_cancel(&mut self, price: Price) -> Result<(), String> {
         match self.get_order_type(){
             OrderType::Market => {
@@ -206,7 +267,7 @@ impl Order {
                 self.order_type = OrderType::GoodTillCancel;
                 Ok(())
             }
-            _ => return Err("Order cannot have its price adjusted, only market orders can.".to_string()),
+            _ => Err("Order cannot have its price adjusted, only market orders can.".to_string()),
         }
     }
 
@@ -250,6 +311,16 @@ impl Order {
         self.filled
     }
 
+    /// Returns the capacity and short-sell indicator.
+    pub const fn get_flags(&self) -> OrderFlags {
+        self.flags
+    }
+
+    /// Sets the capacity and short-sell indicator; do so before submitting the order.
+    pub fn set_flags(&mut self, flags: OrderFlags) {
+        self.flags = flags;
+    }
+
     /// Applies a partial or full fill to the order.
     ///
     /// Decrements `remaining_quantity` and increments `filled_quantity`.
@@ -271,8 +342,8 @@ impl Order {
     }
 }
 
-type OrderPointer = Arc<Mutex<Order>>;
-type OrderPointers = Vec<OrderPointer>;
+pub type OrderPointer = Arc<Mutex<Order>>;
+pub type OrderPointers = Vec<OrderPointer>;
 
 /// Represents a request to modify an existing order.
 ///
@@ -348,7 +419,7 @@ impl OrderModify {
 ///
 /// `TradeInfo` contains the order ID, execution price, and executed
 /// quantity for a single participant in a matched trade.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct TradeInfo {
     /// Identifier of the order participating in the trade.
     pub order_id: OrderId,
@@ -356,13 +427,15 @@ pub struct TradeInfo {
     pub price: Price,
     /// Executed quantity for this side of the trade.
     pub quantity: Quantity,
+    /// Flags of the order, as submitted.
+    pub flags: OrderFlags,
 }
 
 /// Represents an executed trade in the order book.
 ///
 /// A `Trade` pairs the buy-side (`bid_trade`) and sell-side (`ask_trade`)
 /// information that resulted in a match.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Trade {
     /// Information about the bid (buy) side of the trade.
     bid_trade: TradeInfo,
@@ -395,7 +468,7 @@ impl Trade {
 }
 
 
-type Trades = Vec<Trade>;
+pub type Trades = Vec<Trade>;
 
 
 /// Internal record used to track an order’s position in the order book.
@@ -449,8 +522,10 @@ struct LevelData {
 ///
 /// # Example
 /// ```
-/// let book = Orderbook::new();
-/// book.add_order(my_order); // Internally locks `inner`
+/// use orderbook::{Orderbook, Order, OrderType, Side};
+///
+/// let book = Orderbook::new(Default::default(), Default::default());
+/// book.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 10)); // Internally locks `inner`
 /// ```
 #[derive(Debug)]
 /// Represents the main order book structure, providing thread-safe access and management
@@ -463,13 +538,15 @@ struct LevelData {
 /// - `shutdown_mutex`: Mutex used in conjunction with the condition variable to coordinate shutdown.
 /// - `shutdown_condition_variable`: Condition variable used to signal and wait for shutdown events.
 /// - `shutdown`: Atomic flag indicating whether a shutdown has been requested.
+/// - `clock`: Source of the time the pruning thread waits on.
 pub struct Orderbook {
     /// Shared, mutex-protected inner order book state (private to enforce encapsulation).
     inner: Arc<Mutex<InnerOrderbook>>,
     orders_prune_thread: Option<JoinHandle<()>>,
     shutdown_mutex: Arc<Mutex<()>>,
     shutdown_condition_variable: Arc<Condvar>,
-    shutdown: AtomicBool,
+    shutdown: Arc<AtomicBool>,
+    clock: Arc<dyn Clock>,
 }
 
 /// Represents a thread-safe, shareable order book for managing and matching orders.
@@ -486,6 +563,7 @@ pub struct Orderbook {
 /// - `shutdown_mutex`: Mutex used for coordinating shutdown of the pruning thread.
 /// - `shutdown_condition_variable`: Condition variable for waking the pruning thread.
 /// - `shutdown`: Atomic flag to signal shutdown to the pruning thread.
+/// - `clock`: Source of the time the pruning thread waits on.
 ///
 /// # Thread Safety
 /// All public methods lock the inner order book before mutating or reading state.
@@ -494,12 +572,13 @@ pub struct Orderbook {
 /// # Usage
 /// - Use [`Orderbook::new`] to create a book without background pruning.
 /// - Use [`Orderbook::build`] to create a book and launch the pruning thread.
+/// - Use [`Orderbook::build_with_clock`] to run the pruning thread on another [`Clock`].
 /// - Use [`Orderbook::add_order`], [`Orderbook::cancel_order`], and [`Orderbook::modify_order`] to interact with orders.
 /// - Use [`Orderbook::size`] and [`Orderbook::get_order_infos`] to query book state.
 ///
 /// # Background Pruning
 /// If built with [`Orderbook::build`], a background thread will periodically wake up at
-/// the configured cutoff hour (default: 16:00 local time) and cancel all GFD orders.
+/// the configured cutoff hour (default: 16:00 UTC on the book's clock) and cancel all GFD orders.
 /// The thread can be signaled to shut down early via the `shutdown` flag and condition variable.
 /// In test mode, the pruning thread performs a single prune cycle and exits.
 impl Orderbook {
@@ -518,7 +597,8 @@ impl Orderbook {
             orders_prune_thread: None,
             shutdown_mutex: Arc::new(Mutex::new(())),
             shutdown_condition_variable: Condvar::new().into(),
-            shutdown: AtomicBool::new(false)
+            shutdown: Arc::new(AtomicBool::new(false)),
+            clock: Arc::new(SystemClock),
         }
     }
 
@@ -536,6 +616,23 @@ impl Orderbook {
     /// - Stores the join handle in `orders_prune_thread` for lifecycle management.
     /// - Locking uses `Mutex::lock().unwrap()`, which will **panic** if the mutex is poisoned.
     pub fn build(bids: BTreeMap<Price, OrderPointers>, asks: BTreeMap<Price, OrderPointers>, test_mode: bool) -> Self {
+        Self::build_with_clock(bids, asks, test_mode, Arc::new(SystemClock))
+    }
+
+    /// Builds an `Orderbook` whose pruning thread takes the time from `clock`.
+    ///
+    /// With a [`MockClock`](crate::clock::MockClock), tests decide when the cutoff passes
+    /// instead of depending on the wall-clock hour.
+    ///
+    /// # Parameters
+    /// - `bids`, `asks`, `test_mode`: As for [`Orderbook::build`].
+    /// - `clock`: Source of the current time.
+    pub fn build_with_clock(
+        bids: BTreeMap<Price, OrderPointers>,
+        asks: BTreeMap<Price, OrderPointers>,
+        test_mode: bool,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         let inner = Arc::new(Mutex::new(InnerOrderbook::new(bids, asks)));
         
         let shutdown_condition_variable = Arc::new(Condvar::new());
@@ -546,6 +643,7 @@ impl Orderbook {
         let inner_clone = Arc::clone(&inner);
         let shutdown_clone = Arc::clone(&shutdown);
         let shutdown_condition_variable_clone = Arc::clone(&shutdown_condition_variable);
+        let clock_clone = Arc::clone(&clock);
 
         let handle = thread::spawn(move || {
             let orderbook = Orderbook {
@@ -553,7 +651,8 @@ impl Orderbook {
                 orders_prune_thread: None,
                 shutdown_mutex: mutex_clone,
                 shutdown_condition_variable: shutdown_condition_variable_clone,
-                shutdown: AtomicBool::new(false),
+                shutdown: shutdown_clone,
+                clock: clock_clone,
             };
             orderbook.prune_gfd_orders(test_mode);
         });
@@ -563,7 +662,8 @@ impl Orderbook {
             orders_prune_thread: Some(handle),
             shutdown_mutex,
             shutdown_condition_variable,
-            shutdown: AtomicBool::new(false),
+            shutdown,
+            clock,
         }
     }
 
@@ -618,129 +718,230 @@ impl Orderbook {
         self.inner.lock().unwrap().get_order_infos()
     }
 
+    /// Returns the best bid and best ask, if the side has any orders.
+    pub fn get_best_prices(&self) -> (Option<Price>, Option<Price>) {
+        self.inner.lock().unwrap().get_best_prices()
+    }
+
+    /// Returns whether the book trades continuously or is in an auction.
+    pub fn get_phase(&self) -> TradingPhase {
+        self.inner.lock().unwrap().get_phase()
+    }
+
+    /// Starts an auction: orders rest without matching until [`Orderbook::uncross`].
+    pub fn start_auction(&self) {
+        self.inner.lock().unwrap().start_auction()
+    }
+
+    /// Returns the price, quantity and imbalance the auction would uncross at now; `None`
+    /// outside an auction or while nothing crosses.
+    pub fn indicative_uncross(&self) -> Option<IndicativeUncross> {
+        self.inner.lock().unwrap().indicative_uncross()
+    }
+
+    /// Ends the auction, executing every crossing order at the indicative price.
+    ///
+    /// # Returns
+    /// The auction's trades; none if the book was not in an auction.
+    pub fn uncross(&self) -> Trades {
+        self.inner.lock().unwrap().uncross()
+    }
+
+    /// Returns the depth as JSON, both sides best price first (see [`json`](crate::json)).
+    pub fn to_json_depth(&self) -> String {
+        crate::json::depth_to_json(&self.get_order_infos())
+    }
+
+    /// Returns a copy of every order resting on `side`, in priority order: best price first,
+    /// and each level in queue order.
+    pub fn get_resting_orders(&self, side: Side) -> Vec<Order> {
+        self.inner.lock().unwrap().get_resting_orders(side)
+    }
+
+    /// Turns buffering of order-by-order [`events`](crate::events) on or off.
+    ///
+    /// Events are only recorded while this is on; turning it off drops any unread ones.
+    pub fn set_publish_events(&self, enabled: bool) {
+        self.inner.lock().unwrap().set_publish_events(enabled)
+    }
+
+    /// Returns the events buffered since the last call, oldest first.
+    pub fn take_events(&self) -> Vec<SequencedEvent> {
+        self.inner.lock().unwrap().take_events()
+    }
+
+    /// Returns the depth deltas buffered since the last call, oldest first.
+    pub fn take_depth_deltas(&self) -> Vec<DepthDelta> {
+        self.inner.lock().unwrap().take_depth_deltas()
+    }
+
+    /// Returns the current depth, stamped with the sequence of the last depth delta it includes.
+    pub fn get_depth_snapshot(&self) -> DepthSnapshot {
+        self.inner.lock().unwrap().get_depth_snapshot()
+    }
+
+    /// Enables `rule` for short sales entering this book, or disables the check with `None`.
+    pub fn set_short_sale_rule(&self, rule: Option<Arc<dyn ShortSaleRule>>) {
+        self.inner.lock().unwrap().set_short_sale_rule(rule)
+    }
+
+    /// Returns the price and tick of the last trade, if anything traded.
+    pub fn get_last_sale(&self) -> Option<LastSale> {
+        self.inner.lock().unwrap().get_last_sale()
+    }
+
+    /// Returns the book's reference prices.
+    pub fn get_reference_prices(&self) -> ReferencePrices {
+        self.inner.lock().unwrap().get_reference_prices()
+    }
+
+    /// Replaces the book's reference prices; trading keeps updating `open` and `last`.
+    pub fn set_reference_prices(&self, prices: ReferencePrices) {
+        self.inner.lock().unwrap().set_reference_prices(prices)
+    }
+
+    /// Checks a short sale at `price` against the book's rule without submitting anything.
+    ///
+    /// # Errors
+    /// The reason the rule refuses the short sale.
+    pub fn check_short_sale(&self, price: Price) -> Result<(), String> {
+        self.inner.lock().unwrap().check_short_sale(price)
+    }
+
+    /// Re-prices and resizes every resting order for a corporate action, atomically; see
+    /// [`corporate`](crate::corporate).
+    ///
+    /// # Errors
+    /// Returns an error, and changes nothing, if the adjustment is invalid or an adjusted
+    /// price or quantity would be out of range.
+    pub fn adjust_orders(&self, adjustment: &PriceAdjustment) -> Result<AdjustmentReport, String> {
+        self.inner.lock().unwrap().adjust_orders(adjustment)
+    }
+
+    /// Returns `true` if `order_id` is resting in the book.
+    pub fn contains_order(&self, order_id: OrderId) -> bool {
+        self.inner.lock().unwrap().orders.contains_key(&order_id)
+    }
+
+    /// Rebuilds a book by replaying the order events of a [`journal`](crate::journal).
+    ///
+    /// # Errors
+    /// If the events are incomplete or contradict each other.
+    pub fn from_events(entries: impl IntoIterator<Item = JournalEntry>) -> Result<Self, String> {
+        crate::journal::rebuild(entries)
+    }
+
+    /// Rests an order in the book exactly as it is, partial fills included, without matching.
+    ///
+    /// Used to rebuild a book from a [`snapshot`](crate::snapshot); orders must be restored in
+    /// priority order to keep their queue positions.
+    ///
+    /// # Errors
+    /// Returns an error if the order id is taken, the order is filled or of a type that never
+    /// rests, or it would cross the book.
+    pub fn restore_order(&self, order: OrderPointer) -> Result<(), String> {
+        self.inner.lock().unwrap().restore_order(order)
+    }
+
+    /// Returns the trading statistics of the current session.
+    pub fn get_daily_stats(&self) -> DailyStats {
+        self.inner.lock().unwrap().get_daily_stats()
+    }
+
+    /// Ends the trading day, as happens at the daily cutoff (see [`session`](crate::session)).
+    ///
+    /// # Returns
+    /// The report of the session just closed, stamped with the time on the book's clock.
+    pub fn close_session(&self) -> EndOfDayReport {
+        let closed_at = self.clock.now();
+        self.inner.lock().unwrap().close_session(closed_at)
+    }
+
+    /// Returns and clears the end-of-day reports buffered while publishing was on.
+    pub fn take_session_reports(&self) -> Vec<EndOfDayReport> {
+        self.inner.lock().unwrap().take_session_reports()
+    }
+
+    /// Cancels every resting GoodForDay order, as happens at the daily cutoff.
+    ///
+    /// Lets callers that keep their own time, such as a [`simulator`](crate::simulator) on a
+    /// virtual clock, end the trading day themselves.
+    ///
+    /// # Returns
+    /// The number of orders cancelled.
+    pub fn prune_good_for_day(&self) -> usize {
+        self.inner.lock().unwrap().prune_good_for_day()
+    }
+
     /// Background loop that cancels Good-For-Day orders at a daily cutoff.
     ///
-    /// Computes the next cutoff (local `end_hour`), waits on a condition variable
-    /// until either the timeout or `shutdown` is signaled, and on timeout
-    /// cancels all `GoodForDay` orders. When `test_mode` is `true`, performs
-    /// a single prune cycle then exits (useful for tests).
+    /// Computes the next cutoff (`end_hour` UTC on the book's clock), waits on a condition
+    /// variable until the clock reaches it or `shutdown` is signaled, and then cancels all
+    /// `GoodForDay` orders. When `test_mode` is `true`, performs a single prune cycle then
+    /// exits (useful for tests).
     fn prune_gfd_orders(&self, test_mode: bool) {
         let end_hour = 16;
         info!("end_hour: {}", end_hour);
 
         if test_mode {
             // In test mode, prune immediately and exit
-            let mut inner = self.inner.lock().unwrap();
             info!("Pruning Orders! (test mode)");
-            let mut order_ids = vec![];
-
-            for (order_id, entry) in &inner.orders {
-                let order = entry.order.lock().unwrap();
-                if order.get_order_type() == OrderType::GoodForDay {
-                    order_ids.push(*order_id);
-                }
-            }
-
-            for id in order_ids {
-                inner.cancel_order(id);
-            }
-
+            self.prune_good_for_day();
             info!("Finished pruning! test mode on");
             return;
         }
         loop {
             info!("Started Loop!");
-            let now = SystemTime::now();
-            let now_duration = now.duration_since(UNIX_EPOCH).unwrap();
-            debug!("now_duration: {:?}", now_duration);
-            let now_secs = now_duration.as_secs() as i64;
-            debug!("now_secs: {}", now_secs);
-
-            let now_parts = DateTime::from_timestamp(now_secs, 0).unwrap();
-            debug!("now_parts: {:?}", now_parts);
-            let mut date = now_parts.date_naive();
-            debug!("date: {}", date);
-            let hour = now_parts.hour();
-            debug!("hour: {}", hour);
-
-            debug!("Comparing hours!");
-            debug!("Current hour is {}, end hour is {}", hour, end_hour);
-            if hour >= end_hour {
+            let now = self.clock.now();
+            debug!("now: {:?}", now);
+            let mut date = now.date_naive();
+            debug!("Current hour is {}, end hour is {}", now.hour(), end_hour);
+            if now.hour() >= end_hour {
                 date = date.succ_opt().unwrap(); // move to next day
                 debug!("Moved to next day, new date: {}", date);
             }
-
-            let next_cutoff = date.and_hms_opt(end_hour, 0, 0).unwrap();
+            let next_cutoff = date.and_hms_opt(end_hour, 0, 0).unwrap().and_utc();
             debug!("next_cutoff: {}", next_cutoff);
-            let cutoff_ts = UNIX_EPOCH + Duration::from_secs(next_cutoff.and_utc().timestamp() as u64);
-            debug!("cutoff_ts: {:?}", cutoff_ts);
-            let now_system_time = SystemTime::now();
-            debug!("now_system_time: {:?}", now_system_time);
-
-            debug!("Finding wait duration");
-            let wait_duration = cutoff_ts
-                .duration_since(now_system_time)
-                .unwrap_or(Duration::from_secs(0)) + Duration::from_millis(100);
-            debug!("wait_duration: {:?}", wait_duration);
-
-            // Use a dummy mutex for waiting on the condition variable.
-            // let dummy_mutex = Mutex::new(());
-            let guard = self.shutdown_mutex.lock().unwrap();
-            let (guard, result) = self.shutdown_condition_variable
-                .wait_timeout(guard, wait_duration)
-                .unwrap();
-
-            debug!("result.timed_out(): {}", result.timed_out());
-            debug!("self.shutdown: {}", self.shutdown.load(Ordering::Acquire));
-
-            debug!("DEBUG: About to check shutdown condition");
-            if self.shutdown.load(Ordering::Acquire) {
-                info!("Shutdown requested, exiting prune_gfd_orders.");
-                return;
-            }
-
-            debug!("DEBUG: About to check timeout condition");
-            if !result.timed_out() {
-                info!("Woke up early (not timed out), skipping pruning.");
-                continue;
-            }
-
-            debug!("DEBUG: About to start pruning logic");
 
-            // Lock the inner orderbook only for the pruning section
-            {
-                let mut inner = self.inner.lock().unwrap();
-                info!("Pruning Orders!");
-                let mut order_ids = vec![];
-
-                debug!("DEBUG: About to iterate over orders");
-                for (order_id, entry) in &inner.orders {
-                    debug!("DEBUG: Checking order {}", order_id);
-                    let order = entry.order.lock().unwrap();
-                    debug!("DEBUG: Order type: {:?}", order.get_order_type());
-                    if order.get_order_type() == OrderType::GoodForDay {
-                        info!("DEBUG: Adding GFD order {} to cancellation list", order_id);
-                        order_ids.push(*order_id);
-                    }
+            // The clock may be moved by someone else, so check it again after every wake-up.
+            loop {
+                let now = self.clock.now();
+                if now >= next_cutoff {
+                    break;
                 }
-
-                info!("Found {} GFD orders to cancel", order_ids.len());
-
-                for id in order_ids {
-                    info!("Canceling order with id: {}", id);
-                    inner.cancel_order(id);
+                let wait_duration = self.clock.real_wait((next_cutoff - now).to_std().unwrap_or_default());
+                debug!("wait_duration: {:?}", wait_duration);
+
+                // Checked under the lock, so a shutdown signaled from here on wakes the wait.
+                let guard = self.shutdown_mutex.lock().unwrap();
+                if self.shutdown.load(Ordering::Acquire) {
+                    info!("Shutdown requested, exiting prune_gfd_orders.");
+                    return;
                 }
-
-                info!("Orders left: {}", inner.orders.len());
+                let (guard, result) = self.shutdown_condition_variable
+                    .wait_timeout(guard, wait_duration)
+                    .unwrap();
+                if self.shutdown.load(Ordering::Acquire) {
+                    info!("Shutdown requested, exiting prune_gfd_orders.");
+                    return;
+                }
+                debug!("Woke up (timed out: {})", result.timed_out());
             }
+
+            info!("Closing session!");
+            let report = self.close_session();
+            info!("Closed session {}, cancelled {} GFD orders, orders left: {}", report.session, report.pruned, self.size());
         }
     }
 }
 
 impl Drop for Orderbook {
     fn drop(&mut self) {
-        self.shutdown.store(true, Ordering::Release);
-        self.shutdown_condition_variable.notify_one();
+        {
+            let _guard = self.shutdown_mutex.lock().unwrap();
+            self.shutdown.store(true, Ordering::Release);
+            self.shutdown_condition_variable.notify_one();
+        }
         if let Some(handle) = self.orders_prune_thread.take() {
             let _ = handle.join();
         }
@@ -770,6 +971,36 @@ pub struct InnerOrderbook {
     asks: BTreeMap<Price, OrderPointers>,
     /// Fast lookup: order id → (pointer + cached location/side/price).
     orders: HashMap<OrderId, OrderEntry>,
+    /// Remaining quantity per bid level, the source of depth deltas.
+    bid_depth: BTreeMap<Price, Quantity>,
+    /// Remaining quantity per ask level, the source of depth deltas.
+    ask_depth: BTreeMap<Price, Quantity>,
+    /// Depth deltas not yet taken by [`InnerOrderbook::take_depth_deltas`].
+    depth_deltas: Vec<DepthDelta>,
+    /// Sequence number of the last depth delta.
+    last_depth_sequence: u64,
+    /// Whether hooks record [`BookEvent`]s and [`DepthDelta`]s.
+    publish_events: bool,
+    /// Events not yet taken by [`InnerOrderbook::take_events`].
+    events: Vec<SequencedEvent>,
+    /// Sequence number of the last recorded event.
+    last_sequence: u64,
+    /// Order being modified: its cancel is held back and its re-add reported as a replace.
+    replacing: Option<OrderId>,
+    /// Cancel event held back while `replacing` is set.
+    replaced_cancel: Option<BookEvent>,
+    /// Check applied to short sales, if the venue enabled one.
+    short_sale_rule: Option<Arc<dyn ShortSaleRule>>,
+    /// Price and tick of the last trade.
+    last_sale: Option<LastSale>,
+    reference_prices: ReferencePrices,
+    daily_stats: DailyStats,
+    /// Sessions closed so far.
+    sessions_closed: u64,
+    session_reports: Vec<EndOfDayReport>,
+    phase: TradingPhase,
+    /// Uncross of the book as it stands, kept up to date during an auction.
+    indicative: Option<IndicativeUncross>,
 }
 
 impl InnerOrderbook {
@@ -782,6 +1013,23 @@ impl InnerOrderbook {
             asks,
             orders: HashMap::new(),
             data: HashMap::new(),
+            bid_depth: BTreeMap::new(),
+            ask_depth: BTreeMap::new(),
+            depth_deltas: Vec::new(),
+            last_depth_sequence: 0,
+            publish_events: false,
+            events: Vec::new(),
+            last_sequence: 0,
+            replacing: None,
+            replaced_cancel: None,
+            short_sale_rule: None,
+            last_sale: None,
+            reference_prices: ReferencePrices::default(),
+            daily_stats: DailyStats::default(),
+            sessions_closed: 0,
+            session_reports: Vec::new(),
+            phase: TradingPhase::Continuous,
+            indicative: None,
         }
     }
 
@@ -790,6 +1038,44 @@ impl InnerOrderbook {
         self.orders.len()
     }
 
+    /// Returns the highest bid and the lowest ask.
+    pub fn get_best_prices(&self) -> (Option<Price>, Option<Price>) {
+        (self.bids.keys().next_back().copied(), self.asks.keys().next().copied())
+    }
+
+    /// Returns whether the book trades continuously or is in an auction.
+    pub const fn get_phase(&self) -> TradingPhase {
+        self.phase
+    }
+
+    /// Starts an auction, in which orders rest without matching.
+    pub fn start_auction(&mut self) {
+        self.phase = TradingPhase::Auction;
+        self.update_indicative();
+    }
+
+    /// Returns the uncross kept up to date during the auction.
+    pub const fn indicative_uncross(&self) -> Option<IndicativeUncross> {
+        self.indicative
+    }
+
+    /// Ends the auction and executes every crossing order at the indicative price.
+    pub fn uncross(&mut self) -> Trades {
+        if self.phase != TradingPhase::Auction {
+            return vec![];
+        }
+        self.phase = TradingPhase::Continuous;
+        let Some(indicative) = self.indicative.take() else {
+            return vec![];
+        };
+        let trades = self.match_orders(Some(indicative.price));
+        for trade in &trades {
+            self.record_trade(indicative.price, trade.get_bid_trade().quantity);
+        }
+        info!("Uncrossed {} at {} in {} trades", indicative.matched_quantity, indicative.price, trades.len());
+        trades
+    }
+
     /// Produces aggregated depth (level infos) for bids and asks.
     ///
     /// Each level contains `(price, total_remaining_quantity)` gathered from the queues.
@@ -815,6 +1101,218 @@ impl InnerOrderbook {
         OrderbookLevelInfos { bid_infos, ask_infos }
     }
 
+    /// Turns event recording on or off, dropping unread events when turned off.
+    pub fn set_publish_events(&mut self, enabled: bool) {
+        self.publish_events = enabled;
+        if !enabled {
+            self.events.clear();
+            self.depth_deltas.clear();
+        }
+    }
+
+    /// Returns and clears the recorded events, oldest first.
+    pub fn take_events(&mut self) -> Vec<SequencedEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Returns and clears the recorded depth deltas, oldest first.
+    pub fn take_depth_deltas(&mut self) -> Vec<DepthDelta> {
+        std::mem::take(&mut self.depth_deltas)
+    }
+
+    /// Enables `rule` for short sales, or disables the check with `None`.
+    pub fn set_short_sale_rule(&mut self, rule: Option<Arc<dyn ShortSaleRule>>) {
+        self.short_sale_rule = rule;
+    }
+
+    /// Returns the price and tick of the last trade, if anything traded.
+    pub const fn get_last_sale(&self) -> Option<LastSale> {
+        self.last_sale
+    }
+
+    /// Returns the previous close, the session open and the last trade price.
+    pub const fn get_reference_prices(&self) -> ReferencePrices {
+        self.reference_prices
+    }
+
+    /// Replaces the reference prices.
+    pub fn set_reference_prices(&mut self, prices: ReferencePrices) {
+        self.reference_prices = prices;
+    }
+
+    /// Returns the statistics of the current session.
+    pub const fn get_daily_stats(&self) -> DailyStats {
+        self.daily_stats
+    }
+
+    /// Prunes GoodForDay orders, freezes the day's statistics into a report and starts the
+    /// next session.
+    pub fn close_session(&mut self, closed_at: DateTime<Utc>) -> EndOfDayReport {
+        let pruned = self.prune_good_for_day();
+        let stats = std::mem::take(&mut self.daily_stats);
+        self.reference_prices.previous_close = stats.close.or(self.reference_prices.previous_close);
+        self.reference_prices.open = None;
+        self.sessions_closed += 1;
+
+        let report = EndOfDayReport { session: self.sessions_closed, closed_at, stats, pruned };
+        if self.publish_events {
+            self.session_reports.push(report);
+        }
+        report
+    }
+
+    /// Returns and clears the buffered end-of-day reports.
+    pub fn take_session_reports(&mut self) -> Vec<EndOfDayReport> {
+        std::mem::take(&mut self.session_reports)
+    }
+
+    /// Checks a short sale at `price` against the rule, if one is enabled.
+    pub fn check_short_sale(&self, price: Price) -> Result<(), String> {
+        match &self.short_sale_rule {
+            Some(rule) => rule.check(price, self.last_sale),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns every level's remaining quantity, best price first.
+    pub fn get_depth_snapshot(&self) -> DepthSnapshot {
+        DepthSnapshot {
+            sequence: self.last_depth_sequence,
+            bids: self.bid_depth.iter().rev().map(|(price, quantity)| (*price, *quantity)).collect(),
+            asks: self.ask_depth.iter().map(|(price, quantity)| (*price, *quantity)).collect(),
+        }
+    }
+
+    /// Returns a copy of every order resting on `side`, best price first, each level in queue order.
+    pub fn get_resting_orders(&self, side: Side) -> Vec<Order> {
+        let copy = |orders: &OrderPointers| orders.iter().map(|order| order.lock().unwrap().clone()).collect::<Vec<_>>();
+        match side {
+            Side::Buy => self.bids.values().rev().flat_map(copy).collect(),
+            Side::Sell => self.asks.values().flat_map(copy).collect(),
+        }
+    }
+
+    /// Cancels every GoodForDay order, lowest order id first.
+    ///
+    /// Cancels reshuffle queues, so they run in a fixed order rather than the map's.
+    pub fn prune_good_for_day(&mut self) -> usize {
+        let mut order_ids: Vec<OrderId> = self.orders.iter()
+            .filter(|(_, entry)| entry.order.lock().unwrap().get_order_type() == OrderType::GoodForDay)
+            .map(|(order_id, _)| *order_id)
+            .collect();
+        order_ids.sort_unstable();
+        for order_id in &order_ids {
+            debug!("Pruning GFD Order#{}", order_id);
+            self.cancel_order(*order_id);
+        }
+        order_ids.len()
+    }
+
+    /// Applies a corporate action to every resting order and to the reference prices.
+    ///
+    /// Levels are rebuilt best price first, each in queue order, so orders keep their
+    /// priority; see [`corporate`](crate::corporate).
+    pub fn adjust_orders(&mut self, adjustment: &PriceAdjustment) -> Result<AdjustmentReport, String> {
+        adjustment.validate()?;
+        // Check everything before touching anything.
+        for entry in self.orders.values() {
+            let ord = entry.order.lock().unwrap();
+            adjustment.adjust_price(entry.side, entry.price)?;
+            adjustment.adjust_quantity(ord.get_remaining_quantity())?;
+            adjustment.adjust_quantity(ord.get_filled_quantity())?;
+        }
+        let adjust_reference = |price: Option<Price>| price.map(|price| adjustment.adjust_reference(price)).transpose();
+        let reference_prices = ReferencePrices {
+            previous_close: adjust_reference(self.reference_prices.previous_close)?,
+            open: adjust_reference(self.reference_prices.open)?,
+            last: adjust_reference(self.reference_prices.last)?,
+        };
+        let last_sale_price = adjust_reference(self.last_sale.map(|sale| sale.price))?;
+
+        let mut report = AdjustmentReport::default();
+        let bids: Vec<(Price, OrderPointers)> = std::mem::take(&mut self.bids).into_iter().rev().collect();
+        let asks: Vec<(Price, OrderPointers)> = std::mem::take(&mut self.asks).into_iter().collect();
+        for (side, levels) in [(Side::Buy, bids), (Side::Sell, asks)] {
+            for (price, queue) in levels {
+                let new_price = adjustment.adjust_price(side, price)?;
+                for order in queue {
+                    let (order_id, remaining_quantity) = {
+                        let ord = order.lock().unwrap();
+                        (ord.get_order_id(), ord.get_remaining_quantity())
+                    };
+                    self.update_level_data(side, price, remaining_quantity, LevelDataAction::Remove);
+                    let remaining = adjustment.adjust_quantity(remaining_quantity)?;
+                    if remaining == 0 {
+                        self.orders.remove(&order_id);
+                        self.publish(BookEvent::OrderCancelled { order_id, side, price, quantity: remaining_quantity });
+                        report.cancelled.push(order_id);
+                        continue;
+                    }
+                    {
+                        let mut ord = order.lock().unwrap();
+                        let filled = adjustment.adjust_quantity(ord.filled_quantity)?;
+                        ord.price = new_price;
+                        ord.initial_quantity = filled + remaining;
+                        ord.remaining_quantity = remaining;
+                        ord.filled_quantity = filled;
+                    }
+                    let queue = match side {
+                        Side::Buy => self.bids.entry(new_price).or_default(),
+                        Side::Sell => self.asks.entry(new_price).or_default(),
+                    };
+                    queue.push(order.clone());
+                    let location = queue.len() - 1;
+                    self.orders.insert(order_id, OrderEntry { order, location, side, price: new_price });
+                    self.update_level_data(side, new_price, remaining, LevelDataAction::Add);
+                    self.publish(BookEvent::OrderReplaced { order_id, side, price: new_price, quantity: remaining });
+                    report.adjusted += 1;
+                }
+            }
+        }
+
+        self.reference_prices = reference_prices;
+        if let (Some(sale), Some(price)) = (self.last_sale.as_mut(), last_sale_price) {
+            sale.price = price;
+        }
+        info!("Adjusted {} orders and cancelled {} for {:?}", report.adjusted, report.cancelled.len(), adjustment);
+        Ok(report)
+    }
+
+    /// Appends an order to the back of its level as it is, without matching.
+    ///
+    /// Level aggregates count the remaining quantity, since fills happened before the order
+    /// was restored.
+    pub fn restore_order(&mut self, order: OrderPointer) -> Result<(), String> {
+        let (order_id, order_type, side, price, remaining_quantity, is_filled, flags) = {
+            let ord = order.lock().unwrap();
+            (ord.get_order_id(), ord.get_order_type(), ord.get_side(), ord.get_price(), ord.get_remaining_quantity(), ord.is_filled(), ord.get_flags())
+        };
+        if self.orders.contains_key(&order_id) {
+            return Err(format!("Order#{} already exists", order_id));
+        }
+        if !matches!(order_type, OrderType::GoodTillCancel | OrderType::GoodForDay) {
+            return Err(format!("Order#{} is {:?}, which never rests in the book", order_id, order_type));
+        }
+        if is_filled {
+            return Err(format!("Order#{} is already filled", order_id));
+        }
+        if self.can_match(side, price) {
+            return Err(format!("Order#{} at {} would cross the book", order_id, price));
+        }
+
+        let queue = match side {
+            Side::Buy => self.bids.entry(price).or_default(),
+            Side::Sell => self.asks.entry(price).or_default(),
+        };
+        queue.push(order.clone());
+        let location = queue.len() - 1;
+        self.orders.insert(order_id, OrderEntry { order, location, side, price });
+        self.update_level_data(side, price, remaining_quantity, LevelDataAction::Add);
+        self.publish(BookEvent::OrderAdded { order_id, side, price, quantity: remaining_quantity, flags });
+        trace!("Restored Order#{} for {} @ {} side {:?}", order_id, remaining_quantity, price, side);
+        Ok(())
+    }
+
     /// Inserts an order into the book, possibly converting it and/or matching immediately.
     ///
     /// - Rejects duplicate `order_id`.
@@ -833,6 +1331,12 @@ impl InnerOrderbook {
                 return vec![];
             }
 
+            // Nothing executes during an auction, so orders that must execute now cannot enter.
+            if self.phase == TradingPhase::Auction && matches!(ord.get_order_type(), OrderType::Market | OrderType::FillAndKill | OrderType::FillOrKill) {
+                info!("{:?} Order#{} cannot enter during an auction, not adding.", ord.get_order_type(), ord.get_order_id());
+                return vec![];
+            }
+
             // Convert Market → GTC at a price that ensures immediate consideration, if possible.
             if ord.get_order_type() == OrderType::Market {
                 let result = match ord.get_side() {
@@ -858,6 +1362,19 @@ impl InnerOrderbook {
             let initial_quantity = ord.get_initial_quantity();
             let order_id = ord.get_order_id();
 
+            // Short sales: only sells, and only if the venue's rule allows them at this price
+            let flags = ord.get_flags();
+            if flags.short_sell && side == Side::Buy {
+                warn!("InnerOrderbook: Order#{} is a buy marked short sale, not adding.", order_id);
+                return vec![];
+            }
+            if flags.short_sell {
+                if let Err(reason) = self.check_short_sale(price) {
+                    info!("Short sale Order#{} refused: {}", order_id, reason);
+                    return vec![];
+                }
+            }
+
             // F&K: must be crossable *now*
             if order_type == OrderType::FillAndKill && !self.can_match(side, price) {
                 info!("F&K Order#{} cannot match, not adding.", order_id);
@@ -890,13 +1407,38 @@ impl InnerOrderbook {
             self.orders.insert(order_id, OrderEntry {order: order.clone(), location: index, side, price,});
         }
         self.on_order_added(order.clone());
-        let trades = self.match_orders();
+        if self.phase == TradingPhase::Auction {
+            return vec![];
+        }
+        let trades = self.match_orders(None);
         if !trades.is_empty() {
             // info!("InnerOrderbook: Trades occurred after add: {:?}", trades);
         }
+        // Trades happen at the price of the resting order, i.e. the one that was not just added.
+        let aggressor = order.lock().unwrap().get_order_id();
+        for trade in &trades {
+            let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+            let price = if bid.order_id == aggressor { ask.price } else { bid.price };
+            self.record_trade(price, bid.quantity);
+        }
         trades
     }
 
+    /// Updates the last sale, the reference prices and the day's statistics with a trade.
+    fn record_trade(&mut self, price: Price, quantity: Quantity) {
+        self.last_sale = Some(LastSale::next(self.last_sale, price));
+        self.reference_prices.open.get_or_insert(price);
+        self.reference_prices.last = Some(price);
+        self.daily_stats.record(price, quantity);
+    }
+
+    /// Recomputes the indicative uncross if the book is in an auction.
+    fn update_indicative(&mut self) {
+        if self.phase == TradingPhase::Auction {
+            self.indicative = compute_uncross(&self.bid_depth, &self.ask_depth, self.reference_prices.get_current());
+        }
+    }
+
     /// Cancels (removes) an order by ID, repairing queues and indices as needed.
     pub fn cancel_order(&mut self, order_id: OrderId) {
         if let Some(entry) = self.orders.remove(&order_id) {
@@ -943,17 +1485,28 @@ impl InnerOrderbook {
     /// # Returns
     /// Any `Trades` produced by re-insertion.
     pub fn modify_order(&mut self, order: OrderModify) -> Trades {
-        let order_type = self.orders.get(&order.get_order_id())
-            .map(|entry| entry.order.lock().unwrap().get_order_type());
+        let existing = self.orders.get(&order.get_order_id())
+            .map(|entry| {
+                let ord = entry.order.lock().unwrap();
+                (ord.get_order_type(), ord.get_flags())
+            });
 
-        if order_type.is_none() {
+        let Some((order_type, flags)) = existing else {
             warn!("InnerOrderbook: Tried to modify non-existent order_id {}", order.get_order_id());
             return vec![];
-        }
+        };
 
         info!("InnerOrderbook: Modifying order_id {} to price {} qty {} side {:?}", order.get_order_id(), order.get_price(), order.get_quantity(), order.get_side());
+        self.replacing = Some(order.get_order_id());
         self.cancel_order(order.get_order_id());
-        let trades = self.add_order(order.to_order_pointer(order_type.unwrap()));
+        let replacement = order.to_order_pointer(order_type);
+        replacement.lock().unwrap().set_flags(flags);
+        let trades = self.add_order(replacement);
+        // The re-add was dropped, so the held-back cancel is what happened to the order.
+        self.replacing = None;
+        if let Some(cancel) = self.replaced_cancel.take() {
+            self.publish(cancel);
+        }
         if !trades.is_empty() {
             info!("InnerOrderbook: Trades occurred after modify: {:?}", trades);
         }
@@ -961,7 +1514,8 @@ impl InnerOrderbook {
     }
 
     /// Updates per-level aggregates after adds/matches/cancels.
-    fn update_level_data(&mut self, price: Price, quantity: Quantity, action: LevelDataAction) {
+    fn update_level_data(&mut self, side: Side, price: Price, quantity: Quantity, action: LevelDataAction) {
+        self.update_depth(side, price, quantity, action);
         let data = self.data.entry(price).or_insert(LevelData { quantity: 0, count: 0 });
 
         match action {
@@ -983,34 +1537,106 @@ impl InnerOrderbook {
         }
     }
 
+    /// Applies a level change to the per-side depth and records the resulting delta.
+    fn update_depth(&mut self, side: Side, price: Price, quantity: Quantity, action: LevelDataAction) {
+        let depth = match side {
+            Side::Buy => &mut self.bid_depth,
+            Side::Sell => &mut self.ask_depth,
+        };
+        let before = depth.get(&price).copied();
+        let after = match action {
+            LevelDataAction::Add => before.unwrap_or(0) + quantity,
+            LevelDataAction::Remove | LevelDataAction::Match => before.unwrap_or(0).saturating_sub(quantity),
+        };
+        let action = match (before, after) {
+            (Some(before), after) if before == after => return,
+            (None, 0) => return,
+            (None, _) => DepthAction::Added,
+            (Some(_), 0) => DepthAction::Removed,
+            (Some(_), _) => DepthAction::Updated,
+        };
+        if action == DepthAction::Removed {
+            depth.remove(&price);
+        } else {
+            depth.insert(price, after);
+        }
+
+        self.last_depth_sequence += 1;
+        if self.publish_events {
+            self.depth_deltas.push(DepthDelta { sequence: self.last_depth_sequence, side, price, quantity: after, action });
+        }
+        self.update_indicative();
+    }
+
     /// Hook invoked on successful cancel; updates aggregates.
+    ///
+    /// Only the remaining quantity is removed, since fills were already deducted by `on_order_matched`.
     fn on_order_cancelled(&mut self, order: OrderPointer){
         let ord = order.lock().unwrap();
-        self.update_level_data(ord.get_price(), ord.get_initial_quantity(), LevelDataAction::Remove)
+        self.update_level_data(ord.get_side(), ord.get_price(), ord.get_remaining_quantity(), LevelDataAction::Remove);
+        self.publish(BookEvent::OrderCancelled {
+            order_id: ord.get_order_id(),
+            side: ord.get_side(),
+            price: ord.get_price(),
+            quantity: ord.get_remaining_quantity(),
+        });
     }
 
     /// Hook invoked on successful add; updates aggregates.
     fn on_order_added(&mut self, order: OrderPointer) {
         let ord = order.lock().unwrap();
-        self.update_level_data(ord.get_price(), ord.get_initial_quantity(), LevelDataAction::Add)
+        self.update_level_data(ord.get_side(), ord.get_price(), ord.get_initial_quantity(), LevelDataAction::Add);
+        self.publish(BookEvent::OrderAdded {
+            order_id: ord.get_order_id(),
+            side: ord.get_side(),
+            price: ord.get_price(),
+            quantity: ord.get_initial_quantity(),
+            flags: ord.get_flags(),
+        });
     }
 
     /// Hook invoked on each match; decrements or removes level aggregates.
-    fn on_order_matched(&mut self, price: Price, quantity: Quantity, is_fully_filled: bool) {
+    fn on_order_matched(&mut self, order_id: OrderId, side: Side, price: Price, quantity: Quantity, remaining: Quantity) {
+        let is_fully_filled = remaining == 0;
         let action = if is_fully_filled {
             LevelDataAction::Remove
         } else {
             LevelDataAction::Match
         };
         debug!("Order matched @ price {} qty {} fully_filled {}", price, quantity, is_fully_filled);
-        self.update_level_data(price, quantity, action);
+        self.update_level_data(side, price, quantity, action);
+        self.publish(BookEvent::OrderExecuted { order_id, side, price, quantity, remaining });
+    }
+
+    /// Records `event` if publishing is on.
+    ///
+    /// While an order is being modified, its cancel is held back and its re-add is recorded
+    /// as [`BookEvent::OrderReplaced`] instead.
+    fn publish(&mut self, event: BookEvent) {
+        if !self.publish_events {
+            return;
+        }
+        let event = match event {
+            BookEvent::OrderCancelled { order_id, .. } if self.replacing == Some(order_id) => {
+                self.replaced_cancel = Some(event);
+                return;
+            }
+            BookEvent::OrderAdded { order_id, side, price, quantity, .. } if self.replacing == Some(order_id) => {
+                self.replacing = None;
+                self.replaced_cancel = None;
+                BookEvent::OrderReplaced { order_id, side, price, quantity }
+            }
+            event => event,
+        };
+        self.last_sequence += 1;
+        self.events.push(SequencedEvent { sequence: self.last_sequence, event });
     }
 
     /// Returns `true` if a new order on `side` at `price` would cross the book.
     fn can_match(&mut self, side: Side, price: Price) -> bool {
         match side {
-            Side::Buy => self.asks.first_key_value().map_or(false, |(ask, _)| price >= *ask),
-            Side::Sell => self.bids.first_key_value().map_or(false, |(bid, _)| price <= *bid),
+            Side::Buy => self.asks.first_key_value().is_some_and(|(ask, _)| price >= *ask),
+            Side::Sell => self.bids.last_key_value().is_some_and(|(bid, _)| price <= *bid),
         }
     }
 
@@ -1056,7 +1682,7 @@ impl InnerOrderbook {
             quantity -= level_data.quantity
 
         }
-        return false
+        false
     }
 
     /// Removes an order from the side/price queue and fixes indices/maps.
@@ -1092,7 +1718,10 @@ impl InnerOrderbook {
     /// While best bid ≥ best ask, match head-of-queue orders at those prices,
     /// create `Trade`s, update aggregates, and remove/repair queues for fully
     /// filled and partially filled F&K orders.
-    fn match_orders(&mut self) -> Trades {
+    ///
+    /// Each side of a trade carries its own order's price, or `uncross_price` when an auction
+    /// is uncrossed.
+    fn match_orders(&mut self, uncross_price: Option<Price>) -> Trades {
         let mut trades = Vec::with_capacity(self.orders.len());
 
         loop {
@@ -1113,15 +1742,15 @@ impl InnerOrderbook {
                 break;
             }
 
-            let bid_order_ptr = bids.get(0).cloned();
-            let ask_order_ptr = asks.get(0).cloned();
+            let bid_order_ptr = bids.first().cloned();
+            let ask_order_ptr = asks.first().cloned();
 
             let (bid_order_ptr, ask_order_ptr) = match (bid_order_ptr, ask_order_ptr) {
                 (Some(b), Some(a)) => (b, a),
                 _ => break,
             };
 
-            let (bid_filled, ask_filled, bid_id, ask_id, trade_quantity, final_bid_price, final_ask_price, bid_type, ask_type);
+            let (bid_filled, ask_filled, bid_id, ask_id, trade_quantity, final_bid_price, final_ask_price, bid_remaining, ask_remaining, bid_flags, ask_flags);
             {
                 let mut bid = bid_order_ptr.lock().unwrap();
                 let mut ask = ask_order_ptr.lock().unwrap();
@@ -1141,23 +1770,26 @@ impl InnerOrderbook {
                 bid_filled = bid.is_filled();
                 ask_filled = ask.is_filled();
 
+                bid_remaining = bid.get_remaining_quantity();
+                ask_remaining = ask.get_remaining_quantity();
+
                 bid_id = bid.get_order_id();
                 ask_id = ask.get_order_id();
 
                 final_bid_price = bid.get_price();
                 final_ask_price = ask.get_price();
 
-                bid_type = bid.get_order_type();
-                ask_type = ask.get_order_type();
+                bid_flags = bid.get_flags();
+                ask_flags = ask.get_flags();
             }
 
             trades.push(Trade::new(
-                TradeInfo { order_id: bid_id, price: final_bid_price, quantity: trade_quantity },
-                TradeInfo { order_id: ask_id, price: final_ask_price, quantity: trade_quantity },
+                TradeInfo { order_id: bid_id, price: uncross_price.unwrap_or(final_bid_price), quantity: trade_quantity, flags: bid_flags },
+                TradeInfo { order_id: ask_id, price: uncross_price.unwrap_or(final_ask_price), quantity: trade_quantity, flags: ask_flags },
             ));
 
-            self.on_order_matched(final_bid_price, trade_quantity, bid_filled);
-            self.on_order_matched(final_ask_price, trade_quantity, ask_filled);
+            self.on_order_matched(bid_id, Side::Buy, final_bid_price, trade_quantity, bid_remaining);
+            self.on_order_matched(ask_id, Side::Sell, final_ask_price, trade_quantity, ask_remaining);
 
             // Fully filled orders
             if bid_filled {
@@ -1167,23 +1799,30 @@ impl InnerOrderbook {
             if ask_filled {
                 self.remove_order_from_book(ask_id, final_ask_price, Side::Sell);
             }
+        }
 
-            // Remove partially filled F&K orders (should not persist)
-            if !bid_filled && bid_type == OrderType::FillAndKill {
-                info!("Removing partially filled F&K bid order_id {}", bid_id);
-                self.remove_order_from_book(bid_id, final_bid_price, Side::Buy);
-            }
-
-            if !ask_filled && ask_type == OrderType::FillAndKill {
-                info!("Removing partially filled F&K ask order_id {}", ask_id);
-                self.remove_order_from_book(ask_id, final_ask_price, Side::Sell);
+        // Remove what is left of an F&K order (should not persist) once nothing crosses it anymore,
+        // so it first sweeps every level it can. A crossing order is alone at the front of the best level.
+        let best_orders = [
+            self.bids.values().next_back().and_then(|orders| orders.first()).cloned(),
+            self.asks.values().next().and_then(|orders| orders.first()).cloned(),
+        ];
+        for order_ptr in best_orders.into_iter().flatten() {
+            let (order_id, order_type, side, price) = {
+                let ord = order_ptr.lock().unwrap();
+                (ord.get_order_id(), ord.get_order_type(), ord.get_side(), ord.get_price())
+            };
+            if order_type == OrderType::FillAndKill {
+                info!("Removing partially filled F&K order_id {}", order_id);
+                self.remove_order_from_book(order_id, price, side);
+                self.on_order_cancelled(order_ptr);
             }
         }
         trades
     }
 }
 
-/// Tests:
+// Tests:
 
 //Each test implicitly assumes a working match_orders() functionality
 #[cfg(test)]
@@ -1253,6 +1892,20 @@ mod test {
         assert_eq!(orderbook.size(), 1);
     }
 
+    #[test]
+    fn test_fill_and_kill_sweeps_every_crossing_level(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 3));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 99, 3));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 98, 3));
+
+        let trades = ob.add_order(Order::new(OrderType::FillAndKill, 4, Side::Sell, 99, 10));
+        assert_eq!(trades.iter().map(|trade| trade.get_bid_trade().order_id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(ob.get_depth_snapshot().bids, [(98, 3)]);
+        assert!(ob.get_depth_snapshot().asks.is_empty());
+        assert!(!ob.contains_order(4));
+    }
+
     #[test]
     fn test_orderbook_will_cancel_fok(){
         let mut orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
@@ -1273,6 +1926,22 @@ mod test {
         assert_eq!(orderbook.size(), 1);
     }
 
+    #[test]
+    fn test_cancel_partially_filled_order_keeps_level_quantity(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 100, 10));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 100, 4));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 100, 5));
+
+        // Only the 6 left of order 1 leave the level, not the 10 it was entered with.
+        ob.cancel_order(1);
+        assert_eq!(ob.get_depth_snapshot().asks, [(100, 5)]);
+
+        let trades = ob.add_order(Order::new(OrderType::FillOrKill, 4, Side::Buy, 100, 5));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(ob.size(), 0);
+    }
+
     #[test]
     fn test_orderbook_wont_match(){
         let mut ob1 = Orderbook::new(BTreeMap::new(),BTreeMap::new());
@@ -1312,28 +1981,112 @@ mod test {
 
     }
 
+    #[test]
+    fn test_auction_rests_orders_and_uncrosses_at_one_price(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 105, 5));
+        ob.start_auction();
+        assert_eq!(ob.get_phase(), crate::auction::TradingPhase::Auction);
+        assert_eq!(ob.indicative_uncross(), None);
+
+        // Crossing orders rest; orders that must execute now are dropped.
+        assert!(ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 106, 8)).is_empty());
+        assert!(ob.add_order(Order::new(OrderType::FillAndKill, 3, Side::Sell, 100, 1)).is_empty());
+        assert!(ob.add_order(Order::new_market(4, Side::Sell, 1)).is_empty());
+        assert_eq!((ob.size(), ob.get_best_prices()), (2, (Some(106), Some(105))));
+        let indicative = ob.indicative_uncross().unwrap();
+        assert_eq!((indicative.price, indicative.matched_quantity, indicative.imbalance), (105, 5, Some((Side::Buy, 3))));
+
+        // Recomputed as orders arrive and leave.
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 5, Side::Sell, 104, 3));
+        assert_eq!(ob.indicative_uncross().map(|indicative| (indicative.matched_quantity, indicative.imbalance)), Some((8, None)));
+        ob.cancel_order(1);
+        assert_eq!(ob.indicative_uncross().map(|indicative| (indicative.price, indicative.matched_quantity)), Some((104, 3)));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 6, Side::Sell, 106, 4));
+
+        let trades = ob.uncross();
+        let fills: Vec<_> = trades.iter().map(|trade| (trade.get_ask_trade().order_id, trade.get_bid_trade().price, trade.get_ask_trade().price, trade.get_bid_trade().quantity)).collect();
+        assert_eq!(fills, vec![(5, 106, 106, 3), (6, 106, 106, 4)]);
+        assert_eq!(ob.get_phase(), crate::auction::TradingPhase::Continuous);
+        assert_eq!((ob.indicative_uncross(), ob.get_reference_prices().last), (None, Some(106)));
+        assert_eq!(ob.get_daily_stats().volume, 7);
+        assert!(ob.uncross().is_empty());
+
+        // Trading is continuous again.
+        assert_eq!(ob.add_order(Order::new(OrderType::GoodTillCancel, 7, Side::Sell, 106, 1)).len(), 1);
+        assert_eq!(ob.size(), 0);
+    }
+
+    #[test]
+    fn test_publishes_order_by_order_events(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 5));
+        assert!(ob.take_events().is_empty());
+
+        ob.set_publish_events(true);
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 102, 5));
+        ob.add_order(Order::new(OrderType::FillAndKill, 3, Side::Buy, 102, 12));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 4, Side::Buy, 99, 4));
+        ob.modify_order(OrderModify::new(4, Side::Buy, 100, 6));
+        ob.cancel_order(4);
+
+        let events: Vec<BookEvent> = ob.take_events().iter().map(|event| event.event).collect();
+        assert_eq!(events, vec![
+            BookEvent::OrderAdded { order_id: 2, side: Side::Sell, price: 102, quantity: 5, flags: OrderFlags::default() },
+            BookEvent::OrderAdded { order_id: 3, side: Side::Buy, price: 102, quantity: 12, flags: OrderFlags::default() },
+            BookEvent::OrderExecuted { order_id: 3, side: Side::Buy, price: 102, quantity: 5, remaining: 7 },
+            BookEvent::OrderExecuted { order_id: 1, side: Side::Sell, price: 101, quantity: 5, remaining: 0 },
+            BookEvent::OrderExecuted { order_id: 3, side: Side::Buy, price: 102, quantity: 5, remaining: 2 },
+            BookEvent::OrderExecuted { order_id: 2, side: Side::Sell, price: 102, quantity: 5, remaining: 0 },
+            BookEvent::OrderCancelled { order_id: 3, side: Side::Buy, price: 102, quantity: 2 },
+            BookEvent::OrderAdded { order_id: 4, side: Side::Buy, price: 99, quantity: 4, flags: OrderFlags::default() },
+            BookEvent::OrderReplaced { order_id: 4, side: Side::Buy, price: 100, quantity: 6 },
+            BookEvent::OrderCancelled { order_id: 4, side: Side::Buy, price: 100, quantity: 6 },
+        ]);
+
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 5, Side::Buy, 98, 1));
+        let sequences: Vec<u64> = ob.take_events().iter().map(|event| event.sequence).collect();
+        assert_eq!(sequences, vec![11]);
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 6, Side::Buy, 98, 1));
+        ob.set_publish_events(false);
+        assert!(ob.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_reference_prices(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_reference_prices(ReferencePrices { previous_close: Some(95), ..Default::default() });
+        assert_eq!(ob.get_reference_prices().get_current(), Some(95));
+
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 100, 1));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 101, 1));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 101, 2));
+        let prices = ob.get_reference_prices();
+        assert_eq!(prices, ReferencePrices { previous_close: Some(95), open: Some(100), last: Some(101) });
+        assert_eq!(prices.get_current(), Some(101));
+    }
+
     #[test]
     fn test_good_for_day_pruning() {
-        use chrono::Local;
-        let now = Local::now();
-        let minute = now.minute();
-        let second = now.second();
-        let hour = now.hour();
+        use crate::clock::MockClock;
+        let clock = MockClock::new(DateTime::parse_from_rfc3339("2026-10-16T15:59:00Z").unwrap().to_utc());
 
-        let ob = Orderbook::build(BTreeMap::new(), BTreeMap::new(), true);
+        let ob = Orderbook::build_with_clock(BTreeMap::new(), BTreeMap::new(), false, Arc::new(clock.clone()));
         ob.add_order(Order::new(OrderType::GoodForDay, 1, Side::Buy, 100, 10));
         ob.add_order(Order::new(OrderType::GoodForDay, 2, Side::Sell, 200, 10));
         ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 1000, 10));
 
-        // Find time until next hour
-        let secs_until_next_hour = (59 - minute) * 60 + (60 - second);
-        if secs_until_next_hour > 180 {
-            // More than 3 minutes until next hour, pruning won't happen, just check size is 2
-            assert_eq!(ob.size(), 3);
-        } else {
-            // Within 3 minutes of next hour, pruning may happen soon
-            thread::sleep(std::time::Duration::from_millis(200)); // Give prune thread time to run
-            assert_eq!(ob.size(), 1);
+        // Before the cutoff nothing is pruned, however long we wait in real time.
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(ob.size(), 3);
+
+        clock.advance(std::time::Duration::from_secs(61));
+        for _ in 0..100 {
+            if ob.size() == 1 {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10)); // Give prune thread time to run
         }
+        assert_eq!(ob.size(), 1);
     }
 }
\ No newline at end of file
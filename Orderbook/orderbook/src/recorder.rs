@@ -0,0 +1,246 @@
+//! # Recorder Module
+//!
+//! Writes commands, trades and depth updates to rotating CSV files for later analysis and as
+//! input to the [`backtest`](crate::backtest) tooling.
+//!
+//! ## Files
+//! All files live in [`RecorderConfig::directory`] and roll over to a new numbered file every
+//! [`RecorderConfig::max_rows_per_file`] rows. Each file starts with its header row.
+//! - `commands-NNNN.csv`: commands in the backtest command file format (header is a `#` comment).
+//! - `trades-NNNN.csv`: `timestamp_us,bid_order_id,bid_price,ask_order_id,ask_price,quantity`
+//! - `depth-NNNN.csv`: `timestamp_us,side,price,quantity`, one row per changed level;
+//!   a quantity of `0` means the level was removed.
+//!
+//! Timestamps are microseconds on whatever clock the caller uses (virtual or wall-clock).
+//!
+//! The recorder is fed by whoever drives the book
+//! (e.g. as a sink of [`Simulator::add_sink`](crate::simulator::Simulator::add_sink)).
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use log::info;
+use crate::backtest::{format_command_line, side_code, Command, RecordedCommand};
+use crate::orderbook::{LevelInfos, Orderbook, OrderbookLevelInfos, Price, Quantity, Side, Trade};
+use crate::simulator::SimulationSink;
+
+/// Where and how a [`Recorder`] writes its files.
+#[derive(Clone, Debug)]
+pub struct RecorderConfig {
+    /// Output directory; created if missing.
+    pub directory: PathBuf,
+    /// Rows written to a file before rolling over to the next one.
+    pub max_rows_per_file: usize,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("recording"),
+            max_rows_per_file: 100_000,
+        }
+    }
+}
+
+/// Records commands, trades and depth changes to rotating CSV files.
+pub struct Recorder {
+    commands: RotatingCsvWriter,
+    trades: RotatingCsvWriter,
+    depth: RotatingCsvWriter,
+    /// Last recorded quantity per bid level, used to emit only changed levels.
+    last_bids: BTreeMap<Price, Quantity>,
+    /// Last recorded quantity per ask level, used to emit only changed levels.
+    last_asks: BTreeMap<Price, Quantity>,
+}
+
+impl Recorder {
+    /// Creates the output directory and opens the first file of each kind.
+    ///
+    /// # Errors
+    /// Returns any I/O error from creating the directory or files.
+    pub fn new(config: RecorderConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.directory)?;
+        info!("Recorder: writing to {}", config.directory.display());
+        let writer = |prefix, header| RotatingCsvWriter::new(config.directory.clone(), prefix, header, config.max_rows_per_file);
+        Ok(Self {
+            commands: writer("commands", "# timestamp_us,command,order_id,...")?,
+            trades: writer("trades", "timestamp_us,bid_order_id,bid_price,ask_order_id,ask_price,quantity")?,
+            depth: writer("depth", "timestamp_us,side,price,quantity")?,
+            last_bids: BTreeMap::new(),
+            last_asks: BTreeMap::new(),
+        })
+    }
+
+    /// Appends a command in the backtest command file format.
+    pub fn record_command(&mut self, command: &RecordedCommand) -> io::Result<()> {
+        self.commands.write_row(&format_command_line(command))
+    }
+
+    /// Appends one row per trade.
+    pub fn record_trades(&mut self, timestamp: Duration, trades: &[Trade]) -> io::Result<()> {
+        for trade in trades {
+            self.trades.write_row(&format_trade_row(timestamp, trade))?;
+        }
+        Ok(())
+    }
+
+    /// Appends a row for every level whose quantity changed since the last recorded depth.
+    pub fn record_depth(&mut self, timestamp: Duration, infos: &OrderbookLevelInfos) -> io::Result<()> {
+        let bids = changed_levels(&mut self.last_bids, infos.get_bids());
+        let asks = changed_levels(&mut self.last_asks, infos.get_asks());
+
+        for (side, changes) in [(Side::Buy, bids), (Side::Sell, asks)] {
+            for (price, quantity) in changes {
+                self.depth.write_row(&format!("{},{},{},{}", timestamp.as_micros(), side_code(side), price, quantity))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes all buffered rows to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.commands.flush()?;
+        self.trades.flush()?;
+        self.depth.flush()
+    }
+}
+
+/// Records every command, the trades it caused and the resulting depth.
+impl SimulationSink for Recorder {
+    fn on_applied(&mut self, orderbook: &Orderbook, timestamp: Duration, command: Command, trades: &[Trade]) -> Result<(), String> {
+        self.record_command(&RecordedCommand { timestamp, command })
+            .and_then(|_| self.record_trades(timestamp, trades))
+            .and_then(|_| self.record_depth(timestamp, &orderbook.get_order_infos()))
+            .map_err(|err| format!("Recording failed: {}", err))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Diffs `levels` against `last`, updates `last`, and returns the changed `(price, quantity)` pairs.
+pub(crate) fn changed_levels(last: &mut BTreeMap<Price, Quantity>, levels: &LevelInfos) -> Vec<(Price, Quantity)> {
+    let current: BTreeMap<Price, Quantity> = levels.iter().map(|level| (level.price, level.quantity)).collect();
+    let mut changes: Vec<(Price, Quantity)> = current
+        .iter()
+        .filter(|(price, quantity)| last.get(price) != Some(quantity))
+        .map(|(price, quantity)| (*price, *quantity))
+        .collect();
+    changes.extend(last.keys().filter(|price| !current.contains_key(price)).map(|price| (*price, 0)));
+    changes.sort_unstable();
+    *last = current;
+    changes
+}
+
+/// Formats a trade as a row of the trades file.
+pub fn format_trade_row(timestamp: Duration, trade: &Trade) -> String {
+    let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+    format!("{},{},{},{},{},{}", timestamp.as_micros(), bid.order_id, bid.price, ask.order_id, ask.price, bid.quantity)
+}
+
+/// Reads every data row of the rotated files `<prefix>-0001.csv`, `<prefix>-0002.csv`, ...
+/// in `directory`, skipping each file's header row.
+///
+/// # Errors
+/// Returns an error if the first file is missing or any file cannot be read.
+pub fn read_rows(directory: &Path, prefix: &str) -> io::Result<Vec<String>> {
+    let mut rows = Vec::new();
+    for index in 1.. {
+        let path = directory.join(format!("{}-{:04}.csv", prefix, index));
+        if index > 1 && !path.exists() {
+            break;
+        }
+        let contents = fs::read_to_string(&path)?;
+        rows.extend(contents.lines().skip(1).map(str::to_string));
+    }
+    Ok(rows)
+}
+
+/// A CSV writer that starts a new numbered file every `max_rows` rows.
+pub(crate) struct RotatingCsvWriter {
+    directory: PathBuf,
+    prefix: &'static str,
+    header: &'static str,
+    max_rows: usize,
+    rows: usize,
+    index: usize,
+    writer: BufWriter<File>,
+}
+
+impl RotatingCsvWriter {
+    pub(crate) fn new(directory: PathBuf, prefix: &'static str, header: &'static str, max_rows: usize) -> io::Result<Self> {
+        let writer = Self::open(&directory, prefix, header, 1)?;
+        Ok(Self { directory, prefix, header, max_rows: max_rows.max(1), rows: 0, index: 1, writer })
+    }
+
+    fn open(directory: &Path, prefix: &str, header: &str, index: usize) -> io::Result<BufWriter<File>> {
+        let path = directory.join(format!("{}-{:04}.csv", prefix, index));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writeln!(writer, "{}", header)?;
+        Ok(writer)
+    }
+
+    pub(crate) fn write_row(&mut self, row: &str) -> io::Result<()> {
+        if self.rows == self.max_rows {
+            self.writer.flush()?;
+            self.index += 1;
+            self.rows = 0;
+            self.writer = Self::open(&self.directory, self.prefix, self.header, self.index)?;
+            info!("Recorder: rotated {} to file #{}", self.prefix, self.index);
+        }
+        writeln!(self.writer, "{}", row)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::orderbook::{LevelInfo, OrderFlags, TradeInfo};
+
+    #[test]
+    fn test_recorder_rotates_files(){
+        let directory = tempfile::tempdir().unwrap();
+        let mut recorder = Recorder::new(RecorderConfig { directory: directory.path().to_path_buf(), max_rows_per_file: 2 }).unwrap();
+        let trade = Trade::new(
+            TradeInfo { order_id: 1, price: 100, quantity: 5, flags: OrderFlags::default() },
+            TradeInfo { order_id: 2, price: 100, quantity: 5, flags: OrderFlags::default() },
+        );
+        let trades = [trade];
+        for micros in 0..5 {
+            recorder.record_trades(Duration::from_micros(micros), &trades).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let third = fs::read_to_string(directory.path().join("trades-0003.csv")).unwrap();
+        assert_eq!(third, "timestamp_us,bid_order_id,bid_price,ask_order_id,ask_price,quantity\n4,1,100,2,100,5\n");
+        assert!(!directory.path().join("trades-0004.csv").exists());
+        assert_eq!(read_rows(directory.path(), "trades").unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_recorder_writes_only_changed_levels(){
+        let directory = tempfile::tempdir().unwrap();
+        let mut recorder = Recorder::new(RecorderConfig { directory: directory.path().to_path_buf(), max_rows_per_file: 100 }).unwrap();
+        let level = |price, quantity| LevelInfo { price, quantity };
+
+        recorder.record_depth(Duration::from_micros(1), &OrderbookLevelInfos::new(vec![level(99, 10), level(100, 5)], vec![level(101, 7)])).unwrap();
+        recorder.record_depth(Duration::from_micros(2), &OrderbookLevelInfos::new(vec![level(99, 10), level(100, 8)], vec![])).unwrap();
+        recorder.flush().unwrap();
+
+        let depth = fs::read_to_string(directory.path().join("depth-0001.csv")).unwrap();
+        assert_eq!(depth, "timestamp_us,side,price,quantity\n1,BUY,99,10\n1,BUY,100,5\n1,SELL,101,7\n2,BUY,100,8\n2,SELL,101,0\n");
+    }
+}
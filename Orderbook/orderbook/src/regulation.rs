@@ -0,0 +1,160 @@
+//! # Regulation Module
+//!
+//! Venue-specific checks on the [`OrderFlags`](crate::OrderFlags) of incoming orders.
+//!
+//! ## Short Sales
+//! A venue enables a [`ShortSaleRule`] per instrument, i.e. per book, with
+//! [`Orderbook::set_short_sale_rule`](crate::Orderbook::set_short_sale_rule). Every short
+//! sale entering the book is checked against the book's [`LastSale`] and dropped, like an
+//! unfillable FillOrKill, if the rule refuses it.
+//! Market orders are checked at the price they are converted to.
+//!
+//! [`UptickRule`] is the classic tick test: a short sale must be priced above the last sale,
+//! or at it if the last sale was itself higher than the sale before it at a different price.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use orderbook::{Order, OrderFlags, OrderType, Orderbook, Side};
+//! use orderbook::regulation::UptickRule;
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.set_short_sale_rule(Some(Arc::new(UptickRule)));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 5));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 99, 5));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 99, 6)); // trades down to 99
+//!
+//! let short = Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 99, 4);
+//! short.lock().unwrap().set_flags(OrderFlags { short_sell: true, ..Default::default() });
+//! assert!(ob.add_order(short).is_empty());
+//! assert_eq!(ob.size(), 1); // order 2 is untouched
+//! ```
+
+use std::fmt::Debug;
+use crate::orderbook::Price;
+
+/// Direction of the last sale relative to the previous sales.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tick {
+    /// Higher than the previous sale.
+    Plus,
+    /// Same as the previous sale, which was a plus tick.
+    ZeroPlus,
+    /// Lower than the previous sale.
+    Minus,
+    /// Same as the previous sale, which was a minus tick.
+    ZeroMinus,
+}
+
+impl Tick {
+    /// Returns `true` for plus and zero-plus ticks.
+    pub const fn is_up(&self) -> bool {
+        matches!(self, Tick::Plus | Tick::ZeroPlus)
+    }
+}
+
+/// The price of the last trade in a book and how it moved.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LastSale {
+    /// The maker's price of the last trade.
+    pub price: Price,
+    /// Direction of the move; the first trade of a book counts as a plus tick.
+    pub tick: Tick,
+}
+
+impl LastSale {
+    /// Returns the last sale after a trade at `price`, given the one before it, if any.
+    pub fn next(previous: Option<LastSale>, price: Price) -> LastSale {
+        let tick = match previous {
+            None => Tick::Plus,
+            Some(last) if price > last.price => Tick::Plus,
+            Some(last) if price < last.price => Tick::Minus,
+            Some(last) if last.tick.is_up() => Tick::ZeroPlus,
+            Some(_) => Tick::ZeroMinus,
+        };
+        LastSale { price, tick }
+    }
+}
+
+/// Decides whether a short sale may enter a book.
+pub trait ShortSaleRule: Debug + Send + Sync {
+    /// Checks a short sale at `price` against the book's `last_sale`, if any trade happened.
+    ///
+    /// # Errors
+    /// The reason the short sale is refused.
+    fn check(&self, price: Price, last_sale: Option<LastSale>) -> Result<(), String>;
+}
+
+/// Allows short sales above the last sale, or at it after an uptick.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UptickRule;
+
+impl ShortSaleRule for UptickRule {
+    fn check(&self, price: Price, last_sale: Option<LastSale>) -> Result<(), String> {
+        match last_sale {
+            None => Ok(()),
+            Some(last) if price > last.price || (price == last.price && last.tick.is_up()) => Ok(()),
+            Some(last) => Err(format!("Short sale at {} is not above the last sale at {} ({:?})", price, last.price, last.tick)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::BTreeMap, sync::Arc};
+    use crate::orderbook::{Capacity, Order, OrderFlags, OrderModify, OrderType, Orderbook, Side};
+
+    fn short(order_id: u32, price: Price) -> crate::OrderPointer {
+        let order = Order::new(OrderType::GoodTillCancel, order_id, Side::Sell, price, 1);
+        order.lock().unwrap().set_flags(OrderFlags { capacity: Some(Capacity::Agency), short_sell: true });
+        order
+    }
+
+    #[test]
+    fn test_ticks_follow_the_last_different_price(){
+        let mut last = None;
+        let ticks: Vec<Tick> = [100, 101, 101, 99, 99, 100].into_iter().map(|price| {
+            let sale = LastSale::next(last, price);
+            last = Some(sale);
+            sale.tick
+        }).collect();
+        assert_eq!(ticks, [Tick::Plus, Tick::Plus, Tick::ZeroPlus, Tick::Minus, Tick::ZeroMinus, Tick::Plus]);
+    }
+
+    #[test]
+    fn test_uptick_rule_gates_short_sales(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_short_sale_rule(Some(Arc::new(UptickRule)));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 10));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 99, 10));
+
+        // Down to 99: shorts at 99 are refused, above it they may rest.
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 99, 11));
+        assert_eq!(ob.get_last_sale(), Some(LastSale { price: 99, tick: Tick::Minus }));
+        ob.add_order(short(4, 99));
+        ob.add_order(short(5, 101));
+        assert_eq!(ob.size(), 2);
+
+        // Back up to 101: a short at the last sale is now allowed, and trades carry its flags.
+        let trades = ob.add_order(Order::new(OrderType::FillAndKill, 6, Side::Buy, 101, 1));
+        assert_eq!(trades[0].get_ask_trade().flags, OrderFlags { capacity: Some(Capacity::Agency), short_sell: true });
+        ob.add_order(short(7, 101));
+        assert_eq!(ob.size(), 2);
+
+        // Flags survive a modify, which is checked like a new short sale.
+        ob.modify_order(OrderModify::new(7, Side::Sell, 102, 1));
+        let trades = ob.add_order(Order::new(OrderType::FillAndKill, 8, Side::Buy, 102, 1));
+        assert!(trades[0].get_ask_trade().flags.short_sell);
+    }
+
+    #[test]
+    fn test_short_buys_are_refused(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let buy = Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 1);
+        buy.lock().unwrap().set_flags(OrderFlags { short_sell: true, ..Default::default() });
+        ob.add_order(buy);
+        assert_eq!(ob.size(), 0);
+    }
+}
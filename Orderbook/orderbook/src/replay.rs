@@ -0,0 +1,156 @@
+//! # Replay Module
+//!
+//! Re-runs a [`recorder`](crate::recorder) recording through a fresh [`Orderbook`] and checks
+//! that the engine reproduces the recorded trade tape and final depth. Useful both to validate
+//! that a recording can be recovered from and to reproduce a problematic session for debugging.
+
+use std::{collections::{BTreeMap, BTreeSet}, path::Path};
+use log::info;
+use crate::backtest::{apply_command, parse_commands};
+use crate::orderbook::{Orderbook, Price, Quantity};
+use crate::recorder::{format_trade_row, read_rows};
+
+/// Quantity per price level on one side of the book.
+type Levels = BTreeMap<Price, Quantity>;
+
+/// The first point where the replayed trade tape diverges from the recorded one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TapeMismatch {
+    /// Zero-based index of the trade row.
+    pub index: usize,
+    /// Recorded row, or `None` if the replay produced more trades than were recorded.
+    pub expected: Option<String>,
+    /// Replayed row, or `None` if the replay produced fewer trades than were recorded.
+    pub actual: Option<String>,
+}
+
+/// Outcome of [`verify_recording`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayReport {
+    /// Commands replayed.
+    pub commands: usize,
+    /// Trades produced by the replay.
+    pub trades: usize,
+    /// First trade tape divergence, if any.
+    pub trade_mismatch: Option<TapeMismatch>,
+    /// Human-readable description of every level whose final quantity differs.
+    pub depth_mismatches: Vec<String>,
+}
+
+impl ReplayReport {
+    /// Returns `true` if both the trade tape and the final depth matched the recording.
+    pub fn is_consistent(&self) -> bool {
+        self.trade_mismatch.is_none() && self.depth_mismatches.is_empty()
+    }
+}
+
+/// Replays the recording in `directory` and compares the result with the recorded outputs.
+///
+/// # Errors
+/// Returns an error if the recording cannot be read or contains malformed rows.
+pub fn verify_recording(directory: impl AsRef<Path>) -> Result<ReplayReport, String> {
+    let directory = directory.as_ref();
+    let read = |prefix| read_rows(directory, prefix).map_err(|err| format!("Failed to read {} from {}: {}", prefix, directory.display(), err));
+    let commands = parse_commands(&read("commands")?.join("\n"))?;
+    let recorded_trades = read("trades")?;
+    let recorded_depth = rebuild_depth(&read("depth")?)?;
+
+    info!("Replay: replaying {} commands from {}", commands.len(), directory.display());
+    let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+    let mut replayed_trades = Vec::with_capacity(recorded_trades.len());
+    for recorded in &commands {
+        for trade in apply_command(&orderbook, recorded.command) {
+            replayed_trades.push(format_trade_row(recorded.timestamp, &trade));
+        }
+    }
+
+    let mut report = ReplayReport { commands: commands.len(), trades: replayed_trades.len(), ..Default::default() };
+
+    let length = recorded_trades.len().max(replayed_trades.len());
+    report.trade_mismatch = (0..length)
+        .map(|index| (index, recorded_trades.get(index), replayed_trades.get(index)))
+        .find(|(_, expected, actual)| expected != actual)
+        .map(|(index, expected, actual)| TapeMismatch { index, expected: expected.cloned(), actual: actual.cloned() });
+
+    let infos = orderbook.get_order_infos();
+    let (recorded_bids, recorded_asks) = recorded_depth;
+    for (side, recorded, replayed) in [("BUY", recorded_bids, infos.get_bids()), ("SELL", recorded_asks, infos.get_asks())] {
+        let replayed: Levels = replayed.iter().map(|level| (level.price, level.quantity)).collect();
+        for price in recorded.keys().chain(replayed.keys()).collect::<BTreeSet<_>>() {
+            let (expected, actual) = (recorded.get(price).copied().unwrap_or(0), replayed.get(price).copied().unwrap_or(0));
+            if expected != actual {
+                report.depth_mismatches.push(format!("{} {}: recorded {}, replayed {}", side, price, expected, actual));
+            }
+        }
+    }
+
+    info!("Replay: {} trades replayed, consistent: {}", report.trades, report.is_consistent());
+    Ok(report)
+}
+
+/// Applies recorded depth rows in order and returns the final `(bids, asks)` levels.
+fn rebuild_depth(rows: &[String]) -> Result<(Levels, Levels), String> {
+    let mut bids = BTreeMap::new();
+    let mut asks = BTreeMap::new();
+    for row in rows {
+        let invalid = || format!("Invalid depth row: {}", row);
+        let fields: Vec<&str> = row.split(',').collect();
+        let [_, side, price, quantity] = fields[..] else {
+            return Err(invalid());
+        };
+        let price: Price = price.parse().map_err(|_| invalid())?;
+        let quantity: Quantity = quantity.parse().map_err(|_| invalid())?;
+        let levels = match side {
+            "BUY" => &mut bids,
+            "SELL" => &mut asks,
+            _ => return Err(invalid()),
+        };
+        if quantity == 0 {
+            levels.remove(&price);
+        } else {
+            levels.insert(price, quantity);
+        }
+    }
+    Ok((bids, asks))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use crate::recorder::{Recorder, RecorderConfig};
+    use crate::simulator::{Simulator, SimulatorConfig};
+
+    fn record_simulation() -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut sim = Simulator::new(SimulatorConfig { seed: Some(11), max_events: 400, ..Default::default() });
+        sim.add_sink(Recorder::new(RecorderConfig { directory: directory.path().to_path_buf(), max_rows_per_file: 128 }).unwrap());
+        sim.run(&ob);
+        directory
+    }
+
+    #[test]
+    fn test_replay_matches_recording(){
+        let directory = record_simulation();
+        let report = verify_recording(directory.path()).unwrap();
+
+        assert!(report.is_consistent(), "{:?}", report);
+        assert!(report.trades > 0);
+    }
+
+    #[test]
+    fn test_replay_detects_tampered_tape(){
+        let directory = record_simulation();
+        let path = directory.path().join("trades-0001.csv");
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<&str> = contents.lines().collect();
+        let removed = lines.remove(3);
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let report = verify_recording(directory.path()).unwrap();
+        let mismatch = report.trade_mismatch.unwrap();
+        assert_eq!(mismatch.index, 2);
+        assert_eq!(mismatch.actual.as_deref(), Some(removed));
+    }
+}
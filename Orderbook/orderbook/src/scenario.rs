@@ -0,0 +1,335 @@
+//! # Scenario Module
+//!
+//! A tiny line-oriented DSL for driving the in-process [`Orderbook`] from declarative files,
+//! for tests and demos.
+//!
+//! ## Syntax
+//! One statement per line; keywords are case-insensitive, `#` starts a comment.
+//!
+//! | Statement                                   | Meaning                                                   |
+//! |---------------------------------------------|-----------------------------------------------------------|
+//! | `NEW <id> <BUY\|SELL> <qty> @ <price> [type]` | Limit order; `type` is `GTC` (default), `GFD`, `FAK`, `FOK` |
+//! | `NEW <id> <BUY\|SELL> <qty> MKT`             | Market order                                              |
+//! | `MODIFY <id> <BUY\|SELL> <qty> @ <price>`    | Cancel/replace an order                                   |
+//! | `CANCEL <id>`                               | Cancel an order                                           |
+//! | `WAIT <n><us\|ms\|s>`                         | Advance time                                              |
+//! | `EXPECT FILL <id> <qty>`                    | Order `id` has been filled for exactly `qty` in total     |
+//! | `EXPECT SIZE <n>`                           | The book holds exactly `n` orders                         |
+//! | `EXPECT TRADES <n>`                         | Exactly `n` trades happened so far                        |
+//! | `EXPECT BID <price> <qty>`                  | Bid level `price` holds `qty` (`0` = level absent)        |
+//! | `EXPECT ASK <price> <qty>`                  | Ask level `price` holds `qty` (`0` = level absent)        |
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::scenario::{parse_scenario, ScenarioRunner};
+//!
+//! let steps = parse_scenario("
+//!     NEW 1 BUY 100 @ 101 GTC
+//!     NEW 2 SELL 40 @ 101
+//!     EXPECT FILL 1 40
+//!     EXPECT BID 101 60
+//! ").unwrap();
+//! ScenarioRunner::new().run(&steps).unwrap();
+//! ```
+
+use std::{collections::{BTreeMap, HashMap}, thread, time::Duration};
+use log::info;
+use crate::orderbook::{Order, OrderId, OrderModify, OrderType, Orderbook, Price, Quantity, Side};
+
+/// A single parsed DSL statement.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Statement {
+    New { order_id: OrderId, side: Side, quantity: Quantity, price: Option<Price>, order_type: OrderType },
+    Modify { order_id: OrderId, side: Side, quantity: Quantity, price: Price },
+    Cancel { order_id: OrderId },
+    Wait(Duration),
+    ExpectFill { order_id: OrderId, quantity: Quantity },
+    ExpectSize(usize),
+    ExpectTrades(usize),
+    ExpectLevel { side: Side, price: Price, quantity: Quantity },
+}
+
+/// A [`Statement`] together with the 1-based line it was parsed from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Step {
+    pub line: usize,
+    pub statement: Statement,
+}
+
+/// Summary of a successful scenario run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScenarioReport {
+    /// Statements executed.
+    pub steps: usize,
+    /// `EXPECT` statements that passed.
+    pub expectations: usize,
+    /// Trades produced by the book.
+    pub trades: usize,
+    /// Total time advanced by `WAIT` statements.
+    pub elapsed: Duration,
+}
+
+/// Executes parsed scenarios against a fresh in-process [`Orderbook`].
+#[derive(Clone, Debug, Default)]
+pub struct ScenarioRunner {
+    realtime: bool,
+}
+
+impl ScenarioRunner {
+    /// Creates a runner that treats `WAIT` as virtual time (no sleeping).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `WAIT` actually sleep, e.g. for demos or when GFD pruning matters.
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    /// Runs `steps` in order, stopping at the first failed expectation.
+    ///
+    /// # Errors
+    /// Returns a message naming the failing line and the observed value.
+    pub fn run(&self, steps: &[Step]) -> Result<ScenarioReport, String> {
+        let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut filled: HashMap<OrderId, Quantity> = HashMap::new();
+        let mut report = ScenarioReport::default();
+
+        for step in steps {
+            let trades = match step.statement {
+                Statement::New { order_id, side, quantity, price: None, .. } => {
+                    orderbook.add_order(Order::new_market(order_id, side, quantity))
+                }
+                Statement::New { order_id, side, quantity, price: Some(price), order_type } => {
+                    orderbook.add_order(Order::new(order_type, order_id, side, price, quantity))
+                }
+                Statement::Modify { order_id, side, quantity, price } => {
+                    orderbook.modify_order(OrderModify::new(order_id, side, price, quantity))
+                }
+                Statement::Cancel { order_id } => {
+                    orderbook.cancel_order(order_id);
+                    vec![]
+                }
+                Statement::Wait(duration) => {
+                    if self.realtime {
+                        thread::sleep(duration);
+                    }
+                    report.elapsed += duration;
+                    vec![]
+                }
+                expectation => {
+                    check_expectation(&orderbook, &filled, report.trades, expectation)
+                        .map_err(|err| format!("line {}: {}", step.line, err))?;
+                    report.expectations += 1;
+                    vec![]
+                }
+            };
+
+            for trade in &trades {
+                for info in [trade.get_bid_trade(), trade.get_ask_trade()] {
+                    *filled.entry(info.order_id).or_default() += info.quantity;
+                }
+            }
+            report.trades += trades.len();
+            report.steps += 1;
+        }
+        info!("Scenario: {} steps, {} expectations passed", report.steps, report.expectations);
+        Ok(report)
+    }
+}
+
+/// Evaluates one `EXPECT` statement against the current state.
+fn check_expectation(
+    orderbook: &Orderbook,
+    filled: &HashMap<OrderId, Quantity>,
+    trades: usize,
+    expectation: Statement,
+) -> Result<(), String> {
+    match expectation {
+        Statement::ExpectFill { order_id, quantity } => {
+            let actual = filled.get(&order_id).copied().unwrap_or(0);
+            if actual != quantity {
+                return Err(format!("expected order {} filled for {}, got {}", order_id, quantity, actual));
+            }
+        }
+        Statement::ExpectSize(size) => {
+            let actual = orderbook.size();
+            if actual != size {
+                return Err(format!("expected {} orders in the book, got {}", size, actual));
+            }
+        }
+        Statement::ExpectTrades(count) => {
+            if trades != count {
+                return Err(format!("expected {} trades, got {}", count, trades));
+            }
+        }
+        Statement::ExpectLevel { side, price, quantity } => {
+            let infos = orderbook.get_order_infos();
+            let levels = match side {
+                Side::Buy => infos.get_bids(),
+                Side::Sell => infos.get_asks(),
+            };
+            let actual = levels.iter().find(|level| level.price == price).map_or(0, |level| level.quantity);
+            if actual != quantity {
+                return Err(format!("expected {:?} level {} to hold {}, got {}", side, price, quantity, actual));
+            }
+        }
+        _ => unreachable!("not an expectation: {:?}", expectation),
+    }
+    Ok(())
+}
+
+/// Parses a whole scenario.
+///
+/// # Errors
+/// Returns the first parse error prefixed with its line number.
+pub fn parse_scenario(source: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(line).map_err(|err| format!("line {}: {}", index + 1, err))?;
+        steps.push(Step { line: index + 1, statement });
+    }
+    Ok(steps)
+}
+
+/// Parses a single statement such as `NEW 1 BUY 100 @ 101 GTC`.
+///
+/// # Errors
+/// Returns a description of what was malformed.
+pub fn parse_statement(line: &str) -> Result<Statement, String> {
+    let tokens: Vec<String> = line.split_whitespace().map(str::to_ascii_uppercase).collect();
+    let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+    match tokens[..] {
+        ["NEW", id, side, quantity, "MKT"] => Ok(Statement::New {
+            order_id: parse_number(id, "order id")?,
+            side: parse_side(side)?,
+            quantity: parse_number(quantity, "quantity")?,
+            price: None,
+            order_type: OrderType::Market,
+        }),
+        ["NEW", id, side, quantity, "@", price, ref rest @ ..] if rest.len() <= 1 => Ok(Statement::New {
+            order_id: parse_number(id, "order id")?,
+            side: parse_side(side)?,
+            quantity: parse_number(quantity, "quantity")?,
+            price: Some(parse_number(price, "price")?),
+            order_type: rest.first().map_or(Ok(OrderType::GoodTillCancel), |code| parse_order_type(code))?,
+        }),
+        ["MODIFY", id, side, quantity, "@", price] => Ok(Statement::Modify {
+            order_id: parse_number(id, "order id")?,
+            side: parse_side(side)?,
+            quantity: parse_number(quantity, "quantity")?,
+            price: parse_number(price, "price")?,
+        }),
+        ["CANCEL", id] => Ok(Statement::Cancel { order_id: parse_number(id, "order id")? }),
+        ["WAIT", duration] => Ok(Statement::Wait(parse_duration(duration)?)),
+        ["EXPECT", "FILL", id, quantity] => Ok(Statement::ExpectFill {
+            order_id: parse_number(id, "order id")?,
+            quantity: parse_number(quantity, "quantity")?,
+        }),
+        ["EXPECT", "SIZE", size] => Ok(Statement::ExpectSize(parse_number(size, "size")?)),
+        ["EXPECT", "TRADES", count] => Ok(Statement::ExpectTrades(parse_number(count, "trade count")?)),
+        ["EXPECT", level @ ("BID" | "ASK"), price, quantity] => Ok(Statement::ExpectLevel {
+            side: if level == "BID" { Side::Buy } else { Side::Sell },
+            price: parse_number(price, "price")?,
+            quantity: parse_number(quantity, "quantity")?,
+        }),
+        _ => Err(format!("unrecognised statement: {}", line)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(token: &str, name: &str) -> Result<T, String> {
+    token.parse().map_err(|_| format!("invalid {}: {}", name, token))
+}
+
+fn parse_side(token: &str) -> Result<Side, String> {
+    match token {
+        "BUY" => Ok(Side::Buy),
+        "SELL" => Ok(Side::Sell),
+        _ => Err(format!("invalid side: {}", token)),
+    }
+}
+
+fn parse_order_type(token: &str) -> Result<OrderType, String> {
+    match token {
+        "GTC" => Ok(OrderType::GoodTillCancel),
+        "GFD" => Ok(OrderType::GoodForDay),
+        "FAK" => Ok(OrderType::FillAndKill),
+        "FOK" => Ok(OrderType::FillOrKill),
+        _ => Err(format!("invalid order type: {}", token)),
+    }
+}
+
+/// Parses durations like `250US`, `100MS` or `2S` (tokens are already upper-cased).
+fn parse_duration(token: &str) -> Result<Duration, String> {
+    let split = token.find(|c: char| !c.is_ascii_digit()).unwrap_or(token.len());
+    let (value, unit) = token.split_at(split);
+    let value: u64 = parse_number(value, "duration")?;
+    match unit {
+        "US" => Ok(Duration::from_micros(value)),
+        "MS" => Ok(Duration::from_millis(value)),
+        "S" => Ok(Duration::from_secs(value)),
+        _ => Err(format!("invalid duration unit in {}, expected us, ms or s", token)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_statements(){
+        assert_eq!(
+            parse_statement("new 1 buy 100 @ 101 fak").unwrap(),
+            Statement::New { order_id: 1, side: Side::Buy, quantity: 100, price: Some(101), order_type: OrderType::FillAndKill }
+        );
+        assert_eq!(
+            parse_statement("NEW 2 SELL 5 MKT").unwrap(),
+            Statement::New { order_id: 2, side: Side::Sell, quantity: 5, price: None, order_type: OrderType::Market }
+        );
+        assert_eq!(parse_statement("WAIT 100ms").unwrap(), Statement::Wait(Duration::from_millis(100)));
+        assert_eq!(parse_statement("EXPECT FILL 1 100").unwrap(), Statement::ExpectFill { order_id: 1, quantity: 100 });
+        assert!(parse_statement("NEW 1 BUY 100 101").is_err());
+        assert!(parse_statement("WAIT 10m").is_err());
+    }
+
+    #[test]
+    fn test_run_scenario(){
+        let steps = parse_scenario("
+            # Two resting bids, one aggressive sell sweeps them.
+            NEW 1 BUY 100 @ 101 GTC
+            NEW 2 BUY 50 @ 100
+            WAIT 100ms
+            NEW 3 SELL 120 @ 100 GTC
+            EXPECT FILL 1 100
+            EXPECT FILL 2 20
+            EXPECT FILL 3 120
+            EXPECT TRADES 2
+            EXPECT BID 100 30
+            EXPECT BID 101 0
+            MODIFY 2 BUY 30 @ 99
+            CANCEL 2
+            EXPECT SIZE 0
+        ").unwrap();
+        let report = ScenarioRunner::new().run(&steps).unwrap();
+
+        assert_eq!(report.steps, 13);
+        assert_eq!(report.expectations, 7);
+        assert_eq!(report.trades, 2);
+        assert_eq!(report.elapsed, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_run_scenario_reports_failed_expectation(){
+        let steps = parse_scenario("NEW 1 BUY 10 @ 100\nEXPECT FILL 1 10").unwrap();
+        let err = ScenarioRunner::new().run(&steps).unwrap_err();
+
+        assert_eq!(err, "line 2: expected order 1 filled for 10, got 0");
+    }
+}
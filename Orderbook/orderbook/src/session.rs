@@ -0,0 +1,130 @@
+//! # Session Module
+//!
+//! Daily trading statistics of a book and the end-of-day report that freezes them.
+//!
+//! ## Closing a session
+//! [`Orderbook::close_session`](crate::Orderbook::close_session) ends the trading day. The book's pruning thread calls it at
+//! the daily cutoff; callers that keep their own time, such as the
+//! [`simulator`](crate::simulator), call it themselves. It:
+//! 1. cancels every GoodForDay order,
+//! 2. freezes the day's [`DailyStats`] into an [`EndOfDayReport`] and starts new ones,
+//! 3. carries the close over as the previous close of the
+//!    [reference prices](crate::ReferencePrices) and clears the open.
+//!
+//! The report is returned and, while [`set_publish_events`](crate::Orderbook::set_publish_events)
+//! is on, buffered for [`Orderbook::take_session_reports`](crate::Orderbook::take_session_reports), which
+//! [`EventBus::publish_book`](crate::bus::EventBus::publish_book) forwards to subscribers.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Order, OrderType, Orderbook, Side};
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 5));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 101, 2));
+//!
+//! let report = ob.close_session();
+//! assert_eq!((report.session, report.stats.close, report.stats.volume), (1, Some(101), 2));
+//! assert_eq!(ob.get_daily_stats().trade_count, 0);
+//! assert_eq!(ob.get_reference_prices().previous_close, Some(101));
+//! ```
+
+use chrono::{DateTime, Utc};
+use crate::orderbook::{Price, Quantity};
+
+/// Trading statistics of one session.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DailyStats {
+    pub open: Option<Price>,
+    pub high: Option<Price>,
+    pub low: Option<Price>,
+    pub close: Option<Price>,
+    /// Total quantity traded.
+    pub volume: u64,
+    /// Sum of price × quantity over all trades, for the VWAP.
+    pub notional: i64,
+    pub trade_count: u64,
+}
+
+impl DailyStats {
+    /// Adds a trade of `quantity` at `price`.
+    pub fn record(&mut self, price: Price, quantity: Quantity) {
+        self.open.get_or_insert(price);
+        self.high = Some(self.high.map_or(price, |high| high.max(price)));
+        self.low = Some(self.low.map_or(price, |low| low.min(price)));
+        self.close = Some(price);
+        self.volume += u64::from(quantity);
+        self.notional += i64::from(price) * i64::from(quantity);
+        self.trade_count += 1;
+    }
+
+    /// Returns the volume-weighted average price, if anything traded.
+    pub fn get_vwap(&self) -> Option<f64> {
+        if self.volume == 0 {
+            return None;
+        }
+        Some(self.notional as f64 / self.volume as f64)
+    }
+}
+
+/// The frozen statistics of a closed session.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EndOfDayReport {
+    /// Number of the session in the life of the book, starting at 1.
+    pub session: u64,
+    /// Time of the close on the book's clock.
+    pub closed_at: DateTime<Utc>,
+    pub stats: DailyStats,
+    /// GoodForDay orders cancelled at the close.
+    pub pruned: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::BTreeMap, sync::Arc};
+    use crate::bus::{BusEvent, EventBus, Overflow};
+    use crate::clock::MockClock;
+    use crate::orderbook::{Order, OrderType, Orderbook, ReferencePrices, Side};
+
+    #[test]
+    fn test_close_freezes_and_resets_the_day(){
+        // Closed by hand, well before the cutoff the pruning thread waits for.
+        let closed_at = DateTime::parse_from_rfc3339("2026-10-16T12:30:00Z").unwrap().to_utc();
+        let ob = Orderbook::build_with_clock(BTreeMap::new(), BTreeMap::new(), false, Arc::new(MockClock::new(closed_at)));
+        ob.set_publish_events(true);
+        let bus = EventBus::new();
+        let subscriber = bus.subscribe("eod", 64, Overflow::Block);
+
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 100, 2));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 104, 2));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 104, 3));
+        ob.add_order(Order::new(OrderType::GoodForDay, 4, Side::Buy, 101, 5));
+        ob.add_order(Order::new(OrderType::GoodTillCancel, 5, Side::Sell, 101, 1));
+        assert_eq!(ob.get_daily_stats().get_vwap(), Some(101.25));
+
+        let report = ob.close_session();
+        assert_eq!(report, EndOfDayReport {
+            session: 1,
+            closed_at,
+            stats: DailyStats { open: Some(100), high: Some(104), low: Some(100), close: Some(101), volume: 4, notional: 405, trade_count: 3 },
+            pruned: 1,
+        });
+        assert_eq!(ob.size(), 1);
+        assert_eq!(ob.get_daily_stats(), DailyStats::default());
+        assert_eq!(ob.get_reference_prices(), ReferencePrices { previous_close: Some(101), open: None, last: Some(101) });
+
+        // A quiet session keeps the previous close.
+        assert_eq!(ob.close_session().session, 2);
+        assert_eq!(ob.get_reference_prices().previous_close, Some(101));
+
+        bus.publish_book(&ob, &[]);
+        let reports: Vec<u64> = subscriber.try_iter().filter_map(|event| match event {
+            BusEvent::EndOfDay(report) => Some(report.session),
+            _ => None,
+        }).collect();
+        assert_eq!(reports, vec![1, 2]);
+        assert!(ob.take_session_reports().is_empty());
+    }
+}
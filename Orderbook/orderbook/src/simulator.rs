@@ -0,0 +1,565 @@
+//! # Simulator Module
+//!
+//! Generates random order flow against an [`Orderbook`] so the matching engine can be
+//! exercised with something closer to real microstructure than fixed loops.
+//!
+//! ## Model
+//! - **Fair value:** a Gaussian random walk, stepped once per event.
+//! - **Arrivals:** Poisson process; inter-arrival times are exponentially distributed
+//!   with mean `1 / arrival_rate`.
+//! - **Prices:** an offset drawn from [`PriceDistribution`] around the fair value.
+//!   Buys are priced at `fair - offset`, sells at `fair + offset`, so negative offsets
+//!   produce marketable orders.
+//! - **Cancels:** with probability `cancel_ratio` an event cancels a random resting
+//!   order instead of submitting a new one.
+//! - **Order types:** drawn from the weights in [`OrderTypeMix`].
+//! - **Sessions:** with a `session_length`, the book's session is closed each time the
+//!   simulated time crosses the end of a session, like at the book's daily cutoff.
+//!
+//! ## Sinks
+//! Everything that follows a run, such as a [`Recorder`](crate::recorder::Recorder),
+//! [`TradeTape`](crate::tape::TradeTape), [`Journal`](crate::journal::Journal),
+//! [`EventBus`](crate::bus::EventBus) or [`VirtualClock`](crate::clock::VirtualClock), is a
+//! [`SimulationSink`] added with [`Simulator::add_sink`]; each implements it in its own module.
+//! Sinks are called in the order they were added.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::Orderbook;
+//! use orderbook::simulator::{Simulator, SimulatorConfig};
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! let mut sim = Simulator::new(SimulatorConfig { seed: Some(7), max_events: 100, ..Default::default() });
+//! let stats = sim.run(&ob);
+//! assert_eq!(stats.submitted + stats.cancelled, 100);
+//! ```
+
+use std::{thread, time::Duration};
+use log::{debug, info, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::backtest::Command;
+use crate::orderbook::{Order, OrderId, OrderPointer, OrderType, Orderbook, Price, Quantity, Side, Trade};
+
+/// Shape of the price offset distribution around the fair value.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PriceDistribution {
+    /// Offsets uniformly distributed in `[-spread, spread]`.
+    Uniform,
+    /// Offsets normally distributed with mean 0 and standard deviation `spread`.
+    Normal,
+}
+
+/// Relative weights used to pick the type of each new order.
+///
+/// Weights do not need to sum to any particular value; a weight of `0`
+/// disables that order type entirely.
+#[derive(Clone, Copy, Debug)]
+pub struct OrderTypeMix {
+    pub good_till_cancel: u32,
+    pub good_for_day: u32,
+    pub fill_and_kill: u32,
+    pub fill_or_kill: u32,
+    pub market: u32,
+}
+
+impl Default for OrderTypeMix {
+    fn default() -> Self {
+        Self {
+            good_till_cancel: 70,
+            good_for_day: 10,
+            fill_and_kill: 10,
+            fill_or_kill: 5,
+            market: 5,
+        }
+    }
+}
+
+/// Tunable parameters for a [`Simulator`] run.
+#[derive(Clone, Debug)]
+pub struct SimulatorConfig {
+    /// RNG seed. `None` seeds from OS entropy; `Some` makes runs reproducible.
+    pub seed: Option<u64>,
+    /// Number of events (submissions + cancels) to generate.
+    pub max_events: usize,
+    /// Mean number of events per second.
+    pub arrival_rate: f64,
+    /// If `true`, sleep for the sampled inter-arrival time between events.
+    pub realtime: bool,
+    /// Starting fair value of the random walk.
+    pub initial_fair_value: Price,
+    /// Standard deviation of each fair-value step, in ticks.
+    pub volatility: f64,
+    /// Distribution of price offsets around the fair value.
+    pub price_distribution: PriceDistribution,
+    /// Scale of the price offset distribution, in ticks.
+    pub price_spread: f64,
+    /// Smallest quantity of a generated order.
+    pub min_quantity: Quantity,
+    /// Largest quantity of a generated order.
+    pub max_quantity: Quantity,
+    /// Probability in `[0, 1]` that an event is a cancel rather than a new order.
+    pub cancel_ratio: f64,
+    /// Weights for the order type of each new order.
+    pub order_type_mix: OrderTypeMix,
+    /// Simulated length of a trading day; GoodForDay orders are pruned at the end of each.
+    /// `None` never prunes them.
+    pub session_length: Option<Duration>,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            max_events: 1000,
+            arrival_rate: 100.0,
+            realtime: false,
+            initial_fair_value: 100,
+            volatility: 0.5,
+            price_distribution: PriceDistribution::Normal,
+            price_spread: 3.0,
+            min_quantity: 1,
+            max_quantity: 20,
+            cancel_ratio: 0.2,
+            order_type_mix: OrderTypeMix::default(),
+            session_length: None,
+        }
+    }
+}
+
+/// Counters accumulated over a simulator run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulatorStats {
+    /// New orders submitted to the book.
+    pub submitted: usize,
+    /// Cancel requests sent for resting orders.
+    pub cancelled: usize,
+    /// GoodForDay orders pruned at the end of a session.
+    pub pruned: usize,
+    /// Trades generated by the book.
+    pub trades: usize,
+    /// Total quantity traded.
+    pub traded_quantity: u64,
+    /// Simulated time elapsed, i.e. the sum of sampled inter-arrival times.
+    pub elapsed: Duration,
+}
+
+/// Follows a [`Simulator`] run, e.g. to record, export or publish it.
+///
+/// Every method does nothing by default. Timestamps are the simulated time since the start of
+/// the run. A sink that returns an error is logged and dropped for the rest of the run.
+pub trait SimulationSink {
+    /// Called before `command` is applied to `orderbook`.
+    fn on_command(&mut self, _orderbook: &Orderbook, _timestamp: Duration, _command: Command) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after `command` was applied to `orderbook`, with the `trades` it caused.
+    fn on_applied(&mut self, _orderbook: &Orderbook, _timestamp: Duration, _command: Command, _trades: &[Trade]) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after the simulated time moved forward by `wait`, once any session that ended
+    /// has been closed.
+    fn on_advance(&mut self, _orderbook: &Orderbook, _timestamp: Duration, _wait: Duration) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<S: SimulationSink + ?Sized> SimulationSink for &mut S {
+    fn on_command(&mut self, orderbook: &Orderbook, timestamp: Duration, command: Command) -> Result<(), String> {
+        (**self).on_command(orderbook, timestamp, command)
+    }
+
+    fn on_applied(&mut self, orderbook: &Orderbook, timestamp: Duration, command: Command, trades: &[Trade]) -> Result<(), String> {
+        (**self).on_applied(orderbook, timestamp, command, trades)
+    }
+
+    fn on_advance(&mut self, orderbook: &Orderbook, timestamp: Duration, wait: Duration) -> Result<(), String> {
+        (**self).on_advance(orderbook, timestamp, wait)
+    }
+}
+
+/// Random order-flow generator.
+///
+/// Keeps a handle to every order it has left resting in the book so it can
+/// cancel them later; handles are dropped once the order fills.
+pub struct Simulator<'a> {
+    config: SimulatorConfig,
+    rng: StdRng,
+    fair_value: f64,
+    next_order_id: OrderId,
+    resting_orders: Vec<OrderPointer>,
+    stats: SimulatorStats,
+    sinks: Vec<Box<dyn SimulationSink + 'a>>,
+}
+
+impl<'a> Simulator<'a> {
+    /// Creates a simulator from `config`, seeding its RNG.
+    pub fn new(config: SimulatorConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            fair_value: config.initial_fair_value as f64,
+            config,
+            rng,
+            next_order_id: 1,
+            resting_orders: Vec::new(),
+            stats: SimulatorStats::default(),
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Adds `sink` to follow the run, after the sinks already added.
+    ///
+    /// Pass `&mut sink` to keep the sink once the simulator is dropped.
+    pub fn add_sink(&mut self, sink: impl SimulationSink + 'a) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Returns the current fair value of the random walk.
+    pub fn get_fair_value(&self) -> f64 {
+        self.fair_value
+    }
+
+    /// Returns the counters accumulated so far.
+    pub const fn get_stats(&self) -> &SimulatorStats {
+        &self.stats
+    }
+
+    /// Generates `max_events` events against `orderbook`.
+    ///
+    /// # Returns
+    /// The accumulated [`SimulatorStats`].
+    pub fn run(&mut self, orderbook: &Orderbook) -> &SimulatorStats {
+        info!("Simulator: starting run of {} events", self.config.max_events);
+        for _ in 0..self.config.max_events {
+            let wait = self.next_inter_arrival();
+            self.advance(orderbook, wait);
+            if self.config.realtime {
+                thread::sleep(wait);
+            }
+            self.step(orderbook);
+        }
+        info!("Simulator: finished run {:?}", self.stats);
+        &self.stats
+    }
+
+    /// Moves the simulated time forward by `wait`, pruning GoodForDay orders if a session ends.
+    fn advance(&mut self, orderbook: &Orderbook, wait: Duration) {
+        let before = self.stats.elapsed;
+        self.stats.elapsed += wait;
+
+        let ends_session = self
+            .config
+            .session_length
+            .filter(|session| !session.is_zero())
+            .is_some_and(|session| self.stats.elapsed.as_nanos() / session.as_nanos() != before.as_nanos() / session.as_nanos());
+        if ends_session {
+            let report = orderbook.close_session();
+            info!("Simulator: session {} ended at {:?}, pruned {} GFD orders", report.session, self.stats.elapsed, report.pruned);
+            self.stats.pruned += report.pruned;
+            self.resting_orders.retain(|order| order.lock().unwrap().get_order_type() != OrderType::GoodForDay);
+        }
+        let timestamp = self.stats.elapsed;
+        self.notify(|sink| sink.on_advance(orderbook, timestamp, wait));
+    }
+
+    /// Generates a single event: either a cancel or a new order.
+    pub fn step(&mut self, orderbook: &Orderbook) {
+        self.fair_value += self.config.volatility * self.sample_standard_normal();
+
+        self.resting_orders.retain(|order| !order.lock().unwrap().is_filled());
+        if !self.resting_orders.is_empty() && self.rng.gen_bool(self.config.cancel_ratio.clamp(0.0, 1.0)) {
+            let index = self.rng.gen_range(0..self.resting_orders.len());
+            let order = self.resting_orders.swap_remove(index);
+            let order_id = order.lock().unwrap().get_order_id();
+            self.apply(orderbook, Command::Cancel { order_id }, || {
+                orderbook.cancel_order(order_id);
+                Vec::new()
+            });
+            self.stats.cancelled += 1;
+            return;
+        }
+
+        let order = self.next_order();
+        let command = {
+            let ord = order.lock().unwrap();
+            let (order_id, side, quantity) = (ord.get_order_id(), ord.get_side(), ord.get_initial_quantity());
+            match ord.get_order_type() {
+                OrderType::Market => Command::Market { order_id, side, quantity },
+                order_type => Command::New { order_id, side, price: ord.get_price(), quantity, order_type, flags: ord.get_flags() },
+            }
+        };
+        let trades = self.apply(orderbook, command, || orderbook.add_order(order.clone()));
+        self.stats.submitted += 1;
+        self.stats.trades += trades.len();
+        self.stats.traded_quantity += trades
+            .iter()
+            .map(|trade| trade.get_bid_trade().quantity as u64)
+            .sum::<u64>();
+
+        // Only orders that ended up resting can be cancelled later. Market orders that
+        // found liquidity were converted to GoodTillCancel by the book.
+        let ord = order.lock().unwrap();
+        let rests = matches!(ord.get_order_type(), OrderType::GoodTillCancel | OrderType::GoodForDay);
+        if rests && !ord.is_filled() {
+            drop(ord);
+            self.resting_orders.push(order);
+        }
+    }
+
+    /// Runs `apply`, which carries out `command`, between the sinks' hooks.
+    fn apply(&mut self, orderbook: &Orderbook, command: Command, apply: impl FnOnce() -> Vec<Trade>) -> Vec<Trade> {
+        let timestamp = self.stats.elapsed;
+        self.notify(|sink| sink.on_command(orderbook, timestamp, command));
+        let trades = apply();
+        self.notify(|sink| sink.on_applied(orderbook, timestamp, command, &trades));
+        trades
+    }
+
+    /// Calls every sink, dropping those that fail.
+    fn notify(&mut self, mut call: impl FnMut(&mut dyn SimulationSink) -> Result<(), String>) {
+        self.sinks.retain_mut(|sink| match call(sink.as_mut()) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("Simulator: {}, dropping the sink", err);
+                false
+            }
+        });
+    }
+
+    /// Builds the next random order.
+    fn next_order(&mut self) -> OrderPointer {
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+
+        let side = if self.rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
+        let quantity = self.rng.gen_range(self.config.min_quantity..=self.config.max_quantity.max(self.config.min_quantity));
+        let order_type = self.sample_order_type();
+
+        debug!("Simulator: generating {:?} {:?} order#{} (fair value {:.2})", order_type, side, order_id, self.fair_value);
+        if order_type == OrderType::Market {
+            return Order::new_market(order_id, side, quantity);
+        }
+        let price = self.sample_price(side);
+        Order::new(order_type, order_id, side, price, quantity)
+    }
+
+    /// Samples a limit price for `side` around the current fair value.
+    fn sample_price(&mut self, side: Side) -> Price {
+        let spread = self.config.price_spread;
+        let offset = match self.config.price_distribution {
+            PriceDistribution::Uniform if spread > 0.0 => self.rng.gen_range(-spread..=spread),
+            PriceDistribution::Uniform => 0.0,
+            PriceDistribution::Normal => spread * self.sample_standard_normal(),
+        };
+        let price = match side {
+            Side::Buy => self.fair_value - offset,
+            Side::Sell => self.fair_value + offset,
+        };
+        price.round().max(1.0) as Price
+    }
+
+    /// Picks an order type according to the configured weights.
+    fn sample_order_type(&mut self) -> OrderType {
+        let mix = self.config.order_type_mix;
+        let weighted = [
+            (OrderType::GoodTillCancel, mix.good_till_cancel),
+            (OrderType::GoodForDay, mix.good_for_day),
+            (OrderType::FillAndKill, mix.fill_and_kill),
+            (OrderType::FillOrKill, mix.fill_or_kill),
+            (OrderType::Market, mix.market),
+        ];
+        let total: u32 = weighted.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return OrderType::GoodTillCancel;
+        }
+
+        let mut pick = self.rng.gen_range(0..total);
+        for (order_type, weight) in weighted {
+            if pick < weight {
+                return order_type;
+            }
+            pick -= weight;
+        }
+        OrderType::GoodTillCancel
+    }
+
+    /// Samples an exponentially distributed inter-arrival time.
+    fn next_inter_arrival(&mut self) -> Duration {
+        if self.config.arrival_rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        let uniform: f64 = 1.0 - self.rng.gen::<f64>(); // (0, 1], avoids ln(0)
+        Duration::from_secs_f64(-uniform.ln() / self.config.arrival_rate)
+    }
+
+    /// Samples a standard normal variate using the Box–Muller transform.
+    fn sample_standard_normal(&mut self) -> f64 {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::BTreeMap, sync::Arc};
+    use crate::backtest::{Backtester, Strategy, StrategyContext};
+    use crate::bus::{BusEvent, EventBus, Overflow};
+    use crate::recorder::{Recorder, RecorderConfig};
+    use crate::tape::{TradeTape, TradeTapeConfig};
+
+    struct Idle;
+
+    impl Strategy for Idle {
+        fn on_tick(&mut self, _context: &mut StrategyContext) {}
+    }
+
+    #[test]
+    fn test_simulator_is_reproducible_with_seed() {
+        let config = SimulatorConfig { seed: Some(42), max_events: 500, ..Default::default() };
+
+        let ob1 = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let stats1 = Simulator::new(config.clone()).run(&ob1).clone();
+
+        let ob2 = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let stats2 = Simulator::new(config).run(&ob2).clone();
+
+        assert_eq!(stats1, stats2);
+        assert_eq!(ob1.size(), ob2.size());
+    }
+
+    #[test]
+    fn test_simulator_recording_replays_in_backtester(){
+        let directory = tempfile::tempdir().unwrap();
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut sim = Simulator::new(SimulatorConfig { seed: Some(9), max_events: 300, ..Default::default() });
+        sim.add_sink(Recorder::new(RecorderConfig { directory: directory.path().to_path_buf(), max_rows_per_file: 1000 }).unwrap());
+        let stats = sim.run(&ob).clone();
+        drop(sim);
+
+        let report = Backtester::from_file(directory.path().join("commands-0001.csv")).unwrap().run(&mut Idle);
+        assert_eq!(report.commands_replayed, stats.submitted + stats.cancelled);
+        assert_eq!(report.total_trades, stats.trades);
+    }
+
+    #[test]
+    fn test_simulator_writes_trade_tape(){
+        let directory = tempfile::tempdir().unwrap();
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut sim = Simulator::new(SimulatorConfig { seed: Some(5), max_events: 300, ..Default::default() });
+        sim.add_sink(TradeTape::new(TradeTapeConfig { directory: directory.path().to_path_buf(), ..Default::default() }).unwrap());
+        let stats = sim.run(&ob).clone();
+        drop(sim);
+
+        let rows = crate::recorder::read_rows(directory.path(), "tape").unwrap();
+        assert!(stats.trades > 0);
+        assert_eq!(rows.len(), stats.trades);
+    }
+
+    #[test]
+    fn test_simulator_publishes_to_event_bus(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        ob.set_publish_events(true);
+        let bus = Arc::new(EventBus::new());
+        let subscriber = bus.subscribe("metrics", 100_000, Overflow::Drop);
+        let mut sim = Simulator::new(SimulatorConfig { seed: Some(5), max_events: 300, ..Default::default() });
+        sim.add_sink(bus);
+        let stats = sim.run(&ob).clone();
+
+        let trades = subscriber.try_iter().filter(|event| matches!(event, BusEvent::Trade(_))).count();
+        assert!(stats.trades > 0);
+        assert_eq!(trades, stats.trades);
+        assert_eq!(subscriber.get_dropped(), 0);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_simulator_exports_parquet(){
+        use crate::columnar::{partition_directory, ParquetExportConfig, ParquetExporter};
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let directory = tempfile::tempdir().unwrap();
+        let start = chrono::DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z").unwrap().to_utc();
+        let config = ParquetExportConfig { directory: directory.path().to_path_buf(), start, ..Default::default() };
+        let partition = partition_directory(directory.path(), "trades", config.start.date_naive(), &config.symbol);
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut sim = Simulator::new(SimulatorConfig { seed: Some(5), max_events: 300, ..Default::default() });
+        sim.add_sink(ParquetExporter::new(config).unwrap());
+        let stats = sim.run(&ob).clone();
+        drop(sim);
+
+        let reader = SerializedFileReader::new(std::fs::File::open(partition.join("part-0001.parquet")).unwrap()).unwrap();
+        assert!(stats.trades > 0);
+        assert_eq!(reader.metadata().file_metadata().num_rows() as usize, stats.trades);
+    }
+
+    #[test]
+    fn test_simulator_drops_failing_sinks(){
+        #[derive(Default)]
+        struct Counting {
+            commands: usize,
+            fail_after: Option<usize>,
+        }
+
+        impl SimulationSink for Counting {
+            fn on_command(&mut self, _orderbook: &Orderbook, _timestamp: Duration, _command: Command) -> Result<(), String> {
+                self.commands += 1;
+                match self.fail_after {
+                    Some(limit) if self.commands >= limit => Err("full".to_string()),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let (mut healthy, mut failing) = (Counting::default(), Counting { fail_after: Some(10), ..Default::default() });
+        let mut sim = Simulator::new(SimulatorConfig { seed: Some(2), max_events: 50, ..Default::default() });
+        sim.add_sink(&mut failing);
+        sim.add_sink(&mut healthy);
+        sim.run(&ob);
+        drop(sim);
+
+        assert_eq!(healthy.commands, 50);
+        assert_eq!(failing.commands, 10);
+    }
+
+    #[test]
+    fn test_simulator_without_cancels(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut sim = Simulator::new(SimulatorConfig {
+            seed: Some(1),
+            max_events: 200,
+            cancel_ratio: 0.0,
+            ..Default::default()
+        });
+        let stats = sim.run(&ob);
+
+        assert_eq!(stats.cancelled, 0);
+        assert_eq!(stats.submitted, 200);
+        assert!(stats.trades > 0);
+    }
+
+    #[test]
+    fn test_simulator_order_type_mix(){
+        let ob = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        let mut sim = Simulator::new(SimulatorConfig {
+            seed: Some(3),
+            max_events: 100,
+            cancel_ratio: 0.0,
+            order_type_mix: OrderTypeMix { good_till_cancel: 1, good_for_day: 0, fill_and_kill: 0, fill_or_kill: 0, market: 0 },
+            price_distribution: PriceDistribution::Uniform,
+            ..Default::default()
+        });
+        sim.run(&ob);
+
+        // Only GTC orders: every unfilled order the simulator tracks must still rest in the book.
+        sim.resting_orders.retain(|order| !order.lock().unwrap().is_filled());
+        assert_eq!(sim.resting_orders.len(), ob.size());
+    }
+}
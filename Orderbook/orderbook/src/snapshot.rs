@@ -0,0 +1,583 @@
+//! # Snapshot Module
+//!
+//! A compact, versioned binary format for the full state of a book, for handing a book to
+//! another process or recovering it after a restart.
+//!
+//! ## Format (version 2)
+//! All integers are little-endian.
+//!
+//! | Record  | Fields                                                                                               | Bytes |
+//! |---------|------------------------------------------------------------------------------------------------------|-------|
+//! | Header  | magic `OBSN`, version `u16`, flags `u16`, level count `u32`, order count `u32`                       | 16    |
+//! | Level   | side `u8` (`0` buy, `1` sell), price `i32`, quantity `u32`, order count `u32`                        | 13    |
+//! | Order   | order id `u32`, order type `u8`, initial quantity `u32`, remaining quantity `u32`, order flags `u8` | 14    |
+//! | Trailer | CRC-32 (IEEE) of every preceding byte, `u32`                                                         | 4     |
+//!
+//! Each level record is followed by its order records in queue order. Bid levels come first,
+//! best (highest) price first, then ask levels, best (lowest) price first. The quantity of a
+//! level is the sum of the remaining quantities of its orders; it is redundant and checked on
+//! read, as are the counts, the price ordering and the checksum.
+//!
+//! If the [`FLAG_LZ4`] bit of the flags is set, the level and order records are stored as
+//! one LZ4 block instead, as written by [`BookSnapshot::to_compressed_bytes`]. Their
+//! uncompressed size follows from the counts in the header, and the checksum covers the
+//! bytes as stored. No other flags are defined.
+//!
+//! Order types are coded `0` GoodTillCancel, `1` GoodForDay, `2` FillAndKill, `3` FillOrKill,
+//! `4` Market, although only the first two ever rest in a book. Bit 0 of the order flags marks
+//! a short sell and bits 1-2 hold the capacity, `0` none, `1` Agency, `2` Principal; the other
+//! bits are zero.
+//!
+//! Version 1 snapshots, whose order records stop before the order flags, are still read; their
+//! orders have default [`OrderFlags`].
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Orderbook, Order, OrderType, Side};
+//! use orderbook::snapshot::BookSnapshot;
+//!
+//! let book = Orderbook::new(Default::default(), Default::default());
+//! book.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 10));
+//!
+//! let bytes = BookSnapshot::capture(&book).to_bytes();
+//! let restored = BookSnapshot::from_bytes(&bytes).unwrap().restore().unwrap();
+//! assert_eq!(restored.size(), 1);
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+};
+use crate::orderbook::{Capacity, Order, OrderFlags, OrderId, OrderType, Orderbook, Price, Quantity, Side};
+
+/// First bytes of every snapshot.
+pub const MAGIC: [u8; 4] = *b"OBSN";
+/// Version written by [`BookSnapshot::to_bytes`]; [`BookSnapshot::from_bytes`] also reads version 1.
+pub const VERSION: u16 = 2;
+
+/// Header flag marking LZ4-compressed level and order records.
+pub const FLAG_LZ4: u16 = 1;
+/// Largest expansion of an LZ4 block, used to reject implausible record counts before
+/// allocating for them.
+const MAX_LZ4_RATIO: usize = 255;
+
+const HEADER_LEN: usize = 16;
+const LEVEL_LEN: usize = 13;
+const ORDER_LEN: usize = 14;
+/// Order records of version 1 snapshots have no order flags.
+const V1_ORDER_LEN: usize = 13;
+const TRAILER_LEN: usize = 4;
+
+/// One resting order as recorded in a snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderSnapshot {
+    pub order_id: OrderId,
+    pub order_type: OrderType,
+    pub initial_quantity: Quantity,
+    pub remaining_quantity: Quantity,
+    pub flags: OrderFlags,
+}
+
+/// One price level and its orders in queue order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelSnapshot {
+    pub price: Price,
+    pub orders: Vec<OrderSnapshot>,
+}
+
+impl LevelSnapshot {
+    /// Returns the total remaining quantity at this level.
+    pub fn get_quantity(&self) -> Quantity {
+        self.orders.iter().map(|order| order.remaining_quantity).sum()
+    }
+}
+
+/// The resting orders of a book, level by level.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BookSnapshot {
+    /// Bid levels, best (highest) price first.
+    pub bids: Vec<LevelSnapshot>,
+    /// Ask levels, best (lowest) price first.
+    pub asks: Vec<LevelSnapshot>,
+}
+
+impl BookSnapshot {
+    /// Records every order resting in `orderbook`.
+    pub fn capture(orderbook: &Orderbook) -> Self {
+        Self {
+            bids: Self::levels(orderbook.get_resting_orders(Side::Buy)),
+            asks: Self::levels(orderbook.get_resting_orders(Side::Sell)),
+        }
+    }
+
+    /// Groups orders given in priority order into levels.
+    fn levels(orders: Vec<Order>) -> Vec<LevelSnapshot> {
+        let mut levels: Vec<LevelSnapshot> = Vec::new();
+        for order in orders {
+            let record = OrderSnapshot {
+                order_id: order.get_order_id(),
+                order_type: order.get_order_type(),
+                initial_quantity: order.get_initial_quantity(),
+                remaining_quantity: order.get_remaining_quantity(),
+                flags: order.get_flags(),
+            };
+            match levels.last_mut() {
+                Some(level) if level.price == order.get_price() => level.orders.push(record),
+                _ => levels.push(LevelSnapshot { price: order.get_price(), orders: vec![record] }),
+            }
+        }
+        levels
+    }
+
+    /// Returns the number of orders in the snapshot.
+    pub fn get_order_count(&self) -> usize {
+        self.bids.iter().chain(&self.asks).map(|level| level.orders.len()).sum()
+    }
+
+    /// Builds a new book holding exactly the orders of the snapshot, queue positions and
+    /// partial fills included.
+    ///
+    /// # Errors
+    /// Returns an error if the snapshot is not a valid book (duplicate order ids, filled or
+    /// non-resting orders, crossed levels).
+    pub fn restore(&self) -> Result<Orderbook, String> {
+        let orderbook = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        for (side, levels) in [(Side::Buy, &self.bids), (Side::Sell, &self.asks)] {
+            for level in levels {
+                for record in &level.orders {
+                    if record.remaining_quantity > record.initial_quantity {
+                        return Err(format!("Order#{} has more remaining than its initial quantity", record.order_id));
+                    }
+                    let order = Order::new(record.order_type, record.order_id, side, level.price, record.initial_quantity);
+                    order.lock().unwrap().set_flags(record.flags);
+                    order.lock().unwrap().fill(record.initial_quantity - record.remaining_quantity)?;
+                    orderbook.restore_order(order)?;
+                }
+            }
+        }
+        Ok(orderbook)
+    }
+
+    /// Encodes the snapshot in the current format version.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(0, self.encode_records())
+    }
+
+    /// Encodes the snapshot with its records LZ4-compressed, which pays off for deep books
+    /// sent over slow links at the cost of compression time on both ends.
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        self.encode(FLAG_LZ4, lz4_flex::block::compress(&self.encode_records()))
+    }
+
+    /// Frames `records` with the header and the checksum trailer.
+    fn encode(&self, flags: u16, records: Vec<u8>) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + records.len() + TRAILER_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&((self.bids.len() + self.asks.len()) as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.get_order_count() as u32).to_le_bytes());
+        bytes.extend_from_slice(&records);
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Encodes the level and order records, uncompressed.
+    fn encode_records(&self) -> Vec<u8> {
+        let levels = self.bids.len() + self.asks.len();
+        let mut bytes = Vec::with_capacity(levels * LEVEL_LEN + self.get_order_count() * ORDER_LEN);
+        for (side, levels) in [(Side::Buy, &self.bids), (Side::Sell, &self.asks)] {
+            for level in levels {
+                bytes.push(side_code(side));
+                bytes.extend_from_slice(&level.price.to_le_bytes());
+                bytes.extend_from_slice(&level.get_quantity().to_le_bytes());
+                bytes.extend_from_slice(&(level.orders.len() as u32).to_le_bytes());
+                for order in &level.orders {
+                    bytes.extend_from_slice(&order.order_id.to_le_bytes());
+                    bytes.push(order_type_code(order.order_type));
+                    bytes.extend_from_slice(&order.initial_quantity.to_le_bytes());
+                    bytes.extend_from_slice(&order.remaining_quantity.to_le_bytes());
+                    bytes.push(order_flags_code(order.flags));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a snapshot, compressed or not, checking its checksum, counts and level ordering.
+    ///
+    /// # Errors
+    /// Returns an [`io::ErrorKind::InvalidData`] error describing the first problem found.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_LEN + TRAILER_LEN {
+            return Err(invalid("snapshot is truncated"));
+        }
+        let (body, trailer) = bytes.split_at(bytes.len() - TRAILER_LEN);
+        let mut reader = Reader { bytes: body, position: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(invalid("not a book snapshot"));
+        }
+        let version = u16::from_le_bytes(reader.array()?);
+        let order_len = match version {
+            1 => V1_ORDER_LEN,
+            VERSION => ORDER_LEN,
+            _ => return Err(invalid(&format!("unsupported snapshot version {}", version))),
+        };
+        let expected = u32::from_le_bytes(trailer.try_into().unwrap());
+        if crc32(body) != expected {
+            return Err(invalid("checksum mismatch"));
+        }
+        let flags = u16::from_le_bytes(reader.array()?);
+        if flags & !FLAG_LZ4 != 0 {
+            return Err(invalid(&format!("unsupported flags {:#06x}", flags)));
+        }
+        let level_count = reader.u32()?;
+        let order_count = reader.u32()?;
+
+        let decompressed;
+        if flags & FLAG_LZ4 != 0 {
+            let stored = &body[HEADER_LEN..];
+            let size = (level_count as usize)
+                .checked_mul(LEVEL_LEN)
+                .zip((order_count as usize).checked_mul(order_len))
+                .and_then(|(levels, orders)| levels.checked_add(orders))
+                .filter(|size| *size <= stored.len().saturating_mul(MAX_LZ4_RATIO))
+                .ok_or_else(|| invalid("record counts do not fit the compressed records"))?;
+            decompressed = lz4_flex::block::decompress(stored, size).map_err(|err| invalid(&format!("corrupt LZ4 records: {}", err)))?;
+            if decompressed.len() != size {
+                return Err(invalid("record counts do not match the compressed records"));
+            }
+            reader = Reader { bytes: &decompressed, position: 0 };
+        }
+
+        let mut snapshot = Self::default();
+        for _ in 0..level_count {
+            let side = match reader.take(1)?[0] {
+                0 => Side::Buy,
+                1 => Side::Sell,
+                code => return Err(invalid(&format!("invalid side {}", code))),
+            };
+            let price = Price::from_le_bytes(reader.array()?);
+            let quantity = reader.u32()?;
+            let mut level = LevelSnapshot { price, orders: Vec::new() };
+            for _ in 0..reader.u32()? {
+                let order_id = reader.u32()?;
+                let order_type = order_type_from_code(reader.take(1)?[0])?;
+                let (initial_quantity, remaining_quantity) = (reader.u32()?, reader.u32()?);
+                let flags = match version {
+                    1 => OrderFlags::default(),
+                    _ => order_flags_from_code(reader.take(1)?[0])?,
+                };
+                level.orders.push(OrderSnapshot { order_id, order_type, initial_quantity, remaining_quantity, flags });
+            }
+            let total: u64 = level.orders.iter().map(|order| order.remaining_quantity as u64).sum();
+            if level.orders.is_empty() || total != quantity as u64 {
+                return Err(invalid(&format!("level {} does not match its orders", price)));
+            }
+            let levels = match side {
+                Side::Buy if !snapshot.asks.is_empty() => return Err(invalid("bid level after ask levels")),
+                Side::Buy => &mut snapshot.bids,
+                Side::Sell => &mut snapshot.asks,
+            };
+            let in_order = levels.last().is_none_or(|last| match side {
+                Side::Buy => last.price > price,
+                Side::Sell => last.price < price,
+            });
+            if !in_order {
+                return Err(invalid(&format!("level {} is out of order", price)));
+            }
+            levels.push(level);
+        }
+        if reader.position != reader.bytes.len() {
+            return Err(invalid("trailing bytes after the last level"));
+        }
+        if snapshot.get_order_count() != order_count as usize {
+            return Err(invalid("order count does not match the header"));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the encoded snapshot to `writer`.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    /// Reads `reader` to the end and decodes the snapshot.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails or the data is not a valid snapshot.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Reads fixed-size fields from the front of a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let field = self.bytes.get(self.position..self.position + len).ok_or_else(|| invalid("snapshot is truncated"))?;
+        self.position += len;
+        Ok(field)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid book snapshot: {}", message))
+}
+
+fn side_code(side: Side) -> u8 {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+fn order_type_code(order_type: OrderType) -> u8 {
+    match order_type {
+        OrderType::GoodTillCancel => 0,
+        OrderType::GoodForDay => 1,
+        OrderType::FillAndKill => 2,
+        OrderType::FillOrKill => 3,
+        OrderType::Market => 4,
+    }
+}
+
+fn order_type_from_code(code: u8) -> io::Result<OrderType> {
+    match code {
+        0 => Ok(OrderType::GoodTillCancel),
+        1 => Ok(OrderType::GoodForDay),
+        2 => Ok(OrderType::FillAndKill),
+        3 => Ok(OrderType::FillOrKill),
+        4 => Ok(OrderType::Market),
+        _ => Err(invalid(&format!("invalid order type {}", code))),
+    }
+}
+
+fn order_flags_code(flags: OrderFlags) -> u8 {
+    let capacity = match flags.capacity {
+        None => 0,
+        Some(Capacity::Agency) => 1,
+        Some(Capacity::Principal) => 2,
+    };
+    flags.short_sell as u8 | capacity << 1
+}
+
+fn order_flags_from_code(code: u8) -> io::Result<OrderFlags> {
+    let capacity = match code >> 1 {
+        0 => None,
+        1 => Some(Capacity::Agency),
+        2 => Some(Capacity::Principal),
+        _ => return Err(invalid(&format!("invalid order flags {:#04x}", code))),
+    };
+    Ok(OrderFlags { capacity, short_sell: code & 1 != 0 })
+}
+
+/// CRC-32 with the IEEE polynomial, as used by zlib and Ethernet.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_book() -> Orderbook {
+        let book = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        book.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 99, 10));
+        book.add_order(Order::new(OrderType::GoodForDay, 2, Side::Buy, 100, 5));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 100, 7));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 102, 8));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 5, Side::Sell, 103, 1));
+        // Fills order 2 completely and order 3 partially.
+        book.add_order(Order::new(OrderType::GoodTillCancel, 6, Side::Sell, 100, 9));
+        book
+    }
+
+    #[test]
+    fn test_crc32(){
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip(){
+        let book = sample_book();
+        let snapshot = BookSnapshot::capture(&book);
+        let order = |order_id, order_type, initial_quantity, remaining_quantity| OrderSnapshot { order_id, order_type, initial_quantity, remaining_quantity, flags: OrderFlags::default() };
+        assert_eq!(snapshot.bids, vec![
+            LevelSnapshot { price: 100, orders: vec![order(3, OrderType::GoodTillCancel, 7, 3)] },
+            LevelSnapshot { price: 99, orders: vec![order(1, OrderType::GoodTillCancel, 10, 10)] },
+        ]);
+        assert_eq!(snapshot.asks.iter().map(|level| level.price).collect::<Vec<_>>(), vec![102, 103]);
+
+        let bytes = snapshot.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 4 * LEVEL_LEN + 4 * ORDER_LEN + TRAILER_LEN);
+        let mut written = Vec::new();
+        snapshot.write_to(&mut written).unwrap();
+        assert_eq!(BookSnapshot::read_from(written.as_slice()).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_order_flags(){
+        let book = sample_book();
+        let short = Order::new(OrderType::GoodTillCancel, 7, Side::Sell, 103, 4);
+        let flags = OrderFlags { capacity: Some(Capacity::Principal), short_sell: true };
+        short.lock().unwrap().set_flags(flags);
+        book.add_order(short);
+
+        let snapshot = BookSnapshot::from_bytes(&BookSnapshot::capture(&book).to_bytes()).unwrap();
+        assert_eq!(snapshot.asks[1].orders[1].flags, flags);
+        let restored = snapshot.restore().unwrap();
+        assert_eq!(BookSnapshot::capture(&restored), BookSnapshot::capture(&book));
+        let trades = restored.add_order(Order::new(OrderType::GoodTillCancel, 8, Side::Buy, 103, 13));
+        assert_eq!(trades.last().unwrap().get_ask_trade().flags, flags);
+    }
+
+    #[test]
+    fn test_reads_version_1_snapshots(){
+        let snapshot = BookSnapshot::capture(&sample_book());
+        // Version 1 order records are version 2 ones without the trailing flags byte.
+        let mut bytes = snapshot.to_bytes();
+        bytes.truncate(bytes.len() - TRAILER_LEN);
+        let mut v1 = bytes[..HEADER_LEN].to_vec();
+        v1[4..6].copy_from_slice(&1u16.to_le_bytes());
+        let mut position = HEADER_LEN;
+        while position < bytes.len() {
+            let orders = u32::from_le_bytes(bytes[position + 9..position + LEVEL_LEN].try_into().unwrap()) as usize;
+            v1.extend_from_slice(&bytes[position..position + LEVEL_LEN]);
+            position += LEVEL_LEN;
+            for _ in 0..orders {
+                v1.extend_from_slice(&bytes[position..position + V1_ORDER_LEN]);
+                position += ORDER_LEN;
+            }
+        }
+        let checksum = crc32(&v1);
+        v1.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(BookSnapshot::from_bytes(&v1).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_compressed_snapshot_round_trip(){
+        let snapshot = BookSnapshot::capture(&sample_book());
+        let compressed = snapshot.to_compressed_bytes();
+        assert_eq!(u16::from_le_bytes([compressed[6], compressed[7]]), FLAG_LZ4);
+        assert_eq!(BookSnapshot::from_bytes(&compressed).unwrap(), snapshot);
+
+        // A deep book of similar orders compresses well.
+        let book = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        for order_id in 1..=2000 {
+            book.add_order(Order::new(OrderType::GoodTillCancel, order_id, Side::Buy, 90 + (order_id % 10) as Price, 100));
+        }
+        let deep = BookSnapshot::capture(&book);
+        let (plain, compressed) = (deep.to_bytes(), deep.to_compressed_bytes());
+        assert!(compressed.len() * 2 < plain.len(), "{} vs {} bytes", compressed.len(), plain.len());
+        assert_eq!(BookSnapshot::from_bytes(&compressed).unwrap(), deep);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_compressed_snapshots(){
+        let bytes = BookSnapshot::capture(&sample_book()).to_compressed_bytes();
+        let error = |mut bytes: Vec<u8>| {
+            let body = bytes.len() - TRAILER_LEN;
+            let checksum = crc32(&bytes[..body]);
+            bytes[body..].copy_from_slice(&checksum.to_le_bytes());
+            BookSnapshot::from_bytes(&bytes).unwrap_err().to_string()
+        };
+
+        let mut flags = bytes.clone();
+        flags[6] = 3;
+        assert!(error(flags).contains("unsupported flags"));
+        let mut counts = bytes.clone();
+        counts[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(error(counts).contains("do not fit"));
+        let mut short = bytes[..bytes.len() - TRAILER_LEN - 2].to_vec();
+        short.extend_from_slice(&[0; TRAILER_LEN]);
+        assert!(error(short).contains("corrupt LZ4 records"));
+    }
+
+    #[test]
+    fn test_restored_book_matches_like_the_original(){
+        let book = sample_book();
+        let restored = BookSnapshot::capture(&book).restore().unwrap();
+        assert_eq!(restored.size(), book.size());
+        assert_eq!(BookSnapshot::capture(&restored), BookSnapshot::capture(&book));
+
+        // Queue priority and partial fills survive: the sweep trades in the same order.
+        let trades = |book: &Orderbook| {
+            book.add_order(Order::new(OrderType::GoodTillCancel, 7, Side::Buy, 99, 1));
+            book.add_order(Order::new(OrderType::GoodTillCancel, 8, Side::Sell, 99, 14))
+                .iter()
+                .map(|trade| (trade.get_bid_trade().order_id, trade.get_bid_trade().quantity))
+                .collect::<Vec<_>>()
+        };
+        let expected = trades(&book);
+        assert_eq!(expected, vec![(3, 3), (1, 10), (7, 1)]);
+        assert_eq!(trades(&restored), expected);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_snapshots(){
+        let bytes = BookSnapshot::capture(&sample_book()).to_bytes();
+        let error = |bytes: &[u8]| BookSnapshot::from_bytes(bytes).unwrap_err().to_string();
+
+        assert!(error(&bytes[..bytes.len() - 1]).contains("checksum"));
+        assert!(error(&bytes[..10]).contains("truncated"));
+        // Remaining quantities whose sum overflows a level quantity are rejected, not summed.
+        let order = OrderSnapshot { order_id: 1, order_type: OrderType::GoodTillCancel, initial_quantity: u32::MAX, remaining_quantity: u32::MAX, flags: OrderFlags::default() };
+        let mut overflow = BookSnapshot { bids: vec![LevelSnapshot { price: 100, orders: vec![order] }], asks: vec![] }.to_bytes();
+        overflow.truncate(overflow.len() - TRAILER_LEN);
+        let mut second = overflow[HEADER_LEN + LEVEL_LEN..].to_vec();
+        second[0] = 2;
+        overflow.extend_from_slice(&second);
+        overflow[12..16].copy_from_slice(&2u32.to_le_bytes());
+        overflow[HEADER_LEN + 9..HEADER_LEN + LEVEL_LEN].copy_from_slice(&2u32.to_le_bytes());
+        let checksum = crc32(&overflow);
+        overflow.extend_from_slice(&checksum.to_le_bytes());
+        assert!(error(&overflow).contains("does not match its orders"));
+
+        let mut flipped = bytes.clone();
+        flipped[HEADER_LEN + 2] ^= 1;
+        assert!(error(&flipped).contains("checksum"));
+        let mut order_flags = bytes.clone();
+        order_flags[HEADER_LEN + LEVEL_LEN + ORDER_LEN - 1] = 0x07;
+        let body = order_flags.len() - TRAILER_LEN;
+        let checksum = crc32(&order_flags[..body]);
+        order_flags[body..].copy_from_slice(&checksum.to_le_bytes());
+        assert!(error(&order_flags).contains("invalid order flags"));
+        let mut version = bytes.clone();
+        version[4] = 3;
+        assert!(error(&version).contains("version 3"));
+        assert!(error(b"JUNKJUNKJUNKJUNKJUNK").contains("not a book snapshot"));
+
+        // A well-formed snapshot of a crossed book cannot be restored.
+        let level = |price, order_id| LevelSnapshot { price, orders: vec![OrderSnapshot { order_id, order_type: OrderType::GoodTillCancel, initial_quantity: 1, remaining_quantity: 1, flags: OrderFlags::default() }] };
+        let crossed = BookSnapshot { bids: vec![level(101, 1)], asks: vec![level(100, 2)] };
+        let decoded = BookSnapshot::from_bytes(&crossed.to_bytes()).unwrap();
+        assert!(decoded.restore().unwrap_err().contains("cross"));
+        let duplicate = BookSnapshot { bids: vec![level(99, 1)], asks: vec![level(100, 1)] };
+        assert!(duplicate.restore().unwrap_err().contains("already exists"));
+    }
+}
@@ -0,0 +1,179 @@
+//! # Tape Module
+//!
+//! Streams executed trades to rotating CSV files that load directly into pandas or Excel.
+//!
+//! ## Files
+//! `tape-NNNN.csv` in [`TradeTapeConfig::directory`], rolling over every
+//! [`TradeTapeConfig::max_rows_per_file`] rows. Every file starts with the header row:
+//!
+//! ```text
+//! trade_id,timestamp_us,symbol,price,quantity,aggressor,maker_order_id,taker_order_id
+//! ```
+//!
+//! - `trade_id` counts from 1 across all files of a tape.
+//! - `price` is the maker's (resting order's) price, the price the trade happened at.
+//! - `aggressor` is the side of the taker, `BUY` or `SELL`.
+//!
+//! Unlike the [recorder](crate::recorder)'s trades file, each row says which order was the
+//! aggressor, so the caller passes the id of the order whose submission caused the trades.
+//! The [`Simulator`](crate::simulator::Simulator) writes one with
+//! [`Simulator::add_sink`](crate::simulator::Simulator::add_sink).
+
+use std::{io, path::PathBuf, time::Duration};
+use crate::backtest::{side_code, Command};
+use crate::orderbook::{OrderId, Orderbook, Side, Trade};
+use crate::recorder::RotatingCsvWriter;
+use crate::simulator::SimulationSink;
+
+/// Header row of every tape file.
+pub const TAPE_HEADER: &str = "trade_id,timestamp_us,symbol,price,quantity,aggressor,maker_order_id,taker_order_id";
+
+/// Where and how a [`TradeTape`] writes its files.
+#[derive(Clone, Debug)]
+pub struct TradeTapeConfig {
+    /// Output directory; created if missing.
+    pub directory: PathBuf,
+    /// Symbol written on every row.
+    pub symbol: String,
+    /// Rows written to a file before rolling over to the next one.
+    pub max_rows_per_file: usize,
+}
+
+impl Default for TradeTapeConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("tape"),
+            symbol: "SIM".to_string(),
+            max_rows_per_file: 100_000,
+        }
+    }
+}
+
+/// Writes executed trades of one book to rotating CSV files.
+pub struct TradeTape {
+    writer: RotatingCsvWriter,
+    symbol: String,
+    next_trade_id: u64,
+}
+
+impl TradeTape {
+    /// Creates the output directory and opens the first file.
+    ///
+    /// # Errors
+    /// Returns any I/O error from creating the directory or file.
+    pub fn new(config: TradeTapeConfig) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.directory)?;
+        Ok(Self {
+            writer: RotatingCsvWriter::new(config.directory, "tape", TAPE_HEADER, config.max_rows_per_file)?,
+            symbol: csv_field(&config.symbol),
+            next_trade_id: 1,
+        })
+    }
+
+    /// Returns the number of trades written so far.
+    pub fn get_trade_count(&self) -> u64 {
+        self.next_trade_id - 1
+    }
+
+    /// Appends one row per trade caused by the order `aggressor`.
+    ///
+    /// # Errors
+    /// Returns an [`io::ErrorKind::InvalidInput`] error if `aggressor` is on neither side of a
+    /// trade, or any I/O error from writing.
+    pub fn record(&mut self, timestamp: Duration, aggressor: OrderId, trades: &[Trade]) -> io::Result<()> {
+        for trade in trades {
+            let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+            let (side, maker, taker) = if bid.order_id == aggressor {
+                (Side::Buy, ask, bid)
+            } else if ask.order_id == aggressor {
+                (Side::Sell, bid, ask)
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Order#{} took no part in the trade between #{} and #{}", aggressor, bid.order_id, ask.order_id),
+                ));
+            };
+            let row = format!(
+                "{},{},{},{},{},{},{},{}",
+                self.next_trade_id,
+                timestamp.as_micros(),
+                self.symbol,
+                maker.price,
+                bid.quantity,
+                side_code(side),
+                maker.order_id,
+                taker.order_id
+            );
+            self.writer.write_row(&row)?;
+            self.next_trade_id += 1;
+        }
+        Ok(())
+    }
+
+    /// Flushes all buffered rows to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes every trade, with the command's order as the aggressor.
+impl SimulationSink for TradeTape {
+    fn on_applied(&mut self, _orderbook: &Orderbook, timestamp: Duration, command: Command, trades: &[Trade]) -> Result<(), String> {
+        let aggressor = match command {
+            Command::New { order_id, .. } | Command::Market { order_id, .. } | Command::Modify { order_id, .. } | Command::Cancel { order_id } => order_id,
+        };
+        self.record(timestamp, aggressor, trades).map_err(|err| format!("Writing the trade tape failed: {}", err))
+    }
+}
+
+impl Drop for TradeTape {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Quotes `value` if it contains a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::BTreeMap, fs};
+    use crate::orderbook::{Order, OrderType, Orderbook};
+    use crate::recorder::read_rows;
+
+    #[test]
+    fn test_tape_records_aggressor_and_rotates(){
+        let directory = tempfile::tempdir().unwrap();
+        let mut tape = TradeTape::new(TradeTapeConfig { directory: directory.path().to_path_buf(), symbol: "ACME".to_string(), max_rows_per_file: 2 }).unwrap();
+
+        let book = Orderbook::new(BTreeMap::new(), BTreeMap::new());
+        book.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 5));
+        book.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 102, 5));
+        let trades = book.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 102, 8));
+        tape.record(Duration::from_micros(10), 3, &trades).unwrap();
+        book.add_order(Order::new(OrderType::GoodTillCancel, 4, Side::Buy, 100, 4));
+        let trades = book.add_order(Order::new(OrderType::GoodTillCancel, 5, Side::Sell, 99, 1));
+        tape.record(Duration::from_micros(20), 5, &trades).unwrap();
+        assert!(tape.record(Duration::from_micros(30), 9, &trades).is_err());
+        assert_eq!(tape.get_trade_count(), 3);
+        tape.flush().unwrap();
+
+        let second = fs::read_to_string(directory.path().join("tape-0002.csv")).unwrap();
+        assert_eq!(second, format!("{}\n3,20,ACME,100,1,SELL,4,5\n", TAPE_HEADER));
+        assert_eq!(read_rows(directory.path(), "tape").unwrap(), vec!["1,10,ACME,101,5,BUY,1,3", "2,10,ACME,102,3,BUY,2,3", "3,20,ACME,100,1,SELL,4,5"]);
+    }
+
+    #[test]
+    fn test_csv_field(){
+        assert_eq!(csv_field("ACME"), "ACME");
+        assert_eq!(csv_field("A,B"), "\"A,B\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,344 @@
+//! # Triggers Module
+//!
+//! Conditional orders (stops, market-if-touched and trailing stops) that wait outside the
+//! book until the market reaches them, kept apart from matching so the book only ever sees
+//! plain orders.
+//!
+//! ## Model
+//! - [`TriggerEngine::park`] holds a [`ConditionalOrder`] until its [`Condition`] is met,
+//!   judged either on trade prices or on the best bid and ask ([`TriggerOn`]).
+//! - The engine is fed what happens to the book: [`TriggerEngine::on_trades`] after every
+//!   command that traded, [`TriggerEngine::on_quote`] after any other command that may have
+//!   moved the best prices, such as a cancel.
+//! - Triggered orders are submitted to the book as market orders, or as GoodTillCancel
+//!   orders at their limit. Their own trades are fed back in, so activations cascade.
+//!
+//! ## Ordering
+//! Trades are looked at one by one at the maker's price. Orders triggered by the same price
+//! are released in the order they were parked, and every release is submitted, and its
+//! trades looked at, before the next one, so a run is fully deterministic.
+//!
+//! A condition is only checked from the next price on: a stop parked below the market waits
+//! for the next trade rather than firing on the one before it.
+//!
+//! ## Example Usage
+//!
+//! ```rust
+//! use orderbook::{Order, OrderType, Orderbook, Side};
+//! use orderbook::triggers::{Condition, ConditionalOrder, TriggerEngine};
+//!
+//! let ob = Orderbook::new(Default::default(), Default::default());
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 1));
+//! ob.add_order(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 97, 5));
+//!
+//! let mut triggers = TriggerEngine::new();
+//! triggers.park(&ob, ConditionalOrder::new(10, Side::Sell, 2, Condition::Stop { trigger_price: 99 })).unwrap();
+//!
+//! // Trades at 100, then 97: the stop fires and sells into the remaining bid.
+//! let trades = ob.add_order(Order::new(OrderType::GoodTillCancel, 3, Side::Sell, 97, 2));
+//! let activations = triggers.on_trades(&ob, 3, &trades);
+//! assert_eq!(activations[0].order.order_id, 10);
+//! assert_eq!(activations[0].trades[0].get_bid_trade().price, 97);
+//! assert_eq!(triggers.size(), 0);
+//! ```
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use log::debug;
+use crate::orderbook::{Order, OrderId, OrderPointer, OrderType, Orderbook, Price, Quantity, Side, Trade, Trades};
+
+/// When a [`ConditionalOrder`] fires, relative to the price it watches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Condition {
+    /// Buys once the price rises to `trigger_price`, sells once it falls to it.
+    Stop { trigger_price: Price },
+    /// Buys once the price falls to `trigger_price`, sells once it rises to it.
+    MarketIfTouched { trigger_price: Price },
+    /// A stop that follows the market at `offset`: sells once the price falls `offset`
+    /// below the highest price seen since parking, buys once it rises `offset` above the lowest.
+    TrailingStop { offset: Price },
+}
+
+/// The price a [`ConditionalOrder`] watches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TriggerOn {
+    /// The price of every trade.
+    #[default]
+    Trade,
+    /// The best price on the other side: the ask for buys, the bid for sells.
+    Quote,
+}
+
+/// An order parked until its condition is met.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ConditionalOrder {
+    pub order_id: OrderId,
+    pub side: Side,
+    pub quantity: Quantity,
+    pub condition: Condition,
+    /// Price watched by the condition.
+    pub trigger_on: TriggerOn,
+    /// Limit of the released order; `None` releases a market order.
+    pub limit: Option<Price>,
+}
+
+impl ConditionalOrder {
+    /// Creates a conditional market order triggered by trade prices.
+    pub const fn new(order_id: OrderId, side: Side, quantity: Quantity, condition: Condition) -> Self {
+        Self { order_id, side, quantity, condition, trigger_on: TriggerOn::Trade, limit: None }
+    }
+
+    /// Returns the order submitted to the book when the condition is met.
+    pub fn to_order_pointer(&self) -> OrderPointer {
+        match self.limit {
+            Some(price) => Order::new(OrderType::GoodTillCancel, self.order_id, self.side, price, self.quantity),
+            None => Order::new_market(self.order_id, self.side, self.quantity),
+        }
+    }
+}
+
+/// A conditional order that fired, with the trades its submission produced.
+#[derive(Clone, Debug)]
+pub struct Activation {
+    pub order: ConditionalOrder,
+    pub trades: Trades,
+}
+
+/// A parked order and, for trailing stops, the best price seen since parking.
+#[derive(Debug)]
+struct Parked {
+    order: ConditionalOrder,
+    watermark: Option<Price>,
+}
+
+impl Parked {
+    /// Returns `true` if `price` meets the condition, moving a trailing stop's watermark first.
+    fn observe(&mut self, price: Price) -> bool {
+        match (self.order.condition, self.order.side) {
+            (Condition::Stop { trigger_price }, Side::Buy) => price >= trigger_price,
+            (Condition::Stop { trigger_price }, Side::Sell) => price <= trigger_price,
+            (Condition::MarketIfTouched { trigger_price }, Side::Buy) => price <= trigger_price,
+            (Condition::MarketIfTouched { trigger_price }, Side::Sell) => price >= trigger_price,
+            (Condition::TrailingStop { offset }, Side::Buy) => {
+                let low = self.watermark.map_or(price, |low| low.min(price));
+                self.watermark = Some(low);
+                price >= low.saturating_add(offset)
+            }
+            (Condition::TrailingStop { offset }, Side::Sell) => {
+                let high = self.watermark.map_or(price, |high| high.max(price));
+                self.watermark = Some(high);
+                price <= high.saturating_sub(offset)
+            }
+        }
+    }
+}
+
+/// Holds conditional orders and submits them to a book once triggered.
+///
+/// One engine serves one book.
+#[derive(Debug, Default)]
+pub struct TriggerEngine {
+    /// Parked orders by the sequence they were parked in.
+    parked: BTreeMap<u64, Parked>,
+    sequences: HashMap<OrderId, u64>,
+    next_sequence: u64,
+}
+
+impl TriggerEngine {
+    /// Creates an engine with nothing parked.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of parked orders.
+    pub fn size(&self) -> usize {
+        self.parked.len()
+    }
+
+    /// Returns the parked orders in the order they would be released.
+    pub fn get_parked(&self) -> Vec<ConditionalOrder> {
+        self.parked.values().map(|parked| parked.order).collect()
+    }
+
+    /// Parks `order` until its condition is met by a later price of `orderbook`.
+    ///
+    /// A trailing stop starts following from the book's current price, if it has one.
+    ///
+    /// # Errors
+    /// If the order id is parked or resting in the book already, the quantity is zero, or a
+    /// trailing stop's offset is not positive.
+    pub fn park(&mut self, orderbook: &Orderbook, order: ConditionalOrder) -> Result<(), String> {
+        if self.sequences.contains_key(&order.order_id) || orderbook.contains_order(order.order_id) {
+            return Err(format!("Order#{} already exists", order.order_id));
+        }
+        if order.quantity == 0 {
+            return Err(format!("Order#{} has no quantity", order.order_id));
+        }
+        let watermark = match (order.condition, order.trigger_on) {
+            (Condition::TrailingStop { offset }, _) if offset <= 0 => {
+                return Err(format!("Order#{} has a trailing offset of {}", order.order_id, offset));
+            }
+            (Condition::TrailingStop { .. }, TriggerOn::Trade) => orderbook.get_last_sale().map(|last| last.price),
+            (Condition::TrailingStop { .. }, TriggerOn::Quote) => quote(orderbook.get_best_prices(), order.side),
+            _ => None,
+        };
+
+        self.next_sequence += 1;
+        self.sequences.insert(order.order_id, self.next_sequence);
+        self.parked.insert(self.next_sequence, Parked { order, watermark });
+        Ok(())
+    }
+
+    /// Takes a parked order out of the engine.
+    pub fn cancel(&mut self, order_id: OrderId) -> Option<ConditionalOrder> {
+        let sequence = self.sequences.remove(&order_id)?;
+        self.parked.remove(&sequence).map(|parked| parked.order)
+    }
+
+    /// Looks at the `trades` the order `aggressor` just made in `orderbook`, then at the best
+    /// prices, and submits whatever they trigger.
+    ///
+    /// # Returns
+    /// Every order released, including those triggered by earlier releases, in the order
+    /// they were submitted.
+    pub fn on_trades(&mut self, orderbook: &Orderbook, aggressor: OrderId, trades: &[Trade]) -> Vec<Activation> {
+        let mut triggered = VecDeque::new();
+        for trade in trades {
+            triggered.extend(self.observe(TriggerOn::Trade, |_| Some(maker_price(aggressor, trade))));
+        }
+        let best_prices = orderbook.get_best_prices();
+        triggered.extend(self.observe(TriggerOn::Quote, |side| quote(best_prices, side)));
+        self.release(orderbook, triggered)
+    }
+
+    /// Looks at the best prices of `orderbook` and submits whatever they trigger.
+    ///
+    /// # Returns
+    /// Every order released, in the order they were submitted.
+    pub fn on_quote(&mut self, orderbook: &Orderbook) -> Vec<Activation> {
+        self.on_trades(orderbook, 0, &[])
+    }
+
+    /// Submits `triggered` one by one, feeding each submission's trades and quotes back in.
+    fn release(&mut self, orderbook: &Orderbook, mut triggered: VecDeque<ConditionalOrder>) -> Vec<Activation> {
+        let mut activations = Vec::new();
+        while let Some(order) = triggered.pop_front() {
+            debug!("TriggerEngine: releasing Order#{} ({:?})", order.order_id, order.condition);
+            let trades = orderbook.add_order(order.to_order_pointer());
+            for trade in &trades {
+                triggered.extend(self.observe(TriggerOn::Trade, |_| Some(maker_price(order.order_id, trade))));
+            }
+            let best_prices = orderbook.get_best_prices();
+            triggered.extend(self.observe(TriggerOn::Quote, |side| quote(best_prices, side)));
+            activations.push(Activation { order, trades });
+        }
+        activations
+    }
+
+    /// Shows the price of `source` to every order watching it and takes out those it triggers,
+    /// in parking order.
+    fn observe(&mut self, source: TriggerOn, price: impl Fn(Side) -> Option<Price>) -> Vec<ConditionalOrder> {
+        let mut fired = Vec::new();
+        for (sequence, parked) in self.parked.iter_mut().filter(|(_, parked)| parked.order.trigger_on == source) {
+            if price(parked.order.side).is_some_and(|price| parked.observe(price)) {
+                fired.push(*sequence);
+            }
+        }
+        fired
+            .into_iter()
+            .filter_map(|sequence| self.parked.remove(&sequence))
+            .map(|parked| {
+                self.sequences.remove(&parked.order.order_id);
+                parked.order
+            })
+            .collect()
+    }
+}
+
+/// Returns the price `trade` happened at: the price of the side that was not the aggressor.
+fn maker_price(aggressor: OrderId, trade: &Trade) -> Price {
+    let (bid, ask) = (trade.get_bid_trade(), trade.get_ask_trade());
+    if bid.order_id == aggressor { ask.price } else { bid.price }
+}
+
+/// Returns the best price a `side` order would trade against.
+fn quote((best_bid, best_ask): (Option<Price>, Option<Price>), side: Side) -> Option<Price> {
+    match side {
+        Side::Buy => best_ask,
+        Side::Sell => best_bid,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap as Levels;
+
+    fn book(orders: &[(OrderId, Side, Price, Quantity)]) -> Orderbook {
+        let ob = Orderbook::new(Levels::new(), Levels::new());
+        for &(order_id, side, price, quantity) in orders {
+            ob.add_order(Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity));
+        }
+        ob
+    }
+
+    #[test]
+    fn test_stops_cascade_in_parking_order(){
+        let ob = book(&[(1, Side::Buy, 100, 1), (2, Side::Buy, 98, 2), (3, Side::Buy, 95, 10)]);
+        let mut triggers = TriggerEngine::new();
+        triggers.park(&ob, ConditionalOrder::new(10, Side::Sell, 1, Condition::Stop { trigger_price: 99 })).unwrap();
+        triggers.park(&ob, ConditionalOrder::new(11, Side::Sell, 1, Condition::Stop { trigger_price: 99 })).unwrap();
+        triggers.park(&ob, ConditionalOrder::new(12, Side::Sell, 5, Condition::Stop { trigger_price: 96 })).unwrap();
+        triggers.park(&ob, ConditionalOrder::new(13, Side::Sell, 1, Condition::MarketIfTouched { trigger_price: 105 })).unwrap();
+        assert!(triggers.park(&ob, ConditionalOrder::new(1, Side::Sell, 1, Condition::Stop { trigger_price: 90 })).is_err());
+
+        // A trade at 98 fires 10 and 11; 11 empties the 98 level and trades at 95, firing 12.
+        let trades = ob.add_order(Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 98, 2));
+        let activations = triggers.on_trades(&ob, 4, &trades);
+        let released: Vec<(OrderId, Vec<Price>)> = activations
+            .iter()
+            .map(|activation| (activation.order.order_id, activation.trades.iter().map(|trade| trade.get_bid_trade().price).collect()))
+            .collect();
+        assert_eq!(released, vec![(10, vec![98]), (11, vec![95]), (12, vec![95])]);
+        assert_eq!(triggers.get_parked(), vec![ConditionalOrder::new(13, Side::Sell, 1, Condition::MarketIfTouched { trigger_price: 105 })]);
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_the_market(){
+        let ob = book(&[(1, Side::Sell, 100, 1), (2, Side::Sell, 103, 1), (3, Side::Buy, 99, 5)]);
+        let mut triggers = TriggerEngine::new();
+        let trail = ConditionalOrder { limit: Some(99), ..ConditionalOrder::new(10, Side::Sell, 2, Condition::TrailingStop { offset: 3 }) };
+        triggers.park(&ob, trail).unwrap();
+
+        // Rises to 103 without firing, so the stop now sits at 100.
+        for order_id in [4, 5] {
+            let trades = ob.add_order(Order::new(OrderType::FillAndKill, order_id, Side::Buy, 103, 1));
+            assert!(triggers.on_trades(&ob, order_id, &trades).is_empty());
+        }
+        let trades = ob.add_order(Order::new(OrderType::FillAndKill, 6, Side::Sell, 99, 1));
+        let activations = triggers.on_trades(&ob, 6, &trades);
+        assert_eq!(activations[0].trades[0].get_ask_trade().quantity, 2);
+        assert_eq!(ob.size(), 1);
+    }
+
+    #[test]
+    fn test_quote_triggers_and_cancel(){
+        let ob = book(&[(1, Side::Sell, 101, 5), (2, Side::Sell, 104, 5)]);
+        let mut triggers = TriggerEngine::new();
+        let on_quote = |order_id, trigger_price| ConditionalOrder {
+            trigger_on: TriggerOn::Quote,
+            limit: Some(110),
+            ..ConditionalOrder::new(order_id, Side::Buy, 1, Condition::Stop { trigger_price })
+        };
+        triggers.park(&ob, on_quote(10, 103)).unwrap();
+        triggers.park(&ob, on_quote(11, 103)).unwrap();
+        assert_eq!(triggers.cancel(11).map(|order| order.order_id), Some(11));
+        assert_eq!(triggers.cancel(11), None);
+
+        // Pulling the 101 offer lifts the ask to 104 without any trade.
+        ob.cancel_order(1);
+        let activations = triggers.on_quote(&ob);
+        assert_eq!(activations.len(), 1);
+        assert_eq!(activations[0].trades[0].get_ask_trade().price, 104);
+        assert_eq!(triggers.size(), 0);
+    }
+}